@@ -0,0 +1,119 @@
+//! `.anim` sidecars: named frame sub-ranges stored in an MSF `TAGS` chunk
+//!
+//! Shared by asf2msf, mpc2msf and convert-all (included with `#[path]` from
+//! the binaries under `src/bin/`).
+
+use std::path::Path;
+
+/// Extension chunk naming animation sub-ranges (from a `.anim` sidecar)
+pub const CHUNK_TAGS: &[u8; 4] = b"TAGS";
+
+/// Named frame sub-range from a `.anim` sidecar
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimTag {
+    pub name: String,
+    /// First frame, counted within one direction
+    pub start_frame: u16,
+    /// Last frame (inclusive)
+    pub end_frame: u16,
+    pub looping: bool,
+}
+
+/// Parse a `.anim` sidecar: one `name start end [loop]` per line,
+/// blank lines and `#` comments ignored
+pub fn parse_anim_tags(text: &str) -> Result<Vec<AnimTag>, String> {
+    let mut tags: Vec<AnimTag> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let err = |msg: String| format!("line {}: {}", i + 1, msg);
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (name, start, end, looping) = match fields[..] {
+            [name, start, end] => (name, start, end, false),
+            [name, start, end, "loop"] => (name, start, end, true),
+            _ => {
+                return Err(err(format!(
+                    "expected `name start end [loop]`, got {:?}",
+                    line
+                )))
+            }
+        };
+        if name.len() > u8::MAX as usize {
+            return Err(err(format!("tag name longer than 255 bytes: {:?}", name)));
+        }
+        if tags.iter().any(|t| t.name == name) {
+            return Err(err(format!("duplicate tag {:?}", name)));
+        }
+        let frame = |v: &str| {
+            v.parse::<u16>()
+                .map_err(|_| err(format!("bad frame number {:?}", v)))
+        };
+        let (start_frame, end_frame) = (frame(start)?, frame(end)?);
+        if start_frame > end_frame {
+            return Err(err(format!(
+                "tag {:?} starts after it ends ({} > {})",
+                name, start_frame, end_frame
+            )));
+        }
+        tags.push(AnimTag {
+            name: name.to_string(),
+            start_frame,
+            end_frame,
+            looping,
+        });
+    }
+    Ok(tags)
+}
+
+/// Read the `.anim` sidecar next to `source`: its text (part of the
+/// convert-all cache hash) and tags, both empty when there is no sidecar.
+/// Errors name the sidecar path.
+pub fn read_anim_tags(source: &Path) -> Result<(String, Vec<AnimTag>), String> {
+    let anim_path = source.with_extension("anim");
+    match std::fs::read_to_string(&anim_path) {
+        Ok(text) => {
+            let tags = parse_anim_tags(&text).map_err(|e| format!("{:?}: {}", anim_path, e))?;
+            Ok((text, tags))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok((String::new(), Vec::new())),
+        Err(e) => Err(format!("{:?}: {}", anim_path, e)),
+    }
+}
+
+/// Fail if a tag reaches past the frames of one direction
+pub fn check_tag_range(tags: &[AnimTag], frame_count: u16, directions: u8) -> Result<(), String> {
+    let frames_per_direction = if directions > 0 {
+        (frame_count / directions as u16).max(1)
+    } else {
+        frame_count.max(1)
+    };
+    match tags.iter().find(|t| t.end_frame >= frames_per_direction) {
+        Some(tag) => Err(format!(
+            "tag {:?} spans frames {}..={} but each direction has {} frames",
+            tag.name, tag.start_frame, tag.end_frame, frames_per_direction
+        )),
+        None => Ok(()),
+    }
+}
+
+/// `TAGS` chunk (id, length, payload), empty without tags; per tag
+/// `name_len u8, name, start u16, end u16, loop u8`
+pub fn tags_chunk(tags: &[AnimTag]) -> Vec<u8> {
+    if tags.is_empty() {
+        return Vec::new();
+    }
+    let mut payload = Vec::new();
+    for tag in tags {
+        payload.push(tag.name.len() as u8);
+        payload.extend_from_slice(tag.name.as_bytes());
+        payload.extend_from_slice(&tag.start_frame.to_le_bytes());
+        payload.extend_from_slice(&tag.end_frame.to_le_bytes());
+        payload.push(tag.looping as u8);
+    }
+    let mut chunk = CHUNK_TAGS.to_vec();
+    chunk.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(&payload);
+    chunk
+}
@@ -1,12 +1,14 @@
 //! Unified resource converter - one command to convert everything
 //!
 //! Usage:
-//!   convert-all <resources_dir> [--delete-originals]
+//!   convert-all <resources_dir> [--delete-originals] [--zstd-level <1-22>] [--skip-existing]
+//!               [--report <path.json>] [--dedupe-manifest <path.json>] [--jobs <n>]
+//!               [--dry-run] [--log <path>] [--manifest <path.json>] [--cache]
 //!
 //! Performs all conversions in order:
 //! 1. Text encoding: GBK → UTF-8 (.ini, .txt, .npc, .obj)
 //! 2. ASF → MSF v2 (sprite animations, Indexed8Alpha8 2bpp + zstd)
-//! 3. MPC → MSF v2 (map/sprite tiles, Rgba8 + zstd)
+//! 3. MPC → MSF v2 (map/sprite tiles, Rgba8 + zstd; standalone .shd shadows too)
 //!    mpc/effect/ uses palette 4th-byte alpha (magic fly/vanish animations)
 //!    all other mpc/ dirs use binary transparency (RLE skip only)
 //! 4. MAP → MMF (map data, with embedded trap table, zstd)
@@ -14,14 +16,356 @@
 //! 6. Cleanup: delete old .asf, .map, .mpc, .wmv, .wma files (if --delete-originals)
 //!
 //! XNB files are kept as-is (engine has native XNB parser)
+//!
+//! `--jobs <n>` caps the worker threads used by the parallel steps (default:
+//! all cores), so a conversion can share a build machine.
+//!
+//! With `--skip-existing`, steps 2–4 skip files whose output is newer than
+//! the source, so re-runs on a partially updated tree only redo what changed.
+//!
+//! `--cache` does the same by content instead of mtime, for checkouts that
+//! reset timestamps: `<resources_dir>/.convert-cache` holds one
+//! `<xxh3 hex>\t<relative path>` line per source, and steps 2–4 skip files
+//! whose hash (source bytes, the .shd or trap table merged into the output,
+//! and the zstd level) is unchanged and whose output exists. The cache is
+//! rewritten after step 4; delete it to force a full rebuild.
+//!
+//! A `.anim` file next to an `.asf` or `.mpc` (same stem) names frame
+//! sub-ranges, one `name start end [loop]` per line (frames counted within
+//! one direction, `end` inclusive, `#` starts a comment); steps 2–3 store
+//! them in the MSF's `TAGS` chunk.
+//!
+//! Step 5 needs ffmpeg (`--ffmpeg <path>` to pick a binary). Without it the
+//! step is skipped with a warning; `--skip-media` skips it explicitly.
+//!
+//! `--report <path.json>` writes per-step converted/failed/skipped counts,
+//! total input vs output bytes for steps 2–4, and every failed file with
+//! the reason it failed.
+//!
+//! After the media step, every .msf under the resources dir is hashed (xxh3)
+//! and groups of byte-identical files are reported with the bytes that could
+//! be saved. `--dedupe-manifest <path.json>` writes those groups as
+//! `{ canonical, duplicates, size }` entries so a loader can fetch each blob
+//! once; the duplicate files themselves are left in place.
+//!
+//! `--manifest <path.json>` lists every MSF/MMF written by steps 2–4 with its
+//! source, format, size, frame count, directions and output byte size, so
+//! tooling can map original filenames to converted assets. Values are read
+//! from the header of the output that was just built.
+//!
+//! `--log <path>` appends one tab-separated `level, step, file, message` line
+//! per failure and warning to a log file (`level` is `error` or `warning`);
+//! those per-file messages then stay off stderr so the console only shows
+//! progress and step summaries.
+//!
+//! `--dry-run` walks every step and prints each file that would be converted
+//! or (with `--delete-originals`) deleted, with its size, but writes and
+//! deletes nothing; the report and dedupe manifest are not written either.
 
 use encoding_rs::GBK;
 use rayon::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 use walkdir::WalkDir;
 
+#[path = "../anim_tags.rs"]
+mod anim_tags;
+#[path = "../map_layout.rs"]
+mod map_layout;
+
+// ============= Batch Options =============
+
+/// Options shared by the ASF / MPC / MAP conversion steps
+#[derive(Clone, Copy)]
+struct ConvertOptions {
+    /// zstd level for MSF/MMF blobs (`--zstd-level`)
+    zstd_level: i32,
+    /// Skip sources whose output is already newer (`--skip-existing`)
+    skip_existing: bool,
+    /// List what would be converted without writing anything (`--dry-run`)
+    dry_run: bool,
+}
+
+/// Size of `path` in bytes, 0 if it can't be read
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Print a `--dry-run` line for a conversion that was not performed
+fn print_would_convert(source: &Path, output: &Path) {
+    println!(
+        "  [dry-run] would convert {:?} ({} bytes) → {:?}",
+        source,
+        file_size(source),
+        output
+    );
+}
+
+/// True if `target` exists and is at least as new as every existing source
+fn is_up_to_date(target: &Path, sources: &[&Path]) -> bool {
+    let Ok(target_time) = std::fs::metadata(target).and_then(|m| m.modified()) else {
+        return false;
+    };
+    sources.iter().all(|src| {
+        std::fs::metadata(src)
+            .and_then(|m| m.modified())
+            .map(|t| t <= target_time)
+            .unwrap_or(true)
+    })
+}
+
+// ============= Content Cache =============
+
+/// Cache file name under the resources dir (`--cache`)
+const CACHE_FILE: &str = ".convert-cache";
+
+/// Source content hashes from the previous run and the ones to save for the next
+struct SourceCache {
+    resources_dir: PathBuf,
+    previous: HashMap<String, u64>,
+    current: Mutex<HashMap<String, u64>>,
+}
+
+impl SourceCache {
+    /// Read `<resources_dir>/.convert-cache`; a missing file or bad lines are
+    /// treated as not cached
+    fn load(resources_dir: &Path) -> Self {
+        let text = std::fs::read_to_string(resources_dir.join(CACHE_FILE)).unwrap_or_default();
+        let previous: HashMap<String, u64> = text
+            .lines()
+            .filter_map(|line| {
+                let (hash, path) = line.split_once('\t')?;
+                Some((path.to_string(), u64::from_str_radix(hash, 16).ok()?))
+            })
+            .collect();
+        Self {
+            resources_dir: resources_dir.to_path_buf(),
+            current: Mutex::new(previous.clone()),
+            previous,
+        }
+    }
+
+    /// Cache key: path relative to the resources dir with `/` separators
+    fn key(&self, source: &Path) -> String {
+        let rel = source.strip_prefix(&self.resources_dir).unwrap_or(source);
+        rel.to_string_lossy().replace('\\', "/")
+    }
+
+    /// True if `output` exists and `source` hashed the same on the last run
+    fn is_unchanged(&self, source: &Path, hash: u64, output: &Path) -> bool {
+        output.exists() && self.previous.get(&self.key(source)) == Some(&hash)
+    }
+
+    /// Remember the hash of a source that was converted (or skipped as unchanged)
+    fn store(&self, source: &Path, hash: u64) {
+        let key = self.key(source);
+        self.current.lock().unwrap().insert(key, hash);
+    }
+
+    /// Drop a source whose conversion failed, so the next run retries it
+    fn forget(&self, source: &Path) {
+        let key = self.key(source);
+        self.current.lock().unwrap().remove(&key);
+    }
+
+    /// Rewrite the cache file, sorted by path; sources deleted since they
+    /// were cached are dropped so the file doesn't grow forever
+    fn save(&self) -> std::io::Result<()> {
+        let mut current = self.current.lock().unwrap();
+        current.retain(|path, _| self.resources_dir.join(path).exists());
+        let mut entries: Vec<(&String, &u64)> = current.iter().collect();
+        entries.sort();
+        let text: String = entries
+            .into_iter()
+            .map(|(path, hash)| format!("{:016x}\t{}\n", hash, path))
+            .collect();
+        std::fs::write(self.resources_dir.join(CACHE_FILE), text)
+    }
+}
+
+/// xxh3 over each part in turn (each seeded with the previous hash), starting
+/// from `seed`, so the zstd level and merged inputs are part of the hash
+fn content_hash(seed: u64, parts: &[&[u8]]) -> u64 {
+    parts.iter().fold(seed, |hash, part| {
+        twox_hash::XxHash3_64::oneshot_with_seed(hash, part)
+    })
+}
+
+// ============= Conversion Report =============
+
+/// A single file that failed to convert, with the reason
+struct Failure {
+    step: &'static str,
+    path: PathBuf,
+    error: String,
+}
+
+/// One converted asset for `--manifest`
+struct ManifestEntry {
+    source: PathBuf,
+    output: PathBuf,
+    /// "msf" or "mmf"
+    format: &'static str,
+    /// Canvas size for MSF, columns/rows for MMF
+    width: u16,
+    height: u16,
+    /// MSF only
+    frames: Option<u16>,
+    directions: Option<u8>,
+    /// MSF pixel format name
+    pixel_format: Option<&'static str>,
+    bytes: usize,
+}
+
+impl ManifestEntry {
+    /// Build from a freshly encoded MSF (header: w@8, h@10, frames@12, dirs@14, format@24)
+    fn msf(source: &Path, output: &Path, msf: &[u8]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([msf[i], msf[i + 1]]);
+        Self {
+            source: source.to_path_buf(),
+            output: output.to_path_buf(),
+            format: "msf",
+            width: u16_at(8),
+            height: u16_at(10),
+            frames: Some(u16_at(12)),
+            directions: Some(msf[14]),
+            pixel_format: Some(match msf[24] {
+                0 => "rgba8",
+                1 => "indexed8",
+                2 => "indexed8alpha8",
+                _ => "unknown",
+            }),
+            bytes: msf.len(),
+        }
+    }
+
+    /// Build from a freshly encoded MMF (header: columns@8, rows@10)
+    fn mmf(source: &Path, output: &Path, mmf: &[u8]) -> Self {
+        Self {
+            source: source.to_path_buf(),
+            output: output.to_path_buf(),
+            format: "mmf",
+            width: u16::from_le_bytes([mmf[8], mmf[9]]),
+            height: u16::from_le_bytes([mmf[10], mmf[11]]),
+            frames: None,
+            directions: None,
+            pixel_format: None,
+            bytes: mmf.len(),
+        }
+    }
+}
+
+/// Byte totals and failure list collected across steps for `--report`,
+/// plus the optional `--log` file that receives every failure and warning
+#[derive(Default)]
+struct Report {
+    input_bytes: AtomicUsize,
+    output_bytes: AtomicUsize,
+    failures: Mutex<Vec<Failure>>,
+    /// Outputs a `--dry-run` would have written, so the cleanup listing
+    /// matches what a real run would delete
+    planned_outputs: Mutex<HashSet<PathBuf>>,
+    /// `--log` file, opened for appending
+    log: Option<Mutex<std::fs::File>>,
+    /// Every asset written by steps 2–4, for `--manifest`
+    manifest: Mutex<Vec<ManifestEntry>>,
+}
+
+impl Report {
+    fn fail(&self, step: &'static str, path: &Path, error: impl Into<String>) {
+        let error = error.into();
+        self.log_line("error", step, path, &error);
+        self.failures.lock().unwrap().push(Failure {
+            step,
+            path: path.to_path_buf(),
+            error,
+        });
+    }
+
+    /// Record a non-fatal problem: to the log file with `--log`, else stderr
+    fn warn(&self, step: &'static str, path: &Path, message: &str) {
+        if self.log.is_some() {
+            self.log_line("warning", step, path, message);
+        } else {
+            eprintln!("  WARNING {:?}: {}", path, message);
+        }
+    }
+
+    /// Print a per-file message to stderr unless `--log` is collecting them
+    fn echo(&self, message: std::fmt::Arguments) {
+        if self.log.is_none() {
+            eprintln!("{}", message);
+        }
+    }
+
+    fn log_line(&self, level: &str, step: &str, path: &Path, message: &str) {
+        use std::io::Write;
+        if let Some(log) = &self.log {
+            let mut file = log.lock().unwrap();
+            let _ = writeln!(file, "{}\t{}\t{}\t{}", level, step, path.display(), message);
+        }
+    }
+
+    fn record(&self, entry: ManifestEntry) {
+        self.manifest.lock().unwrap().push(entry);
+    }
+
+    fn plan_output(&self, path: &Path) {
+        self.planned_outputs
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf());
+    }
+
+    fn add_bytes(&self, input: usize, output: usize) {
+        self.input_bytes.fetch_add(input, Ordering::Relaxed);
+        self.output_bytes.fetch_add(output, Ordering::Relaxed);
+    }
+
+    /// Write the JSON report; `steps` holds (name, converted, failed, skipped)
+    fn write(&self, path: &Path, steps: &[(&str, usize, usize, usize)]) -> std::io::Result<()> {
+        let mut failures = std::mem::take(&mut *self.failures.lock().unwrap());
+        failures.sort_by(|a, b| (a.step, &a.path).cmp(&(b.step, &b.path)));
+
+        let steps: serde_json::Map<String, serde_json::Value> = steps
+            .iter()
+            .map(|&(name, converted, failed, skipped)| {
+                (
+                    name.to_string(),
+                    serde_json::json!({
+                        "converted": converted,
+                        "failed": failed,
+                        "skipped": skipped,
+                    }),
+                )
+            })
+            .collect();
+        let failures: Vec<serde_json::Value> = failures
+            .iter()
+            .map(|f| {
+                serde_json::json!({
+                    "step": f.step,
+                    "path": f.path.display().to_string(),
+                    "error": f.error,
+                })
+            })
+            .collect();
+        let report = serde_json::json!({
+            "steps": steps,
+            "bytes": {
+                "input": self.input_bytes.load(Ordering::Relaxed),
+                "output": self.output_bytes.load(Ordering::Relaxed),
+            },
+            "total_failures": failures.len(),
+            "failures": failures,
+        });
+        let text = serde_json::to_string_pretty(&report).map_err(std::io::Error::other)?;
+        std::fs::write(path, text)
+    }
+}
+
 // ============= Text Encoding Conversion =============
 
 /// Heuristic: if the text contains any CJK Unified Ideographs (U+4E00..U+9FFF)
@@ -39,7 +383,7 @@ fn looks_like_valid_chinese_utf8(text: &str) -> bool {
     })
 }
 
-fn convert_encoding(resources_dir: &Path) -> (usize, usize, usize) {
+fn convert_encoding(resources_dir: &Path, dry_run: bool, report: &Report) -> (usize, usize, usize) {
     println!("\n╔══════════════════════════════════════╗");
     println!("║  Step 1: GBK → UTF-8 Encoding       ║");
     println!("╚══════════════════════════════════════╝");
@@ -94,11 +438,21 @@ fn convert_encoding(resources_dir: &Path) -> (usize, usize, usize) {
                     // Fall through to GBK decode.
                 }
 
+                if dry_run {
+                    println!(
+                        "  [dry-run] would re-encode {:?} ({} bytes)",
+                        file,
+                        raw.len()
+                    );
+                    converted.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+
                 // Decode from GBK
                 let (decoded, _, had_errors) = GBK.decode(&raw);
                 if had_errors {
                     // Still write it, but note the error
-                    eprintln!("  WARNING: encoding errors in {:?}", file);
+                    report.warn("encoding", file, "GBK decode had errors");
                 }
 
                 match std::fs::write(file, decoded.as_bytes()) {
@@ -106,13 +460,15 @@ fn convert_encoding(resources_dir: &Path) -> (usize, usize, usize) {
                         converted.fetch_add(1, Ordering::Relaxed);
                     }
                     Err(e) => {
-                        eprintln!("  WRITE ERROR {:?}: {}", file, e);
+                        report.echo(format_args!("  WRITE ERROR {:?}: {}", file, e));
+                        report.fail("encoding", file, format!("write error: {}", e));
                         failed.fetch_add(1, Ordering::Relaxed);
                     }
                 }
             }
             Err(e) => {
-                eprintln!("  READ ERROR {:?}: {}", file, e);
+                report.echo(format_args!("  READ ERROR {:?}: {}", file, e));
+                report.fail("encoding", file, format!("read error: {}", e));
                 failed.fetch_add(1, Ordering::Relaxed);
             }
         }
@@ -129,9 +485,14 @@ fn convert_encoding(resources_dir: &Path) -> (usize, usize, usize) {
 
 // Re-use the msf module from main.rs
 mod asf_msf {
+    use crate::anim_tags::{check_tag_range, tags_chunk, AnimTag};
+    use std::collections::HashMap;
+
     pub const MSF_MAGIC: &[u8; 4] = b"MSF2";
     pub const MSF_VERSION: u16 = 2;
     pub const CHUNK_END: &[u8; 4] = b"END\0";
+    /// Extension chunk holding the exact ASF interval (ms, u16)
+    pub const CHUNK_IVAL: &[u8; 4] = b"IVAL";
     const FRAME_ENTRY_SIZE: usize = 16;
 
     struct FrameEntry {
@@ -278,13 +639,16 @@ mod asf_msf {
         }
     }
 
-    pub fn convert_asf_to_msf(asf_data: &[u8]) -> Option<Vec<u8>> {
-        if asf_data.len() < 80 {
-            return None;
-        }
-        let sig = std::str::from_utf8(&asf_data[0..7]).ok()?;
-        if sig != "ASF 1.0" {
-            return None;
+    /// Fails for non-ASF data, for files with an empty palette, whose
+    /// frames would otherwise convert to a blank sheet, and for tags past
+    /// the frames of one direction
+    pub fn convert_asf_to_msf(
+        asf_data: &[u8],
+        zstd_level: i32,
+        tags: &[AnimTag],
+    ) -> Result<Vec<u8>, String> {
+        if asf_data.len() < 80 || &asf_data[0..7] != b"ASF 1.0" {
+            return Err("not an ASF 1.0 file".to_string());
         }
 
         let mut offset = 16usize;
@@ -323,6 +687,13 @@ mod asf_msf {
             offset += 4;
             palette.push([r, g, b, 255]);
         }
+        if palette.is_empty() && frame_count > 0 {
+            return Err(format!(
+                "empty palette (color_count = {}), frames would decode blank",
+                color_count
+            ));
+        }
+        check_tag_range(tags, frame_count, directions)?;
 
         let mut frame_offsets = Vec::with_capacity(frame_count as usize);
         let mut frame_lengths = Vec::with_capacity(frame_count as usize);
@@ -397,23 +768,34 @@ mod asf_msf {
             }
         }
 
+        // Byte-identical payloads (e.g. repeated idle frames) are stored once
         let mut concat_raw = Vec::new();
+        let mut stored: HashMap<&[u8], u32> = HashMap::new();
         for (i, data) in raw_frame_data.iter().enumerate() {
-            frame_entries[i].data_offset = concat_raw.len() as u32;
+            let data_offset = *stored.entry(data.as_slice()).or_insert_with(|| {
+                let off = concat_raw.len() as u32;
+                concat_raw.extend_from_slice(data);
+                off
+            });
+            frame_entries[i].data_offset = data_offset;
             frame_entries[i].data_length = data.len() as u32;
-            concat_raw.extend_from_slice(data);
         }
 
         let flags: u16 = 1;
-        let compressed_blob = zstd::bulk::compress(&concat_raw, 3).ok()?;
+        let compressed_blob =
+            zstd::bulk::compress(&concat_raw, zstd_level).map_err(|e| format!("zstd: {}", e))?;
         let palette_bytes = palette.len() * 4;
         let frame_table_bytes = frame_count as usize * FRAME_ENTRY_SIZE;
+        let ival_chunk_bytes = if interval > 0 { 8 + 2 } else { 0 };
+        let tags_chunk = tags_chunk(tags);
         let end_chunk_bytes = 8;
         let total = 8
             + 16
             + 4
             + palette_bytes
             + frame_table_bytes
+            + ival_chunk_bytes
+            + tags_chunk.len()
             + end_chunk_bytes
             + compressed_blob.len();
         let mut out = Vec::with_capacity(total);
@@ -443,16 +825,24 @@ mod asf_msf {
             out.extend_from_slice(&entry.data_offset.to_le_bytes());
             out.extend_from_slice(&entry.data_length.to_le_bytes());
         }
+        if interval > 0 {
+            out.extend_from_slice(CHUNK_IVAL);
+            out.extend_from_slice(&2u32.to_le_bytes());
+            out.extend_from_slice(&interval.to_le_bytes());
+        }
+        out.extend_from_slice(&tags_chunk);
         out.extend_from_slice(CHUNK_END);
         out.extend_from_slice(&0u32.to_le_bytes());
         out.extend_from_slice(&compressed_blob);
-        Some(out)
+        Ok(out)
     }
 }
 
 // ============= MPC → MSF Conversion =============
 
 mod mpc_msf {
+    use crate::anim_tags::{check_tag_range, tags_chunk, AnimTag};
+
     pub const MSF_MAGIC: &[u8; 4] = b"MSF2";
     pub const MSF_VERSION: u16 = 2;
     pub const CHUNK_END: &[u8; 4] = b"END\0";
@@ -615,6 +1005,7 @@ mod mpc_msf {
     ///
     /// This avoids all palette-index ambiguity and works correctly even when all 256
     /// palette entries are in use (which happens for ~1879 files in resources-sword2).
+    #[allow(clippy::too_many_arguments)]
     fn decode_mpc_rle_to_rgba(
         data: &[u8],
         rle_start: usize,
@@ -677,13 +1068,16 @@ mod mpc_msf {
         mpc_data: &[u8],
         shd_data: Option<&[u8]>,
         use_palette_alpha: bool,
-    ) -> Option<Vec<u8>> {
+        zstd_level: i32,
+        tags: &[AnimTag],
+    ) -> Result<Vec<u8>, String> {
         if mpc_data.len() < 160 {
-            return None;
+            return Err(format!("file too short: {} bytes", mpc_data.len()));
         }
-        let sig = std::str::from_utf8(&mpc_data[0..12]).ok()?;
-        if !sig.starts_with("MPC File Ver") {
-            return None;
+        let sig = String::from_utf8_lossy(&mpc_data[0..12]);
+        let is_shd = sig.starts_with("SHD File Ver");
+        if !sig.starts_with("MPC File Ver") && !is_shd {
+            return Err(format!("unrecognized signature {:?}", sig));
         }
 
         let off = 64;
@@ -691,9 +1085,15 @@ mod mpc_msf {
         let global_height = get_u32_le(mpc_data, off + 8) as u16;
         let frame_count = get_u32_le(mpc_data, off + 12) as u16;
         let direction = get_u32_le(mpc_data, off + 16) as u8;
-        let color_count = get_u32_le(mpc_data, off + 20) as usize;
+        // SHD shares the MPC header but has no palette: frame offsets start at byte 128
+        let color_count = if is_shd {
+            0
+        } else {
+            get_u32_le(mpc_data, off + 20) as usize
+        };
         let interval = get_u32_le(mpc_data, off + 24) as u16;
         let raw_bottom = get_i32_le(mpc_data, off + 28);
+        check_tag_range(tags, frame_count, direction)?;
 
         let left = (global_width / 2) as i16;
         let bottom = if global_height >= 16 {
@@ -738,9 +1138,14 @@ mod mpc_msf {
         let frame_data_start = offsets_start + frame_count as usize * 4;
 
         // Decode SHD shadow frames if provided
-        let shd_frames = shd_data
-            .map(|sd| decode_shd_frames(sd, frame_count as usize))
-            .unwrap_or_default();
+        // (a standalone SHD file is decoded as its own shadow frames)
+        let shd_frames = if is_shd {
+            decode_shd_frames(mpc_data, frame_count as usize)
+        } else {
+            shd_data
+                .map(|sd| decode_shd_frames(sd, frame_count as usize))
+                .unwrap_or_default()
+        };
 
         let mut frame_entries: Vec<FrameEntry> = Vec::with_capacity(frame_count as usize);
         let mut raw_frame_data: Vec<Vec<u8>> = Vec::with_capacity(frame_count as usize);
@@ -791,16 +1196,25 @@ mod mpc_msf {
                 .get(i)
                 .filter(|s| !s.is_empty())
                 .map(|s| s.as_slice());
-            let rgba = decode_mpc_rle_to_rgba(
-                mpc_data,
-                rle_start,
-                rle_end,
-                width as usize,
-                height as usize,
-                &palette,
-                shadow,
-                use_palette_alpha,
-            );
+            let rgba = if is_shd {
+                // SHD runs carry no palette indices; the shadow decoder already made RGBA
+                shd_frames
+                    .get(i)
+                    .filter(|s| !s.is_empty())
+                    .cloned()
+                    .unwrap_or_else(|| vec![0u8; width as usize * height as usize * 4])
+            } else {
+                decode_mpc_rle_to_rgba(
+                    mpc_data,
+                    rle_start,
+                    rle_end,
+                    width as usize,
+                    height as usize,
+                    &palette,
+                    shadow,
+                    use_palette_alpha,
+                )
+            };
             frame_entries.push(FrameEntry {
                 offset_x: 0,
                 offset_y: 0,
@@ -836,10 +1250,12 @@ mod mpc_msf {
             .unwrap_or(global_height);
 
         let flags: u16 = 1; // zstd
-        let compressed_blob = zstd::bulk::compress(&concat_raw, 3).ok()?;
+        let compressed_blob =
+            zstd::bulk::compress(&concat_raw, zstd_level).map_err(|e| format!("zstd: {}", e))?;
         // PixelFormat 0 = Rgba8, no palette needed
         let frame_table_bytes = frame_count as usize * FRAME_ENTRY_SIZE;
-        let total = 8 + 16 + 4 + frame_table_bytes + 8 + compressed_blob.len();
+        let tags_chunk = tags_chunk(tags);
+        let total = 8 + 16 + 4 + frame_table_bytes + tags_chunk.len() + 8 + compressed_blob.len();
         let mut out = Vec::with_capacity(total);
 
         out.extend_from_slice(MSF_MAGIC);
@@ -866,10 +1282,11 @@ mod mpc_msf {
             out.extend_from_slice(&entry.data_offset.to_le_bytes());
             out.extend_from_slice(&entry.data_length.to_le_bytes());
         }
+        out.extend_from_slice(&tags_chunk);
         out.extend_from_slice(CHUNK_END);
         out.extend_from_slice(&0u32.to_le_bytes());
         out.extend_from_slice(&compressed_blob);
-        Some(out)
+        Ok(out)
     }
 }
 
@@ -877,6 +1294,7 @@ mod mpc_msf {
 
 mod map_mmf {
     use super::*;
+    use crate::map_layout::{is_map_file, map_layout, MAP_LAYOUT_CLASSIC};
 
     struct MapTile {
         l1_frame: u8,
@@ -895,6 +1313,8 @@ mod map_mmf {
         mpc_names: Vec<Option<String>>,
         mpc_looping: Vec<bool>,
         tiles: Vec<MapTile>,
+        /// Set when the header version is unrecognized and its layout was guessed
+        warning: Option<String>,
     }
 
     fn get_i32_le(data: &[u8], offset: usize) -> i32 {
@@ -925,22 +1345,26 @@ mod map_mmf {
         decoded.into_owned()
     }
 
-    fn parse_old_map(data: &[u8]) -> Option<OldMapData> {
-        if data.len() < 16512 {
-            return None;
+    fn parse_old_map(data: &[u8]) -> Result<OldMapData, String> {
+        if !is_map_file(data) {
+            return Err("missing \"MAP File Ver\" signature".to_string());
         }
-        let header = std::str::from_utf8(&data[0..12]).ok()?;
-        if header != "MAP File Ver" {
-            return None;
+        if data.len() < MAP_LAYOUT_CLASSIC.tile_offset {
+            return Err(format!(
+                "{} bytes is shorter than the {}-byte header",
+                data.len(),
+                MAP_LAYOUT_CLASSIC.tile_offset
+            ));
         }
+        let (layout, warning) = map_layout(data);
 
-        let columns = get_i32_le(data, 68) as u16;
-        let rows = get_i32_le(data, 72) as u16;
+        let columns = get_i32_le(data, layout.dims_offset) as u16;
+        let rows = get_i32_le(data, layout.dims_offset + 4) as u16;
 
         let mut mpc_names: Vec<Option<String>> = Vec::with_capacity(255);
         let mut mpc_looping: Vec<bool> = Vec::with_capacity(255);
         for k in 0..255 {
-            let offset = 192 + k * 64;
+            let offset = layout.mpc_list_offset + k * 64;
             let name = read_gbk_string(data, offset, 32);
             if name.is_empty() {
                 mpc_names.push(None);
@@ -953,9 +1377,9 @@ mod map_mmf {
 
         let total_tiles = columns as usize * rows as usize;
         let mut tiles = Vec::with_capacity(total_tiles);
-        let mut offset = 16512;
+        let mut offset = layout.tile_offset;
         for _ in 0..total_tiles {
-            if offset + 10 > data.len() {
+            if offset + layout.tile_stride > data.len() {
                 break;
             }
             tiles.push(MapTile {
@@ -968,15 +1392,16 @@ mod map_mmf {
                 barrier: data[offset + 6],
                 trap: data[offset + 7],
             });
-            offset += 10;
+            offset += layout.tile_stride;
         }
 
-        Some(OldMapData {
+        Ok(OldMapData {
             columns,
             rows,
             mpc_names,
             mpc_looping,
             tiles,
+            warning,
         })
     }
 
@@ -990,14 +1415,28 @@ mod map_mmf {
         looping: bool,
     }
 
-    fn convert_map_to_mmf(map_data: &OldMapData, trap_entries: &[TrapEntry]) -> Vec<u8> {
+    /// Most MSF table entries a map can hold: tiles store the 1-based index as
+    /// a single byte, with 0 meaning empty
+    const MAX_MSF_ENTRIES: usize = 255;
+
+    fn convert_map_to_mmf(
+        map_data: &OldMapData,
+        trap_entries: &[TrapEntry],
+        zstd_level: i32,
+    ) -> Result<Vec<u8>, String> {
+        let used = map_data.mpc_names.iter().flatten().count();
+        if used > MAX_MSF_ENTRIES {
+            return Err(format!(
+                "map references {} MSF files, but the MMF tile index holds at most {}",
+                used, MAX_MSF_ENTRIES
+            ));
+        }
         let mut old_to_new: HashMap<u8, u8> = HashMap::new();
         let mut msf_entries: Vec<MsfEntry> = Vec::new();
-        let mut new_idx: u8 = 1;
 
         for (old_idx, name_opt) in map_data.mpc_names.iter().enumerate() {
             if let Some(name) = name_opt {
-                old_to_new.insert(old_idx as u8, new_idx);
+                old_to_new.insert(old_idx as u8, (msf_entries.len() + 1) as u8);
                 let msf_name = if name.to_lowercase().ends_with(".mpc") {
                     format!("{}.msf", &name[..name.len() - 4])
                 } else {
@@ -1007,7 +1446,6 @@ mod map_mmf {
                     name: msf_name,
                     looping: map_data.mpc_looping[old_idx],
                 });
-                new_idx += 1;
             }
         }
 
@@ -1098,9 +1536,9 @@ mod map_mmf {
             blob.push(tile.trap);
         }
 
-        let compressed = zstd::bulk::compress(&blob, 3).expect("zstd compression failed");
+        let compressed = zstd::bulk::compress(&blob, zstd_level).expect("zstd compression failed");
         out.extend_from_slice(&compressed);
-        out
+        Ok(out)
     }
 
     pub fn parse_traps_ini(content: &str) -> HashMap<String, HashMap<u8, String>> {
@@ -1132,11 +1570,14 @@ mod map_mmf {
     pub fn convert_all_maps(
         resources_dir: &Path,
         all_traps: &HashMap<String, HashMap<u8, String>>,
-    ) -> (usize, usize) {
+        opts: &ConvertOptions,
+        cache: Option<&SourceCache>,
+        report: &Report,
+    ) -> (usize, usize, usize) {
         let map_dir = resources_dir.join("map");
         if !map_dir.exists() {
             println!("  No map directory found, skipping");
-            return (0, 0);
+            return (0, 0, 0);
         }
 
         let map_files: Vec<PathBuf> = WalkDir::new(&map_dir)
@@ -1156,8 +1597,14 @@ mod map_mmf {
 
         let converted = AtomicUsize::new(0);
         let failed = AtomicUsize::new(0);
+        let skipped = AtomicUsize::new(0);
 
         map_files.par_iter().for_each(|map_path| {
+            if opts.skip_existing && is_up_to_date(&map_path.with_extension("mmf"), &[map_path]) {
+                skipped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+
             let map_name = map_path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
             let trap_entries: Vec<TrapEntry> = all_traps
                 .get(map_name)
@@ -1172,39 +1619,88 @@ mod map_mmf {
                 })
                 .unwrap_or_default();
 
-            match std::fs::read(map_path) {
-                Ok(raw) => {
-                    // Skip files that don't look like MAP format (wrong header or too small)
-                    let is_map = raw.len() >= 12
-                        && std::str::from_utf8(&raw[0..12])
-                            .map(|h| h == "MAP File Ver")
-                            .unwrap_or(false);
-                    if !is_map {
-                        eprintln!(
-                            "  SKIP (not a MAP file, {} bytes) {:?}",
-                            raw.len(),
-                            map_path
-                        );
-                        return;
+            let raw = match std::fs::read(map_path) {
+                Ok(raw) => raw,
+                Err(e) => {
+                    report.fail("map", map_path, format!("read error: {}", e));
+                    failed.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            };
+            // Skip files that don't look like MAP format (wrong header)
+            if !is_map_file(&raw) {
+                let message = format!("skipped, not a MAP file ({} bytes)", raw.len());
+                report.warn("map", map_path, &message);
+                return;
+            }
+
+            let mmf_path = map_path.with_extension("mmf");
+            // The trap table is embedded in the MMF, so it is part of the hash
+            let mut trap_lines: Vec<String> = trap_entries
+                .iter()
+                .map(|t| format!("{}={}\n", t.trap_index, t.script_path))
+                .collect();
+            trap_lines.sort();
+            let hash = content_hash(
+                opts.zstd_level as u64,
+                &[&raw, trap_lines.concat().as_bytes()],
+            );
+            if let Some(cache) = cache {
+                if cache.is_unchanged(map_path, hash, &mmf_path) {
+                    skipped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            }
+            if opts.dry_run {
+                print_would_convert(map_path, &mmf_path);
+                report.plan_output(&mmf_path);
+                converted.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+
+            let map_data = match parse_old_map(&raw) {
+                Ok(map_data) => {
+                    if let Some(warning) = &map_data.warning {
+                        report.warn("map", map_path, warning);
                     }
-                    match parse_old_map(&raw) {
-                        Some(map_data) => {
-                            let mmf_data = convert_map_to_mmf(&map_data, &trap_entries);
-                            let mut mmf_path = map_path.clone();
-                            mmf_path.set_extension("mmf");
-                            if std::fs::write(&mmf_path, &mmf_data).is_ok() {
-                                converted.fetch_add(1, Ordering::Relaxed);
-                            } else {
-                                failed.fetch_add(1, Ordering::Relaxed);
-                            }
-                        }
-                        None => {
-                            failed.fetch_add(1, Ordering::Relaxed);
-                        }
+                    map_data
+                }
+                Err(e) => {
+                    report.fail("map", map_path, format!("failed to parse MAP data: {}", e));
+                    failed.fetch_add(1, Ordering::Relaxed);
+                    if let Some(cache) = cache {
+                        cache.forget(map_path);
+                    }
+                    return;
+                }
+            };
+            let mmf_data = match convert_map_to_mmf(&map_data, &trap_entries, opts.zstd_level) {
+                Ok(mmf_data) => mmf_data,
+                Err(e) => {
+                    report.echo(format_args!("  CONVERT ERROR {:?}: {}", map_path, e));
+                    report.fail("map", map_path, e);
+                    failed.fetch_add(1, Ordering::Relaxed);
+                    if let Some(cache) = cache {
+                        cache.forget(map_path);
+                    }
+                    return;
+                }
+            };
+            match std::fs::write(&mmf_path, &mmf_data) {
+                Ok(_) => {
+                    report.add_bytes(raw.len(), mmf_data.len());
+                    report.record(ManifestEntry::mmf(map_path, &mmf_path, &mmf_data));
+                    if let Some(cache) = cache {
+                        cache.store(map_path, hash);
                     }
+                    converted.fetch_add(1, Ordering::Relaxed);
                 }
-                Err(_) => {
+                Err(e) => {
+                    report.fail("map", map_path, format!("write error: {}", e));
                     failed.fetch_add(1, Ordering::Relaxed);
+                    if let Some(cache) = cache {
+                        cache.forget(map_path);
+                    }
                 }
             }
         });
@@ -1212,6 +1708,7 @@ mod map_mmf {
         (
             converted.load(Ordering::Relaxed),
             failed.load(Ordering::Relaxed),
+            skipped.load(Ordering::Relaxed),
         )
     }
 }
@@ -1249,9 +1746,6 @@ fn mpc_output_path(resources_dir: &Path, mpc_path: &Path) -> PathBuf {
             // Redirect: mpc/{sprite_dir}/…/foo.mpc → asf/{sprite_dir}/…/foo.msf
             let mut out = resources_dir.join("asf").join(rel);
             out.set_extension("msf");
-            if let Some(parent) = out.parent() {
-                let _ = std::fs::create_dir_all(parent);
-            }
             return out;
         }
     }
@@ -1261,11 +1755,16 @@ fn mpc_output_path(resources_dir: &Path, mpc_path: &Path) -> PathBuf {
     out
 }
 
-fn convert_asf_files(resources_dir: &Path) -> (usize, usize) {
+fn convert_asf_files(
+    resources_dir: &Path,
+    opts: &ConvertOptions,
+    cache: Option<&SourceCache>,
+    report: &Report,
+) -> (usize, usize, usize) {
     let asf_dir = resources_dir.join("asf");
     if !asf_dir.exists() {
         println!("  No asf directory found, skipping");
-        return (0, 0);
+        return (0, 0, 0);
     }
 
     let asf_files: Vec<PathBuf> = WalkDir::new(&asf_dir)
@@ -1285,54 +1784,116 @@ fn convert_asf_files(resources_dir: &Path) -> (usize, usize) {
 
     let converted = AtomicUsize::new(0);
     let failed = AtomicUsize::new(0);
+    let skipped = AtomicUsize::new(0);
 
-    asf_files
-        .par_iter()
-        .for_each(|asf_path| match std::fs::read(asf_path) {
-            Ok(asf_data) => match asf_msf::convert_asf_to_msf(&asf_data) {
-                Some(msf_data) => {
-                    let mut msf_path = asf_path.clone();
-                    msf_path.set_extension("msf");
-                    if std::fs::write(&msf_path, &msf_data).is_ok() {
-                        let n = converted.fetch_add(1, Ordering::Relaxed) + 1;
-                        if n % 200 == 0 || n == total {
-                            println!("  [{}/{}]", n, total);
-                        }
-                    } else {
-                        failed.fetch_add(1, Ordering::Relaxed);
-                    }
-                }
-                None => {
+    asf_files.par_iter().for_each(|asf_path| {
+        let msf_path = asf_path.with_extension("msf");
+        let anim_path = asf_path.with_extension("anim");
+        if opts.skip_existing && is_up_to_date(&msf_path, &[asf_path, &anim_path]) {
+            skipped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let asf_data = match std::fs::read(asf_path) {
+            Ok(data) => data,
+            Err(e) => {
+                report.fail("asf", asf_path, format!("read error: {}", e));
+                failed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+        let (anim_text, tags) = match anim_tags::read_anim_tags(asf_path) {
+            Ok(anim) => anim,
+            Err(e) => {
+                report.fail("asf", asf_path, format!("anim error: {}", e));
+                failed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+        let hash = content_hash(opts.zstd_level as u64, &[&asf_data, anim_text.as_bytes()]);
+        if let Some(cache) = cache {
+            if cache.is_unchanged(asf_path, hash, &msf_path) {
+                skipped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        if opts.dry_run {
+            print_would_convert(asf_path, &msf_path);
+            report.plan_output(&msf_path);
+            converted.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        match asf_msf::convert_asf_to_msf(&asf_data, opts.zstd_level, &tags) {
+            Ok(msf_data) => {
+                let written = std::fs::write(&msf_path, &msf_data);
+                if let Err(e) = written {
+                    report.fail("asf", asf_path, format!("write error: {}", e));
                     failed.fetch_add(1, Ordering::Relaxed);
+                    if let Some(cache) = cache {
+                        cache.forget(asf_path);
+                    }
+                } else {
+                    report.add_bytes(asf_data.len(), msf_data.len());
+                    report.record(ManifestEntry::msf(asf_path, &msf_path, &msf_data));
+                    if let Some(cache) = cache {
+                        cache.store(asf_path, hash);
+                    }
+                    let n = converted.fetch_add(1, Ordering::Relaxed) + 1;
+                    if n.is_multiple_of(200) || n == total {
+                        println!("  [{}/{}]", n, total);
+                    }
                 }
-            },
-            Err(_) => {
+            }
+            Err(e) => {
+                report.echo(format_args!("  CONVERT ERROR {:?}: {}", asf_path, e));
+                report.fail("asf", asf_path, e);
                 failed.fetch_add(1, Ordering::Relaxed);
+                if let Some(cache) = cache {
+                    cache.forget(asf_path);
+                }
             }
-        });
+        }
+    });
 
     (
         converted.load(Ordering::Relaxed),
         failed.load(Ordering::Relaxed),
+        skipped.load(Ordering::Relaxed),
     )
 }
 
-fn convert_mpc_files(resources_dir: &Path) -> (usize, usize) {
+/// True if an .mpc with the same stem sits next to this .shd
+fn has_mpc_sibling(shd_path: &Path) -> bool {
+    shd_path.with_extension("mpc").exists() || shd_path.with_extension("MPC").exists()
+}
+
+fn convert_mpc_files(
+    resources_dir: &Path,
+    opts: &ConvertOptions,
+    cache: Option<&SourceCache>,
+    report: &Report,
+) -> (usize, usize, usize) {
     let resources_dir = resources_dir.to_path_buf(); // own for Send in parallel closure
     let mpc_dir = resources_dir.join("mpc");
     if !mpc_dir.exists() {
         println!("  No mpc directory found, skipping");
-        return (0, 0);
+        return (0, 0, 0);
     }
 
     let mpc_files: Vec<PathBuf> = WalkDir::new(&mpc_dir)
         .into_iter()
         .filter_map(|e| e.ok())
         .filter(|e| {
-            e.path()
+            let ext = e
+                .path()
                 .extension()
-                .map(|ext| ext.eq_ignore_ascii_case("mpc"))
-                .unwrap_or(false)
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("");
+            // SHD files next to an MPC are merged into its MSF as shadows;
+            // only standalone ones are converted on their own
+            ext.eq_ignore_ascii_case("mpc")
+                || (ext.eq_ignore_ascii_case("shd") && !has_mpc_sibling(e.path()))
         })
         .map(|e| e.into_path())
         .collect();
@@ -1342,12 +1903,56 @@ fn convert_mpc_files(resources_dir: &Path) -> (usize, usize) {
 
     let converted = AtomicUsize::new(0);
     let failed = AtomicUsize::new(0);
+    let skipped = AtomicUsize::new(0);
 
     mpc_files.par_iter().for_each(|mpc_path| {
         // Check for adjacent .shd file (same stem, same directory)
         let shd_path = mpc_path.with_extension("shd");
+        let anim_path = mpc_path.with_extension("anim");
+        let msf_path = mpc_output_path(&resources_dir, mpc_path);
+        if opts.skip_existing && is_up_to_date(&msf_path, &[mpc_path, &shd_path, &anim_path]) {
+            skipped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let mpc_data = match std::fs::read(mpc_path) {
+            Ok(data) => data,
+            Err(e) => {
+                report.fail("mpc", mpc_path, format!("read error: {}", e));
+                failed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
         let shd_bytes = std::fs::read(&shd_path).ok();
         let shd_data = shd_bytes.as_deref();
+        let (anim_text, tags) = match anim_tags::read_anim_tags(mpc_path) {
+            Ok(anim) => anim,
+            Err(e) => {
+                report.fail("mpc", mpc_path, format!("anim error: {}", e));
+                failed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+        let hash = content_hash(
+            opts.zstd_level as u64,
+            &[
+                &mpc_data,
+                shd_data.unwrap_or_default(),
+                anim_text.as_bytes(),
+            ],
+        );
+        if let Some(cache) = cache {
+            if cache.is_unchanged(mpc_path, hash, &msf_path) {
+                skipped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        if opts.dry_run {
+            print_would_convert(mpc_path, &msf_path);
+            report.plan_output(&msf_path);
+            converted.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
 
         // Whether to honour the palette 4th-byte as per-pixel alpha:
         //
@@ -1363,7 +1968,7 @@ fn convert_mpc_files(resources_dir: &Path) -> (usize, usize) {
         // All other files follow binary transparency: transparent = RLE skip (>0x80),
         //   visible = opaque (alpha = 0xFF).
         let use_palette_alpha = {
-            if let Ok(rel) = mpc_path.strip_prefix(&resources_dir.join("mpc")) {
+            if let Ok(rel) = mpc_path.strip_prefix(resources_dir.join("mpc")) {
                 let comps: Vec<_> = rel
                     .components()
                     .filter_map(|c| c.as_os_str().to_str().map(|s| s.to_lowercase()))
@@ -1380,27 +1985,44 @@ fn convert_mpc_files(resources_dir: &Path) -> (usize, usize) {
                 false
             }
         };
-        match std::fs::read(mpc_path) {
-            Ok(mpc_data) => {
-                match mpc_msf::convert_mpc_to_msf(&mpc_data, shd_data, use_palette_alpha) {
-                    Some(msf_data) => {
-                        let msf_path = mpc_output_path(&resources_dir, mpc_path);
-                        if std::fs::write(&msf_path, &msf_data).is_ok() {
-                            let n = converted.fetch_add(1, Ordering::Relaxed) + 1;
-                            if n % 100 == 0 || n == total {
-                                println!("  [{}/{}]", n, total);
-                            }
-                        } else {
-                            failed.fetch_add(1, Ordering::Relaxed);
-                        }
+        match mpc_msf::convert_mpc_to_msf(
+            &mpc_data,
+            shd_data,
+            use_palette_alpha,
+            opts.zstd_level,
+            &tags,
+        ) {
+            Ok(msf_data) => {
+                // Sprite MPCs are redirected under asf/, which may not exist yet
+                if let Some(parent) = msf_path.parent() {
+                    let _ = std::fs::create_dir_all(parent);
+                }
+                let written = std::fs::write(&msf_path, &msf_data);
+                if let Err(e) = written {
+                    report.fail("mpc", mpc_path, format!("write error: {}", e));
+                    failed.fetch_add(1, Ordering::Relaxed);
+                    if let Some(cache) = cache {
+                        cache.forget(mpc_path);
                     }
-                    None => {
-                        failed.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    let shd_len = shd_data.map_or(0, |d| d.len());
+                    report.add_bytes(mpc_data.len() + shd_len, msf_data.len());
+                    report.record(ManifestEntry::msf(mpc_path, &msf_path, &msf_data));
+                    if let Some(cache) = cache {
+                        cache.store(mpc_path, hash);
+                    }
+                    let n = converted.fetch_add(1, Ordering::Relaxed) + 1;
+                    if n.is_multiple_of(100) || n == total {
+                        println!("  [{}/{}]", n, total);
                     }
                 }
             }
-            Err(_) => {
+            Err(e) => {
+                report.fail("mpc", mpc_path, e);
                 failed.fetch_add(1, Ordering::Relaxed);
+                if let Some(cache) = cache {
+                    cache.forget(mpc_path);
+                }
             }
         }
     });
@@ -1408,14 +2030,46 @@ fn convert_mpc_files(resources_dir: &Path) -> (usize, usize) {
     (
         converted.load(Ordering::Relaxed),
         failed.load(Ordering::Relaxed),
+        skipped.load(Ordering::Relaxed),
     )
 }
 
 // ============= Media conversion (ffmpeg) =============
 
-fn convert_media_files(resources_dir: &Path) -> (usize, usize, usize) {
+/// Default VP9 CRF for WMV → WebM
+const DEFAULT_VIDEO_CRF: u32 = 30;
+
+/// ffmpeg settings for the media step
+struct MediaOptions {
+    /// ffmpeg binary (`--ffmpeg`, default `ffmpeg` from PATH)
+    ffmpeg: PathBuf,
+    /// VP9 CRF, 0–63 (`--video-crf`)
+    video_crf: u32,
+    /// Audio bitrate such as `128k` (`--audio-bitrate`); applies to the Opus
+    /// track of videos and to OGG music, which otherwise uses Vorbis quality 6
+    audio_bitrate: Option<String>,
+}
+
+/// True if `ffmpeg -version` runs successfully
+fn ffmpeg_available(ffmpeg: &Path) -> bool {
+    std::process::Command::new(ffmpeg)
+        .arg("-version")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+fn convert_media_files(
+    resources_dir: &Path,
+    media: &MediaOptions,
+    dry_run: bool,
+    report: &Report,
+) -> (usize, usize, usize, usize) {
     let mut video_ok = 0usize;
     let mut music_ok = 0usize;
+    let mut skipped = 0usize;
     let mut failed = 0usize;
 
     // Video: WMV → WebM
@@ -1440,23 +2094,32 @@ fn convert_media_files(resources_dir: &Path) -> (usize, usize, usize) {
             let webm = wmv.with_extension("webm");
             if webm.exists() {
                 println!("  [skip] {:?} already exists", webm.file_name().unwrap());
+                skipped += 1;
+                continue;
+            }
+            if dry_run {
+                print_would_convert(wmv, &webm);
+                report.plan_output(&webm);
+                video_ok += 1;
                 continue;
             }
             println!("  Converting {:?}...", wmv.file_name().unwrap());
-            let result = std::process::Command::new("ffmpeg")
+            let crf = media.video_crf.to_string();
+            let bitrate = media.audio_bitrate.as_deref().unwrap_or("128k");
+            let result = std::process::Command::new(&media.ffmpeg)
                 .args(["-y", "-i"])
                 .arg(wmv)
                 .args([
                     "-c:v",
                     "libvpx-vp9",
                     "-crf",
-                    "30",
+                    &crf,
                     "-b:v",
                     "0",
                     "-c:a",
                     "libopus",
                     "-b:a",
-                    "128k",
+                    bitrate,
                 ])
                 .arg(&webm)
                 .args(["-loglevel", "warning"])
@@ -1466,9 +2129,15 @@ fn convert_media_files(resources_dir: &Path) -> (usize, usize, usize) {
                     video_ok += 1;
                     println!("  [done] {:?}", webm.file_name().unwrap());
                 }
-                _ => {
+                Ok(status) => {
                     failed += 1;
-                    eprintln!("  [fail] {:?}", wmv.file_name().unwrap());
+                    report.fail("media", wmv, format!("ffmpeg exited with {}", status));
+                    report.echo(format_args!("  [fail] {:?}", wmv.file_name().unwrap()));
+                }
+                Err(e) => {
+                    failed += 1;
+                    report.fail("media", wmv, format!("failed to run ffmpeg: {}", e));
+                    report.echo(format_args!("  [fail] {:?}", wmv.file_name().unwrap()));
                 }
             }
         }
@@ -1494,13 +2163,25 @@ fn convert_media_files(resources_dir: &Path) -> (usize, usize, usize) {
         for wma in &wma_files {
             let ogg = wma.with_extension("ogg");
             if ogg.exists() {
+                skipped += 1;
+                continue;
+            }
+            if dry_run {
+                print_would_convert(wma, &ogg);
+                report.plan_output(&ogg);
+                music_ok += 1;
                 continue;
             }
             println!("  Converting {:?}...", wma.file_name().unwrap());
-            let result = std::process::Command::new("ffmpeg")
+            let quality = match &media.audio_bitrate {
+                Some(bitrate) => ["-b:a", bitrate.as_str()],
+                None => ["-q:a", "6"],
+            };
+            let result = std::process::Command::new(&media.ffmpeg)
                 .args(["-y", "-i"])
                 .arg(wma)
-                .args(["-acodec", "libvorbis", "-q:a", "6"])
+                .args(["-acodec", "libvorbis"])
+                .args(quality)
                 .arg(&ogg)
                 .args(["-loglevel", "warning"])
                 .status();
@@ -1508,23 +2189,164 @@ fn convert_media_files(resources_dir: &Path) -> (usize, usize, usize) {
                 Ok(status) if status.success() => {
                     music_ok += 1;
                 }
-                _ => {
+                Ok(status) => {
                     failed += 1;
+                    report.fail("media", wma, format!("ffmpeg exited with {}", status));
+                }
+                Err(e) => {
+                    failed += 1;
+                    report.fail("media", wma, format!("failed to run ffmpeg: {}", e));
                 }
             }
         }
     }
 
-    (video_ok, music_ok, failed)
+    (video_ok, music_ok, skipped, failed)
+}
+
+// ============= Duplicate MSF detection =============
+
+/// A set of byte-identical MSF files; `paths[0]` is the canonical copy
+struct DuplicateGroup {
+    size: usize,
+    paths: Vec<PathBuf>,
+}
+
+/// Hash every .msf under `resources_dir` (xxh3 over the file bytes) and return
+/// groups of identical files, largest potential saving first
+fn find_duplicate_msf(resources_dir: &Path) -> Vec<DuplicateGroup> {
+    let msf_files: Vec<PathBuf> = WalkDir::new(resources_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .map(|ext| ext.eq_ignore_ascii_case("msf"))
+                .unwrap_or(false)
+        })
+        .map(|e| e.into_path())
+        .collect();
+
+    let hashed: Vec<((usize, u64), PathBuf)> = msf_files
+        .into_par_iter()
+        .filter_map(|path| {
+            let data = std::fs::read(&path).ok()?;
+            let hash = twox_hash::XxHash3_64::oneshot(&data);
+            Some(((data.len(), hash), path))
+        })
+        .collect();
+
+    let mut by_content: HashMap<(usize, u64), Vec<PathBuf>> = HashMap::new();
+    for (key, path) in hashed {
+        by_content.entry(key).or_default().push(path);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_content
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((size, _), mut paths)| {
+            paths.sort();
+            DuplicateGroup { size, paths }
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        let saved = |g: &DuplicateGroup| g.size * (g.paths.len() - 1);
+        saved(b).cmp(&saved(a)).then_with(|| a.paths.cmp(&b.paths))
+    });
+    groups
+}
+
+/// Write a JSON manifest mapping each duplicate to its canonical copy
+/// (paths relative to `resources_dir`, `/`-separated)
+fn write_dedupe_manifest(
+    resources_dir: &Path,
+    groups: &[DuplicateGroup],
+    path: &Path,
+) -> std::io::Result<()> {
+    let rel = |p: &Path| {
+        p.strip_prefix(resources_dir)
+            .unwrap_or(p)
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    let groups: Vec<serde_json::Value> = groups
+        .iter()
+        .map(|g| {
+            serde_json::json!({
+                "canonical": rel(&g.paths[0]),
+                "duplicates": g.paths[1..].iter().map(|p| rel(p)).collect::<Vec<_>>(),
+                "size": g.size,
+            })
+        })
+        .collect();
+    let manifest = serde_json::json!({ "groups": groups });
+    let text = serde_json::to_string_pretty(&manifest).map_err(std::io::Error::other)?;
+    std::fs::write(path, text)
+}
+
+/// Write the `--manifest` JSON: one entry per converted asset, sorted by
+/// source, with paths relative to `resources_dir`
+fn write_asset_manifest(
+    resources_dir: &Path,
+    entries: &mut [ManifestEntry],
+    path: &Path,
+) -> std::io::Result<()> {
+    let rel = |p: &Path| {
+        p.strip_prefix(resources_dir)
+            .unwrap_or(p)
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    entries.sort_by(|a, b| a.source.cmp(&b.source));
+    let assets: Vec<serde_json::Value> = entries
+        .iter()
+        .map(|e| {
+            serde_json::json!({
+                "source": rel(&e.source),
+                "output": rel(&e.output),
+                "format": e.format,
+                "width": e.width,
+                "height": e.height,
+                "frames": e.frames,
+                "directions": e.directions,
+                "pixel_format": e.pixel_format,
+                "bytes": e.bytes,
+            })
+        })
+        .collect();
+    let manifest = serde_json::json!({ "assets": assets });
+    let text = serde_json::to_string_pretty(&manifest).map_err(std::io::Error::other)?;
+    std::fs::write(path, text)
 }
 
 // ============= Cleanup =============
 
-fn delete_old_files(resources_dir: &Path) -> (usize, usize, usize) {
+/// Delete originals whose replacement exists. With `planned` (`--dry-run`)
+/// nothing is removed: each original whose replacement exists or is in
+/// `planned` is printed with its size instead.
+fn delete_old_files(
+    resources_dir: &Path,
+    planned: Option<&HashSet<PathBuf>>,
+) -> (usize, usize, usize) {
     let mut asf_deleted = 0usize;
     let mut mpc_deleted = 0usize;
     let mut map_deleted = 0usize;
 
+    let remove = |original: &Path, replacement: &Path| match planned {
+        None => replacement.exists() && std::fs::remove_file(original).is_ok(),
+        Some(planned) => {
+            if !replacement.exists() && !planned.contains(replacement) {
+                return false;
+            }
+            println!(
+                "  [dry-run] would delete {:?} ({} bytes)",
+                original,
+                file_size(original)
+            );
+            true
+        }
+    };
+
     // Delete .asf files (replaced by .msf)
     let asf_dir = resources_dir.join("asf");
     if asf_dir.exists() {
@@ -1542,10 +2364,8 @@ fn delete_old_files(resources_dir: &Path) -> (usize, usize, usize) {
         for f in &asf_files {
             // Only delete if corresponding .msf exists
             let msf = f.with_extension("msf");
-            if msf.exists() {
-                if std::fs::remove_file(f).is_ok() {
-                    asf_deleted += 1;
-                }
+            if remove(f, &msf) {
+                asf_deleted += 1;
             }
         }
     }
@@ -1566,10 +2386,8 @@ fn delete_old_files(resources_dir: &Path) -> (usize, usize, usize) {
             .collect();
         for f in &mpc_files {
             let msf = f.with_extension("msf");
-            if msf.exists() {
-                if std::fs::remove_file(f).is_ok() {
-                    mpc_deleted += 1;
-                }
+            if remove(f, &msf) {
+                mpc_deleted += 1;
             }
         }
     }
@@ -1590,10 +2408,8 @@ fn delete_old_files(resources_dir: &Path) -> (usize, usize, usize) {
             .collect();
         for f in &map_files {
             let mmf = f.with_extension("mmf");
-            if mmf.exists() {
-                if std::fs::remove_file(f).is_ok() {
-                    map_deleted += 1;
-                }
+            if remove(f, &mmf) {
+                map_deleted += 1;
             }
         }
     }
@@ -1614,10 +2430,7 @@ fn delete_old_files(resources_dir: &Path) -> (usize, usize, usize) {
             .map(|e| e.path())
             .collect();
         for f in &wmv_files {
-            let webm = f.with_extension("webm");
-            if webm.exists() {
-                let _ = std::fs::remove_file(f);
-            }
+            remove(f, &f.with_extension("webm"));
         }
     }
 
@@ -1637,10 +2450,7 @@ fn delete_old_files(resources_dir: &Path) -> (usize, usize, usize) {
             .map(|e| e.path())
             .collect();
         for f in &wma_files {
-            let ogg = f.with_extension("ogg");
-            if ogg.exists() {
-                let _ = std::fs::remove_file(f);
-            }
+            remove(f, &f.with_extension("ogg"));
         }
     }
 
@@ -1649,10 +2459,86 @@ fn delete_old_files(resources_dir: &Path) -> (usize, usize, usize) {
 
 // ============= Main =============
 
+/// Default zstd level for frame/tile blobs
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// Parse `--zstd-level <n>` (1–22, default 3); exits with an error on bad input
+fn parse_zstd_level(args: &[String]) -> i32 {
+    let Some(pos) = args.iter().position(|a| a == "--zstd-level") else {
+        return DEFAULT_ZSTD_LEVEL;
+    };
+    match args.get(pos + 1).and_then(|v| v.parse::<i32>().ok()) {
+        Some(level) if (1..=22).contains(&level) => level,
+        _ => {
+            eprintln!("Error: --zstd-level expects an integer between 1 and 22");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parse the media flags; None if `--skip-media` is given. Exits on bad input
+fn parse_media_options(args: &[String]) -> Option<MediaOptions> {
+    if args.iter().any(|a| a == "--skip-media") {
+        return None;
+    }
+    let flag_value = |name: &str| {
+        let pos = args.iter().position(|a| a == name)?;
+        match args.get(pos + 1) {
+            Some(v) => Some(v.clone()),
+            None => {
+                eprintln!("Error: {} expects a value", name);
+                std::process::exit(1);
+            }
+        }
+    };
+    let video_crf = match flag_value("--video-crf") {
+        Some(v) => match v.parse::<u32>() {
+            Ok(crf) if crf <= 63 => crf,
+            _ => {
+                eprintln!("Error: --video-crf expects an integer between 0 and 63");
+                std::process::exit(1);
+            }
+        },
+        None => DEFAULT_VIDEO_CRF,
+    };
+    Some(MediaOptions {
+        ffmpeg: flag_value("--ffmpeg").map_or_else(|| PathBuf::from("ffmpeg"), PathBuf::from),
+        video_crf,
+        audio_bitrate: flag_value("--audio-bitrate"),
+    })
+}
+
+/// Parse `--jobs <n>` and cap rayon's global pool at `n` threads (default: all
+/// cores); exits with an error on bad input
+fn init_thread_pool(args: &[String]) {
+    let Some(pos) = args.iter().position(|a| a == "--jobs") else {
+        return;
+    };
+    let jobs = match args.get(pos + 1).and_then(|v| v.parse::<usize>().ok()) {
+        Some(n) if n >= 1 => n,
+        _ => {
+            eprintln!("Error: --jobs expects a positive integer");
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build_global()
+    {
+        eprintln!("Error: failed to configure thread pool: {}", e);
+        std::process::exit(1);
+    }
+}
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() < 2 {
-        eprintln!("Usage: convert-all <resources_dir> [--delete-originals]");
+        eprintln!(
+            "Usage: convert-all <resources_dir> [--delete-originals] [--zstd-level <1-22>] \
+             [--skip-existing] [--report <path.json>] [--dedupe-manifest <path.json>] \
+             [--skip-media] [--ffmpeg <path>] [--video-crf <0-63>] [--audio-bitrate <rate>] \
+             [--jobs <n>] [--dry-run] [--log <path>] [--manifest <path.json>] [--cache]"
+        );
         eprintln!();
         eprintln!("All-in-one resource converter for Miu2D Engine.");
         eprintln!("Converts ASF/MPC→MSF, MAP→MMF, GBK→UTF-8, WMV→WebM, WMA→OGG.");
@@ -1661,40 +2547,144 @@ fn main() {
         eprintln!(
             "  --delete-originals  Delete old .asf, .mpc, .map, .wmv, .wma files after conversion"
         );
+        eprintln!("  --zstd-level <n>    Zstd level for MSF/MMF blobs, 1-22 (default 3)");
+        eprintln!("  --skip-existing     Skip ASF/MPC/MAP files whose output is already newer");
+        eprintln!("  --report <path>     Write a JSON report of step counts, sizes and failures");
+        eprintln!("  --dedupe-manifest <path>");
+        eprintln!("                      Write a JSON map of byte-identical MSF files to one copy");
+        eprintln!("  --skip-media        Skip the WMV/WMA step (no ffmpeg needed)");
+        eprintln!("  --ffmpeg <path>     ffmpeg binary to use (default: ffmpeg from PATH)");
+        eprintln!("  --video-crf <n>     VP9 CRF for WebM videos, 0-63 (default 30)");
+        eprintln!("  --audio-bitrate <rate>");
+        eprintln!("                      Audio bitrate, e.g. 96k (default: Opus 128k, Vorbis q6)");
+        eprintln!("  --jobs <n>          Worker threads for parallel steps (default: all cores)");
+        eprintln!(
+            "  --dry-run           List files that would be converted/deleted; write nothing"
+        );
+        eprintln!("  --log <path>        Append per-file failures/warnings to a log, not stderr");
+        eprintln!("  --manifest <path>   Write a JSON list of converted assets and their headers");
+        eprintln!("  --cache             Skip ASF/MPC/MAP files whose content hash is unchanged");
         std::process::exit(1);
     }
+    init_thread_pool(&args);
 
     let resources_dir = PathBuf::from(&args[1]);
     let delete_originals = args.iter().any(|a| a == "--delete-originals");
+    let opts = ConvertOptions {
+        zstd_level: parse_zstd_level(&args),
+        skip_existing: args.iter().any(|a| a == "--skip-existing"),
+        dry_run: args.iter().any(|a| a == "--dry-run"),
+    };
+    let report_path = match args.iter().position(|a| a == "--report") {
+        Some(pos) => match args.get(pos + 1) {
+            Some(p) => Some(PathBuf::from(p)),
+            None => {
+                eprintln!("Error: --report expects a file path");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let dedupe_manifest_path = match args.iter().position(|a| a == "--dedupe-manifest") {
+        Some(pos) => match args.get(pos + 1) {
+            Some(p) => Some(PathBuf::from(p)),
+            None => {
+                eprintln!("Error: --dedupe-manifest expects a file path");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let asset_manifest_path = match args.iter().position(|a| a == "--manifest") {
+        Some(pos) => match args.get(pos + 1) {
+            Some(p) => Some(PathBuf::from(p)),
+            None => {
+                eprintln!("Error: --manifest expects a file path");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let cache = args
+        .iter()
+        .any(|a| a == "--cache")
+        .then(|| SourceCache::load(&resources_dir));
+    let mut report = Report::default();
+    if let Some(pos) = args.iter().position(|a| a == "--log") {
+        let Some(path) = args.get(pos + 1) else {
+            eprintln!("Error: --log expects a file path");
+            std::process::exit(1);
+        };
+        match std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+        {
+            Ok(file) => report.log = Some(Mutex::new(file)),
+            Err(e) => {
+                eprintln!("Error: failed to open log {:?}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
 
     if !resources_dir.exists() {
         eprintln!("Error: directory {:?} does not exist", resources_dir);
         std::process::exit(1);
     }
 
+    // Check ffmpeg before the long sprite/map steps rather than failing every media file
+    let media = parse_media_options(&args).filter(|media| {
+        let found = ffmpeg_available(&media.ffmpeg);
+        if !found {
+            eprintln!(
+                "Warning: ffmpeg not found at {:?}; the media step will be skipped. \
+                 Install ffmpeg, pass --ffmpeg <path>, or use --skip-media to silence this.",
+                media.ffmpeg
+            );
+        }
+        found
+    });
+
     println!("╔══════════════════════════════════════════╗");
     println!("║  Miu2D All-in-One Resource Converter     ║");
     println!("╠══════════════════════════════════════════╣");
     println!("║  Resources: {:?}", resources_dir);
     println!("║  Delete originals: {}", delete_originals);
+    println!("║  Zstd level: {}", opts.zstd_level);
+    println!("║  Skip existing: {}", opts.skip_existing);
+    println!("║  Content cache: {}", cache.is_some());
+    println!("║  Dry run: {}", opts.dry_run);
+    match &media {
+        Some(m) => println!("║  Media: {:?}, CRF {}", m.ffmpeg, m.video_crf),
+        None => println!("║  Media: skipped"),
+    }
     println!("╚══════════════════════════════════════════╝");
 
     // Step 1: Encoding conversion
-    let (enc_ok, enc_skip, enc_fail) = convert_encoding(&resources_dir);
+    let (enc_ok, enc_skip, enc_fail) = convert_encoding(&resources_dir, opts.dry_run, &report);
 
     // Step 2: ASF → MSF
     println!("\n╔══════════════════════════════════════╗");
     println!("║  Step 2: ASF → MSF v2                ║");
     println!("╚══════════════════════════════════════╝");
-    let (asf_ok, asf_fail) = convert_asf_files(&resources_dir);
-    println!("  Converted: {}, Failed: {}", asf_ok, asf_fail);
+    let (asf_ok, asf_fail, asf_skip) =
+        convert_asf_files(&resources_dir, &opts, cache.as_ref(), &report);
+    println!(
+        "  Converted: {}, Failed: {}, Skipped: {}",
+        asf_ok, asf_fail, asf_skip
+    );
 
     // Step 3: MPC → MSF
     println!("\n╔══════════════════════════════════════╗");
     println!("║  Step 3: MPC → MSF v2                ║");
     println!("╚══════════════════════════════════════╝");
-    let (mpc_ok, mpc_fail) = convert_mpc_files(&resources_dir);
-    println!("  Converted: {}, Failed: {}", mpc_ok, mpc_fail);
+    let (mpc_ok, mpc_fail, mpc_skip) =
+        convert_mpc_files(&resources_dir, &opts, cache.as_ref(), &report);
+    println!(
+        "  Converted: {}, Failed: {}, Skipped: {}",
+        mpc_ok, mpc_fail, mpc_skip
+    );
 
     // Step 4: MAP → MMF
     println!("\n╔══════════════════════════════════════╗");
@@ -1714,33 +2704,99 @@ fn main() {
         };
         map_mmf::parse_traps_ini(&content)
     } else {
-        println!("  Warning: Traps.ini not found at {:?}", traps_path);
+        report.warn(
+            "map",
+            &traps_path,
+            "Traps.ini not found, maps get no trap table",
+        );
         HashMap::new()
     };
     println!("  Loaded trap definitions for {} maps", all_traps.len());
 
-    let (map_ok, map_fail) = map_mmf::convert_all_maps(&resources_dir, &all_traps);
-    println!("  Converted: {}, Failed: {}", map_ok, map_fail);
+    let (map_ok, map_fail, map_skip) =
+        map_mmf::convert_all_maps(&resources_dir, &all_traps, &opts, cache.as_ref(), &report);
+    println!(
+        "  Converted: {}, Failed: {}, Skipped: {}",
+        map_ok, map_fail, map_skip
+    );
+    if let Some(cache) = cache.as_ref().filter(|_| !opts.dry_run) {
+        if let Err(e) = cache.save() {
+            eprintln!("  Error: failed to write {}: {}", CACHE_FILE, e);
+        }
+    }
 
     // Step 5: Media conversion
     println!("\n╔══════════════════════════════════════╗");
     println!("║  Step 5: Media (WMV→WebM, WMA→OGG)  ║");
     println!("╚══════════════════════════════════════╝");
-    let (vid_ok, mus_ok, media_fail) = convert_media_files(&resources_dir);
+    let (vid_ok, mus_ok, media_skip, media_fail) = match &media {
+        Some(media) => {
+            let counts = convert_media_files(&resources_dir, media, opts.dry_run, &report);
+            println!(
+                "  Videos: {}, Music: {}, Skipped: {}, Failed: {}",
+                counts.0, counts.1, counts.2, counts.3
+            );
+            counts
+        }
+        None => {
+            println!("  Skipped (--skip-media or ffmpeg not found)");
+            (0, 0, 0, 0)
+        }
+    };
+
+    // Duplicate MSF outputs
+    println!("\n╔══════════════════════════════════════╗");
+    println!("║  Duplicate MSF outputs               ║");
+    println!("╚══════════════════════════════════════╝");
+    let duplicates = find_duplicate_msf(&resources_dir);
+    let dup_files: usize = duplicates.iter().map(|g| g.paths.len() - 1).sum();
+    let dup_bytes: usize = duplicates
+        .iter()
+        .map(|g| g.size * (g.paths.len() - 1))
+        .sum();
+    for g in duplicates.iter().take(10) {
+        println!("  {} × {} bytes:", g.paths.len(), g.size);
+        for p in &g.paths {
+            println!("    {:?}", p.strip_prefix(&resources_dir).unwrap_or(p));
+        }
+    }
+    if duplicates.len() > 10 {
+        println!("  ... and {} more groups", duplicates.len() - 10);
+    }
     println!(
-        "  Videos: {}, Music: {}, Failed: {}",
-        vid_ok, mus_ok, media_fail
+        "  {} groups, {} redundant files, {:.1} KB could be saved",
+        duplicates.len(),
+        dup_files,
+        dup_bytes as f64 / 1024.0
     );
+    if let Some(path) = dedupe_manifest_path.as_ref().filter(|_| opts.dry_run) {
+        println!("  [dry-run] would write dedupe manifest to {:?}", path);
+    } else if let Some(path) = &dedupe_manifest_path {
+        match write_dedupe_manifest(&resources_dir, &duplicates, path) {
+            Ok(()) => println!("  Dedupe manifest written to {:?}", path),
+            Err(e) => eprintln!("  Error: failed to write dedupe manifest {:?}: {}", path, e),
+        }
+    }
 
     // Step 6: Cleanup
     if delete_originals {
         println!("\n╔══════════════════════════════════════╗");
         println!("║  Step 6: Cleanup (delete originals)  ║");
         println!("╚══════════════════════════════════════╝");
-        let (asf_del, mpc_del, map_del) = delete_old_files(&resources_dir);
+        let planned = opts
+            .dry_run
+            .then(|| std::mem::take(&mut *report.planned_outputs.lock().unwrap()));
+        let (asf_del, mpc_del, map_del) = delete_old_files(&resources_dir, planned.as_ref());
         println!(
-            "  Deleted: {} ASF, {} MPC, {} MAP files",
-            asf_del, mpc_del, map_del
+            "  {}: {} ASF, {} MPC, {} MAP files",
+            if opts.dry_run {
+                "Would delete"
+            } else {
+                "Deleted"
+            },
+            asf_del,
+            mpc_del,
+            map_del
         );
     }
 
@@ -1753,14 +2809,53 @@ fn main() {
         "║  Encoding: {} converted, {} skipped      ",
         enc_ok, enc_skip
     );
-    println!("║  ASF→MSF:  {} converted                  ", asf_ok);
-    println!("║  MPC→MSF:  {} converted                  ", mpc_ok);
-    println!("║  MAP→MMF:  {} converted                  ", map_ok);
+    println!(
+        "║  ASF→MSF:  {} converted, {} skipped      ",
+        asf_ok, asf_skip
+    );
+    println!(
+        "║  MPC→MSF:  {} converted, {} skipped      ",
+        mpc_ok, mpc_skip
+    );
+    println!(
+        "║  MAP→MMF:  {} converted, {} skipped      ",
+        map_ok, map_skip
+    );
     println!("║  Video:    {} converted                  ", vid_ok);
     println!("║  Music:    {} converted                  ", mus_ok);
     println!("║  Total failures: {}                      ", total_fail);
     println!("╚══════════════════════════════════════════╝");
 
+    if let Some(path) = asset_manifest_path.as_ref().filter(|_| opts.dry_run) {
+        println!("[dry-run] would write asset manifest to {:?}", path);
+    } else if let Some(path) = &asset_manifest_path {
+        let mut entries = std::mem::take(&mut *report.manifest.lock().unwrap());
+        match write_asset_manifest(&resources_dir, &mut entries, path) {
+            Ok(()) => println!(
+                "Asset manifest ({} entries) written to {:?}",
+                entries.len(),
+                path
+            ),
+            Err(e) => eprintln!("Error: failed to write asset manifest {:?}: {}", path, e),
+        }
+    }
+
+    if let Some(path) = report_path.as_ref().filter(|_| opts.dry_run) {
+        println!("[dry-run] would write report to {:?}", path);
+    } else if let Some(path) = &report_path {
+        let steps = [
+            ("encoding", enc_ok, enc_fail, enc_skip),
+            ("asf", asf_ok, asf_fail, asf_skip),
+            ("mpc", mpc_ok, mpc_fail, mpc_skip),
+            ("map", map_ok, map_fail, map_skip),
+            ("media", vid_ok + mus_ok, media_fail, media_skip),
+        ];
+        match report.write(path, &steps) {
+            Ok(()) => println!("Report written to {:?}", path),
+            Err(e) => eprintln!("Error: failed to write report {:?}: {}", path, e),
+        }
+    }
+
     if total_fail > 0 {
         std::process::exit(1);
     }
@@ -1,23 +1,40 @@
 //! MAP → MMF batch conversion tool
 //!
 //! Usage:
-//!   map2mmf <resources_dir> [--traps <traps_ini_path>]
+//!   map2mmf <resources_dir> [--traps <traps_ini_path> | --traps-dir <dir>]
+//!           [--zstd-level <1-22>] [--check-assets <msf_dir>] [--jobs <n>]
 //!
 //! Converts all .map files in `<resources_dir>/map/` to MMF format in-place,
 //! embedding trap definitions from Traps.ini.
 //!
+//! With `--traps-dir <dir>`, trap definitions are read from one INI per map
+//! instead: every `*.ini` in `<dir>` supplies the traps for the map named by
+//! its file stem (`map_003_武当山下.ini` → `map_003_武当山下`).
+//!
 //! The converter:
-//! 1. Reads old .map files (GBK encoded)
+//! 1. Reads old .map files (GBK encoded); the layout is chosen from the
+//!    `"MAP File Ver X.Y"` header and file length: unversioned, 1.x and 2.x
+//!    maps use 10-byte tiles; other versions get a warning and are read the
+//!    same way, unless the file length only fits 12-byte tiles
 //! 2. Converts to MMF format (UTF-8, zstd compressed)
 //! 3. Remaps MPC indices to compact MSF indices
 //! 4. Embeds trap table from Traps.ini
 //! 5. Writes .mmf files alongside .map files
+//!
+//! With `--check-assets <msf_dir>`, every MSF name in a map's table is looked up
+//! as `<msf_dir>/<map_name>/<name>` (the layout the engine loads from), and
+//! missing files are reported grouped by map.
 
 use encoding_rs::GBK;
 use rayon::prelude::*;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+#[path = "../map_layout.rs"]
+mod map_layout;
+use map_layout::{is_map_file, map_layout, MAP_LAYOUT_CLASSIC};
 
 // ============= MAP Parser =============
 
@@ -46,6 +63,8 @@ struct OldMapData {
     mpc_looping: Vec<bool>,
     /// All tiles in row-major order
     tiles: Vec<MapTile>,
+    /// Set when the header version is unrecognized and its layout was guessed
+    warning: Option<String>,
 }
 
 fn get_i32_le(data: &[u8], offset: usize) -> i32 {
@@ -77,27 +96,29 @@ fn read_gbk_string(data: &[u8], offset: usize, max_len: usize) -> String {
     decoded.into_owned()
 }
 
-fn parse_old_map(data: &[u8]) -> Option<OldMapData> {
-    if data.len() < 16512 {
-        return None;
+fn parse_old_map(data: &[u8]) -> Result<OldMapData, String> {
+    if !is_map_file(data) {
+        return Err("missing \"MAP File Ver\" signature".to_string());
     }
-
-    // Check header: "MAP File Ver"
-    let header = std::str::from_utf8(&data[0..12]).ok()?;
-    if header != "MAP File Ver" {
-        return None;
+    if data.len() < MAP_LAYOUT_CLASSIC.tile_offset {
+        return Err(format!(
+            "{} bytes is shorter than the {}-byte header",
+            data.len(),
+            MAP_LAYOUT_CLASSIC.tile_offset
+        ));
     }
+    let (layout, warning) = map_layout(data);
 
-    // Read dimensions at offset 68
-    let columns = get_i32_le(data, 68) as u16;
-    let rows = get_i32_le(data, 72) as u16;
+    // Read dimensions
+    let columns = get_i32_le(data, layout.dims_offset) as u16;
+    let rows = get_i32_le(data, layout.dims_offset + 4) as u16;
 
-    // Read MPC file list: 255 entries, each 64 bytes, starting at offset 192
+    // Read MPC file list: 255 entries, each 64 bytes
     let mut mpc_names: Vec<Option<String>> = Vec::with_capacity(255);
     let mut mpc_looping: Vec<bool> = Vec::with_capacity(255);
 
     for k in 0..255 {
-        let offset = 192 + k * 64;
+        let offset = layout.mpc_list_offset + k * 64;
         let name = read_gbk_string(data, offset, 32);
         if name.is_empty() {
             mpc_names.push(None);
@@ -109,13 +130,13 @@ fn parse_old_map(data: &[u8]) -> Option<OldMapData> {
         }
     }
 
-    // Tile data starts at offset 16512
+    // Tile data follows the header
     let total_tiles = columns as usize * rows as usize;
     let mut tiles = Vec::with_capacity(total_tiles);
-    let mut offset = 16512;
+    let mut offset = layout.tile_offset;
 
     for _ in 0..total_tiles {
-        if offset + 10 > data.len() {
+        if offset + layout.tile_stride > data.len() {
             break;
         }
         tiles.push(MapTile {
@@ -128,20 +149,40 @@ fn parse_old_map(data: &[u8]) -> Option<OldMapData> {
             barrier: data[offset + 6],
             trap: data[offset + 7],
         });
-        offset += 10; // 8 bytes data + 2 bytes padding
+        offset += layout.tile_stride;
     }
 
-    Some(OldMapData {
+    Ok(OldMapData {
         columns,
         rows,
         mpc_names,
         mpc_looping,
         tiles,
+        warning,
     })
 }
 
 // ============= Traps.ini Parser =============
 
+/// Read an INI file as text, trying UTF-8 first and falling back to GBK
+fn read_ini_text(path: &Path) -> std::io::Result<String> {
+    let raw = std::fs::read(path)?;
+    Ok(match std::str::from_utf8(&raw) {
+        Ok(s) => s.to_string(),
+        Err(_) => {
+            let (decoded, _, _) = GBK.decode(&raw);
+            decoded.into_owned()
+        }
+    })
+}
+
+/// Parse a `trap_index=script_path` line; returns None for anything else
+fn parse_trap_line(line: &str) -> Option<(u8, String)> {
+    let (key, value) = line.split_once('=')?;
+    let trap_index = key.trim().parse::<u8>().ok()?;
+    Some((trap_index, value.trim().to_string()))
+}
+
 /// Parse a Traps.ini file (already UTF-8 or GBK) into map_name -> (trap_index -> script_path)
 fn parse_traps_ini(content: &str) -> HashMap<String, HashMap<u8, String>> {
     let mut result: HashMap<String, HashMap<u8, String>> = HashMap::new();
@@ -158,14 +199,11 @@ fn parse_traps_ini(content: &str) -> HashMap<String, HashMap<u8, String>> {
             continue;
         }
         if let Some(ref section) = current_section {
-            if let Some((key, value)) = line.split_once('=') {
-                if let Ok(trap_index) = key.trim().parse::<u8>() {
-                    let script_path = value.trim().to_string();
-                    result
-                        .entry(section.clone())
-                        .or_default()
-                        .insert(trap_index, script_path);
-                }
+            if let Some((trap_index, script_path)) = parse_trap_line(line) {
+                result
+                    .entry(section.clone())
+                    .or_default()
+                    .insert(trap_index, script_path);
             }
         }
     }
@@ -173,6 +211,40 @@ fn parse_traps_ini(content: &str) -> HashMap<String, HashMap<u8, String>> {
     result
 }
 
+/// Load every `*.ini` in `dir` as the trap table of the map named by its file stem.
+/// Section headers inside the files are ignored; the file name is the key.
+fn parse_traps_dir(dir: &Path) -> std::io::Result<HashMap<String, HashMap<u8, String>>> {
+    let mut result: HashMap<String, HashMap<u8, String>> = HashMap::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_ini = path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("ini"))
+            .unwrap_or(false);
+        if !path.is_file() || !is_ini {
+            continue;
+        }
+        let Some(map_name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+
+        let content = read_ini_text(&path)?;
+        let traps = result.entry(map_name.to_string()).or_default();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+            if let Some((trap_index, script_path)) = parse_trap_line(line) {
+                traps.insert(trap_index, script_path);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
 // ============= MMF Writer =============
 
 struct MsfEntry {
@@ -185,27 +257,55 @@ struct TrapEntry {
     script_path: String,
 }
 
-fn convert_map_to_mmf(map_data: &OldMapData, trap_entries: &[TrapEntry]) -> Vec<u8> {
+/// MSF table name for an MPC slot name (`.mpc` → `.msf`, others unchanged)
+fn msf_name_for(mpc_name: &str) -> String {
+    if mpc_name.to_lowercase().ends_with(".mpc") {
+        format!("{}.msf", &mpc_name[..mpc_name.len() - 4])
+    } else {
+        mpc_name.to_string()
+    }
+}
+
+/// MSF names referenced by the map that have no file under `<msf_dir>/<map_name>/`
+fn missing_msf_assets(map_data: &OldMapData, msf_dir: &Path, map_name: &str) -> Vec<String> {
+    let map_msf_dir = msf_dir.join(map_name);
+    map_data
+        .mpc_names
+        .iter()
+        .flatten()
+        .map(|name| msf_name_for(name))
+        .filter(|msf_name| !map_msf_dir.join(msf_name).is_file())
+        .collect()
+}
+
+/// Most MSF table entries a map can hold: tiles store the 1-based index as a
+/// single byte, with 0 meaning empty
+const MAX_MSF_ENTRIES: usize = 255;
+
+fn convert_map_to_mmf(
+    map_data: &OldMapData,
+    trap_entries: &[TrapEntry],
+    zstd_level: i32,
+) -> Result<Vec<u8>, String> {
     // Step 1: Compact MSF table - only include used MPC entries
     // Build old_index -> new_index mapping (new index is 1-based, 0 = empty)
+    let used = map_data.mpc_names.iter().flatten().count();
+    if used > MAX_MSF_ENTRIES {
+        return Err(format!(
+            "map references {} MSF files, but the MMF tile index holds at most {}",
+            used, MAX_MSF_ENTRIES
+        ));
+    }
     let mut old_to_new: HashMap<u8, u8> = HashMap::new();
     let mut msf_entries: Vec<MsfEntry> = Vec::new();
-    let mut new_idx: u8 = 1;
 
     for (old_idx, name_opt) in map_data.mpc_names.iter().enumerate() {
         if let Some(name) = name_opt {
-            old_to_new.insert(old_idx as u8, new_idx);
-            // Convert .mpc -> .msf file extension
-            let msf_name = if name.to_lowercase().ends_with(".mpc") {
-                format!("{}.msf", &name[..name.len() - 4])
-            } else {
-                name.clone()
-            };
+            old_to_new.insert(old_idx as u8, (msf_entries.len() + 1) as u8);
             msf_entries.push(MsfEntry {
-                name: msf_name,
+                name: msf_name_for(name),
                 looping: map_data.mpc_looping[old_idx],
             });
-            new_idx += 1;
         }
     }
 
@@ -311,30 +411,98 @@ fn convert_map_to_mmf(map_data: &OldMapData, trap_entries: &[TrapEntry]) -> Vec<
     }
 
     // Compress with zstd
-    let compressed = zstd::bulk::compress(&blob, 3).expect("zstd compression failed");
+    let compressed = zstd::bulk::compress(&blob, zstd_level).expect("zstd compression failed");
     out.extend_from_slice(&compressed);
 
-    out
+    Ok(out)
+}
+
+/// Default zstd level for frame/tile blobs
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// Parse `--zstd-level <n>` (1–22, default 3); exits with an error on bad input
+fn parse_zstd_level(args: &[String]) -> i32 {
+    let Some(pos) = args.iter().position(|a| a == "--zstd-level") else {
+        return DEFAULT_ZSTD_LEVEL;
+    };
+    match args.get(pos + 1).and_then(|v| v.parse::<i32>().ok()) {
+        Some(level) if (1..=22).contains(&level) => level,
+        _ => {
+            eprintln!("Error: --zstd-level expects an integer between 1 and 22");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parse `--jobs <n>` and cap rayon's global pool at `n` threads (default: all
+/// cores); exits with an error on bad input
+fn init_thread_pool(args: &[String]) {
+    let Some(pos) = args.iter().position(|a| a == "--jobs") else {
+        return;
+    };
+    let jobs = match args.get(pos + 1).and_then(|v| v.parse::<usize>().ok()) {
+        Some(n) if n >= 1 => n,
+        _ => {
+            eprintln!("Error: --jobs expects a positive integer");
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build_global()
+    {
+        eprintln!("Error: failed to configure thread pool: {}", e);
+        std::process::exit(1);
+    }
 }
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() < 2 {
-        eprintln!("Usage: map2mmf <resources_dir> [--traps <traps_ini_path>]");
+        eprintln!(
+            "Usage: map2mmf <resources_dir> [--traps <traps_ini_path> | --traps-dir <dir>] \
+             [--zstd-level <1-22>] [--check-assets <msf_dir>] [--jobs <n>]"
+        );
         eprintln!();
         eprintln!("Converts all .map files to .mmf format.");
         eprintln!("Default traps path: <resources_dir>/save/game/Traps.ini");
+        eprintln!("--traps-dir loads one <map_name>.ini per map instead of a single Traps.ini.");
+        eprintln!("--check-assets warns about MSF names with no <msf_dir>/<map_name>/<name> file.");
+        eprintln!("--jobs caps the number of worker threads (default: all cores).");
         std::process::exit(1);
     }
+    init_thread_pool(&args);
 
     let resources_dir = PathBuf::from(&args[1]);
+    let zstd_level = parse_zstd_level(&args);
     let map_dir = resources_dir.join("map");
+    let check_assets_dir = match args.iter().position(|a| a == "--check-assets") {
+        Some(pos) => match args.get(pos + 1) {
+            Some(dir) => Some(PathBuf::from(dir)),
+            None => {
+                eprintln!("Error: --check-assets expects an MSF directory");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
 
     if !map_dir.exists() {
         eprintln!("Error: map directory {:?} does not exist", map_dir);
         std::process::exit(1);
     }
 
+    let traps_dir = match args.iter().position(|a| a == "--traps-dir") {
+        Some(pos) => match args.get(pos + 1) {
+            Some(dir) => Some(PathBuf::from(dir)),
+            None => {
+                eprintln!("Error: --traps-dir expects a directory");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
     // Find traps.ini path
     let traps_path = if let Some(pos) = args.iter().position(|a| a == "--traps") {
         PathBuf::from(&args[pos + 1])
@@ -342,18 +510,19 @@ fn main() {
         resources_dir.join("save/game/Traps.ini")
     };
 
-    // Load traps.ini
-    let all_traps = if traps_path.exists() {
-        println!("Loading traps from: {:?}", traps_path);
-        let raw = std::fs::read(&traps_path).expect("Failed to read Traps.ini");
-        // Try UTF-8 first, fall back to GBK
-        let content = match std::str::from_utf8(&raw) {
-            Ok(s) => s.to_string(),
-            Err(_) => {
-                let (decoded, _, _) = GBK.decode(&raw);
-                decoded.into_owned()
+    // Load per-map trap INIs, or the monolithic traps.ini
+    let all_traps = if let Some(dir) = &traps_dir {
+        println!("Loading per-map traps from: {:?}", dir);
+        match parse_traps_dir(dir) {
+            Ok(traps) => traps,
+            Err(e) => {
+                eprintln!("Error: failed to read traps directory {:?}: {}", dir, e);
+                std::process::exit(1);
             }
-        };
+        }
+    } else if traps_path.exists() {
+        println!("Loading traps from: {:?}", traps_path);
+        let content = read_ini_text(&traps_path).expect("Failed to read Traps.ini");
         parse_traps_ini(&content)
     } else {
         println!(
@@ -385,6 +554,8 @@ fn main() {
     let failed = AtomicUsize::new(0);
     let total_map_bytes = AtomicUsize::new(0);
     let total_mmf_bytes = AtomicUsize::new(0);
+    // (map name, missing MSF names) for --check-assets
+    let missing_assets: Mutex<Vec<(String, Vec<String>)>> = Mutex::new(Vec::new());
 
     map_files.par_iter().for_each(|map_path| {
         // Extract map name without extension for trap lookup
@@ -409,18 +580,39 @@ fn main() {
         match std::fs::read(map_path) {
             Ok(map_data_raw) => {
                 let map_size = map_data_raw.len();
-                // Skip files that don't look like MAP format (wrong header or too small)
-                let is_map = map_data_raw.len() >= 12
-                    && std::str::from_utf8(&map_data_raw[0..12])
-                        .map(|h| h == "MAP File Ver")
-                        .unwrap_or(false);
-                if !is_map {
+                // Skip files that don't look like MAP format (wrong header)
+                if !is_map_file(&map_data_raw) {
                     eprintln!("  SKIP (not a MAP file, {} bytes) {:?}", map_size, map_path);
                     return;
                 }
                 match parse_old_map(&map_data_raw) {
-                    Some(map_data) => {
-                        let mmf_data = convert_map_to_mmf(&map_data, &trap_entries);
+                    Ok(map_data) => {
+                        if let Some(warning) = &map_data.warning {
+                            eprintln!("  WARNING {:?}: {}", map_path, warning);
+                        }
+                        if let Some(msf_dir) = &check_assets_dir {
+                            let missing = missing_msf_assets(&map_data, msf_dir, map_name);
+                            if !missing.is_empty() {
+                                eprintln!(
+                                    "  WARNING {:?}: {} missing MSF file(s)",
+                                    map_path,
+                                    missing.len()
+                                );
+                                missing_assets
+                                    .lock()
+                                    .unwrap()
+                                    .push((map_name.to_string(), missing));
+                            }
+                        }
+                        let mmf_data =
+                            match convert_map_to_mmf(&map_data, &trap_entries, zstd_level) {
+                                Ok(mmf_data) => mmf_data,
+                                Err(e) => {
+                                    eprintln!("  CONVERT ERROR {:?}: {}", map_path, e);
+                                    failed.fetch_add(1, Ordering::Relaxed);
+                                    return;
+                                }
+                            };
                         let mmf_size = mmf_data.len();
 
                         let mut mmf_path = map_path.clone();
@@ -437,7 +629,7 @@ fn main() {
                                 String::new()
                             };
 
-                            if n % 10 == 0 || n == total {
+                            if n.is_multiple_of(10) || n == total {
                                 println!(
                                     "  [{}/{}] {} → {} bytes{}",
                                     n, total, map_size, mmf_size, trap_info
@@ -448,8 +640,8 @@ fn main() {
                             failed.fetch_add(1, Ordering::Relaxed);
                         }
                     }
-                    None => {
-                        eprintln!("  PARSE ERROR {:?}", map_path);
+                    Err(e) => {
+                        eprintln!("  PARSE ERROR {:?}: {}", map_path, e);
                         failed.fetch_add(1, Ordering::Relaxed);
                     }
                 }
@@ -478,4 +670,104 @@ fn main() {
         "  MAP: {:.1} KB → MMF: {:.1} KB ({:.1}%)",
         map_kb, mmf_kb, ratio
     );
+
+    if let Some(msf_dir) = &check_assets_dir {
+        let mut missing = missing_assets.into_inner().unwrap();
+        missing.sort();
+        let missing_total: usize = missing.iter().map(|(_, names)| names.len()).sum();
+        println!("\n=== Asset check ({:?}) ===", msf_dir);
+        println!(
+            "  Missing MSF files: {} across {} maps",
+            missing_total,
+            missing.len()
+        );
+        for (map_name, names) in &missing {
+            println!("  {}:", map_name);
+            for name in names {
+                println!("    {}", name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 1×1 map whose MSF table lists `count` distinct MPC names
+    fn map_with_mpc_names(count: usize) -> OldMapData {
+        OldMapData {
+            columns: 1,
+            rows: 1,
+            mpc_names: (0..count).map(|i| Some(format!("tile{}.mpc", i))).collect(),
+            mpc_looping: vec![false; count],
+            tiles: vec![MapTile {
+                l1_frame: 0,
+                l1_mpc: 1,
+                l2_frame: 0,
+                l2_mpc: 0,
+                l3_frame: 0,
+                l3_mpc: 0,
+                barrier: 0,
+                trap: 0,
+            }],
+            warning: None,
+        }
+    }
+
+    /// A `.map` file with the given version string and a 2×1 tile grid
+    fn map_bytes(version: &str, tile_stride: usize) -> Vec<u8> {
+        let mut data = vec![0u8; 16512 + 2 * tile_stride];
+        let header = format!("MAP File Ver {}", version);
+        data[..header.len()].copy_from_slice(header.as_bytes());
+        data[68..72].copy_from_slice(&2i32.to_le_bytes());
+        data[72..76].copy_from_slice(&1i32.to_le_bytes());
+        data[192..200].copy_from_slice(b"a.mpc\0\0\0");
+        for (i, tile) in data[16512..].chunks_mut(tile_stride).enumerate() {
+            tile[..8].copy_from_slice(&[i as u8, 1, 0, 0, 0, 0, 0, i as u8 + 5]);
+        }
+        data
+    }
+
+    #[test]
+    fn file_length_picks_tile_stride() {
+        for (version, stride) in [("", 10), ("2.0", 10), ("2.0", 12)] {
+            let map = parse_old_map(&map_bytes(version, stride)).unwrap();
+            assert_eq!((map.columns, map.rows), (2, 1), "version {:?}", version);
+            assert_eq!(map.mpc_names[0].as_deref(), Some("a.mpc"));
+            let traps: Vec<u8> = map.tiles.iter().map(|t| t.trap).collect();
+            assert_eq!(traps, [5, 6], "version {:?} stride {}", version, stride);
+            assert_eq!(map.warning, None);
+        }
+        // Trailing bytes don't prove a wider stride
+        let mut data = map_bytes("2.0", 10);
+        data.push(0);
+        let (layout, _) = map_layout(&data);
+        assert_eq!(layout.tile_stride, 10);
+    }
+
+    #[test]
+    fn unknown_map_version_falls_back_with_a_warning() {
+        assert_eq!(map_layout::map_version(&map_bytes("9.1", 10)), "9.1");
+        for version in ["9.1", "3.0", "beta"] {
+            let map = parse_old_map(&map_bytes(version, 10)).unwrap();
+            let traps: Vec<u8> = map.tiles.iter().map(|t| t.trap).collect();
+            assert_eq!(traps, [5, 6], "version {:?}", version);
+            let warning = map.warning.unwrap();
+            assert!(warning.contains(version), "{}", warning);
+        }
+    }
+
+    #[test]
+    fn msf_table_overflow_is_an_error() {
+        let err = convert_map_to_mmf(&map_with_mpc_names(300), &[], 3).unwrap_err();
+        assert!(err.contains("300"), "{}", err);
+    }
+
+    #[test]
+    fn full_msf_table_converts() {
+        let mmf = convert_map_to_mmf(&map_with_mpc_names(MAX_MSF_ENTRIES), &[], 3).unwrap();
+        let msf_count = u16::from_le_bytes([mmf[12], mmf[13]]);
+        assert_eq!(msf_count as usize, MAX_MSF_ENTRIES);
+    }
 }
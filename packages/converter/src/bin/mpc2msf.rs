@@ -1,18 +1,28 @@
 //! MPC → MSF v2 batch conversion tool
 //!
 //! Usage:
-//!   mpc2msf <input_dir> <output_dir>
+//!   mpc2msf <input_dir> <output_dir> [--zstd-level <1-22>] [--crop] [--jobs <n>]
 //!
-//! Recursively converts all .mpc files to MSF v2 format.
+//! Recursively converts all .mpc files (and standalone .shd shadow files) to MSF v2 format.
 //! MSF v2: Rgba8 (4bpp) + zstd compression.
 //! Transparency is decoded from the MPC RLE stream directly (no palette index trick).
+//! With `--crop`, each frame is stored as its tight non-transparent bounding box
+//! (like asf2msf) instead of the full declared frame size.
+//! With `--jobs <n>`, at most `n` worker threads are used (default: all cores).
+//! A `.anim` sidecar (same stem) names frame sub-ranges in a `TAGS` chunk,
+//! in the same format as asf2msf.
 
 use rayon::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use walkdir::WalkDir;
 
+#[path = "../anim_tags.rs"]
+mod anim_tags;
+
 mod msf {
+    use crate::anim_tags::{check_tag_range, tags_chunk, AnimTag};
+
     pub const MSF_MAGIC: &[u8; 4] = b"MSF2";
     pub const MSF_VERSION: u16 = 2;
     pub const CHUNK_END: &[u8; 4] = b"END\0";
@@ -53,6 +63,54 @@ mod msf {
         ])
     }
 
+    /// Tight bounding box of non-transparent pixels: (x, y, w, h), all zero if empty
+    fn compute_tight_bbox(pixels: &[u8], width: usize, height: usize) -> (i16, i16, u16, u16) {
+        let mut min_x = width;
+        let mut min_y = height;
+        let mut max_x: usize = 0;
+        let mut max_y: usize = 0;
+        let mut has_content = false;
+
+        for y in 0..height {
+            for x in 0..width {
+                let idx = (y * width + x) * 4;
+                if idx + 3 < pixels.len() && pixels[idx + 3] > 0 {
+                    has_content = true;
+                    min_x = min_x.min(x);
+                    max_x = max_x.max(x);
+                    min_y = min_y.min(y);
+                    max_y = max_y.max(y);
+                }
+            }
+        }
+
+        if !has_content {
+            return (0, 0, 0, 0);
+        }
+        (
+            min_x as i16,
+            min_y as i16,
+            (max_x - min_x + 1) as u16,
+            (max_y - min_y + 1) as u16,
+        )
+    }
+
+    fn extract_bbox_pixels(
+        pixels: &[u8],
+        full_width: usize,
+        ox: usize,
+        oy: usize,
+        w: usize,
+        h: usize,
+    ) -> Vec<u8> {
+        let mut out = Vec::with_capacity(w * h * 4);
+        for y in oy..oy + h {
+            let start = (y * full_width + ox) * 4;
+            out.extend_from_slice(&pixels[start..start + w * 4]);
+        }
+        out
+    }
+
     /// Decode SHD RLE into per-frame shadow canvases (RGBA).
     ///
     /// SHD format (Shd.cs):
@@ -163,6 +221,7 @@ mod msf {
     ///
     /// This avoids all palette-index ambiguity and works correctly even when all 256
     /// palette entries are in use (which happens for ~1879 files in resources-sword2).
+    #[allow(clippy::too_many_arguments)]
     fn decode_mpc_rle_to_rgba(
         data: &[u8],
         rle_start: usize,
@@ -222,19 +281,28 @@ mod msf {
         buf
     }
 
-    /// Convert a single MPC file to MSF v2 (Rgba8 + zstd)
+    /// Convert a single MPC (or standalone SHD) file to MSF v2 (Rgba8 + zstd)
+    ///
+    /// With `crop`, frames are cropped to their tight bounding box and the
+    /// offset recorded in the frame table; the canvas keeps the full frame size.
+    /// Non-empty `tags` are written to a `TAGS` chunk; a tag reaching past the
+    /// frames of one direction fails the conversion.
     pub fn convert_mpc_to_msf(
         mpc_data: &[u8],
         shd_data: Option<&[u8]>,
         use_palette_alpha: bool,
-    ) -> Option<Vec<u8>> {
+        zstd_level: i32,
+        crop: bool,
+        tags: &[AnimTag],
+    ) -> Result<Vec<u8>, String> {
         if mpc_data.len() < 160 {
-            return None;
+            return Err(format!("file too short: {} bytes", mpc_data.len()));
         }
 
-        let sig = std::str::from_utf8(&mpc_data[0..12]).ok()?;
-        if !sig.starts_with("MPC File Ver") {
-            return None;
+        let sig = String::from_utf8_lossy(&mpc_data[0..12]);
+        let is_shd = sig.starts_with("SHD File Ver");
+        if !sig.starts_with("MPC File Ver") && !is_shd {
+            return Err(format!("unrecognized signature {:?}", sig));
         }
 
         let off = 64;
@@ -242,10 +310,17 @@ mod msf {
         let global_height = get_u32_le(mpc_data, off + 8) as u16;
         let frame_count = get_u32_le(mpc_data, off + 12) as u16;
         let direction = get_u32_le(mpc_data, off + 16) as u8;
-        let color_count = get_u32_le(mpc_data, off + 20) as usize;
+        // SHD shares the MPC header but has no palette: frame offsets start at byte 128
+        let color_count = if is_shd {
+            0
+        } else {
+            get_u32_le(mpc_data, off + 20) as usize
+        };
         let interval = get_u32_le(mpc_data, off + 24) as u16;
         let raw_bottom = get_i32_le(mpc_data, off + 28);
 
+        check_tag_range(tags, frame_count, direction)?;
+
         let left = (global_width / 2) as i16;
         let bottom = if global_height >= 16 {
             (global_height as i32 - 16 - raw_bottom) as i16
@@ -288,13 +363,21 @@ mod msf {
         let frame_data_start = offsets_start + frame_count as usize * 4;
 
         // Decode SHD shadow frames if provided
-        let shd_frames = shd_data
-            .map(|sd| decode_shd_frames(sd, frame_count as usize))
-            .unwrap_or_default();
+        // (a standalone SHD file is decoded as its own shadow frames)
+        let shd_frames = if is_shd {
+            decode_shd_frames(mpc_data, frame_count as usize)
+        } else {
+            shd_data
+                .map(|sd| decode_shd_frames(sd, frame_count as usize))
+                .unwrap_or_default()
+        };
 
         // Process frames: decode to RGBA directly
         let mut frame_entries: Vec<FrameEntry> = Vec::with_capacity(frame_count as usize);
         let mut raw_frame_data: Vec<Vec<u8>> = Vec::with_capacity(frame_count as usize);
+        // Declared (uncropped) frame size, used for the canvas
+        let mut full_width = 0u16;
+        let mut full_height = 0u16;
 
         for i in 0..frame_count as usize {
             if i >= data_offsets.len() {
@@ -341,32 +424,75 @@ mod msf {
                 continue;
             }
 
+            // Bytes ds+12..ds+20 are the frame header's 8 reserved bytes. No
+            // known MPC stores a per-frame position there (the original engine
+            // and every decoder skip them): frames are drawn at the canvas
+            // origin and aligned by the global left/bottom anchor, so the MSF
+            // offsets stay 0 unless `--crop` moves the frame within its canvas.
             let rle_start = ds + 20;
             let rle_end = ds + data_len;
             let shadow = shd_frames
                 .get(i)
                 .filter(|s| !s.is_empty())
                 .map(|s| s.as_slice());
-            let rgba = decode_mpc_rle_to_rgba(
-                mpc_data,
-                rle_start,
-                rle_end,
-                width as usize,
-                height as usize,
-                &palette,
-                shadow,
-                use_palette_alpha,
-            );
+            let rgba = if is_shd {
+                // SHD runs carry no palette indices; the shadow decoder already made RGBA
+                shd_frames
+                    .get(i)
+                    .filter(|s| !s.is_empty())
+                    .cloned()
+                    .unwrap_or_else(|| vec![0u8; width as usize * height as usize * 4])
+            } else {
+                decode_mpc_rle_to_rgba(
+                    mpc_data,
+                    rle_start,
+                    rle_end,
+                    width as usize,
+                    height as usize,
+                    &palette,
+                    shadow,
+                    use_palette_alpha,
+                )
+            };
+
+            full_width = full_width.max(width);
+            full_height = full_height.max(height);
 
+            if !crop {
+                frame_entries.push(FrameEntry {
+                    offset_x: 0,
+                    offset_y: 0,
+                    width,
+                    height,
+                    data_offset: 0,
+                    data_length: 0,
+                });
+                raw_frame_data.push(rgba);
+                continue;
+            }
+
+            let (ox, oy, bw, bh) = compute_tight_bbox(&rgba, width as usize, height as usize);
+            let cropped = if bw == 0 || bh == 0 {
+                Vec::new()
+            } else {
+                extract_bbox_pixels(
+                    &rgba,
+                    width as usize,
+                    ox as usize,
+                    oy as usize,
+                    bw as usize,
+                    bh as usize,
+                )
+            };
             frame_entries.push(FrameEntry {
-                offset_x: 0,
-                offset_y: 0,
-                width,
-                height,
+                offset_x: ox,
+                offset_y: oy,
+                width: bw,
+                height: bh,
                 data_offset: 0,
                 data_length: 0,
             });
-            raw_frame_data.push(rgba);
+            raw_frame_data.push(cropped);
         }
 
         // Concatenate frame data
@@ -377,27 +503,28 @@ mod msf {
             concat_raw.extend_from_slice(data);
         }
 
-        // Canvas dimensions = actual frame content size (may exceed global_width/height).
-        // global_width is only for anchor computation; canvas must hold all frame pixels.
-        let canvas_width = frame_entries
-            .iter()
-            .filter(|e| e.width > 0)
-            .map(|e| (e.offset_x.max(0) as u16).saturating_add(e.width))
-            .max()
-            .unwrap_or(global_width);
-        let canvas_height = frame_entries
-            .iter()
-            .filter(|e| e.height > 0)
-            .map(|e| (e.offset_y.max(0) as u16).saturating_add(e.height))
-            .max()
-            .unwrap_or(global_height);
+        // Canvas dimensions = declared frame size (may exceed global_width/height).
+        // global_width is only for anchor computation; canvas must hold all frame pixels,
+        // and cropping must not shrink it.
+        let canvas_width = if full_width > 0 {
+            full_width
+        } else {
+            global_width
+        };
+        let canvas_height = if full_height > 0 {
+            full_height
+        } else {
+            global_height
+        };
 
         let flags: u16 = 1; // zstd
-        let compressed_blob = zstd::bulk::compress(&concat_raw, 3).ok()?;
+        let compressed_blob =
+            zstd::bulk::compress(&concat_raw, zstd_level).map_err(|e| format!("zstd: {}", e))?;
 
         // PixelFormat=0 (Rgba8), no palette in MSF header
         let frame_table_bytes = frame_count as usize * FRAME_ENTRY_SIZE;
-        let total = 8 + 16 + 4 + frame_table_bytes + 8 + compressed_blob.len();
+        let tags_chunk = tags_chunk(tags);
+        let total = 8 + 16 + 4 + frame_table_bytes + tags_chunk.len() + 8 + compressed_blob.len();
         let mut out = Vec::with_capacity(total);
 
         // Preamble
@@ -432,6 +559,9 @@ mod msf {
             out.extend_from_slice(&entry.data_length.to_le_bytes());
         }
 
+        // Named frame sub-ranges
+        out.extend_from_slice(&tags_chunk);
+
         // End sentinel
         out.extend_from_slice(CHUNK_END);
         out.extend_from_slice(&0u32.to_le_bytes());
@@ -439,19 +569,68 @@ mod msf {
         // Compressed blob
         out.extend_from_slice(&compressed_blob);
 
-        Some(out)
+        Ok(out)
+    }
+}
+
+/// True if an .mpc with the same stem sits next to this .shd
+fn has_mpc_sibling(shd_path: &Path) -> bool {
+    shd_path.with_extension("mpc").exists() || shd_path.with_extension("MPC").exists()
+}
+
+/// Default zstd level for frame/tile blobs
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// Parse `--zstd-level <n>` (1–22, default 3); exits with an error on bad input
+fn parse_zstd_level(args: &[String]) -> i32 {
+    let Some(pos) = args.iter().position(|a| a == "--zstd-level") else {
+        return DEFAULT_ZSTD_LEVEL;
+    };
+    match args.get(pos + 1).and_then(|v| v.parse::<i32>().ok()) {
+        Some(level) if (1..=22).contains(&level) => level,
+        _ => {
+            eprintln!("Error: --zstd-level expects an integer between 1 and 22");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parse `--jobs <n>` and cap rayon's global pool at `n` threads (default: all
+/// cores); exits with an error on bad input
+fn init_thread_pool(args: &[String]) {
+    let Some(pos) = args.iter().position(|a| a == "--jobs") else {
+        return;
+    };
+    let jobs = match args.get(pos + 1).and_then(|v| v.parse::<usize>().ok()) {
+        Some(n) if n >= 1 => n,
+        _ => {
+            eprintln!("Error: --jobs expects a positive integer");
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build_global()
+    {
+        eprintln!("Error: failed to configure thread pool: {}", e);
+        std::process::exit(1);
     }
 }
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() < 3 {
-        eprintln!("Usage: mpc2msf <input_dir> <output_dir>");
+        eprintln!(
+            "Usage: mpc2msf <input_dir> <output_dir> [--zstd-level <1-22>] [--crop] [--jobs <n>]"
+        );
         std::process::exit(1);
     }
+    init_thread_pool(&args);
 
     let input_dir = PathBuf::from(&args[1]);
     let output_dir = PathBuf::from(&args[2]);
+    let zstd_level = parse_zstd_level(&args);
+    let crop = args.iter().any(|a| a == "--crop");
 
     if !input_dir.exists() {
         eprintln!("Error: input directory {:?} does not exist", input_dir);
@@ -462,10 +641,15 @@ fn main() {
         .into_iter()
         .filter_map(|e| e.ok())
         .filter(|e| {
-            e.path()
+            let ext = e
+                .path()
                 .extension()
-                .map(|ext| ext.eq_ignore_ascii_case("mpc"))
-                .unwrap_or(false)
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("");
+            // SHD files next to an MPC are merged into its MSF as shadows;
+            // only standalone ones are converted on their own
+            ext.eq_ignore_ascii_case("mpc")
+                || (ext.eq_ignore_ascii_case("shd") && !has_mpc_sibling(e.path()))
         })
         .map(|e| e.into_path())
         .collect();
@@ -506,25 +690,40 @@ fn main() {
         let use_palette_alpha = path_lower.contains("/magic/")
             || path_lower.contains("/effect/")
             || path_lower.ends_with("/ui/column/column2.mpc");
+        let tags = match anim_tags::read_anim_tags(mpc_path) {
+            Ok((_, tags)) => tags,
+            Err(e) => {
+                eprintln!("  ANIM ERROR {}", e);
+                failed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
         match std::fs::read(mpc_path) {
             Ok(mpc_data) => {
                 let mpc_size = mpc_data.len();
-                match msf::convert_mpc_to_msf(&mpc_data, shd_data, use_palette_alpha) {
-                    Some(msf_data) => {
+                match msf::convert_mpc_to_msf(
+                    &mpc_data,
+                    shd_data,
+                    use_palette_alpha,
+                    zstd_level,
+                    crop,
+                    &tags,
+                ) {
+                    Ok(msf_data) => {
                         let msf_size = msf_data.len();
                         if std::fs::write(&msf_path, &msf_data).is_ok() {
                             let n = converted.fetch_add(1, Ordering::Relaxed) + 1;
                             total_mpc_bytes.fetch_add(mpc_size, Ordering::Relaxed);
                             total_msf_bytes.fetch_add(msf_size, Ordering::Relaxed);
-                            if n % 100 == 0 || n == total {
+                            if n.is_multiple_of(100) || n == total {
                                 println!("  [{}/{}]", n, total);
                             }
                         } else {
                             failed.fetch_add(1, Ordering::Relaxed);
                         }
                     }
-                    None => {
-                        eprintln!("  CONVERT ERROR {:?}", mpc_path);
+                    Err(e) => {
+                        eprintln!("  CONVERT ERROR {:?}: {}", mpc_path, e);
                         failed.fetch_add(1, Ordering::Relaxed);
                     }
                 }
@@ -0,0 +1,301 @@
+//! MSF v2 → PNG preview tool
+//!
+//! Usage: cargo run --release --bin msf2png <input_dir> <output_dir> [--atlas]
+//!
+//! Decodes every .msf file under `<input_dir>` to canvas-size RGBA frames and
+//! writes them as PNGs into `<output_dir>`, mirroring the directory structure:
+//! - default:   `<name>/000.png`, `<name>/001.png`, … (one PNG per frame)
+//! - `--atlas`: `<name>.png`, a grid with one row per direction
+//!
+//! Handles Rgba8, Indexed8 and Indexed8Alpha8 frames; transparency is preserved.
+
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use walkdir::WalkDir;
+
+// ============================================================================
+// MSF v2 decoder
+// ============================================================================
+
+struct MsfFrame {
+    offset_x: i16,
+    offset_y: i16,
+    width: u16,
+    height: u16,
+    data_offset: u32,
+    data_length: u32,
+}
+
+struct MsfImage {
+    canvas_w: usize,
+    canvas_h: usize,
+    directions: usize,
+    /// Canvas-size RGBA per frame
+    frames: Vec<Vec<u8>>,
+}
+
+#[inline]
+fn get_u16_le(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+#[inline]
+fn get_u32_le(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// Undo the MSF left-delta row filter (flags bit 2): `row[i] += row[i - bpp]`
+fn unfilter_row_delta(data: &mut [u8], row_bytes: usize, bpp: usize) {
+    for row in data.chunks_exact_mut(row_bytes) {
+        for i in bpp..row_bytes {
+            row[i] = row[i].wrapping_add(row[i - bpp]);
+        }
+    }
+}
+
+fn decode_msf(data: &[u8]) -> Result<MsfImage, String> {
+    if data.len() < 28 || &data[0..4] != b"MSF2" {
+        return Err("not an MSF v2 file".to_string());
+    }
+
+    let flags = get_u16_le(data, 6);
+    let canvas_w = get_u16_le(data, 8) as usize;
+    let canvas_h = get_u16_le(data, 10) as usize;
+    let frame_count = get_u16_le(data, 12) as usize;
+    let directions = (data[14] as usize).max(1);
+
+    let pixel_format = data[24];
+    let palette_size = get_u16_le(data, 25) as usize;
+    let bpp = match pixel_format {
+        0 => 4, // Rgba8
+        1 => 1, // Indexed8
+        2 => 2, // Indexed8Alpha8
+        other => return Err(format!("unknown pixel format {}", other)),
+    };
+
+    let mut palette = [[0u8; 4]; 256];
+    let palette_start = 28;
+    for (i, entry) in palette.iter_mut().enumerate().take(palette_size.min(256)) {
+        let po = palette_start + i * 4;
+        if po + 4 > data.len() {
+            break;
+        }
+        *entry = [data[po], data[po + 1], data[po + 2], data[po + 3]];
+    }
+
+    let frame_table_start = palette_start + palette_size * 4;
+    if frame_table_start + frame_count * 16 > data.len() {
+        return Err("frame table truncated".to_string());
+    }
+    let frame_entries: Vec<MsfFrame> = (0..frame_count)
+        .map(|i| {
+            let ft = frame_table_start + i * 16;
+            MsfFrame {
+                offset_x: get_u16_le(data, ft) as i16,
+                offset_y: get_u16_le(data, ft + 2) as i16,
+                width: get_u16_le(data, ft + 4),
+                height: get_u16_le(data, ft + 6),
+                data_offset: get_u32_le(data, ft + 8),
+                data_length: get_u32_le(data, ft + 12),
+            }
+        })
+        .collect();
+
+    // Skip extension chunks
+    let mut ext_off = frame_table_start + frame_count * 16;
+    loop {
+        if ext_off + 8 > data.len() {
+            return Err("missing END chunk".to_string());
+        }
+        let chunk_id = &data[ext_off..ext_off + 4];
+        let chunk_len = get_u32_le(data, ext_off + 4) as usize;
+        ext_off += 8;
+        if chunk_id == b"END\0" {
+            break;
+        }
+        ext_off += chunk_len;
+    }
+
+    let decompressed: Vec<u8>;
+    let blob: &[u8] = match flags & 3 {
+        0 => &data[ext_off..],
+        1 => {
+            decompressed = zstd::bulk::decompress(&data[ext_off..], 256 * 1024 * 1024)
+                .map_err(|e| format!("zstd: {}", e))?;
+            &decompressed
+        }
+        _ => return Err(format!("unsupported compression flags {:#x}", flags)),
+    };
+
+    let mut frames = Vec::with_capacity(frame_count);
+    for entry in &frame_entries {
+        let mut pixels = vec![0u8; canvas_w * canvas_h * 4];
+        let fw = entry.width as usize;
+        let fh = entry.height as usize;
+        let blob_off = entry.data_offset as usize;
+        let blob_len = entry.data_length as usize;
+
+        if fw > 0 && fh > 0 && blob_off + blob_len <= blob.len() {
+            let mut raw = blob[blob_off..blob_off + blob_len].to_vec();
+            if flags & 4 != 0 {
+                unfilter_row_delta(&mut raw, fw * bpp, bpp);
+            }
+            for y in 0..fh {
+                let cy = entry.offset_y as isize + y as isize;
+                if cy < 0 || cy >= canvas_h as isize {
+                    continue;
+                }
+                for x in 0..fw {
+                    let cx = entry.offset_x as isize + x as isize;
+                    if cx < 0 || cx >= canvas_w as isize {
+                        continue;
+                    }
+                    let src = (y * fw + x) * bpp;
+                    if src + bpp > raw.len() {
+                        continue;
+                    }
+                    let rgba = match pixel_format {
+                        0 => [raw[src], raw[src + 1], raw[src + 2], raw[src + 3]],
+                        1 => palette[raw[src] as usize],
+                        _ => {
+                            let c = palette[raw[src] as usize];
+                            [c[0], c[1], c[2], raw[src + 1]]
+                        }
+                    };
+                    if rgba[3] == 0 {
+                        continue;
+                    }
+                    let dst = (cy as usize * canvas_w + cx as usize) * 4;
+                    pixels[dst..dst + 4].copy_from_slice(&rgba);
+                }
+            }
+        }
+
+        frames.push(pixels);
+    }
+
+    Ok(MsfImage {
+        canvas_w,
+        canvas_h,
+        directions,
+        frames,
+    })
+}
+
+// ============================================================================
+// PNG output
+// ============================================================================
+
+fn write_frames(img: &MsfImage, out_dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(out_dir).map_err(|e| e.to_string())?;
+    for (i, pixels) in img.frames.iter().enumerate() {
+        let frame =
+            image::RgbaImage::from_raw(img.canvas_w as u32, img.canvas_h as u32, pixels.clone())
+                .ok_or("frame buffer size mismatch")?;
+        frame
+            .save(out_dir.join(format!("{:03}.png", i)))
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn write_atlas(img: &MsfImage, png_path: &Path) -> Result<(), String> {
+    let cols = (img.frames.len() / img.directions).max(1);
+    let rows = img.frames.len().div_ceil(cols).max(1);
+    let mut atlas =
+        image::RgbaImage::new((cols * img.canvas_w) as u32, (rows * img.canvas_h) as u32);
+    for (i, pixels) in img.frames.iter().enumerate() {
+        let frame =
+            image::RgbaImage::from_raw(img.canvas_w as u32, img.canvas_h as u32, pixels.clone())
+                .ok_or("frame buffer size mismatch")?;
+        let cell_x = (i % cols * img.canvas_w) as i64;
+        let cell_y = (i / cols * img.canvas_h) as i64;
+        image::imageops::replace(&mut atlas, &frame, cell_x, cell_y);
+    }
+    if let Some(parent) = png_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    atlas.save(png_path).map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// Main
+// ============================================================================
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 {
+        eprintln!("Usage: msf2png <input_dir> <output_dir> [--atlas]");
+        eprintln!("  Writes one PNG per frame, or one grid atlas per file with --atlas");
+        std::process::exit(1);
+    }
+
+    let input_dir = PathBuf::from(&args[1]);
+    let output_dir = PathBuf::from(&args[2]);
+    let atlas = args.iter().any(|a| a == "--atlas");
+
+    if !input_dir.exists() {
+        eprintln!("Error: input directory {:?} does not exist", input_dir);
+        std::process::exit(1);
+    }
+
+    let msf_files: Vec<PathBuf> = WalkDir::new(&input_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .map(|ext| ext.eq_ignore_ascii_case("msf"))
+                .unwrap_or(false)
+        })
+        .map(|e| e.into_path())
+        .collect();
+
+    let total = msf_files.len();
+    println!("Found {} MSF files", total);
+
+    let converted = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+
+    msf_files.par_iter().for_each(|msf_path| {
+        let relative = msf_path.strip_prefix(&input_dir).unwrap_or(msf_path);
+        let result = std::fs::read(msf_path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| decode_msf(&data))
+            .and_then(|img| {
+                if atlas {
+                    write_atlas(&img, &output_dir.join(relative).with_extension("png"))
+                } else {
+                    write_frames(&img, &output_dir.join(relative).with_extension(""))
+                }
+            });
+        match result {
+            Ok(()) => {
+                let n = converted.fetch_add(1, Ordering::Relaxed) + 1;
+                if n.is_multiple_of(100) || n == total {
+                    println!("  [{}/{}]", n, total);
+                }
+            }
+            Err(e) => {
+                eprintln!("  ERROR {:?}: {}", msf_path, e);
+                failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    });
+
+    let c = converted.load(Ordering::Relaxed);
+    let f = failed.load(Ordering::Relaxed);
+    println!("\n=== Done ===");
+    println!("  Converted: {}/{}", c, total);
+    println!("  Failed:    {}", f);
+
+    if f > 0 {
+        std::process::exit(1);
+    }
+}
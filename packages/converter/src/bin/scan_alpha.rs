@@ -96,19 +96,16 @@ fn main() {
     let mut total_semi_all = 0usize;
 
     for path in &asf_files {
-        match std::fs::read(path) {
-            Ok(data) => {
-                let (opaque, semi, alphas) = scan_asf(&data);
-                total_opaque_all += opaque;
-                total_semi_all += semi;
-                if semi > 0 {
-                    files_with_semi += 1;
-                    let rel = path.strip_prefix(&input_dir).unwrap_or(path);
-                    println!("  {:60} opaque={:8} semi={:8} alphas={:?}",
-                        rel.display(), opaque, semi, alphas);
-                }
+        if let Ok(data) = std::fs::read(path) {
+            let (opaque, semi, alphas) = scan_asf(&data);
+            total_opaque_all += opaque;
+            total_semi_all += semi;
+            if semi > 0 {
+                files_with_semi += 1;
+                let rel = path.strip_prefix(&input_dir).unwrap_or(path);
+                println!("  {:60} opaque={:8} semi={:8} alphas={:?}",
+                    rel.display(), opaque, semi, alphas);
             }
-            Err(_) => {}
         }
     }
 
@@ -169,6 +169,15 @@ struct MsfFrame {
     data_length: u32,
 }
 
+/// Undo the MSF left-delta row filter (flags bit 2): `row[i] += row[i - bpp]`
+fn unfilter_row_delta(data: &mut [u8], row_bytes: usize, bpp: usize) {
+    for row in data.chunks_exact_mut(row_bytes) {
+        for i in bpp..row_bytes {
+            row[i] = row[i].wrapping_add(row[i - bpp]);
+        }
+    }
+}
+
 fn decode_msf_to_rgba(data: &[u8]) -> Option<(usize, usize, usize, Vec<Vec<u8>>)> {
     if data.len() < 28 || &data[0..4] != b"MSF2" {
         return None;
@@ -184,21 +193,22 @@ fn decode_msf_to_rgba(data: &[u8]) -> Option<(usize, usize, usize, Vec<Vec<u8>>)
     let pixel_format = data[pf_off];
     let palette_size = u16::from_le_bytes([data[pf_off + 1], data[pf_off + 2]]) as usize;
 
-    if pixel_format != 2 {
-        // Only Indexed8Alpha8 expected for ASF
-        return None;
-    }
-    let bpp = 2usize;
+    // ASF converts to Indexed8Alpha8 (default) or Rgba8 (`asf2msf --format rgba8`)
+    let bpp = match pixel_format {
+        0 => 4usize,
+        2 => 2usize,
+        _ => return None,
+    };
 
     // Read palette
     let mut palette = [[0u8; 4]; 256];
     let palette_start = 28;
-    for i in 0..palette_size.min(256) {
+    for (i, entry) in palette.iter_mut().enumerate().take(palette_size.min(256)) {
         let po = palette_start + i * 4;
         if po + 4 > data.len() {
             break;
         }
-        palette[i] = [data[po], data[po + 1], data[po + 2], data[po + 3]];
+        *entry = [data[po], data[po + 1], data[po + 2], data[po + 3]];
     }
 
     // Frame table
@@ -259,25 +269,29 @@ fn decode_msf_to_rgba(data: &[u8]) -> Option<(usize, usize, usize, Vec<Vec<u8>>)
             let blob_off = entry.data_offset as usize;
             let blob_len = entry.data_length as usize;
             if blob_off + blob_len <= blob.len() {
-                let raw = &blob[blob_off..blob_off + blob_len];
+                let mut raw = blob[blob_off..blob_off + blob_len].to_vec();
+                if flags & 4 != 0 {
+                    unfilter_row_delta(&mut raw, fw * bpp, bpp);
+                }
 
                 for y in 0..fh {
                     for x in 0..fw {
                         let src = (y * fw + x) * bpp;
-                        if src + 1 >= raw.len() {
+                        if src + bpp > raw.len() {
                             continue;
                         }
-                        let color_idx = raw[src] as usize;
-                        let alpha = raw[src + 1];
-                        if alpha == 0 {
+                        let rgba = if bpp == 4 {
+                            [raw[src], raw[src + 1], raw[src + 2], raw[src + 3]]
+                        } else {
+                            let c = palette[raw[src] as usize];
+                            [c[0], c[1], c[2], raw[src + 1]]
+                        };
+                        if rgba[3] == 0 {
                             continue;
                         }
                         let dst = ((oy + y) * canvas_w + ox + x) * 4;
-                        if dst + 4 <= pixels.len() && color_idx < 256 {
-                            pixels[dst] = palette[color_idx][0];
-                            pixels[dst + 1] = palette[color_idx][1];
-                            pixels[dst + 2] = palette[color_idx][2];
-                            pixels[dst + 3] = alpha;
+                        if dst + 4 <= pixels.len() {
+                            pixels[dst..dst + 4].copy_from_slice(&rgba);
                         }
                     }
                 }
@@ -389,7 +403,7 @@ fn main() {
                 }
 
                 let n = passed.fetch_add(1, Ordering::Relaxed) + 1;
-                if n % 200 == 0 || n == total {
+                if n.is_multiple_of(200) || n == total {
                     println!("  [{}/{}] verified OK", n, total);
                 }
             }
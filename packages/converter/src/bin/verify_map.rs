@@ -0,0 +1,385 @@
+//! MAP ↔ MMF round-trip verification tool
+//!
+//! Usage: cargo run --release --bin verify_map <resources_dir>
+//!
+//! For each .map file under `<resources_dir>/map/`, decodes the .mmf written
+//! next to it by `map2mmf` and checks every tile: the MSF name each layer
+//! resolves to (after MPC → compact MSF index remapping), the frame, the
+//! barrier and the trap index must all match the source map. Looping flags of
+//! referenced MSF entries are checked too.
+
+use encoding_rs::GBK;
+use rayon::prelude::*;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[path = "../map_layout.rs"]
+mod map_layout;
+use map_layout::{is_map_file, map_layout, MAP_LAYOUT_CLASSIC};
+
+// ============================================================================
+// MAP parser
+// ============================================================================
+
+struct MapTile {
+    /// Per layer: (frame, 1-based MPC slot, 0 = empty)
+    layers: [(u8, u8); 3],
+    barrier: u8,
+    trap: u8,
+}
+
+struct OldMapData {
+    columns: u16,
+    rows: u16,
+    /// 255 MPC file names (GBK decoded), None for empty slots
+    mpc_names: Vec<Option<String>>,
+    /// Looping flags per MPC slot
+    mpc_looping: Vec<bool>,
+    /// All tiles in row-major order
+    tiles: Vec<MapTile>,
+}
+
+fn get_i32_le(data: &[u8], offset: usize) -> i32 {
+    if offset + 4 > data.len() {
+        return 0;
+    }
+    i32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn read_gbk_string(data: &[u8], offset: usize, max_len: usize) -> String {
+    let end = offset + max_len;
+    if end > data.len() {
+        return String::new();
+    }
+    let mut len = 0;
+    while len < max_len && data[offset + len] != 0 {
+        len += 1;
+    }
+    if len == 0 {
+        return String::new();
+    }
+    let (decoded, _, _) = GBK.decode(&data[offset..offset + len]);
+    decoded.into_owned()
+}
+
+fn parse_old_map(data: &[u8]) -> Result<OldMapData, String> {
+    if !is_map_file(data) || data.len() < MAP_LAYOUT_CLASSIC.tile_offset {
+        return Err("invalid MAP file".to_string());
+    }
+    let (layout, _) = map_layout(data);
+
+    let columns = get_i32_le(data, layout.dims_offset) as u16;
+    let rows = get_i32_le(data, layout.dims_offset + 4) as u16;
+
+    let mut mpc_names: Vec<Option<String>> = Vec::with_capacity(255);
+    let mut mpc_looping: Vec<bool> = Vec::with_capacity(255);
+    for k in 0..255 {
+        let offset = layout.mpc_list_offset + k * 64;
+        let name = read_gbk_string(data, offset, 32);
+        if name.is_empty() {
+            mpc_names.push(None);
+            mpc_looping.push(false);
+        } else {
+            mpc_names.push(Some(name));
+            mpc_looping.push(data[offset + 36] == 1);
+        }
+    }
+
+    let total_tiles = columns as usize * rows as usize;
+    let mut tiles = Vec::with_capacity(total_tiles);
+    let mut offset = layout.tile_offset;
+    for _ in 0..total_tiles {
+        if offset + layout.tile_stride > data.len() {
+            break;
+        }
+        tiles.push(MapTile {
+            layers: [
+                (data[offset], data[offset + 1]),
+                (data[offset + 2], data[offset + 3]),
+                (data[offset + 4], data[offset + 5]),
+            ],
+            barrier: data[offset + 6],
+            trap: data[offset + 7],
+        });
+        offset += layout.tile_stride;
+    }
+
+    Ok(OldMapData {
+        columns,
+        rows,
+        mpc_names,
+        mpc_looping,
+        tiles,
+    })
+}
+
+/// MSF table name for an MPC slot name (`.mpc` → `.msf`, others unchanged)
+fn msf_name_for(mpc_name: &str) -> String {
+    if mpc_name.to_lowercase().ends_with(".mpc") {
+        format!("{}.msf", &mpc_name[..mpc_name.len() - 4])
+    } else {
+        mpc_name.to_string()
+    }
+}
+
+// ============================================================================
+// MMF decoder
+// ============================================================================
+
+struct MmfData {
+    columns: u16,
+    rows: u16,
+    /// (name, looping) per entry; tile index N refers to entry N - 1
+    msf_entries: Vec<(String, bool)>,
+    /// Decompressed tile blob: 3 layers × [msfIndex, frame], barriers, traps
+    blob: Vec<u8>,
+}
+
+fn decode_mmf(data: &[u8]) -> Result<MmfData, String> {
+    if data.len() < 20 || &data[0..4] != b"MMF1" {
+        return Err("not an MMF v1 file".to_string());
+    }
+    let read_u16 = |off: usize| u16::from_le_bytes([data[off], data[off + 1]]);
+    let flags = read_u16(6);
+    let columns = read_u16(8);
+    let rows = read_u16(10);
+    let msf_count = read_u16(12);
+    let trap_count = read_u16(14);
+    let truncated = || "truncated tables".to_string();
+
+    let mut off = 20;
+    let mut msf_entries = Vec::with_capacity(msf_count as usize);
+    for _ in 0..msf_count {
+        let name_len = *data.get(off).ok_or_else(truncated)? as usize;
+        let name = data
+            .get(off + 1..off + 1 + name_len)
+            .ok_or_else(truncated)?;
+        let entry_flags = *data.get(off + 1 + name_len).ok_or_else(truncated)?;
+        msf_entries.push((
+            String::from_utf8_lossy(name).into_owned(),
+            entry_flags & 1 != 0,
+        ));
+        off += name_len + 2;
+    }
+
+    if flags & 0x02 != 0 {
+        for _ in 0..trap_count {
+            let entry = data.get(off..off + 3).ok_or_else(truncated)?;
+            off += 3 + u16::from_le_bytes([entry[1], entry[2]]) as usize;
+        }
+    }
+
+    loop {
+        let chunk = data.get(off..off + 8).ok_or("missing END chunk")?;
+        let chunk_len = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as usize;
+        off += 8;
+        if &chunk[0..4] == b"END\0" {
+            break;
+        }
+        off += chunk_len;
+    }
+
+    let rest = data.get(off..).ok_or("missing tile blob")?;
+    let blob = if flags & 0x01 != 0 {
+        zstd::bulk::decompress(rest, 64 * 1024 * 1024).map_err(|e| format!("zstd: {}", e))?
+    } else {
+        rest.to_vec()
+    };
+
+    let total_tiles = columns as usize * rows as usize;
+    if blob.len() < total_tiles * 8 {
+        return Err(format!(
+            "tile blob is {} bytes, expected {}",
+            blob.len(),
+            total_tiles * 8
+        ));
+    }
+
+    Ok(MmfData {
+        columns,
+        rows,
+        msf_entries,
+        blob,
+    })
+}
+
+// ============================================================================
+// Comparison
+// ============================================================================
+
+/// Compare a source map with its MMF, returning the first mismatch
+fn verify_map(map: &OldMapData, mmf: &MmfData) -> Result<usize, String> {
+    if (map.columns, map.rows) != (mmf.columns, mmf.rows) {
+        return Err(format!(
+            "size MAP={}x{} MMF={}x{}",
+            map.columns, map.rows, mmf.columns, mmf.rows
+        ));
+    }
+    let total_tiles = map.columns as usize * map.rows as usize;
+    if map.tiles.len() != total_tiles {
+        return Err(format!(
+            "MAP has {} tiles, expected {}",
+            map.tiles.len(),
+            total_tiles
+        ));
+    }
+
+    let barriers = &mmf.blob[total_tiles * 6..total_tiles * 7];
+    let traps = &mmf.blob[total_tiles * 7..total_tiles * 8];
+
+    for (i, tile) in map.tiles.iter().enumerate() {
+        let (col, row) = (i % map.columns as usize, i / map.columns as usize);
+
+        for (layer, &(frame, mpc)) in tile.layers.iter().enumerate() {
+            let cell = layer * total_tiles * 2 + i * 2;
+            let (msf_index, msf_frame) = (mmf.blob[cell], mmf.blob[cell + 1]);
+
+            // Source: 1-based MPC slot; an empty slot resolves to nothing
+            let expected = match mpc {
+                0 => None,
+                slot => map.mpc_names[slot as usize - 1]
+                    .as_ref()
+                    .map(|name| (msf_name_for(name), map.mpc_looping[slot as usize - 1])),
+            };
+            // MMF: 1-based MSF table index
+            let actual =
+                match msf_index {
+                    0 => None,
+                    idx => Some(mmf.msf_entries.get(idx as usize - 1).cloned().ok_or_else(
+                        || {
+                            format!(
+                                "tile ({}, {}) layer {}: MSF index {} out of range ({} entries)",
+                                col,
+                                row,
+                                layer + 1,
+                                idx,
+                                mmf.msf_entries.len()
+                            )
+                        },
+                    )?),
+                };
+
+            if expected != actual {
+                return Err(format!(
+                    "tile ({}, {}) layer {}: MAP resolves to {:?}, MMF to {:?}",
+                    col,
+                    row,
+                    layer + 1,
+                    expected,
+                    actual
+                ));
+            }
+            if frame != msf_frame {
+                return Err(format!(
+                    "tile ({}, {}) layer {}: frame MAP={} MMF={}",
+                    col,
+                    row,
+                    layer + 1,
+                    frame,
+                    msf_frame
+                ));
+            }
+        }
+
+        if tile.barrier != barriers[i] {
+            return Err(format!(
+                "tile ({}, {}): barrier MAP={} MMF={}",
+                col, row, tile.barrier, barriers[i]
+            ));
+        }
+        if tile.trap != traps[i] {
+            return Err(format!(
+                "tile ({}, {}): trap MAP={} MMF={}",
+                col, row, tile.trap, traps[i]
+            ));
+        }
+    }
+
+    Ok(total_tiles)
+}
+
+// ============================================================================
+// Main
+// ============================================================================
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        eprintln!("Usage: verify_map <resources_dir>");
+        eprintln!("  Checks every <resources_dir>/map/*.map against the .mmf next to it");
+        std::process::exit(1);
+    }
+
+    let map_dir = PathBuf::from(&args[1]).join("map");
+    if !map_dir.exists() {
+        eprintln!("Error: map directory {:?} does not exist", map_dir);
+        std::process::exit(1);
+    }
+
+    let map_files: Vec<PathBuf> = walkdir::WalkDir::new(&map_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .map(|ext| ext.eq_ignore_ascii_case("map"))
+                .unwrap_or(false)
+        })
+        .map(|e| e.into_path())
+        .collect();
+
+    let total = map_files.len();
+    println!("Verifying {} MAP ↔ MMF file pairs...", total);
+
+    let passed = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    let skipped = AtomicUsize::new(0);
+
+    map_files.par_iter().for_each(|map_path| {
+        let mmf_path = map_path.with_extension("mmf");
+        if !mmf_path.exists() {
+            skipped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let result = std::fs::read(map_path)
+            .map_err(|e| format!("read MAP: {}", e))
+            .and_then(|d| parse_old_map(&d))
+            .and_then(|map| {
+                let mmf = std::fs::read(&mmf_path)
+                    .map_err(|e| format!("read MMF: {}", e))
+                    .and_then(|d| decode_mmf(&d))?;
+                verify_map(&map, &mmf)
+            });
+
+        match result {
+            Ok(tiles) => {
+                passed.fetch_add(1, Ordering::Relaxed);
+                println!("  [PASS] {:?} — {} tiles", map_path, tiles);
+            }
+            Err(e) => {
+                eprintln!("  MISMATCH {:?}: {}", map_path, e);
+                failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    });
+
+    let p = passed.load(Ordering::Relaxed);
+    let f = failed.load(Ordering::Relaxed);
+    let s = skipped.load(Ordering::Relaxed);
+
+    println!();
+    println!("=== Verification Complete ===");
+    println!("  Passed:  {}", p);
+    println!("  Failed:  {}", f);
+    println!("  Skipped: {} (no .mmf found)", s);
+
+    if f > 0 {
+        std::process::exit(1);
+    }
+}
@@ -4,6 +4,8 @@
 //!
 //! For each .mpc file, finds the corresponding .msf file and verifies
 //! that decoding both produces identical RGBA pixel data.
+//! Handles Rgba8 and Indexed8 MSF output, including frames cropped with
+//! `mpc2msf --crop` (re-placed at their offset before comparing).
 
 use rayon::prelude::*;
 use std::path::PathBuf;
@@ -148,6 +150,8 @@ fn decode_mpc(data: &[u8]) -> Option<(usize, Vec<[u8; 4]>, Vec<MpcFrame>)> {
 // ============================================================================
 
 struct MsfIndFrame {
+    offset_x: usize,
+    offset_y: usize,
     width: usize,
     height: usize,
     rgba: Vec<u8>,
@@ -168,18 +172,19 @@ fn decode_msf_individual(data: &[u8]) -> Option<Vec<MsfIndFrame>> {
     let pixel_format = data[pf_off];
     let palette_size = u16::from_le_bytes([data[pf_off + 1], data[pf_off + 2]]) as usize;
 
-    if pixel_format != 1 {
+    // 0 = Rgba8, 1 = Indexed8
+    if pixel_format > 1 {
         return None;
     }
 
     let mut palette = [[0u8; 4]; 256];
     let palette_start = 28;
-    for i in 0..palette_size.min(256) {
+    for (i, entry) in palette.iter_mut().enumerate().take(palette_size.min(256)) {
         let po = palette_start + i * 4;
         if po + 4 > data.len() {
             break;
         }
-        palette[i] = [data[po], data[po + 1], data[po + 2], data[po + 3]];
+        *entry = [data[po], data[po + 1], data[po + 2], data[po + 3]];
     }
 
     let frame_table_start = palette_start + palette_size * 4;
@@ -188,6 +193,8 @@ fn decode_msf_individual(data: &[u8]) -> Option<Vec<MsfIndFrame>> {
     }
 
     struct FE {
+        offset_x: i16,
+        offset_y: i16,
         width: u16,
         height: u16,
         data_offset: u32,
@@ -197,14 +204,14 @@ fn decode_msf_individual(data: &[u8]) -> Option<Vec<MsfIndFrame>> {
     let mut frame_entries = Vec::with_capacity(frame_count);
     let mut ft_off = frame_table_start;
     for _ in 0..frame_count {
-        let _ox = i16::from_le_bytes([data[ft_off], data[ft_off + 1]]);
-        let _oy = i16::from_le_bytes([data[ft_off + 2], data[ft_off + 3]]);
+        let ox = i16::from_le_bytes([data[ft_off], data[ft_off + 1]]);
+        let oy = i16::from_le_bytes([data[ft_off + 2], data[ft_off + 3]]);
         let w = u16::from_le_bytes([data[ft_off + 4], data[ft_off + 5]]);
         let h = u16::from_le_bytes([data[ft_off + 6], data[ft_off + 7]]);
         let doff = u32::from_le_bytes([data[ft_off + 8], data[ft_off + 9], data[ft_off + 10], data[ft_off + 11]]);
         let dlen = u32::from_le_bytes([data[ft_off + 12], data[ft_off + 13], data[ft_off + 14], data[ft_off + 15]]);
         ft_off += 16;
-        frame_entries.push(FE { width: w, height: h, data_offset: doff, data_length: dlen });
+        frame_entries.push(FE { offset_x: ox, offset_y: oy, width: w, height: h, data_offset: doff, data_length: dlen });
     }
 
     // Skip extension chunks
@@ -238,6 +245,8 @@ fn decode_msf_individual(data: &[u8]) -> Option<Vec<MsfIndFrame>> {
 
         if fw == 0 || fh == 0 {
             result.push(MsfIndFrame {
+                offset_x: 0,
+                offset_y: 0,
                 width: 0,
                 height: 0,
                 rgba: Vec::new(),
@@ -249,7 +258,11 @@ fn decode_msf_individual(data: &[u8]) -> Option<Vec<MsfIndFrame>> {
         let blob_len = entry.data_length as usize;
         let mut pixels = vec![0u8; fw * fh * 4];
 
-        if blob_off + blob_len <= blob.len() {
+        if blob_off + blob_len <= blob.len() && pixel_format == 0 {
+            let raw = &blob[blob_off..blob_off + blob_len];
+            let n = raw.len().min(pixels.len());
+            pixels[..n].copy_from_slice(&raw[..n]);
+        } else if blob_off + blob_len <= blob.len() {
             let raw = &blob[blob_off..blob_off + blob_len];
 
             for p in 0..fw * fh {
@@ -270,6 +283,8 @@ fn decode_msf_individual(data: &[u8]) -> Option<Vec<MsfIndFrame>> {
         }
 
         result.push(MsfIndFrame {
+            offset_x: entry.offset_x.max(0) as usize,
+            offset_y: entry.offset_y.max(0) as usize,
             width: fw,
             height: fh,
             rgba: pixels,
@@ -279,6 +294,20 @@ fn decode_msf_individual(data: &[u8]) -> Option<Vec<MsfIndFrame>> {
     Some(result)
 }
 
+/// Place a (possibly cropped) MSF frame into a full-size transparent buffer
+fn place_frame(frame: &MsfIndFrame, width: usize, height: usize) -> Vec<u8> {
+    if frame.offset_x == 0 && frame.offset_y == 0 && frame.width == width && frame.height == height {
+        return frame.rgba.clone();
+    }
+    let mut out = vec![0u8; width * height * 4];
+    for y in 0..frame.height {
+        let src = y * frame.width * 4;
+        let dst = ((frame.offset_y + y) * width + frame.offset_x) * 4;
+        out[dst..dst + frame.width * 4].copy_from_slice(&frame.rgba[src..src + frame.width * 4]);
+    }
+    out
+}
+
 // ============================================================================
 // Main
 // ============================================================================
@@ -357,20 +386,22 @@ fn main() {
                     let mpc_f = &mpc_frames[f];
                     let msf_f = &msf_frames[f];
 
-                    if mpc_f.width != msf_f.width || mpc_f.height != msf_f.height {
+                    if msf_f.offset_x + msf_f.width > mpc_f.width || msf_f.offset_y + msf_f.height > mpc_f.height {
                         eprintln!(
-                            "  MISMATCH {:?} frame {}: size MPC={}x{} MSF={}x{}",
-                            mpc_path, f, mpc_f.width, mpc_f.height, msf_f.width, msf_f.height
+                            "  MISMATCH {:?} frame {}: size MPC={}x{} MSF={}x{} at ({}, {})",
+                            mpc_path, f, mpc_f.width, mpc_f.height, msf_f.width, msf_f.height,
+                            msf_f.offset_x, msf_f.offset_y
                         );
                         failed.fetch_add(1, Ordering::Relaxed);
                         return;
                     }
 
-                    if mpc_f.rgba != msf_f.rgba {
+                    let msf_rgba = place_frame(msf_f, mpc_f.width, mpc_f.height);
+                    if mpc_f.rgba != msf_rgba {
                         let mut diff_count = 0;
                         let mut first_diff = None;
-                        for p in 0..mpc_f.rgba.len() {
-                            if mpc_f.rgba[p] != msf_f.rgba[p] {
+                        for (p, (a, b)) in mpc_f.rgba.iter().zip(&msf_rgba).enumerate() {
+                            if a != b {
                                 diff_count += 1;
                                 if first_diff.is_none() {
                                     first_diff = Some(p);
@@ -387,7 +418,7 @@ fn main() {
                 }
 
                 let n = passed.fetch_add(1, Ordering::Relaxed) + 1;
-                if n % 50 == 0 || n == total {
+                if n.is_multiple_of(50) || n == total {
                     println!("  [{}/{}] verified OK", n, total);
                 }
             }
@@ -1,21 +1,112 @@
 //! ASF → MSF v2 batch conversion tool
 //!
 //! Usage:
-//!   asf2msf <input_dir> <output_dir>
+//!   asf2msf <input_dir> <output_dir> [--png] [--zstd-level <1-22>] [--requantize]
+//!           [--row-filter] [--format <indexed8alpha8|rgba8>] [--crc] [--jobs <n>]
 //!
 //! Recursively converts all .asf files to MSF v2 format.
-//! MSF v2: Indexed8Alpha8 (2bpp) + zstd compression, no row filters.
+//! With `--png`, also writes a `.png` atlas of all frames next to each `.msf`
+//! (one row per direction, canvas-sized cells) for visual inspection.
+//! With `--requantize`, the palette is rebuilt from the decoded pixels by
+//! median cut instead of using the file's palette (always done for files
+//! declaring more than 256 colors).
+//! With `--row-filter`, each frame row is left-delta filtered before zstd
+//! (flags bit 2); off by default since it rarely helps palette indices.
+//! With `--format rgba8`, frames are stored as full RGBA instead of palette
+//! index + alpha, for effect sprites whose blending the palette can't hold.
+//! With `--crc`, a `CRC3` chunk stores the CRC-32 of the compressed blob
+//! (flags bit 3) so corrupted downloads can be detected.
+//! With `--jobs <n>`, at most `n` worker threads are used (default: all cores).
+//! A `.anim` file next to an `.asf` (same stem) names frame sub-ranges, one
+//! `name start end [loop]` per line (frames counted within one direction,
+//! `end` inclusive, `#` starts a comment); they are stored in a `TAGS` chunk.
+//! MSF v2: Indexed8Alpha8 (2bpp, default) or Rgba8 (4bpp) + zstd compression.
 
 use rayon::prelude::*;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use walkdir::WalkDir;
 
+mod anim_tags;
+
 mod msf {
+    use crate::anim_tags::{check_tag_range, tags_chunk, AnimTag};
+    use rayon::prelude::*;
+    use std::collections::HashMap;
+
     pub const MSF_MAGIC: &[u8; 4] = b"MSF2";
     pub const MSF_VERSION: u16 = 2;
     pub const CHUNK_END: &[u8; 4] = b"END\0";
+    /// Extension chunk holding the exact ASF interval (ms, u16); fps is rounded
+    pub const CHUNK_IVAL: &[u8; 4] = b"IVAL";
+    /// Extension chunk holding the CRC-32 of the stored (compressed) blob
+    pub const CHUNK_CRC3: &[u8; 4] = b"CRC3";
+    /// Flags bit 0: frame blob is zstd-compressed
+    const FLAG_ZSTD: u16 = 1;
+    /// Flags bit 2: frame rows are left-delta filtered
+    const FLAG_ROW_DELTA: u16 = 1 << 2;
+    /// Flags bit 3: a `CRC3` chunk is present
+    const FLAG_CRC: u16 = 1 << 3;
     const FRAME_ENTRY_SIZE: usize = 16;
+    /// Frame count above which a single file's frames are decoded in parallel
+    const PARALLEL_FRAME_THRESHOLD: usize = 64;
+
+    /// Stored pixel format (`--format`)
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum PixelFormat {
+        /// Palette index + alpha per pixel (2bpp)
+        Indexed8Alpha8,
+        /// Full RGBA per pixel (4bpp), no palette
+        Rgba8,
+    }
+
+    impl PixelFormat {
+        /// MSF header pixel format code
+        fn code(self) -> u8 {
+            match self {
+                PixelFormat::Rgba8 => 0,
+                PixelFormat::Indexed8Alpha8 => 2,
+            }
+        }
+
+        pub fn bytes_per_pixel(self) -> usize {
+            match self {
+                PixelFormat::Rgba8 => 4,
+                PixelFormat::Indexed8Alpha8 => 2,
+            }
+        }
+    }
+
+    /// Per-run conversion options (from CLI flags)
+    #[derive(Clone, Copy)]
+    pub struct ConvertOptions {
+        /// Also return a debug PNG atlas (`--png`)
+        pub atlas: bool,
+        /// zstd level for the frame blob (`--zstd-level`)
+        pub zstd_level: i32,
+        /// Rebuild the palette from decoded pixels (`--requantize`)
+        pub requantize: bool,
+        /// Left-delta filter each frame row before compression (`--row-filter`)
+        pub row_filter: bool,
+        /// Stored pixel format (`--format`)
+        pub format: PixelFormat,
+        /// Write a `CRC3` chunk over the compressed blob (`--crc`)
+        pub crc: bool,
+    }
+
+    impl Default for ConvertOptions {
+        /// Same as running with no flags
+        fn default() -> Self {
+            ConvertOptions {
+                atlas: false,
+                zstd_level: crate::DEFAULT_ZSTD_LEVEL,
+                requantize: false,
+                row_filter: false,
+                format: PixelFormat::Indexed8Alpha8,
+                crc: false,
+            }
+        }
+    }
 
     struct FrameEntry {
         offset_x: i16,
@@ -78,6 +169,16 @@ mod msf {
         out
     }
 
+    /// Left-delta filter rows in place: `row[i] -= row[i - bpp]`, right to left
+    /// so every byte is differenced against the original previous pixel
+    fn filter_row_delta(data: &mut [u8], row_bytes: usize, bpp: usize) {
+        for row in data.chunks_exact_mut(row_bytes) {
+            for i in (bpp..row_bytes).rev() {
+                row[i] = row[i].wrapping_sub(row[i - bpp]);
+            }
+        }
+    }
+
     /// Convert RGBA pixels to Indexed8Alpha8 (2bpp): [palette_index, alpha] per pixel.
     fn rgba_to_indexed_alpha(pixels: &[u8], palette: &[[u8; 4]]) -> Vec<u8> {
         let pixel_count = pixels.len() / 4;
@@ -113,6 +214,101 @@ mod msf {
         data
     }
 
+    /// Channel (0=R, 1=G, 2=B) with the widest value range in a color box
+    fn widest_channel(colors: &[([u8; 3], u32)]) -> (usize, u8) {
+        let mut lo = [255u8; 3];
+        let mut hi = [0u8; 3];
+        for (c, _) in colors {
+            for ch in 0..3 {
+                lo[ch] = lo[ch].min(c[ch]);
+                hi[ch] = hi[ch].max(c[ch]);
+            }
+        }
+        (0..3)
+            .map(|ch| (ch, hi[ch] - lo[ch]))
+            .max_by_key(|&(_, range)| range)
+            .unwrap_or((0, 0))
+    }
+
+    /// Build a palette of at most `max_colors` entries from RGBA pixels by median cut.
+    ///
+    /// Only visible pixels (alpha > 0) count; each color is weighted by how often
+    /// it occurs, and each box is represented by its weighted mean.
+    fn median_cut_palette<'a>(
+        frames: impl Iterator<Item = &'a [u8]>,
+        max_colors: usize,
+    ) -> Vec<[u8; 4]> {
+        let mut histogram: HashMap<[u8; 3], u32> = HashMap::new();
+        for pixels in frames {
+            for px in pixels.chunks_exact(4) {
+                if px[3] > 0 {
+                    *histogram.entry([px[0], px[1], px[2]]).or_default() += 1;
+                }
+            }
+        }
+        let mut colors: Vec<([u8; 3], u32)> = histogram.into_iter().collect();
+        colors.sort_unstable();
+
+        if colors.len() <= max_colors {
+            return colors
+                .iter()
+                .map(|(c, _)| [c[0], c[1], c[2], 255])
+                .collect();
+        }
+
+        let mut boxes = vec![colors];
+        while boxes.len() < max_colors {
+            // Split the box with the widest channel range at its weighted median
+            let Some((bi, ch)) = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.len() > 1)
+                .map(|(i, b)| (i, widest_channel(b)))
+                .max_by_key(|&(_, (_, range))| range)
+                .map(|(i, (ch, _))| (i, ch))
+            else {
+                break;
+            };
+
+            let mut b = boxes.swap_remove(bi);
+            b.sort_unstable_by_key(|(c, _)| c[ch]);
+            let total: u64 = b.iter().map(|&(_, n)| n as u64).sum();
+            let mut acc = 0u64;
+            let mut split = b.len() / 2;
+            for (i, &(_, n)) in b.iter().enumerate() {
+                acc += n as u64;
+                if acc * 2 >= total {
+                    split = (i + 1).clamp(1, b.len() - 1);
+                    break;
+                }
+            }
+            let upper = b.split_off(split);
+            boxes.push(b);
+            boxes.push(upper);
+        }
+
+        boxes
+            .iter()
+            .map(|b| {
+                let mut sum = [0u64; 3];
+                let mut weight = 0u64;
+                for &(c, n) in b {
+                    for ch in 0..3 {
+                        sum[ch] += c[ch] as u64 * n as u64;
+                    }
+                    weight += n as u64;
+                }
+                let w = weight.max(1);
+                [
+                    ((sum[0] + w / 2) / w) as u8,
+                    ((sum[1] + w / 2) / w) as u8,
+                    ((sum[2] + w / 2) / w) as u8,
+                    255,
+                ]
+            })
+            .collect()
+    }
+
     #[inline]
     fn get_i32_le(data: &[u8], offset: usize) -> i32 {
         if offset + 4 > data.len() {
@@ -166,14 +362,24 @@ mod msf {
         }
     }
 
-    /// Convert a single ASF file to MSF v2 (Indexed8 1bpp + zstd)
-    pub fn convert_asf_to_msf(asf_data: &[u8]) -> Option<Vec<u8>> {
-        if asf_data.len() < 80 {
-            return None;
-        }
-        let sig = std::str::from_utf8(&asf_data[0..7]).ok()?;
-        if sig != "ASF 1.0" {
-            return None;
+    /// Convert a single ASF file to MSF v2 (`opts.format` + zstd)
+    ///
+    /// With `opts.atlas`, also returns a debug atlas of the decoded frames:
+    /// canvas-sized cells, one row per direction.
+    ///
+    /// Non-empty `tags` are written to a `TAGS` chunk.
+    ///
+    /// Fails with a description for files that are not ASF 1.0, whose
+    /// palette is empty (frame pixels are palette indices, so there is
+    /// nothing to recover and the output would be a blank sheet), or whose
+    /// tags reach past the frames of one direction.
+    pub fn convert_asf_to_msf(
+        asf_data: &[u8],
+        opts: &ConvertOptions,
+        tags: &[AnimTag],
+    ) -> Result<(Vec<u8>, Option<image::RgbaImage>), String> {
+        if asf_data.len() < 80 || &asf_data[0..7] != b"ASF 1.0" {
+            return Err("not an ASF 1.0 file".to_string());
         }
 
         let mut offset = 16usize;
@@ -213,6 +419,12 @@ mod msf {
             offset += 4;
             palette.push([r, g, b, 255]);
         }
+        if palette.is_empty() && frame_count > 0 {
+            return Err(format!(
+                "empty palette (color_count = {}), frames would decode blank",
+                color_count
+            ));
+        }
 
         // Frame offsets
         let mut frame_offsets = Vec::with_capacity(frame_count as usize);
@@ -230,11 +442,26 @@ mod msf {
         let w = width as usize;
         let h = height as usize;
 
-        // Phase 1: Decode frames → RGBA → tight bbox
-        let mut frames_rgba: Vec<(Vec<u8>, i16, i16, u16, u16)> =
-            Vec::with_capacity(frame_count as usize);
+        let frames_per_direction = if directions > 0 {
+            (frame_count as usize / directions as usize).max(1)
+        } else {
+            (frame_count as usize).max(1)
+        };
+        check_tag_range(tags, frame_count, directions)?;
+
+        let atlas_cols = frames_per_direction;
+        let atlas_rows = (frame_count as usize).div_ceil(atlas_cols).max(1);
+        let mut atlas = if opts.atlas {
+            Some(image::RgbaImage::new(
+                (atlas_cols * w) as u32,
+                (atlas_rows * h) as u32,
+            ))
+        } else {
+            None
+        };
 
-        for i in 0..frame_count as usize {
+        // Phase 1: Decode frames → RGBA → tight bbox
+        let decode_frame = |i: usize| -> (Vec<u8>, i16, i16, u16, u16) {
             let mut pixels = vec![0u8; w * h * 4];
             if i < frame_offsets.len() {
                 decode_asf_rle_frame(
@@ -250,7 +477,7 @@ mod msf {
 
             let (ox, oy, bw, bh) = compute_tight_bbox(&pixels, w, h);
             if bw == 0 || bh == 0 {
-                frames_rgba.push((Vec::new(), 0, 0, 0, 0));
+                (Vec::new(), 0, 0, 0, 0)
             } else {
                 let cropped = extract_bbox_pixels(
                     &pixels,
@@ -260,11 +487,46 @@ mod msf {
                     bw as usize,
                     bh as usize,
                 );
-                frames_rgba.push((cropped, ox, oy, bw, bh));
+                (cropped, ox, oy, bw, bh)
+            }
+        };
+        // Files are already converted in parallel; only split a single file's
+        // frames across threads when there are enough of them to pay off
+        let frames_rgba: Vec<(Vec<u8>, i16, i16, u16, u16)> =
+            if frame_count as usize >= PARALLEL_FRAME_THRESHOLD {
+                (0..frame_count as usize)
+                    .into_par_iter()
+                    .map(decode_frame)
+                    .collect()
+            } else {
+                (0..frame_count as usize).map(decode_frame).collect()
+            };
+
+        if let Some(atlas) = atlas.as_mut() {
+            // Pixels outside the bbox are fully transparent, so placing the
+            // cropped frame at its offset reproduces the full frame
+            for (i, (pixels, ox, oy, bw, bh)) in frames_rgba.iter().enumerate() {
+                let cell_x = (i % atlas_cols * w) as i64 + *ox as i64;
+                let cell_y = (i / atlas_cols * h) as i64 + *oy as i64;
+                if let Some(frame) =
+                    image::RgbaImage::from_raw(*bw as u32, *bh as u32, pixels.clone())
+                {
+                    image::imageops::replace(atlas, &frame, cell_x, cell_y);
+                }
             }
         }
 
-        // Phase 2: Convert to Indexed8Alpha8 (2bpp)
+        // Palette indices are u8, so more than 256 declared colors forces
+        // requantization; Rgba8 output stores no palette at all
+        let palette = match opts.format {
+            PixelFormat::Rgba8 => Vec::new(),
+            PixelFormat::Indexed8Alpha8 if opts.requantize || palette.len() > 256 => {
+                median_cut_palette(frames_rgba.iter().map(|f| f.0.as_slice()), 256)
+            }
+            PixelFormat::Indexed8Alpha8 => palette,
+        };
+
+        // Phase 2: Convert to the stored pixel format
         let mut frame_entries: Vec<FrameEntry> = Vec::with_capacity(frame_count as usize);
         let mut raw_frame_data: Vec<Vec<u8>> = Vec::with_capacity(frame_count as usize);
 
@@ -280,7 +542,10 @@ mod msf {
                 });
                 raw_frame_data.push(Vec::new());
             } else {
-                let indexed = rgba_to_indexed_alpha(pixels, &palette);
+                let stored = match opts.format {
+                    PixelFormat::Indexed8Alpha8 => rgba_to_indexed_alpha(pixels, &palette),
+                    PixelFormat::Rgba8 => pixels.clone(),
+                };
                 frame_entries.push(FrameEntry {
                     offset_x: *ox,
                     offset_y: *oy,
@@ -289,29 +554,54 @@ mod msf {
                     data_offset: 0,
                     data_length: 0,
                 });
-                raw_frame_data.push(indexed);
+                raw_frame_data.push(stored);
             }
         }
 
-        // Concatenate frame data
+        // Concatenate frame data; byte-identical payloads (e.g. repeated idle
+        // frames) are stored once and shared through the frame table
         let mut concat_raw = Vec::new();
+        let mut stored: HashMap<&[u8], u32> = HashMap::new();
         for (i, data) in raw_frame_data.iter().enumerate() {
-            frame_entries[i].data_offset = concat_raw.len() as u32;
+            let data_offset = *stored.entry(data.as_slice()).or_insert_with(|| {
+                let off = concat_raw.len() as u32;
+                concat_raw.extend_from_slice(data);
+                if opts.row_filter {
+                    let bpp = opts.format.bytes_per_pixel();
+                    let row_bytes = frame_entries[i].width as usize * bpp;
+                    filter_row_delta(&mut concat_raw[off as usize..], row_bytes, bpp);
+                }
+                off
+            });
+            frame_entries[i].data_offset = data_offset;
             frame_entries[i].data_length = data.len() as u32;
-            concat_raw.extend_from_slice(data);
         }
 
-        let flags: u16 = 1; // bit 0: zstd
-        let compressed_blob = zstd::bulk::compress(&concat_raw, 3).ok()?;
+        let mut flags = if opts.row_filter {
+            FLAG_ZSTD | FLAG_ROW_DELTA
+        } else {
+            FLAG_ZSTD
+        };
+        if opts.crc {
+            flags |= FLAG_CRC;
+        }
+        let compressed_blob = zstd::bulk::compress(&concat_raw, opts.zstd_level)
+            .map_err(|e| format!("zstd: {}", e))?;
 
         let palette_bytes = palette.len() * 4;
         let frame_table_bytes = frame_count as usize * FRAME_ENTRY_SIZE;
+        let ival_chunk_bytes = if interval > 0 { 8 + 2 } else { 0 };
+        let tags_chunk = tags_chunk(tags);
+        let crc_chunk_bytes = if opts.crc { 8 + 4 } else { 0 };
         let end_chunk_bytes = 8;
         let total = 8
             + 16
             + 4
             + palette_bytes
             + frame_table_bytes
+            + ival_chunk_bytes
+            + tags_chunk.len()
+            + crc_chunk_bytes
             + end_chunk_bytes
             + compressed_blob.len();
         let mut out = Vec::with_capacity(total);
@@ -331,8 +621,8 @@ mod msf {
         out.extend_from_slice(&bottom.to_le_bytes());
         out.extend_from_slice(&[0u8; 4]);
 
-        // Pixel format: Indexed8Alpha8 (2)
-        out.push(2);
+        // Pixel format block (Rgba8 has palette_size = 0)
+        out.push(opts.format.code());
         out.extend_from_slice(&(palette.len() as u16).to_le_bytes());
         out.push(0);
 
@@ -351,6 +641,23 @@ mod msf {
             out.extend_from_slice(&entry.data_length.to_le_bytes());
         }
 
+        // Exact interval (1000 / fps doesn't round-trip)
+        if interval > 0 {
+            out.extend_from_slice(CHUNK_IVAL);
+            out.extend_from_slice(&2u32.to_le_bytes());
+            out.extend_from_slice(&interval.to_le_bytes());
+        }
+
+        // Named frame sub-ranges
+        out.extend_from_slice(&tags_chunk);
+
+        // Integrity check over the blob exactly as stored
+        if opts.crc {
+            out.extend_from_slice(CHUNK_CRC3);
+            out.extend_from_slice(&4u32.to_le_bytes());
+            out.extend_from_slice(&crc32fast::hash(&compressed_blob).to_le_bytes());
+        }
+
         // End sentinel
         out.extend_from_slice(CHUNK_END);
         out.extend_from_slice(&0u32.to_le_bytes());
@@ -358,19 +665,359 @@ mod msf {
         // Compressed blob
         out.extend_from_slice(&compressed_blob);
 
-        Some(out)
+        Ok((out, atlas))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::anim_tags::{parse_anim_tags, CHUNK_TAGS};
+
+        /// Build an ASF 1.0 file with one direction and fully opaque
+        /// `width × height` frames given as palette indices
+        fn build_asf(
+            width: usize,
+            height: usize,
+            palette: &[[u8; 3]],
+            frames: &[Vec<u8>],
+        ) -> Vec<u8> {
+            let mut out = b"ASF 1.0".to_vec();
+            out.resize(16, 0);
+            for v in [width, height, frames.len(), 1, palette.len(), 100, 0, 0] {
+                out.extend_from_slice(&(v as i32).to_le_bytes());
+            }
+            out.extend_from_slice(&[0u8; 16]);
+            for [r, g, b] in palette {
+                out.extend_from_slice(&[*b, *g, *r, 0]);
+            }
+
+            // RLE rows: [count, alpha=255, indices...]
+            let rle: Vec<Vec<u8>> = frames
+                .iter()
+                .map(|indices| {
+                    indices
+                        .chunks(width)
+                        .flat_map(|row| {
+                            let mut run = vec![row.len() as u8, 255];
+                            run.extend_from_slice(row);
+                            run
+                        })
+                        .collect()
+                })
+                .collect();
+            let mut data_off = out.len() + frames.len() * 8;
+            for run in &rle {
+                out.extend_from_slice(&(data_off as i32).to_le_bytes());
+                out.extend_from_slice(&(run.len() as i32).to_le_bytes());
+                data_off += run.len();
+            }
+            for run in &rle {
+                out.extend_from_slice(run);
+            }
+            out
+        }
+
+        /// `(data_offset, data_length)` per frame and the decompressed blob size
+        fn frame_layout(msf: &[u8]) -> (Vec<(u32, u32)>, usize) {
+            let frame_count = u16::from_le_bytes([msf[12], msf[13]]) as usize;
+            let palette_size = u16::from_le_bytes([msf[25], msf[26]]) as usize;
+            let table = 28 + palette_size * 4;
+            let entries: Vec<(u32, u32)> = (0..frame_count)
+                .map(|i| {
+                    let e = table + i * FRAME_ENTRY_SIZE;
+                    let read =
+                        |o: usize| u32::from_le_bytes(msf[e + o..e + o + 4].try_into().unwrap());
+                    (read(8), read(12))
+                })
+                .collect();
+            (entries, decompressed_blob(msf).len())
+        }
+
+        /// Extension chunk `(id, payload)`
+        type Chunk = ([u8; 4], Vec<u8>);
+
+        /// Extension chunks before END and the offset of the blob after it
+        fn extension_chunks(msf: &[u8]) -> (Vec<Chunk>, usize) {
+            let frame_count = u16::from_le_bytes([msf[12], msf[13]]) as usize;
+            let palette_size = u16::from_le_bytes([msf[25], msf[26]]) as usize;
+            let mut off = 28 + palette_size * 4 + frame_count * FRAME_ENTRY_SIZE;
+            let mut chunks = Vec::new();
+            loop {
+                let id: [u8; 4] = msf[off..off + 4].try_into().unwrap();
+                let len = u32::from_le_bytes(msf[off + 4..off + 8].try_into().unwrap()) as usize;
+                off += 8;
+                if &id == CHUNK_END {
+                    return (chunks, off);
+                }
+                chunks.push((id, msf[off..off + len].to_vec()));
+                off += len;
+            }
+        }
+
+        /// Decompressed frame blob
+        fn decompressed_blob(msf: &[u8]) -> Vec<u8> {
+            let (_, blob_start) = extension_chunks(msf);
+            zstd::bulk::decompress(&msf[blob_start..], 1 << 20).unwrap()
+        }
+
+        #[test]
+        fn identical_frames_share_one_payload() {
+            let opts = ConvertOptions::default();
+            let palette = [[0, 0, 0], [255, 0, 0], [0, 255, 0], [0, 0, 255]];
+            let frame: Vec<u8> = (0..64).map(|i| (i % 3 + 1) as u8).collect();
+            let other: Vec<u8> = (0..64).map(|i| (i % 2 + 1) as u8).collect();
+
+            let (same, _) = convert_asf_to_msf(
+                &build_asf(8, 8, &palette, &[frame.clone(), frame.clone()]),
+                &opts,
+                &[],
+            )
+            .unwrap();
+            let (different, _) =
+                convert_asf_to_msf(&build_asf(8, 8, &palette, &[frame, other]), &opts, &[])
+                    .unwrap();
+
+            // 8×8 opaque Indexed8Alpha8 frame = 128 bytes
+            let (entries, blob_len) = frame_layout(&same);
+            assert_eq!(entries, vec![(0, 128), (0, 128)]);
+            assert_eq!(blob_len, 128);
+
+            let (entries, blob_len) = frame_layout(&different);
+            assert_eq!(entries, vec![(0, 128), (128, 128)]);
+            assert_eq!(blob_len, 256);
+        }
+
+        #[test]
+        fn row_filter_round_trips() {
+            let mut opts = ConvertOptions::default();
+            let palette = [[0, 0, 0], [255, 0, 0], [0, 255, 0], [0, 0, 255]];
+            let frame: Vec<u8> = (0..64).map(|i| (i % 3 + 1) as u8).collect();
+            let asf = build_asf(8, 8, &palette, &[frame.clone(), frame]);
+
+            let (plain, _) = convert_asf_to_msf(&asf, &opts, &[]).unwrap();
+            opts.row_filter = true;
+            let (filtered, _) = convert_asf_to_msf(&asf, &opts, &[]).unwrap();
+            assert_eq!(plain[6..8], FLAG_ZSTD.to_le_bytes());
+            assert_eq!(filtered[6..8], (FLAG_ZSTD | FLAG_ROW_DELTA).to_le_bytes());
+
+            // The shared payload is filtered once; undoing the filter restores it
+            assert_eq!(frame_layout(&filtered).0, vec![(0, 128), (0, 128)]);
+            let mut blob = decompressed_blob(&filtered);
+            assert_ne!(blob, decompressed_blob(&plain));
+            let bpp = PixelFormat::Indexed8Alpha8.bytes_per_pixel();
+            for row in blob.chunks_exact_mut(8 * bpp) {
+                for i in bpp..row.len() {
+                    row[i] = row[i].wrapping_add(row[i - bpp]);
+                }
+            }
+            assert_eq!(blob, decompressed_blob(&plain));
+        }
+
+        #[test]
+        fn rgba8_format_stores_full_pixels() {
+            let opts = ConvertOptions {
+                format: PixelFormat::Rgba8,
+                ..ConvertOptions::default()
+            };
+            let palette = [[0, 0, 0], [255, 0, 0], [0, 255, 0]];
+            let frame: Vec<u8> = (0..16).map(|i| (i % 2 + 1) as u8).collect();
+            let (msf, _) = convert_asf_to_msf(
+                &build_asf(4, 4, &palette, std::slice::from_ref(&frame)),
+                &opts,
+                &[],
+            )
+            .unwrap();
+
+            // Pixel format 0, no palette
+            assert_eq!(msf[24], 0);
+            assert_eq!(u16::from_le_bytes([msf[25], msf[26]]), 0);
+            assert_eq!(frame_layout(&msf).0, vec![(0, 64)]);
+
+            let expected: Vec<u8> = frame
+                .iter()
+                .flat_map(|&i| {
+                    let [r, g, b] = palette[i as usize];
+                    [r, g, b, 255]
+                })
+                .collect();
+            assert_eq!(decompressed_blob(&msf), expected);
+        }
+
+        #[test]
+        fn interval_kept_in_ival_chunk() {
+            let opts = ConvertOptions::default();
+            let palette = [[0, 0, 0], [255, 0, 0]];
+            let mut asf = build_asf(2, 2, &palette, &[vec![1; 4]]);
+            // Interval field: 66 ms rounds to 15 fps, which reads back as 67 ms
+            asf[36..40].copy_from_slice(&66i32.to_le_bytes());
+            let (msf, _) = convert_asf_to_msf(&asf, &opts, &[]).unwrap();
+
+            assert_eq!(msf[15], 15);
+            let (chunks, _) = extension_chunks(&msf);
+            assert_eq!(chunks, vec![(*CHUNK_IVAL, 66u16.to_le_bytes().to_vec())]);
+            assert_eq!(decompressed_blob(&msf).len(), 8);
+
+            // No interval: fps falls back to 15 and no chunk is written
+            asf[36..40].copy_from_slice(&0i32.to_le_bytes());
+            let (msf, _) = convert_asf_to_msf(&asf, &opts, &[]).unwrap();
+            assert!(extension_chunks(&msf).0.is_empty());
+        }
+
+        #[test]
+        fn crc_chunk_covers_stored_blob() {
+            let mut opts = ConvertOptions::default();
+            let palette = [[0, 0, 0], [255, 0, 0], [0, 255, 0]];
+            let frame: Vec<u8> = (0..16).map(|i| (i % 2 + 1) as u8).collect();
+            let asf = build_asf(4, 4, &palette, &[frame]);
+            let (plain, _) = convert_asf_to_msf(&asf, &opts, &[]).unwrap();
+            opts.crc = true;
+            let (checked, _) = convert_asf_to_msf(&asf, &opts, &[]).unwrap();
+
+            assert_eq!(checked[6..8], (FLAG_ZSTD | FLAG_CRC).to_le_bytes());
+            let (chunks, blob_start) = extension_chunks(&checked);
+            let crc = crc32fast::hash(&checked[blob_start..]);
+            assert_eq!(
+                chunks.last().unwrap(),
+                &(*CHUNK_CRC3, crc.to_le_bytes().to_vec())
+            );
+            assert_eq!(decompressed_blob(&checked), decompressed_blob(&plain));
+        }
+
+        #[test]
+        fn anim_tags_written_to_tags_chunk() {
+            let tags =
+                parse_anim_tags("# 待机与行走\nidle 0 1 loop\n\nwalk 2 3  # 不循环\n").unwrap();
+            assert_eq!(
+                tags,
+                vec![
+                    AnimTag {
+                        name: "idle".to_string(),
+                        start_frame: 0,
+                        end_frame: 1,
+                        looping: true,
+                    },
+                    AnimTag {
+                        name: "walk".to_string(),
+                        start_frame: 2,
+                        end_frame: 3,
+                        looping: false,
+                    },
+                ]
+            );
+            for bad in [
+                "idle 0",
+                "idle 0 1 once",
+                "idle 2 1",
+                "idle 0 x",
+                "a 0 1\na 1 2",
+            ] {
+                assert!(parse_anim_tags(bad).is_err(), "{:?}", bad);
+            }
+
+            let opts = ConvertOptions::default();
+            let palette = [[0, 0, 0], [255, 0, 0]];
+            let mut asf = build_asf(1, 1, &palette, &vec![vec![1]; 4]);
+            asf[36..40].copy_from_slice(&0i32.to_le_bytes());
+            let (msf, _) = convert_asf_to_msf(&asf, &opts, &tags).unwrap();
+            let (chunks, _) = extension_chunks(&msf);
+            let mut payload = b"\x04idle".to_vec();
+            payload.extend_from_slice(&[0, 0, 1, 0, 1]);
+            payload.extend_from_slice(b"\x04walk");
+            payload.extend_from_slice(&[2, 0, 3, 0, 0]);
+            assert_eq!(chunks, vec![(*CHUNK_TAGS, payload)]);
+
+            // Frame 4 doesn't exist in a 4-frame, single-direction file
+            let past_end = parse_anim_tags("attack 2 4").unwrap();
+            let err = convert_asf_to_msf(&asf, &opts, &past_end).unwrap_err();
+            assert!(err.contains("\"attack\" spans frames 2..=4"), "{}", err);
+        }
+    }
+}
+
+/// Default zstd level for frame/tile blobs
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// Parse `--zstd-level <n>` (1–22, default 3); exits with an error on bad input
+fn parse_zstd_level(args: &[String]) -> i32 {
+    let Some(pos) = args.iter().position(|a| a == "--zstd-level") else {
+        return DEFAULT_ZSTD_LEVEL;
+    };
+    match args.get(pos + 1).and_then(|v| v.parse::<i32>().ok()) {
+        Some(level) if (1..=22).contains(&level) => level,
+        _ => {
+            eprintln!("Error: --zstd-level expects an integer between 1 and 22");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parse `--jobs <n>` and cap rayon's global pool at `n` threads (default: all
+/// cores); exits with an error on bad input
+fn init_thread_pool(args: &[String]) {
+    let Some(pos) = args.iter().position(|a| a == "--jobs") else {
+        return;
+    };
+    let jobs = match args.get(pos + 1).and_then(|v| v.parse::<usize>().ok()) {
+        Some(n) if n >= 1 => n,
+        _ => {
+            eprintln!("Error: --jobs expects a positive integer");
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build_global()
+    {
+        eprintln!("Error: failed to configure thread pool: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Parse `--format <indexed8alpha8|rgba8>` (default indexed8alpha8); exits on bad input
+fn parse_format(args: &[String]) -> msf::PixelFormat {
+    let Some(pos) = args.iter().position(|a| a == "--format") else {
+        return msf::PixelFormat::Indexed8Alpha8;
+    };
+    match args.get(pos + 1).map(|v| v.to_ascii_lowercase()).as_deref() {
+        Some("indexed8alpha8") => msf::PixelFormat::Indexed8Alpha8,
+        Some("rgba8") => msf::PixelFormat::Rgba8,
+        _ => {
+            eprintln!("Error: --format expects indexed8alpha8 or rgba8");
+            std::process::exit(1);
+        }
     }
 }
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() < 3 {
-        eprintln!("Usage: asf2msf <input_dir> <output_dir>");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    init_thread_pool(&args);
+    let opts = msf::ConvertOptions {
+        atlas: args.iter().any(|a| a == "--png"),
+        zstd_level: parse_zstd_level(&args),
+        requantize: args.iter().any(|a| a == "--requantize"),
+        row_filter: args.iter().any(|a| a == "--row-filter"),
+        format: parse_format(&args),
+        crc: args.iter().any(|a| a == "--crc"),
+    };
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(a) = iter.next() {
+        if a == "--zstd-level" || a == "--jobs" || a == "--format" {
+            iter.next();
+        } else if !a.starts_with("--") {
+            positional.push(a);
+        }
+    }
+    if positional.len() < 2 {
+        eprintln!(
+            "Usage: asf2msf <input_dir> <output_dir> [--png] [--zstd-level <1-22>] [--requantize] [--row-filter] \
+             [--format <indexed8alpha8|rgba8>] [--crc] [--jobs <n>]"
+        );
         std::process::exit(1);
     }
 
-    let input_dir = PathBuf::from(&args[1]);
-    let output_dir = PathBuf::from(&args[2]);
+    let input_dir = PathBuf::from(positional[0]);
+    let output_dir = PathBuf::from(positional[1]);
 
     if !input_dir.exists() {
         eprintln!("Error: input directory {:?} does not exist", input_dir);
@@ -390,7 +1037,10 @@ fn main() {
         .collect();
 
     let total = asf_files.len();
-    println!("Found {} ASF files (MSF v2: Indexed8Alpha8 + zstd)", total);
+    println!(
+        "Found {} ASF files (MSF v2: {:?} + zstd level {})",
+        total, opts.format, opts.zstd_level
+    );
 
     let converted = AtomicUsize::new(0);
     let failed = AtomicUsize::new(0);
@@ -407,25 +1057,40 @@ fn main() {
             let _ = std::fs::create_dir_all(parent);
         }
 
+        let tags = match anim_tags::read_anim_tags(asf_path) {
+            Ok((_, tags)) => tags,
+            Err(e) => {
+                eprintln!("  ANIM ERROR {}", e);
+                failed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+
         match std::fs::read(asf_path) {
             Ok(asf_data) => {
                 let asf_size = asf_data.len();
-                match msf::convert_asf_to_msf(&asf_data) {
-                    Some(msf_data) => {
+                match msf::convert_asf_to_msf(&asf_data, &opts, &tags) {
+                    Ok((msf_data, atlas)) => {
+                        if let Some(atlas) = atlas {
+                            let png_path = msf_path.with_extension("png");
+                            if let Err(e) = atlas.save(&png_path) {
+                                eprintln!("  PNG ERROR {:?}: {}", png_path, e);
+                            }
+                        }
                         let msf_size = msf_data.len();
                         if std::fs::write(&msf_path, &msf_data).is_ok() {
                             let n = converted.fetch_add(1, Ordering::Relaxed) + 1;
                             total_asf_bytes.fetch_add(asf_size, Ordering::Relaxed);
                             total_msf_bytes.fetch_add(msf_size, Ordering::Relaxed);
-                            if n % 100 == 0 || n == total {
+                            if n.is_multiple_of(100) || n == total {
                                 println!("  [{}/{}]", n, total);
                             }
                         } else {
                             failed.fetch_add(1, Ordering::Relaxed);
                         }
                     }
-                    None => {
-                        eprintln!("  CONVERT ERROR {:?}", asf_path);
+                    Err(e) => {
+                        eprintln!("  CONVERT ERROR {:?}: {}", asf_path, e);
                         failed.fetch_add(1, Ordering::Relaxed);
                     }
                 }
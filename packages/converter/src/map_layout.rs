@@ -0,0 +1,87 @@
+//! `.map` header layout detection
+//!
+//! Shared by map2mmf, convert-all and verify_map (included with `#[path]`
+//! from the binaries under `src/bin/`).
+
+/// Signature every `.map` header starts with, followed by an optional " X.Y" version
+pub const MAP_SIGNATURE: &[u8; 12] = b"MAP File Ver";
+/// The signature and version string share the first 32 header bytes
+const MAP_VERSION_END: usize = 32;
+
+/// Where a `.map` file keeps its fields
+pub struct MapLayout {
+    /// Offset of the column count (the row count follows it)
+    pub dims_offset: usize,
+    /// Offset of the 255 × 64-byte MPC list
+    pub mpc_list_offset: usize,
+    /// Offset of the first tile record
+    pub tile_offset: usize,
+    /// Bytes per tile record: 8 bytes of data followed by padding
+    pub tile_stride: usize,
+}
+
+/// Unversioned, 1.x and 2.x maps
+pub const MAP_LAYOUT_CLASSIC: MapLayout = MapLayout {
+    dims_offset: 68,
+    mpc_list_offset: 192,
+    tile_offset: 16512,
+    tile_stride: 10,
+};
+
+/// Same header with 4 more bytes of padding per tile, only picked when the
+/// file length matches it exactly
+pub const MAP_LAYOUT_WIDE_TILES: MapLayout = MapLayout {
+    tile_stride: 12,
+    ..MAP_LAYOUT_CLASSIC
+};
+
+pub fn is_map_file(data: &[u8]) -> bool {
+    data.starts_with(MAP_SIGNATURE)
+}
+
+/// Version from the `"MAP File Ver X.Y"` string, empty when the header has none
+pub fn map_version(data: &[u8]) -> String {
+    let tail = &data[MAP_SIGNATURE.len().min(data.len())..MAP_VERSION_END.min(data.len())];
+    let end = tail.iter().position(|&b| b == 0).unwrap_or(tail.len());
+    String::from_utf8_lossy(&tail[..end]).trim().to_string()
+}
+
+/// Pick the layout of `data` (which must be at least the classic header long),
+/// plus a warning when the header version is not one we know the layout of.
+///
+/// The tile stride is taken from the file length, since the version string
+/// alone doesn't prove it; anything else falls back to the classic layout.
+pub fn map_layout(data: &[u8]) -> (&'static MapLayout, Option<String>) {
+    let version = map_version(data);
+    let known = version.is_empty()
+        || version
+            .split_once('.')
+            .and_then(|(major, minor)| {
+                minor.parse::<u32>().ok()?;
+                major.parse::<u32>().ok()
+            })
+            .is_some_and(|major| major == 1 || major == 2);
+
+    let classic = &MAP_LAYOUT_CLASSIC;
+    let dim = |offset: usize| {
+        data.get(offset..offset + 4).map_or(0, |b| {
+            i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as u16 as usize
+        })
+    };
+    let tiles = dim(classic.dims_offset) * dim(classic.dims_offset + 4);
+    let wide = &MAP_LAYOUT_WIDE_TILES;
+    // With at least one tile the two strides can't give the same length
+    let layout = if tiles > 0 && data.len() == wide.tile_offset + tiles * wide.tile_stride {
+        wide
+    } else {
+        classic
+    };
+
+    let warning = (!known).then(|| {
+        format!(
+            "unrecognized MAP version {:?}, reading it with {}-byte tiles",
+            version, layout.tile_stride
+        )
+    });
+    (layout, warning)
+}
@@ -1,13 +1,35 @@
 //! ASF 精灵帧解码器 - 高性能 Rust 实现
 //!
 //! ASF 文件格式：
-//! - Header(16) + Metadata(64) + Palette(colors*4) + FrameOffsets(frames*8) + RLE压缩帧数据
+//! - Header(16) + Metadata(48) + Palette(colors*4) + FrameOffsets(frames*8) + RLE压缩帧数据
 //!
 //! 使用无状态函数实现零拷贝输入，性能比 TypeScript 快 2x+
+//!
+//! 另提供 ASF → MSF v2 转换（`convert_asf_to_msf_wasm`），可在浏览器内完成资源转换
 
+use crate::msf_codec::{MsfEncodeFrame, MsfEncodeInput, PixelFormat};
 use js_sys::Uint8Array;
 use wasm_bindgen::prelude::*;
 
+/// 调色板起始偏移：Header(16) + Metadata(48)
+const PALETTE_START: usize = 64;
+
+/// 单帧解码的宽高上限，损坏的头部不至于申请巨大缓冲区
+const MAX_FRAME_SIZE: usize = 2048;
+
+/// 读取调色板 (BGRA -> RGBA，alpha 固定 255)，数据截断时其余项为 0
+fn read_palette(data: &[u8], color_count: usize) -> [[u8; 4]; 256] {
+    let mut palette = [[0u8; 4]; 256];
+    for (i, entry) in palette.iter_mut().enumerate().take(color_count.min(256)) {
+        let off = PALETTE_START + i * 4;
+        if off + 4 > data.len() {
+            break;
+        }
+        *entry = [data[off + 2], data[off + 1], data[off], 255]; // BGR -> RGB
+    }
+    palette
+}
+
 /// ASF 文件头信息
 #[wasm_bindgen(getter_with_clone)]
 #[derive(Clone, Debug)]
@@ -53,11 +75,10 @@ pub fn parse_asf_header(data: &[u8]) -> Option<AsfHeader> {
     offset += 4;
     let bottom = get_i32_le(data, offset);
 
-    let frames_per_direction = if directions > 0 {
-        (frame_count / directions).max(1)
-    } else {
-        frame_count.max(1)
-    };
+    let frames_per_direction = frame_count
+        .checked_div(directions)
+        .unwrap_or(frame_count)
+        .max(1);
 
     Some(AsfHeader {
         width,
@@ -106,22 +127,8 @@ pub fn decode_asf_frames(data: &[u8], output: &Uint8Array) -> u32 {
     offset += 4;
     offset += 28; // 跳过 interval(4), left(4), bottom(4), reserved(16)
 
-    // 读取调色板 (BGRA -> RGBA)
-    let mut palette = [0u8; 256 * 4];
-    for i in 0..color_count.min(256) {
-        if offset + 4 > data.len() {
-            break;
-        }
-        let b = data[offset];
-        let g = data[offset + 1];
-        let r = data[offset + 2];
-        offset += 4;
-
-        palette[i * 4] = r;
-        palette[i * 4 + 1] = g;
-        palette[i * 4 + 2] = b;
-        palette[i * 4 + 3] = 255;
-    }
+    let palette = read_palette(data, color_count);
+    offset += color_count.min(256) * 4;
 
     // 读取帧偏移和长度
     let mut frame_offsets = Vec::with_capacity(frame_count as usize);
@@ -164,11 +171,201 @@ pub fn decode_asf_frames(data: &[u8], output: &Uint8Array) -> u32 {
     frame_count
 }
 
+/// 只解码单帧（预览用，无需解出整个动作循环）
+///
+/// 向 `output` 开头写入 `width * height * 4` 字节 RGBA，
+/// 返回 `(width << 16) | height`。
+/// 数据无效、宽高超过 2048、索引越界或 `output` 太小时返回 0。
+#[wasm_bindgen]
+pub fn decode_asf_single_frame(data: &[u8], frame_index: u32, output: &Uint8Array) -> u32 {
+    let (width, height, pixels) = match decode_single_frame(data, frame_index as usize) {
+        Some(v) => v,
+        None => return 0,
+    };
+    if (output.length() as usize) < pixels.len() {
+        return 0;
+    }
+    output.subarray(0, pixels.len() as u32).copy_from(&pixels);
+    ((width as u32) << 16) | height as u32
+}
+
+/// 通过帧偏移/长度表定位并解码单帧，返回 (width, height, RGBA)
+fn decode_single_frame(data: &[u8], frame_index: usize) -> Option<(usize, usize, Vec<u8>)> {
+    let header = parse_asf_header(data)?;
+    let width = header.width as usize;
+    let height = header.height as usize;
+    if frame_index >= header.frame_count as usize
+        || width == 0
+        || height == 0
+        || width > MAX_FRAME_SIZE
+        || height > MAX_FRAME_SIZE
+    {
+        return None;
+    }
+
+    let color_count = header.color_count as usize;
+    let palette = read_palette(data, color_count);
+
+    let table_entry = PALETTE_START + color_count * 4 + frame_index * 8;
+    if table_entry + 8 > data.len() {
+        return None;
+    }
+    let frame_offset = get_i32_le(data, table_entry) as usize;
+    let frame_length = get_i32_le(data, table_entry + 4) as usize;
+
+    let mut pixels = vec![0u8; width * height * 4];
+    decode_rle_frame(
+        data,
+        &palette,
+        frame_offset,
+        frame_length,
+        width,
+        height,
+        &mut pixels,
+    );
+    Some((width, height, pixels))
+}
+
+/// 将 ASF 转换为 MSF 编码输入（Indexed8Alpha8，沿用 ASF 调色板）
+///
+/// RLE 中的调色板索引与 alpha 原样写入，无需重新量化，结果与原图逐像素一致；
+/// 每帧裁剪到非透明包围盒，全透明帧宽高为 0。
+/// 数据无效、宽高超过 2048 或调色板为空（帧像素无法还原）时返回 None。
+pub fn asf_to_msf_input(data: &[u8]) -> Option<MsfEncodeInput> {
+    let header = parse_asf_header(data)?;
+    let width = header.width as usize;
+    let height = header.height as usize;
+    let color_count = header.color_count as usize;
+    if color_count == 0 || width > MAX_FRAME_SIZE || height > MAX_FRAME_SIZE {
+        return None;
+    }
+
+    // 索引为 u8，最多 256 色；调色板被截断时无法还原
+    let used_colors = color_count.min(256);
+    if PALETTE_START + used_colors * 4 > data.len() {
+        return None;
+    }
+    let palette = read_palette(data, color_count)[..used_colors].to_vec();
+
+    let table_start = PALETTE_START + color_count * 4;
+    let mut frames = Vec::with_capacity(header.frame_count as usize);
+    let mut indexed = vec![0u8; width * height * 2];
+    for i in 0..header.frame_count as usize {
+        let entry = table_start + i * 8;
+        if entry + 8 > data.len() {
+            return None;
+        }
+        let frame_offset = get_i32_le(data, entry) as usize;
+        let frame_length = get_i32_le(data, entry + 4) as usize;
+
+        indexed.fill(0);
+        decode_rle_indexed(data, frame_offset, frame_length, &mut indexed);
+        frames.push(crop_indexed_frame(&indexed, width, height));
+    }
+
+    // interval 为 0 时按 15 fps
+    let fps = 1000u32
+        .checked_div(header.interval)
+        .map_or(15, |fps| fps.min(255) as u8);
+
+    Some(MsfEncodeInput {
+        canvas_width: width as u16,
+        canvas_height: height as u16,
+        directions: header.directions as u8,
+        fps,
+        anchor_x: header.left as i16,
+        anchor_y: header.bottom as i16,
+        pixel_format: PixelFormat::Indexed8Alpha8,
+        palette,
+        frames,
+    })
+}
+
+/// ASF 文件直接转换为 MSF v2 文件（浏览器内资源转换）
+///
+/// 启用 `zstd-encode` 特性时帧数据使用 zstd 压缩，否则不压缩。
+/// 数据无效或调色板为空时返回 None（JS 侧为 undefined）
+#[wasm_bindgen]
+pub fn convert_asf_to_msf_wasm(asf: &[u8]) -> Option<Vec<u8>> {
+    let input = asf_to_msf_input(asf)?;
+
+    #[cfg(feature = "zstd-encode")]
+    {
+        Some(crate::msf_codec::encode_msf_compressed(&input))
+    }
+    #[cfg(not(feature = "zstd-encode"))]
+    {
+        Some(crate::msf_codec::encode_msf(&input))
+    }
+}
+
+/// RLE 解压缩单帧为 [调色板索引, alpha] 对（每像素 2 字节）
+fn decode_rle_indexed(data: &[u8], offset: usize, length: usize, out: &mut [u8]) {
+    let data_end = offset.saturating_add(length);
+    let mut data_offset = offset;
+    let mut pixel_idx = 0usize;
+
+    while data_offset < data_end && data_offset + 1 < data.len() && pixel_idx < out.len() {
+        let pixel_count = data[data_offset];
+        let pixel_alpha = data[data_offset + 1];
+        data_offset += 2;
+
+        for _ in 0..pixel_count {
+            if pixel_idx >= out.len() {
+                break;
+            }
+            if pixel_alpha != 0 && data_offset < data.len() {
+                out[pixel_idx] = data[data_offset];
+                out[pixel_idx + 1] = pixel_alpha;
+                data_offset += 1;
+            }
+            pixel_idx += 2;
+        }
+    }
+}
+
+/// 将画布大小的 [索引, alpha] 帧裁剪到非透明包围盒
+fn crop_indexed_frame(indexed: &[u8], width: usize, height: usize) -> MsfEncodeFrame {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, 0, 0);
+    for y in 0..height {
+        for x in 0..width {
+            if indexed[(y * width + x) * 2 + 1] != 0 {
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x + 1);
+                max_y = max_y.max(y + 1);
+            }
+        }
+    }
+
+    if min_x >= max_x {
+        return MsfEncodeFrame {
+            offset_x: 0,
+            offset_y: 0,
+            width: 0,
+            height: 0,
+            data: Vec::new(),
+        };
+    }
+
+    let mut data = Vec::with_capacity((max_x - min_x) * (max_y - min_y) * 2);
+    for y in min_y..max_y {
+        data.extend_from_slice(&indexed[(y * width + min_x) * 2..(y * width + max_x) * 2]);
+    }
+    MsfEncodeFrame {
+        offset_x: min_x as i16,
+        offset_y: min_y as i16,
+        width: (max_x - min_x) as u16,
+        height: (max_y - min_y) as u16,
+        data,
+    }
+}
+
 /// RLE 解压缩单帧
 #[inline]
 fn decode_rle_frame(
     data: &[u8],
-    palette: &[u8; 256 * 4],
+    palette: &[[u8; 4]; 256],
     offset: usize,
     length: usize,
     width: usize,
@@ -201,9 +398,8 @@ fn decode_rle_frame(
                     data_offset += 1;
 
                     if color_index < 256 {
-                        pixels[pixel_idx] = palette[color_index * 4];
-                        pixels[pixel_idx + 1] = palette[color_index * 4 + 1];
-                        pixels[pixel_idx + 2] = palette[color_index * 4 + 2];
+                        pixels[pixel_idx..pixel_idx + 3]
+                            .copy_from_slice(&palette[color_index][..3]);
                         pixels[pixel_idx + 3] = pixel_alpha;
                     } else {
                         // 无效颜色索引，使用品红色
@@ -250,4 +446,91 @@ mod tests {
         let result = parse_asf_header(data);
         assert!(result.is_none());
     }
+
+    /// 构造最小 ASF：2x1 画布，2 色调色板，每帧一段 RLE
+    fn build_asf(frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![0u8; PALETTE_START];
+        data[..7].copy_from_slice(b"ASF 1.0");
+        let fields = [2i32, 1, frames.len() as i32, 1, 2];
+        for (i, v) in fields.iter().enumerate() {
+            data[16 + i * 4..20 + i * 4].copy_from_slice(&v.to_le_bytes());
+        }
+        // 调色板 (BGRA)：0 = 红，1 = 绿
+        data.extend_from_slice(&[0, 0, 255, 0, 0, 255, 0, 0]);
+
+        let mut offset = data.len() + frames.len() * 8;
+        for rle in frames {
+            data.extend_from_slice(&(offset as i32).to_le_bytes());
+            data.extend_from_slice(&(rle.len() as i32).to_le_bytes());
+            offset += rle.len();
+        }
+        for rle in frames {
+            data.extend_from_slice(rle);
+        }
+        data
+    }
+
+    #[test]
+    fn test_decode_single_frame() {
+        let data = build_asf(&[
+            vec![2, 255, 0, 0],
+            // 1 透明 + 1 半透明绿
+            vec![1, 0, 1, 128, 1],
+        ]);
+        let (w, h, pixels) = decode_single_frame(&data, 1).unwrap();
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(pixels, vec![0, 0, 0, 0, 0, 255, 0, 128]);
+
+        let (_, _, first) = decode_single_frame(&data, 0).unwrap();
+        assert_eq!(first, vec![255, 0, 0, 255, 255, 0, 0, 255]);
+
+        assert!(decode_single_frame(&data, 2).is_none());
+    }
+
+    #[test]
+    fn test_convert_asf_to_msf() {
+        let data = build_asf(&[
+            vec![2, 255, 0, 1],
+            vec![1, 0, 1, 128, 1],
+            // 全透明帧
+            vec![2, 0],
+        ]);
+        let msf = convert_asf_to_msf_wasm(&data).unwrap();
+        crate::msf_codec::validate_msf(&msf).unwrap();
+
+        let header = crate::msf_codec::parse_msf_header(&msf).unwrap();
+        assert_eq!((header.canvas_width, header.canvas_height), (2, 1));
+        assert_eq!(header.frame_count, 3);
+        assert_eq!(header.pixel_format, PixelFormat::Indexed8Alpha8 as u8);
+
+        // 第 2 帧裁剪为右侧 1 像素
+        let input = asf_to_msf_input(&data).unwrap();
+        let frame = &input.frames[1];
+        assert_eq!((frame.offset_x, frame.width, frame.height), (1, 1, 1));
+        assert_eq!(frame.data, vec![1, 128]);
+        assert_eq!(input.frames[2].width, 0);
+
+        // 空调色板无法还原像素
+        let mut no_palette = data.clone();
+        no_palette[32..36].copy_from_slice(&0i32.to_le_bytes());
+        assert!(convert_asf_to_msf_wasm(&no_palette).is_none());
+        assert!(convert_asf_to_msf_wasm(b"ASF 1.0").is_none());
+    }
+
+    #[test]
+    fn test_oversized_header() {
+        let data = build_asf(&[vec![2, 255, 0, 1]]);
+        for (width, height) in [(100_000i32, 1i32), (1, -1), (2049, 2048)] {
+            let mut bad = data.clone();
+            bad[16..20].copy_from_slice(&width.to_le_bytes());
+            bad[20..24].copy_from_slice(&height.to_le_bytes());
+            assert!(asf_to_msf_input(&bad).is_none(), "{}x{}", width, height);
+            assert!(
+                decode_single_frame(&bad, 0).is_none(),
+                "{}x{}",
+                width,
+                height
+            );
+        }
+    }
 }
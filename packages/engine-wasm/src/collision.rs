@@ -2,8 +2,24 @@
 //!
 //! 使用空间哈希网格进行快速碰撞查询
 //! 适用于大量移动实体的碰撞检测场景
+//!
+//! 所有返回 id 的查询结果都按 id 升序排列，碰撞对按 (id1, id2) 排序，
+//! 保证相同的插入序列在任意运行中得到相同输出（锁步联机所需）。
+//! 代价是每次查询额外一次 O(k log k) 排序，k 为结果数量。
+//! 例外：`query_segment` 按沿线段的距离排序（相同时按 id）。
+//!
+//! 设置世界尺寸并开启环绕（`set_world_bounds` + `set_wrap`）后，网格单元在边缘
+//! 环绕，半径查询与碰撞检测按最短环绕距离计算（每个轴取直接差值与绕过边缘差值
+//! 中较小者）。`query_segment` 不做环绕。
+//!
+//! 实体另有碰撞层 `layer`（如 0 = 地面、1 = 空中，默认 0），与表示阵营的 `group`
+//! 无关：碰撞检测只在同一层的实体之间进行；范围查询默认不区分层，
+//! 需要时用 `query_radius_in_layer`。
 
 use hashbrown::{HashMap, HashSet};
+use js_sys::Uint32Array;
+use std::cell::RefCell;
+use std::ops::RangeInclusive;
 use wasm_bindgen::prelude::*;
 
 /// 实体数据
@@ -14,6 +30,35 @@ struct Entity {
     y: f32,
     radius: f32,
     group: u32, // 用于区分敌我阵营
+    /// 速度（像素/秒），用于预测碰撞
+    vx: f32,
+    vy: f32,
+    /// 调用方自定义标记（隐身、无敌等位标志），随查询结果返回
+    user_data: u32,
+    /// 碰撞层：不同层的实体互不碰撞
+    layer: u8,
+}
+
+/// 序列化格式魔数与版本
+const SNAPSHOT_MAGIC: &[u8; 4] = b"SPH1";
+const SNAPSHOT_VERSION: u16 = 2;
+/// 头部: magic(4) + version u16 + reserved u16 + cell_size f32 + entity_count u32
+const SNAPSHOT_HEADER_SIZE: usize = 16;
+/// 单个实体: id u32 + x f32 + y f32 + radius f32 + group u32
+///         + vx f32 + vy f32 + user_data u32 + layer u8 + reserved(3)
+const SNAPSHOT_ENTITY_SIZE: usize = 36;
+/// 版本 1 的实体只有前 5 个字段，仍可读取（其余字段为 0）
+const SNAPSHOT_V1_ENTITY_SIZE: usize = 20;
+
+/// detect_all_collisions_into 每次调用先清空再复用的临时缓冲区
+#[derive(Default)]
+struct CollisionScratch {
+    /// 按 id 升序的实体副本
+    entities: Vec<Entity>,
+    /// 当前实体覆盖的网格单元
+    cells: Vec<(i32, i32)>,
+    /// 已检测过的实体对
+    checked: HashSet<(u32, u32)>,
 }
 
 /// 空间哈希网格
@@ -25,6 +70,14 @@ pub struct SpatialHash {
     grid: HashMap<(i32, i32), Vec<u32>>,
     /// 实体数据
     entities: HashMap<u32, Entity>,
+    /// 世界尺寸 (width, height)，仅在 wrap 开启时生效
+    world_bounds: Option<(f32, f32)>,
+    /// 是否在世界边缘环绕（环形世界）
+    wrap: bool,
+    /// detect_all_collisions_to_array 复用的碰撞对缓冲区
+    pair_buffer: RefCell<Vec<u32>>,
+    /// detect_all_collisions_into 复用的临时缓冲区
+    scratch: RefCell<CollisionScratch>,
 }
 
 #[wasm_bindgen]
@@ -36,9 +89,30 @@ impl SpatialHash {
             cell_size: cell_size.max(1.0),
             grid: HashMap::new(),
             entities: HashMap::new(),
+            world_bounds: None,
+            wrap: false,
+            pair_buffer: RefCell::new(Vec::new()),
+            scratch: RefCell::default(),
         }
     }
 
+    /// 设置世界尺寸（环绕用）；宽高不是正数时清除，恢复无边界
+    /// 只有同时 `set_wrap(true)` 才会改变查询结果
+    #[wasm_bindgen]
+    pub fn set_world_bounds(&mut self, width: f32, height: f32) {
+        self.world_bounds =
+            (width > 0.0 && height > 0.0 && width.is_finite() && height.is_finite())
+                .then_some((width, height));
+        self.rebuild_grid();
+    }
+
+    /// 开启 / 关闭边缘环绕（默认关闭）；未设置世界尺寸时无效
+    #[wasm_bindgen]
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+        self.rebuild_grid();
+    }
+
     /// 清空所有数据
     #[wasm_bindgen]
     pub fn clear(&mut self) {
@@ -46,11 +120,67 @@ impl SpatialHash {
         self.entities.clear();
     }
 
-    /// 添加或更新实体
+    /// 添加或更新实体（速度置为 0）
     #[wasm_bindgen]
     pub fn upsert(&mut self, id: u32, x: f32, y: f32, radius: f32, group: u32) {
+        self.upsert_with_velocity(id, x, y, 0.0, 0.0, radius, group);
+    }
+
+    /// 添加或更新实体，并附带调用方标记（见 `query_radius_with_data`）
+    #[wasm_bindgen]
+    pub fn upsert_with_data(
+        &mut self,
+        id: u32,
+        x: f32,
+        y: f32,
+        radius: f32,
+        group: u32,
+        user_data: u32,
+    ) {
+        self.upsert(id, x, y, radius, group);
+        if let Some(entity) = self.entities.get_mut(&id) {
+            entity.user_data = user_data;
+        }
+    }
+
+    /// 添加或更新实体，并指定碰撞层（如地面 0、空中 1）
+    /// 之后的 `upsert` / `batch_upsert` 等更新保留该层
+    #[wasm_bindgen]
+    pub fn upsert_with_layer(
+        &mut self,
+        id: u32,
+        x: f32,
+        y: f32,
+        radius: f32,
+        group: u32,
+        layer: u8,
+    ) {
+        self.upsert(id, x, y, radius, group);
+        if let Some(entity) = self.entities.get_mut(&id) {
+            entity.layer = layer;
+        }
+    }
+
+    /// 添加或更新实体，并记录速度（供 `detect_predicted_collisions` 使用）
+    /// 已存在实体的 user_data 与 layer 保持不变，新实体均为 0
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn upsert_with_velocity(
+        &mut self,
+        id: u32,
+        x: f32,
+        y: f32,
+        vx: f32,
+        vy: f32,
+        radius: f32,
+        group: u32,
+    ) {
         // 如果实体已存在，先移除旧位置
+        let mut user_data = 0;
+        let mut layer = 0;
         if let Some(old_entity) = self.entities.get(&id) {
+            user_data = old_entity.user_data;
+            layer = old_entity.layer;
             let old_cell = self.get_cell(old_entity.x, old_entity.y);
             if let Some(cell_entities) = self.grid.get_mut(&old_cell) {
                 cell_entities.retain(|&eid| eid != id);
@@ -64,6 +194,10 @@ impl SpatialHash {
             y,
             radius,
             group,
+            vx,
+            vy,
+            user_data,
+            layer,
         };
         self.entities.insert(id, entity);
 
@@ -83,6 +217,25 @@ impl SpatialHash {
         }
     }
 
+    /// 移除指定阵营的所有实体（如一波敌人被清空）
+    /// 返回被移除的实体数量
+    #[wasm_bindgen]
+    pub fn remove_group(&mut self, group: u32) -> u32 {
+        let ids: Vec<u32> = self
+            .entities
+            .values()
+            .filter(|e| e.group == group)
+            .map(|e| e.id)
+            .collect();
+
+        // remove 会按实体当前位置重新计算网格单元，保证 grid 中不残留旧 id
+        for &id in &ids {
+            self.remove(id);
+        }
+
+        ids.len() as u32
+    }
+
     /// 批量更新实体位置
     /// positions: [id1, x1, y1, id2, x2, y2, ...]
     #[wasm_bindgen]
@@ -118,31 +271,110 @@ impl SpatialHash {
         }
     }
 
+    /// 批量添加或更新实体（每帧一次同步全部实体）
+    /// data: [id1, x1, y1, radius1, group1, id2, ...]，id 与 group 以 f32 传入（精确到 2^24）
+    /// 每项与 `upsert` 相同：不存在则创建，已存在则移到新网格单元并更新半径/阵营，
+    /// 速度置为 0，user_data 保持不变；末尾不足 5 个数的部分忽略
+    #[wasm_bindgen]
+    pub fn batch_upsert(&mut self, data: &[f32]) {
+        for chunk in data.chunks_exact(5) {
+            self.upsert(
+                chunk[0] as u32,
+                chunk[1],
+                chunk[2],
+                chunk[3],
+                chunk[4] as u32,
+            );
+        }
+    }
+
     /// 查询圆形范围内的所有实体
     /// 返回实体 ID 数组
     #[wasm_bindgen]
     pub fn query_radius(&self, x: f32, y: f32, radius: f32) -> Vec<u32> {
-        let mut result = Vec::new();
+        let mut result: Vec<u32> = self
+            .entities_in_radius(x, y, radius)
+            .map(|e| e.id)
+            .collect();
+        result.sort_unstable();
+        result
+    }
+
+    /// 查询圆形范围内的所有实体，按到 (x, y) 的距离由近到远排序（距离相同按 id 升序）
+    /// 用于目标优先级选择，JS 侧无需再排序
+    #[wasm_bindgen]
+    pub fn query_radius_sorted(&self, x: f32, y: f32, radius: f32) -> Vec<u32> {
+        let mut hits: Vec<(f32, u32)> = self
+            .entities_in_radius_with_dist_sq(x, y, radius)
+            .map(|(entity, dist_sq)| (dist_sq, entity.id))
+            .collect();
+        hits.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        hits.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// 查询圆形范围内指定碰撞层的实体，返回实体 ID 数组
+    #[wasm_bindgen]
+    pub fn query_radius_in_layer(&self, x: f32, y: f32, radius: f32, layer: u8) -> Vec<u32> {
+        let mut result: Vec<u32> = self
+            .entities_in_radius(x, y, radius)
+            .filter(|e| e.layer == layer)
+            .map(|e| e.id)
+            .collect();
+        result.sort_unstable();
+        result
+    }
+
+    /// 圆形范围内的实体数量（与 query_radius 判定相同，不分配结果数组）
+    #[wasm_bindgen]
+    pub fn count_in_radius(&self, x: f32, y: f32, radius: f32) -> u32 {
+        self.entities_in_radius(x, y, radius).count() as u32
+    }
+
+    /// 圆形范围内是否存在非 exclude_group 阵营的实体，命中第一个即返回
+    /// （不需要排除时传入未使用的阵营，如 u32::MAX）
+    #[wasm_bindgen]
+    pub fn any_in_radius(&self, x: f32, y: f32, radius: f32, exclude_group: u32) -> bool {
+        self.entities_in_radius(x, y, radius)
+            .any(|e| e.group != exclude_group)
+    }
+
+    /// 查询圆形范围内的所有实体，并附带中心距离（用于范围伤害衰减）
+    /// 返回 [id, distance, id, distance, ...]，id 以 f32 表示（精确到 2^24）
+    #[wasm_bindgen]
+    pub fn query_radius_with_distance(&self, x: f32, y: f32, radius: f32) -> Vec<f32> {
+        let mut hits: Vec<(u32, f32)> = Vec::new();
         let cells = self.get_cells_in_radius(x, y, radius);
 
         for cell in cells {
             if let Some(entity_ids) = self.grid.get(&cell) {
                 for &id in entity_ids {
                     if let Some(entity) = self.entities.get(&id) {
-                        let dx = entity.x - x;
-                        let dy = entity.y - y;
+                        let (dx, dy) = self.delta(x, y, entity.x, entity.y);
                         let dist_sq = dx * dx + dy * dy;
                         let combined_radius = radius + entity.radius;
 
                         if dist_sq <= combined_radius * combined_radius {
-                            result.push(id);
+                            hits.push((id, dist_sq.sqrt()));
                         }
                     }
                 }
             }
         }
 
-        result
+        hits.sort_unstable_by_key(|h| h.0);
+        hits.into_iter()
+            .flat_map(|(id, dist)| [id as f32, dist])
+            .collect()
+    }
+
+    /// 查询圆形范围内的所有实体，并附带各自的 user_data
+    /// 返回 [id, user_data, id, user_data, ...]，按 id 升序
+    #[wasm_bindgen]
+    pub fn query_radius_with_data(&self, x: f32, y: f32, radius: f32) -> Vec<u32> {
+        self.query_radius(x, y, radius)
+            .into_iter()
+            .flat_map(|id| [id, self.entities[&id].user_data])
+            .collect()
     }
 
     /// 查询指定位置的实体（精确匹配网格单元）
@@ -192,32 +424,295 @@ impl SpatialHash {
         }
     }
 
-    /// 检测所有碰撞对
+    /// 查询线段（按 thickness 加粗为胶囊体）经过的所有实体，用于穿透型投射物
+    /// 返回与胶囊体相交的实体 id，按最近点到起点的距离排序，距离相同时按 id
+    /// exclude_group 阵营的实体被忽略（不需要排除时传入未使用的阵营，如 u32::MAX）
+    #[wasm_bindgen]
+    pub fn query_segment(
+        &self,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        thickness: f32,
+        exclude_group: u32,
+    ) -> Vec<u32> {
+        let thickness = thickness.max(0.0);
+        let max_radius = self
+            .entities
+            .values()
+            .map(|e| e.radius)
+            .fold(0.0f32, f32::max);
+        // 网格按实体中心索引：中心离线段超过 thickness + 最大半径的格子不可能命中
+        let reach = thickness + max_radius;
+        let size = self.wrap_size();
+        let xs = self.axis_cells(x1.min(x2) - reach, x1.max(x2) + reach, size.map(|s| s.0));
+        let ys = self.axis_cells(y1.min(y2) - reach, y1.max(y2) + reach, size.map(|s| s.1));
+        let length = ((x2 - x1) * (x2 - x1) + (y2 - y1) * (y2 - y1)).sqrt();
+
+        let mut hits: Vec<(f32, u32)> = Vec::new();
+        for cx in xs.into_iter().flatten() {
+            for cy in ys.clone().into_iter().flatten() {
+                let Some(entity_ids) = self.grid.get(&(cx, cy)) else {
+                    continue;
+                };
+                // 环绕时网格坐标已折回，与线段坐标不可直接比较，跳过这一步粗筛
+                let rx = cx as f32 * self.cell_size - reach;
+                let ry = cy as f32 * self.cell_size - reach;
+                let rw = self.cell_size + reach * 2.0;
+                if size.is_none() && !segment_intersects_rect(x1, y1, x2, y2, rx, ry, rw, rw) {
+                    continue;
+                }
+
+                for &id in entity_ids {
+                    let Some(entity) = self.entities.get(&id) else {
+                        continue;
+                    };
+                    if entity.group == exclude_group {
+                        continue;
+                    }
+                    let t = segment_closest_t(entity.x, entity.y, x1, y1, x2, y2);
+                    let dx = entity.x - (x1 + (x2 - x1) * t);
+                    let dy = entity.y - (y1 + (y2 - y1) * t);
+                    let combined_radius = thickness + entity.radius;
+                    if dx * dx + dy * dy <= combined_radius * combined_radius {
+                        hits.push((t * length, id));
+                    }
+                }
+            }
+        }
+
+        hits.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        hits.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// 查找指定阵营中距离 (x, y) 最近的实体（按中心距离，不超过 max_radius）
+    /// 返回实体 id，找不到时返回 -1（id 超过 i32::MAX 时无法区分，调用方需保证 id 范围）
+    ///
+    /// 从中心所在网格向外逐圈扩展，找到候选后一旦下一圈不可能更近即停止，
+    /// 开销只与附近的实体数量有关。距离相同时取 id 较小者
+    #[wasm_bindgen]
+    pub fn nearest_in_group(&self, x: f32, y: f32, group: u32, max_radius: f32) -> i32 {
+        if max_radius < 0.0 {
+            return -1;
+        }
+        let center = self.get_cell(x, y);
+        let max_ring = (max_radius / self.cell_size).ceil() as i32 + 1;
+        let max_dist_sq = max_radius * max_radius;
+        let mut best: Option<(f32, u32)> = None;
+
+        for ring in 0..=max_ring {
+            // 第 ring 圈之外的格子与 (x, y) 的距离至少为 ring * cell_size
+            if let Some((best_dist_sq, _)) = best {
+                let min_next = ring.saturating_sub(1) as f32 * self.cell_size;
+                if best_dist_sq <= min_next * min_next {
+                    break;
+                }
+            }
+
+            for cell in Self::ring_cells(center, ring) {
+                let Some(entity_ids) = self.grid.get(&self.wrap_cell(cell)) else {
+                    continue;
+                };
+                for &id in entity_ids {
+                    let Some(entity) = self.entities.get(&id) else {
+                        continue;
+                    };
+                    if entity.group != group {
+                        continue;
+                    }
+                    let (dx, dy) = self.delta(x, y, entity.x, entity.y);
+                    let dist_sq = dx * dx + dy * dy;
+                    if dist_sq > max_dist_sq {
+                        continue;
+                    }
+                    let closer = match best {
+                        Some((best_dist_sq, best_id)) => {
+                            dist_sq < best_dist_sq || (dist_sq == best_dist_sq && id < best_id)
+                        }
+                        None => true,
+                    };
+                    if closer {
+                        best = Some((dist_sq, id));
+                    }
+                }
+            }
+        }
+
+        best.map_or(-1, |(_, id)| id as i32)
+    }
+
+    /// 检测所有碰撞对（只配对同一碰撞层的实体）
     /// 返回碰撞对数组 [id1, id2, id3, id4, ...]
     #[wasm_bindgen]
     pub fn detect_all_collisions(&self) -> Vec<u32> {
+        let mut collisions = Vec::new();
+        self.detect_all_collisions_into(&mut collisions);
+        collisions
+    }
+
+    /// 与 `detect_all_collisions` 相同，但写入 JS 传入的 Uint32Array，返回碰撞对数量
+    ///
+    /// 内部缓冲区跨帧复用，实体数量稳定时不再分配。output 不足 2 × 返回值时
+    /// 只写入放得下的部分，调用方可据返回值扩容后重试
+    #[wasm_bindgen]
+    pub fn detect_all_collisions_to_array(&self, output: &Uint32Array) -> u32 {
+        let mut pairs = self.pair_buffer.borrow_mut();
+        let count = self.detect_all_collisions_into(&mut pairs);
+        let len = pairs.len().min(output.length() as usize);
+        output.subarray(0, len as u32).copy_from(&pairs[..len]);
+        count
+    }
+
+    /// 只检测指定碰撞层内的碰撞对，格式同 `detect_all_collisions`
+    #[wasm_bindgen]
+    pub fn detect_collisions_in_layer(&self, layer: u8) -> Vec<u32> {
+        self.detect_all_collisions()
+            .chunks_exact(2)
+            .filter(|pair| self.entities[&pair[0]].layer == layer)
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// 计算重叠实体的分离向量（简单软体分离，免去 JS 侧计算 MTV）
+    /// 返回 [id, push_x, push_y, ...]，按 id 升序，只包含与其他实体重叠的实体
+    /// （仅相切不算重叠），id 转为 f32（精确到 2^24）
+    ///
+    /// 重叠判定与 `detect_all_collisions` 相同；push 为该实体相对每个重叠邻居的
+    /// 最小平移向量（沿圆心连线向外，长度为穿透深度）之和。邻居不动时按 push
+    /// 移动即可分开，双方都移动时各取一半。圆心重合时沿 x 轴分开，较小 id 向 -x
+    #[wasm_bindgen]
+    pub fn resolve_overlaps(&self) -> Vec<f32> {
+        let mut pushes: HashMap<u32, (f32, f32)> = HashMap::new();
+
+        // 碰撞对按 (较小 id, 较大 id) 给出
+        for pair in self.detect_all_collisions().chunks_exact(2) {
+            let (Some(a), Some(b)) = (self.entities.get(&pair[0]), self.entities.get(&pair[1]))
+            else {
+                continue;
+            };
+            let (dx, dy) = self.delta(a.x, a.y, b.x, b.y);
+            let dist = (dx * dx + dy * dy).sqrt();
+            let depth = a.radius + b.radius - dist;
+            if depth <= 0.0 {
+                continue;
+            }
+
+            // a → b 单位向量
+            let (nx, ny) = if dist > 0.0 {
+                (dx / dist, dy / dist)
+            } else {
+                (1.0, 0.0)
+            };
+            let push_a = pushes.entry(a.id).or_insert((0.0, 0.0));
+            push_a.0 -= nx * depth;
+            push_a.1 -= ny * depth;
+            let push_b = pushes.entry(b.id).or_insert((0.0, 0.0));
+            push_b.0 += nx * depth;
+            push_b.1 += ny * depth;
+        }
+
+        let mut ids: Vec<u32> = pushes.keys().copied().collect();
+        ids.sort_unstable();
+        ids.into_iter()
+            .flat_map(|id| {
+                let (px, py) = pushes[&id];
+                [id as f32, px, py]
+            })
+            .collect()
+    }
+
+    /// 预测 dt 秒内会发生的碰撞（按当前速度做扫掠检测）
+    /// 返回碰撞对数组 [id1, id2, ...]，包含当前已重叠的碰撞对
+    /// 速度为 0 的实体按静止处理；dt <= 0 时等价于 `detect_all_collisions`（同样只配对同层实体）
+    #[wasm_bindgen]
+    pub fn detect_predicted_collisions(&self, dt: f32) -> Vec<u32> {
+        let dt = dt.max(0.0);
+        let mut collisions = Vec::new();
+
+        // 任一实体在 dt 内可覆盖的最大范围（半径 + 位移），决定邻域搜索半径
+        let max_reach = self
+            .entities
+            .values()
+            .map(|e| e.radius + (e.vx * e.vx + e.vy * e.vy).sqrt() * dt)
+            .fold(0.0f32, f32::max);
+
+        for entity in self.sorted_entities() {
+            let first = collisions.len();
+            let reach = entity.radius + (entity.vx * entity.vx + entity.vy * entity.vy).sqrt() * dt;
+            let cells = self.get_cells_in_radius(entity.x, entity.y, reach + max_reach);
+
+            for cell in cells {
+                if let Some(entity_ids) = self.grid.get(&cell) {
+                    for &other_id in entity_ids {
+                        if entity.id >= other_id {
+                            continue; // 每对只检测一次
+                        }
+                        let Some(other) = self.entities.get(&other_id) else {
+                            continue;
+                        };
+                        if other.layer != entity.layer {
+                            continue;
+                        }
+
+                        // 相对运动：other 相对 entity 的位置从 d 移动到 d + w·dt，
+                        // 检测该线段到原点的最近距离
+                        let (dx, dy) = self.delta(entity.x, entity.y, other.x, other.y);
+                        let wx = (other.vx - entity.vx) * dt;
+                        let wy = (other.vy - entity.vy) * dt;
+                        let combined_radius = entity.radius + other.radius;
+
+                        if segment_distance_sq(0.0, 0.0, dx, dy, dx + wx, dy + wy)
+                            <= combined_radius * combined_radius
+                        {
+                            collisions.push(entity.id);
+                            collisions.push(other_id);
+                        }
+                    }
+                }
+            }
+            sort_pairs_tail(&mut collisions, first);
+        }
+
+        collisions
+    }
+
+    /// 只检测两个阵营之间的碰撞对（如敌我碰撞）
+    /// 返回碰撞对数组 [a1, b1, a2, b2, ...]，每对中第一个属于 group_a
+    /// group_a == group_b 时等价于只检测该阵营内部的碰撞
+    #[wasm_bindgen]
+    pub fn detect_collisions_between_groups(&self, group_a: u32, group_b: u32) -> Vec<u32> {
         let mut collisions = Vec::new();
         let mut checked = HashSet::new();
 
-        for entity in self.entities.values() {
+        for entity in self
+            .sorted_entities()
+            .into_iter()
+            .filter(|e| e.group == group_a)
+        {
+            let first = collisions.len();
             let cells = self.get_cells_in_radius(entity.x, entity.y, entity.radius);
 
             for cell in cells {
                 if let Some(entity_ids) = self.grid.get(&cell) {
                     for &other_id in entity_ids {
-                        if entity.id >= other_id {
-                            continue; // 避免重复检测
+                        if other_id == entity.id {
+                            continue;
                         }
 
                         let pair = (entity.id.min(other_id), entity.id.max(other_id));
                         if checked.contains(&pair) {
                             continue;
                         }
-                        checked.insert(pair);
 
                         if let Some(other) = self.entities.get(&other_id) {
-                            let dx = other.x - entity.x;
-                            let dy = other.y - entity.y;
+                            if other.group != group_b || other.layer != entity.layer {
+                                continue;
+                            }
+                            checked.insert(pair);
+
+                            let (dx, dy) = self.delta(entity.x, entity.y, other.x, other.y);
                             let dist_sq = dx * dx + dy * dy;
                             let combined_radius = entity.radius + other.radius;
 
@@ -229,12 +724,13 @@ impl SpatialHash {
                     }
                 }
             }
+            sort_pairs_tail(&mut collisions, first);
         }
 
         collisions
     }
 
-    /// 检测指定实体与其他实体的碰撞
+    /// 检测指定实体与同一碰撞层其他实体的碰撞
     #[wasm_bindgen]
     pub fn detect_collisions_for(&self, id: u32) -> Vec<u32> {
         let Some(entity) = self.entities.get(&id) else {
@@ -252,8 +748,10 @@ impl SpatialHash {
                     }
 
                     if let Some(other) = self.entities.get(&other_id) {
-                        let dx = other.x - entity.x;
-                        let dy = other.y - entity.y;
+                        if other.layer != entity.layer {
+                            continue;
+                        }
+                        let (dx, dy) = self.delta(entity.x, entity.y, other.x, other.y);
                         let dist_sq = dx * dx + dy * dy;
                         let combined_radius = entity.radius + other.radius;
 
@@ -265,6 +763,7 @@ impl SpatialHash {
             }
         }
 
+        collisions.sort_unstable();
         collisions
     }
 
@@ -274,32 +773,339 @@ impl SpatialHash {
         self.entities.len() as u32
     }
 
+    /// 读取实体当前状态，返回 [x, y, radius, group]（group 转为 f32）
+    /// 实体不存在时返回 None（JS 侧为 undefined）
+    #[wasm_bindgen]
+    pub fn get_entity(&self, id: u32) -> Option<Vec<f32>> {
+        self.entities
+            .get(&id)
+            .map(|e| vec![e.x, e.y, e.radius, e.group as f32])
+    }
+
+    /// 读取全部实体（调试叠加层绘制碰撞体用），按 id 升序
+    /// 返回 [id, x, y, radius, group, ...]，id 与 group 转为 f32（精确到 2^24）
+    #[wasm_bindgen]
+    pub fn all_entities(&self) -> Vec<f32> {
+        self.sorted_entities()
+            .into_iter()
+            .flat_map(|e| [e.id as f32, e.x, e.y, e.radius, e.group as f32])
+            .collect()
+    }
+
+    /// 序列化为字节数组（小端），用于存档 / 确定性回放
+    /// 实体按 id 排序，保证同一状态总是得到相同的字节
+    /// 世界尺寸与环绕设置不保存
+    #[wasm_bindgen]
+    pub fn serialize(&self) -> Vec<u8> {
+        let entities = self.sorted_entities();
+
+        let mut out =
+            Vec::with_capacity(SNAPSHOT_HEADER_SIZE + entities.len() * SNAPSHOT_ENTITY_SIZE);
+        out.extend_from_slice(SNAPSHOT_MAGIC);
+        out.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(&self.cell_size.to_le_bytes());
+        out.extend_from_slice(&(entities.len() as u32).to_le_bytes());
+        for e in entities {
+            out.extend_from_slice(&e.id.to_le_bytes());
+            out.extend_from_slice(&e.x.to_le_bytes());
+            out.extend_from_slice(&e.y.to_le_bytes());
+            out.extend_from_slice(&e.radius.to_le_bytes());
+            out.extend_from_slice(&e.group.to_le_bytes());
+            out.extend_from_slice(&e.vx.to_le_bytes());
+            out.extend_from_slice(&e.vy.to_le_bytes());
+            out.extend_from_slice(&e.user_data.to_le_bytes());
+            out.extend_from_slice(&[e.layer, 0, 0, 0]);
+        }
+        out
+    }
+
+    /// 从 serialize 的输出恢复空间哈希，网格在加载时重建
+    /// 也接受版本 1 的快照（无速度、user_data 与碰撞层，均按 0 恢复）
+    /// 魔数 / 版本不匹配或数据被截断时返回 None
+    #[wasm_bindgen]
+    pub fn deserialize(bytes: &[u8]) -> Option<SpatialHash> {
+        if bytes.len() < SNAPSHOT_HEADER_SIZE || &bytes[0..4] != SNAPSHOT_MAGIC {
+            return None;
+        }
+        let entity_size = match u16::from_le_bytes([bytes[4], bytes[5]]) {
+            1 => SNAPSHOT_V1_ENTITY_SIZE,
+            SNAPSHOT_VERSION => SNAPSHOT_ENTITY_SIZE,
+            _ => return None,
+        };
+
+        let read_u32 = |off: usize| {
+            u32::from_le_bytes([bytes[off], bytes[off + 1], bytes[off + 2], bytes[off + 3]])
+        };
+        let read_f32 = |off: usize| f32::from_bits(read_u32(off));
+
+        let cell_size = read_f32(8);
+        let count = read_u32(12) as usize;
+        // wasm32 上 usize 只有 32 位，构造的 count 会让乘加溢出，需按截断处理
+        let needed = count
+            .checked_mul(entity_size)
+            .and_then(|n| n.checked_add(SNAPSHOT_HEADER_SIZE))?;
+        if bytes.len() < needed {
+            return None;
+        }
+
+        let mut hash = SpatialHash::new(cell_size);
+        for i in 0..count {
+            let off = SNAPSHOT_HEADER_SIZE + i * entity_size;
+            let id = read_u32(off);
+            let (x, y, radius, group) = (
+                read_f32(off + 4),
+                read_f32(off + 8),
+                read_f32(off + 12),
+                read_u32(off + 16),
+            );
+            if entity_size == SNAPSHOT_V1_ENTITY_SIZE {
+                hash.upsert(id, x, y, radius, group);
+                continue;
+            }
+            let (vx, vy) = (read_f32(off + 20), read_f32(off + 24));
+            hash.upsert_with_velocity(id, x, y, vx, vy, radius, group);
+            if let Some(entity) = hash.entities.get_mut(&id) {
+                entity.user_data = read_u32(off + 28);
+                entity.layer = bytes[off + 32];
+            }
+        }
+        Some(hash)
+    }
+
+    /// 按 id 升序排列的实体列表（遍历顺序与 HashMap 无关）
+    fn sorted_entities(&self) -> Vec<&Entity> {
+        let mut entities: Vec<&Entity> = self.entities.values().collect();
+        entities.sort_unstable_by_key(|e| e.id);
+        entities
+    }
+
+    /// 生效的环绕尺寸：设置了世界尺寸且开启 wrap 时为 Some
+    #[inline]
+    fn wrap_size(&self) -> Option<(f32, f32)> {
+        self.world_bounds.filter(|_| self.wrap)
+    }
+
+    /// 环绕配置变化后按新的 get_cell 重新分配网格
+    fn rebuild_grid(&mut self) {
+        self.grid.clear();
+        for entity in self.entities.values() {
+            let cell = self.get_cell(entity.x, entity.y);
+            self.grid.entry(cell).or_default().push(entity.id);
+        }
+        for ids in self.grid.values_mut() {
+            ids.sort_unstable();
+        }
+    }
+
+    /// from 指向 to 的位移；环绕时每个轴取最短的那个方向
+    #[inline]
+    fn delta(&self, from_x: f32, from_y: f32, to_x: f32, to_y: f32) -> (f32, f32) {
+        let dx = to_x - from_x;
+        let dy = to_y - from_y;
+        match self.wrap_size() {
+            Some((w, h)) => (dx - w * (dx / w).round(), dy - h * (dy / h).round()),
+            None => (dx, dy),
+        }
+    }
+
+    /// 单个轴上的网格坐标；环绕时先折回 [0, size)
+    #[inline]
+    fn axis_cell(&self, v: f32, size: Option<f32>) -> i32 {
+        match size {
+            Some(size) => {
+                let cells = ((size / self.cell_size).ceil() as i32).max(1);
+                ((v.rem_euclid(size) / self.cell_size).floor() as i32).min(cells - 1)
+            }
+            None => (v / self.cell_size).floor() as i32,
+        }
+    }
+
+    /// [lo, hi] 覆盖的网格坐标；环绕时跨过边缘的区间拆成不重叠的两段
+    fn axis_cells(&self, lo: f32, hi: f32, size: Option<f32>) -> [RangeInclusive<i32>; 2] {
+        let Some(size) = size else {
+            return [
+                self.axis_cell(lo, None)..=self.axis_cell(hi, None),
+                RangeInclusive::new(1, 0),
+            ];
+        };
+        let cells = ((size / self.cell_size).ceil() as i32).max(1);
+        if hi - lo >= size {
+            return [0..=cells - 1, RangeInclusive::new(1, 0)];
+        }
+        let a = lo.rem_euclid(size);
+        let b = a + (hi - lo);
+        let first = self.axis_cell(a, Some(size));
+        if b < size {
+            [
+                first..=self.axis_cell(b, Some(size)),
+                RangeInclusive::new(1, 0),
+            ]
+        } else {
+            let wrapped = self.axis_cell(b - size, Some(size)).min(first - 1);
+            [first..=cells - 1, 0..=wrapped]
+        }
+    }
+
     /// 获取位置所在的网格单元
     #[inline]
     fn get_cell(&self, x: f32, y: f32) -> (i32, i32) {
+        let size = self.wrap_size();
         (
-            (x / self.cell_size).floor() as i32,
-            (y / self.cell_size).floor() as i32,
+            self.axis_cell(x, size.map(|s| s.0)),
+            self.axis_cell(y, size.map(|s| s.1)),
         )
     }
 
+    /// 环绕时把网格坐标折回世界范围内
+    #[inline]
+    fn wrap_cell(&self, cell: (i32, i32)) -> (i32, i32) {
+        match self.wrap_size() {
+            Some((w, h)) => {
+                let cells_x = ((w / self.cell_size).ceil() as i32).max(1);
+                let cells_y = ((h / self.cell_size).ceil() as i32).max(1);
+                (cell.0.rem_euclid(cells_x), cell.1.rem_euclid(cells_y))
+            }
+            None => cell,
+        }
+    }
+
+    /// 与 center 的切比雪夫距离恰好为 ring 的网格单元（ring = 0 时为 center 本身）
+    fn ring_cells(center: (i32, i32), ring: i32) -> Vec<(i32, i32)> {
+        if ring == 0 {
+            return vec![center];
+        }
+        let (cx, cy) = center;
+        let mut cells = Vec::with_capacity(ring as usize * 8);
+        for dx in -ring..=ring {
+            cells.push((cx + dx, cy - ring));
+            cells.push((cx + dx, cy + ring));
+        }
+        for dy in (-ring + 1)..ring {
+            cells.push((cx - ring, cy + dy));
+            cells.push((cx + ring, cy + dy));
+        }
+        cells
+    }
+
+    /// 与圆形范围相交的实体（圆心距 ≤ radius + 实体半径），按网格顺序惰性遍历
+    fn entities_in_radius(&self, x: f32, y: f32, radius: f32) -> impl Iterator<Item = &Entity> {
+        self.entities_in_radius_with_dist_sq(x, y, radius)
+            .map(|(entity, _)| entity)
+    }
+
+    /// 同 `entities_in_radius`，附带实体到 (x, y) 的距离平方
+    fn entities_in_radius_with_dist_sq(
+        &self,
+        x: f32,
+        y: f32,
+        radius: f32,
+    ) -> impl Iterator<Item = (&Entity, f32)> {
+        let size = self.wrap_size();
+        let xs = self.axis_cells(x - radius, x + radius, size.map(|s| s.0));
+        let ys = self.axis_cells(y - radius, y + radius, size.map(|s| s.1));
+        xs.into_iter()
+            .flatten()
+            .flat_map(move |cx| ys.clone().into_iter().flatten().map(move |cy| (cx, cy)))
+            .filter_map(|cell| self.grid.get(&cell))
+            .flatten()
+            .filter_map(|id| self.entities.get(id))
+            .filter_map(move |entity| {
+                let (dx, dy) = self.delta(x, y, entity.x, entity.y);
+                let dist_sq = dx * dx + dy * dy;
+                let combined_radius = radius + entity.radius;
+                (dist_sq <= combined_radius * combined_radius).then_some((entity, dist_sq))
+            })
+    }
+
     /// 获取圆形范围覆盖的所有网格单元
     fn get_cells_in_radius(&self, x: f32, y: f32, radius: f32) -> Vec<(i32, i32)> {
-        let min_cell = self.get_cell(x - radius, y - radius);
-        let max_cell = self.get_cell(x + radius, y + radius);
-
         let mut cells = Vec::new();
-        for cx in min_cell.0..=max_cell.0 {
-            for cy in min_cell.1..=max_cell.1 {
+        self.collect_cells_in_radius(x, y, radius, &mut cells);
+        cells
+    }
+
+    /// 同 `get_cells_in_radius`，清空后写入调用方复用的缓冲区
+    fn collect_cells_in_radius(&self, x: f32, y: f32, radius: f32, cells: &mut Vec<(i32, i32)>) {
+        let size = self.wrap_size();
+        let xs = self.axis_cells(x - radius, x + radius, size.map(|s| s.0));
+        let ys = self.axis_cells(y - radius, y + radius, size.map(|s| s.1));
+
+        cells.clear();
+        for cx in xs.into_iter().flatten() {
+            for cy in ys.clone().into_iter().flatten() {
                 cells.push((cx, cy));
             }
         }
-        cells
     }
 }
 
+impl SpatialHash {
+    /// 与 `detect_all_collisions` 相同，但先清空再写入调用方的缓冲区，返回碰撞对数量
+    /// 每帧复用同一个 Vec，且内部临时数据存于 SpatialHash 的缓冲区，实体数量稳定时不再分配
+    pub fn detect_all_collisions_into(&self, collisions: &mut Vec<u32>) -> u32 {
+        collisions.clear();
+        let mut scratch = self.scratch.borrow_mut();
+        let CollisionScratch {
+            entities,
+            cells,
+            checked,
+        } = &mut *scratch;
+        entities.clear();
+        entities.extend(self.entities.values().copied());
+        entities.sort_unstable_by_key(|e| e.id);
+        checked.clear();
+
+        for entity in entities.iter() {
+            let first = collisions.len();
+            self.collect_cells_in_radius(entity.x, entity.y, entity.radius, cells);
+
+            for cell in cells.iter() {
+                if let Some(entity_ids) = self.grid.get(cell) {
+                    for &other_id in entity_ids {
+                        if entity.id >= other_id {
+                            continue; // 避免重复检测
+                        }
+
+                        let pair = (entity.id.min(other_id), entity.id.max(other_id));
+                        if checked.contains(&pair) {
+                            continue;
+                        }
+                        checked.insert(pair);
+
+                        if let Some(other) = self.entities.get(&other_id) {
+                            if other.layer != entity.layer {
+                                continue;
+                            }
+                            let (dx, dy) = self.delta(entity.x, entity.y, other.x, other.y);
+                            let dist_sq = dx * dx + dy * dy;
+                            let combined_radius = entity.radius + other.radius;
+
+                            if dist_sq <= combined_radius * combined_radius {
+                                collisions.push(entity.id);
+                                collisions.push(other_id);
+                            }
+                        }
+                    }
+                }
+            }
+            sort_pairs_tail(collisions, first);
+        }
+
+        (collisions.len() / 2) as u32
+    }
+}
+
+/// 将 `pairs[first..]` 中的扁平碰撞对按第二个 id 升序排列
+/// （同一实体的碰撞对第一个 id 相同，外层已按 id 升序遍历），原地排序不分配
+fn sort_pairs_tail(pairs: &mut [u32], first: usize) {
+    let (tail, _) = pairs[first..].as_chunks_mut::<2>();
+    tail.sort_unstable_by_key(|pair| pair[1]);
+}
+
 /// 矩形碰撞检测（AABB）
 #[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
 pub fn check_aabb_collision(
     x1: f32,
     y1: f32,
@@ -337,6 +1143,96 @@ pub fn point_in_circle(px: f32, py: f32, cx: f32, cy: f32, radius: f32) -> bool
     dx * dx + dy * dy <= radius * radius
 }
 
+/// 线段 AB 上距点 P 最近的点的参数 t ∈ [0, 1]（A、B 重合时为 0）
+fn segment_closest_t(px: f32, py: f32, ax: f32, ay: f32, bx: f32, by: f32) -> f32 {
+    let abx = bx - ax;
+    let aby = by - ay;
+    let len_sq = abx * abx + aby * aby;
+    if len_sq > 0.0 {
+        (((px - ax) * abx + (py - ay) * aby) / len_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+/// 点到线段 AB 的最近距离平方（A、B 重合时退化为点距离）
+fn segment_distance_sq(px: f32, py: f32, ax: f32, ay: f32, bx: f32, by: f32) -> f32 {
+    let t = segment_closest_t(px, py, ax, ay, bx, by);
+    let dx = px - (ax + (bx - ax) * t);
+    let dy = py - (ay + (by - ay) * t);
+    dx * dx + dy * dy
+}
+
+/// 点是否在胶囊体内（线段 AB 加半径，用于近战挥砍轨迹）
+#[wasm_bindgen]
+pub fn point_in_capsule(px: f32, py: f32, ax: f32, ay: f32, bx: f32, by: f32, radius: f32) -> bool {
+    segment_distance_sq(px, py, ax, ay, bx, by) <= radius * radius
+}
+
+/// 胶囊体与圆形碰撞检测
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn capsule_circle_collision(
+    ax: f32,
+    ay: f32,
+    bx: f32,
+    by: f32,
+    cap_r: f32,
+    cx: f32,
+    cy: f32,
+    circle_r: f32,
+) -> bool {
+    let combined_radius = cap_r + circle_r;
+    segment_distance_sq(cx, cy, ax, ay, bx, by) <= combined_radius * combined_radius
+}
+
+/// 线段与矩形相交检测（Liang–Barsky 裁剪，用于视线遮挡判断）
+///
+/// 线段端点落在矩形内或边界上也算相交，与 `point_in_rect` 一致
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn segment_intersects_rect(
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+    rx: f32,
+    ry: f32,
+    rw: f32,
+    rh: f32,
+) -> bool {
+    let dx = x2 - x1;
+    let dy = y2 - y1;
+    let mut t0 = 0.0f32;
+    let mut t1 = 1.0f32;
+
+    // 依次用左、右、上、下四条边裁剪参数区间 [t0, t1]
+    for (p, q) in [
+        (-dx, x1 - rx),
+        (dx, rx + rw - x1),
+        (-dy, y1 - ry),
+        (dy, ry + rh - y1),
+    ] {
+        if p == 0.0 {
+            // 与该边平行：起点在外侧则不可能相交
+            if q < 0.0 {
+                return false;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                t0 = t0.max(r);
+            } else {
+                t1 = t1.min(r);
+            }
+            if t0 > t1 {
+                return false;
+            }
+        }
+    }
+    true
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -361,6 +1257,513 @@ mod tests {
         assert_eq!(collisions.len(), 2);
     }
 
+    #[test]
+    fn test_collisions_between_groups() {
+        let mut hash = SpatialHash::new(256.0);
+        hash.upsert(1, 100.0, 100.0, 16.0, 0); // 我方
+        hash.upsert(2, 110.0, 100.0, 16.0, 0); // 我方，与 1 重叠
+        hash.upsert(3, 125.0, 100.0, 16.0, 1); // 敌方，与 1、2 重叠
+        hash.upsert(4, 135.0, 100.0, 16.0, 1); // 敌方，与 2、3 重叠
+
+        let mut pairs: Vec<(u32, u32)> = hash
+            .detect_collisions_between_groups(0, 1)
+            .chunks(2)
+            .map(|c| (c[0], c[1]))
+            .collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(1, 3), (2, 3), (2, 4)]);
+
+        // 同阵营内部
+        assert_eq!(hash.detect_collisions_between_groups(1, 1).len(), 2);
+    }
+
+    #[test]
+    fn test_query_radius_with_distance() {
+        let mut hash = SpatialHash::new(64.0);
+        hash.upsert(1, 100.0, 100.0, 16.0, 0);
+        hash.upsert(2, 130.0, 140.0, 16.0, 0);
+        hash.upsert(3, 400.0, 400.0, 16.0, 0);
+
+        let mut pairs: Vec<(u32, f32)> = hash
+            .query_radius_with_distance(100.0, 100.0, 50.0)
+            .chunks(2)
+            .map(|c| (c[0] as u32, c[1]))
+            .collect();
+        pairs.sort_by_key(|p| p.0);
+        assert_eq!(pairs, vec![(1, 0.0), (2, 50.0)]);
+
+        let ids: Vec<u32> = pairs.iter().map(|p| p.0).collect();
+        let mut expected = hash.query_radius(100.0, 100.0, 50.0);
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn test_query_radius_sorted() {
+        let mut hash = SpatialHash::new(32.0);
+        hash.upsert(1, 200.0, 100.0, 5.0, 0);
+        hash.upsert(2, 110.0, 100.0, 5.0, 0);
+        hash.upsert(3, 100.0, 150.0, 5.0, 0);
+        hash.upsert(4, 90.0, 100.0, 5.0, 0); // 与 2 等距
+        hash.upsert(5, 500.0, 500.0, 5.0, 0);
+
+        assert_eq!(
+            hash.query_radius_sorted(100.0, 100.0, 100.0),
+            vec![2, 4, 3, 1]
+        );
+        let mut ids = hash.query_radius_sorted(100.0, 100.0, 100.0);
+        ids.sort_unstable();
+        assert_eq!(ids, hash.query_radius(100.0, 100.0, 100.0));
+        assert!(hash.query_radius_sorted(0.0, 0.0, 10.0).is_empty());
+
+        // 环形世界按环绕后的距离排序
+        hash.set_world_bounds(1000.0, 1000.0);
+        hash.set_wrap(true);
+        hash.upsert(6, 995.0, 100.0, 5.0, 0);
+        assert_eq!(hash.query_radius_sorted(5.0, 100.0, 100.0), vec![6, 4, 2]);
+    }
+
+    #[test]
+    fn test_deterministic_output() {
+        let build = |ids: &[u32]| {
+            let mut hash = SpatialHash::new(32.0);
+            for &id in ids {
+                let x = 100.0 + (id % 7) as f32 * 9.0;
+                let y = 100.0 + (id / 7) as f32 * 9.0;
+                hash.upsert(id, x, y, 8.0, id % 2);
+            }
+            hash
+        };
+        let ids: Vec<u32> = (1..=40).collect();
+        let reversed: Vec<u32> = ids.iter().rev().copied().collect();
+
+        let a = build(&ids);
+        let b = build(&ids);
+        let c = build(&reversed);
+
+        for other in [&b, &c] {
+            assert_eq!(
+                a.query_radius(120.0, 120.0, 40.0),
+                other.query_radius(120.0, 120.0, 40.0)
+            );
+            assert_eq!(a.detect_all_collisions(), other.detect_all_collisions());
+            assert_eq!(
+                a.detect_collisions_between_groups(0, 1),
+                other.detect_collisions_between_groups(0, 1)
+            );
+            assert_eq!(a.detect_collisions_for(9), other.detect_collisions_for(9));
+        }
+
+        let ids = a.query_radius(120.0, 120.0, 40.0);
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        let pairs: Vec<(u32, u32)> = a
+            .detect_all_collisions()
+            .chunks(2)
+            .map(|c| (c[0], c[1]))
+            .collect();
+        assert!(!pairs.is_empty());
+        assert!(pairs.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_predicted_collisions() {
+        let mut hash = SpatialHash::new(64.0);
+        // 相向而行：当前间距 100，半径和 20，相对速度 200 px/s
+        hash.upsert_with_velocity(1, 0.0, 0.0, 100.0, 0.0, 10.0, 0);
+        hash.upsert_with_velocity(2, 100.0, 0.0, -100.0, 0.0, 10.0, 1);
+        // 静止且远离
+        hash.upsert(3, 0.0, 500.0, 10.0, 0);
+
+        assert!(hash.detect_all_collisions().is_empty());
+        assert!(hash.detect_predicted_collisions(0.3).is_empty());
+        assert_eq!(hash.detect_predicted_collisions(0.4), vec![1, 2]);
+        // 高速穿过：dt 很大时相遇点在区间中间，仍能检测到
+        assert_eq!(hash.detect_predicted_collisions(10.0), vec![1, 2]);
+
+        // 同向同速：相对静止，不会碰撞
+        hash.upsert_with_velocity(2, 100.0, 0.0, 100.0, 0.0, 10.0, 1);
+        assert!(hash.detect_predicted_collisions(10.0).is_empty());
+
+        // 普通 upsert 速度归零，且已重叠的碰撞对仍然返回
+        hash.upsert(1, 95.0, 0.0, 10.0, 0);
+        hash.upsert(2, 100.0, 0.0, 10.0, 1);
+        assert_eq!(hash.detect_predicted_collisions(1.0), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_nearest_in_group() {
+        let mut hash = SpatialHash::new(32.0);
+        hash.upsert(1, 105.0, 100.0, 8.0, 0); // 最近，但阵营不同
+        hash.upsert(2, 160.0, 100.0, 8.0, 1);
+        hash.upsert(3, 100.0, 30.0, 8.0, 1); // 跨多圈，距离 70
+        hash.upsert(4, 40.0, 100.0, 8.0, 1); // 距离 60，与 2 同距
+        hash.upsert(5, 400.0, 400.0, 8.0, 2);
+
+        assert_eq!(hash.nearest_in_group(100.0, 100.0, 0, 100.0), 1);
+        // 2 与 4 同距时取较小 id
+        assert_eq!(hash.nearest_in_group(100.0, 100.0, 1, 100.0), 2);
+        assert_eq!(hash.nearest_in_group(100.0, 40.0, 1, 100.0), 3);
+        // 超出 max_radius
+        assert_eq!(hash.nearest_in_group(100.0, 100.0, 1, 59.0), -1);
+        assert_eq!(hash.nearest_in_group(100.0, 100.0, 2, 100.0), -1);
+        assert_eq!(hash.nearest_in_group(100.0, 100.0, 9, 1000.0), -1);
+
+        // 与暴力搜索结果一致
+        for (px, py) in [(0.0, 0.0), (130.0, 70.0), (-50.0, 220.0), (390.0, 390.0)] {
+            for group in 0..3 {
+                let expected = hash
+                    .query_radius_with_distance(px, py, 1000.0)
+                    .chunks(2)
+                    .map(|c| (c[0] as u32, c[1]))
+                    .filter(|&(id, _)| hash.entities[&id].group == group)
+                    .min_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)))
+                    .map_or(-1, |(id, _)| id as i32);
+                assert_eq!(hash.nearest_in_group(px, py, group, 1000.0), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_query_segment() {
+        let mut hash = SpatialHash::new(32.0);
+        hash.upsert(1, 300.0, 0.0, 10.0, 1);
+        hash.upsert(2, 100.0, 15.0, 10.0, 1); // 距线段 15，半径 10 + 厚度 5 恰好相切
+        hash.upsert(3, 200.0, -5.0, 10.0, 0); // 被排除的阵营
+        hash.upsert(4, 50.0, 40.0, 10.0, 1); // 太远
+        hash.upsert(5, -12.0, 0.0, 15.0, 1); // 起点后方，但圆覆盖起点
+        hash.upsert(6, 420.0, 0.0, 10.0, 1); // 终点之后
+
+        // 按沿线段的距离排序，而不是按 id
+        assert_eq!(
+            hash.query_segment(0.0, 0.0, 400.0, 0.0, 5.0, 0),
+            vec![5, 2, 1]
+        );
+        // 不排除阵营、不加粗
+        assert_eq!(
+            hash.query_segment(0.0, 0.0, 400.0, 0.0, 0.0, u32::MAX),
+            vec![5, 3, 1]
+        );
+        // 反方向：顺序反转
+        assert_eq!(
+            hash.query_segment(400.0, 0.0, 0.0, 0.0, 5.0, 0),
+            vec![1, 2, 5]
+        );
+        // 斜线跨越多个网格
+        let mut diag = SpatialHash::new(32.0);
+        diag.upsert(7, 205.0, 195.0, 10.0, 1); // 距对角线约 7.1
+        diag.upsert(8, 100.0, 100.0, 5.0, 1);
+        diag.upsert(9, 150.0, 100.0, 10.0, 1); // 距对角线约 35.4
+        assert_eq!(
+            diag.query_segment(0.0, 0.0, 300.0, 300.0, 0.0, 0),
+            vec![8, 7]
+        );
+        // 退化为点
+        assert_eq!(hash.query_segment(300.0, 5.0, 300.0, 5.0, 0.0, 0), vec![1]);
+    }
+
+    #[test]
+    fn test_remove_group() {
+        let mut hash = SpatialHash::new(64.0);
+        hash.upsert(1, 100.0, 100.0, 16.0, 1);
+        hash.upsert(2, 110.0, 100.0, 16.0, 1);
+        hash.upsert(3, 120.0, 100.0, 16.0, 0);
+        hash.upsert(4, 500.0, 500.0, 16.0, 1);
+
+        assert_eq!(hash.remove_group(1), 3);
+        assert_eq!(hash.count(), 1);
+        assert_eq!(hash.query_radius(100.0, 100.0, 50.0), vec![3]);
+        assert!(hash.query_at(500.0, 500.0).is_empty());
+        assert_eq!(hash.remove_group(1), 0);
+    }
+
+    #[test]
+    fn test_resolve_overlaps() {
+        let mut hash = SpatialHash::new(64.0);
+        assert!(hash.resolve_overlaps().is_empty());
+
+        // 1 与 2 穿透 5，1 与 3 穿透 4，2 与 3 不重叠
+        hash.upsert(1, 0.0, 0.0, 10.0, 0);
+        hash.upsert(2, 15.0, 0.0, 10.0, 0);
+        hash.upsert(3, 0.0, 16.0, 10.0, 1);
+        // 圆心重合
+        hash.upsert(5, 500.0, 500.0, 5.0, 0);
+        hash.upsert(6, 500.0, 500.0, 5.0, 0);
+        // 仅相切
+        hash.upsert(7, 1000.0, 0.0, 5.0, 0);
+        hash.upsert(8, 1010.0, 0.0, 5.0, 0);
+
+        assert_eq!(
+            hash.resolve_overlaps(),
+            vec![
+                1.0, -5.0, -4.0, //
+                2.0, 5.0, 0.0, //
+                3.0, 0.0, 4.0, //
+                5.0, -10.0, 0.0, //
+                6.0, 10.0, 0.0,
+            ]
+        );
+
+        // 各自移动一半后不再重叠
+        let pushes = hash.resolve_overlaps();
+        for chunk in pushes.chunks_exact(3) {
+            let id = chunk[0] as u32;
+            let e = hash.get_entity(id).unwrap();
+            hash.upsert(
+                id,
+                e[0] + chunk[1] / 2.0,
+                e[1] + chunk[2] / 2.0,
+                e[2],
+                e[3] as u32,
+            );
+        }
+        assert!(hash.resolve_overlaps().is_empty());
+    }
+
+    #[test]
+    fn test_batch_upsert() {
+        let mut hash = SpatialHash::new(64.0);
+        hash.upsert_with_data(1, 10.0, 10.0, 8.0, 0, 0b1);
+
+        // 1 已存在：跨网格单元移动并改半径/阵营；2、3 新建；末尾残缺项忽略
+        hash.batch_upsert(&[
+            1.0, 300.0, 10.0, 12.0, 2.0, //
+            2.0, 20.0, 20.0, 4.0, 1.0, //
+            3.0, 500.0, 500.0, 6.0, 0.0, //
+            4.0, 0.0,
+        ]);
+
+        assert_eq!(hash.count(), 3);
+        assert_eq!(hash.get_entity(1), Some(vec![300.0, 10.0, 12.0, 2.0]));
+        assert_eq!(hash.get_entity(2), Some(vec![20.0, 20.0, 4.0, 1.0]));
+        assert_eq!(hash.get_entity(4), None);
+        assert_eq!(hash.query_at(10.0, 10.0), vec![2]);
+        assert_eq!(hash.query_at(300.0, 10.0), vec![1]);
+        assert_eq!(hash.query_radius_with_data(300.0, 10.0, 1.0), vec![1, 0b1]);
+        assert_eq!(hash.query_radius(20.0, 20.0, 1.0), vec![2]);
+
+        // 网格中不残留旧位置
+        let cells: usize = hash.grid.values().map(Vec::len).sum();
+        assert_eq!(cells, 3);
+    }
+
+    #[test]
+    fn test_wrapping_world() {
+        // 宽度不是 cell_size 的整数倍，最后一列网格不满
+        let mut hash = SpatialHash::new(64.0);
+        hash.upsert(1, 995.0, 400.0, 10.0, 0);
+        hash.upsert(2, 5.0, 400.0, 10.0, 1);
+        hash.upsert(3, 500.0, 797.0, 4.0, 0);
+        hash.upsert(4, 500.0, 2.0, 4.0, 0);
+        hash.upsert(5, 500.0, 400.0, 4.0, 0);
+
+        // 默认无边界：相对边缘的实体互不相干
+        assert!(hash.detect_all_collisions().is_empty());
+        hash.set_world_bounds(1000.0, 800.0);
+        assert!(hash.detect_all_collisions().is_empty());
+
+        hash.set_wrap(true);
+        assert_eq!(hash.detect_all_collisions(), vec![1, 2, 3, 4]);
+        assert_eq!(hash.query_radius(0.0, 400.0, 8.0), vec![1, 2]);
+        assert_eq!(hash.query_radius(998.0, 0.0, 5.0), vec![]);
+        assert_eq!(hash.query_radius(500.0, 799.0, 1.0), vec![3, 4]);
+        assert_eq!(hash.count_in_radius(0.0, 400.0, 8.0), 2);
+        assert_eq!(hash.detect_collisions_for(2), vec![1]);
+        assert_eq!(hash.nearest_in_group(20.0, 400.0, 0, 100.0), 1);
+        let dist = hash.query_radius_with_distance(0.0, 400.0, 8.0);
+        assert_eq!(dist, vec![1.0, 5.0, 2.0, 5.0]);
+
+        // 分离方向指向绕过边缘的一侧
+        let pushes = hash.resolve_overlaps();
+        assert_eq!(&pushes[..6], &[1.0, -10.0, 0.0, 2.0, 10.0, 0.0]);
+
+        // 跨越边缘移动后仍在正确的网格中
+        hash.batch_update_positions(&[2.0, 1003.0, 400.0]);
+        assert_eq!(hash.query_radius(0.0, 400.0, 8.0), vec![1, 2]);
+
+        hash.set_wrap(false);
+        assert_eq!(hash.query_radius(0.0, 400.0, 8.0), vec![]);
+        assert_eq!(hash.query_radius(1003.0, 400.0, 1.0), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_collision_layers() {
+        let mut hash = SpatialHash::new(100.0);
+        hash.upsert(1, 0.0, 0.0, 10.0, 0); // 地面
+        hash.upsert_with_layer(2, 5.0, 0.0, 10.0, 0, 1); // 空中
+        hash.upsert_with_layer(3, 10.0, 0.0, 10.0, 1, 1); // 空中，敌方
+        hash.upsert(4, 8.0, 0.0, 10.0, 1); // 地面，敌方
+
+        assert_eq!(hash.detect_all_collisions(), vec![1, 4, 2, 3]);
+        assert_eq!(hash.detect_collisions_in_layer(0), vec![1, 4]);
+        assert_eq!(hash.detect_collisions_in_layer(1), vec![2, 3]);
+        assert!(hash.detect_collisions_in_layer(2).is_empty());
+        assert_eq!(hash.detect_collisions_for(2), vec![3]);
+        assert_eq!(
+            hash.detect_collisions_between_groups(0, 1),
+            vec![1, 4, 2, 3]
+        );
+        assert_eq!(hash.detect_predicted_collisions(0.0), vec![1, 4, 2, 3]);
+
+        // 范围查询默认不分层
+        assert_eq!(hash.query_radius(0.0, 0.0, 1.0), vec![1, 2, 3, 4]);
+        assert_eq!(hash.query_radius_in_layer(0.0, 0.0, 1.0, 1), vec![2, 3]);
+
+        // 普通更新保留层
+        hash.upsert(2, 6.0, 0.0, 10.0, 0);
+        hash.batch_update_positions(&[3.0, 9.0, 0.0]);
+        assert_eq!(hash.detect_collisions_in_layer(1), vec![2, 3]);
+        assert_eq!(hash.query_radius_in_layer(0.0, 0.0, 1.0, 0), vec![1, 4]);
+    }
+
+    #[test]
+    fn test_detect_all_collisions_into() {
+        let mut hash = SpatialHash::new(50.0);
+        hash.upsert(1, 0.0, 0.0, 10.0, 0);
+        hash.upsert(2, 15.0, 0.0, 10.0, 0);
+        hash.upsert(3, 25.0, 0.0, 10.0, 0);
+
+        let mut out = vec![99; 64];
+        assert_eq!(hash.detect_all_collisions_into(&mut out), 2);
+        assert_eq!(out, hash.detect_all_collisions());
+        assert_eq!(out, vec![1, 2, 2, 3]);
+        let capacity = out.capacity();
+
+        // 复用缓冲区：先清空再写入，不重新分配
+        hash.remove(2);
+        assert_eq!(hash.detect_all_collisions_into(&mut out), 0);
+        assert!(out.is_empty());
+        assert_eq!(out.capacity(), capacity);
+
+        // 内部临时缓冲区同样跨调用复用
+        let capacities = |hash: &SpatialHash| {
+            let scratch = hash.scratch.borrow();
+            (
+                scratch.entities.capacity(),
+                scratch.cells.capacity(),
+                scratch.checked.capacity(),
+            )
+        };
+        let before = capacities(&hash);
+        assert!(before.0 >= 2 && before.1 > 0);
+        hash.upsert(2, 15.0, 0.0, 10.0, 0);
+        assert_eq!(hash.detect_all_collisions_into(&mut out), 2);
+        assert_eq!(capacities(&hash), before);
+    }
+
+    #[test]
+    fn test_get_entity() {
+        let mut hash = SpatialHash::new(64.0);
+        hash.upsert(7, 100.0, 200.0, 16.0, 2);
+        hash.batch_update_positions(&[7.0, 150.0, 250.0]);
+
+        assert_eq!(hash.get_entity(7), Some(vec![150.0, 250.0, 16.0, 2.0]));
+        assert_eq!(hash.get_entity(8), None);
+    }
+
+    #[test]
+    fn test_count_and_any_in_radius() {
+        let mut hash = SpatialHash::new(50.0);
+        hash.upsert(1, 0.0, 0.0, 5.0, 0);
+        hash.upsert(2, 30.0, 0.0, 5.0, 0);
+        hash.upsert(3, 80.0, 0.0, 5.0, 1);
+        hash.upsert(4, 400.0, 400.0, 5.0, 1);
+
+        for radius in [0.0, 20.0, 60.0, 100.0] {
+            assert_eq!(
+                hash.count_in_radius(0.0, 0.0, radius) as usize,
+                hash.query_radius(0.0, 0.0, radius).len()
+            );
+        }
+        assert_eq!(hash.count_in_radius(0.0, 0.0, 60.0), 2);
+        assert_eq!(hash.count_in_radius(200.0, 200.0, 10.0), 0);
+
+        assert!(hash.any_in_radius(0.0, 0.0, 60.0, u32::MAX));
+        // 范围内只有阵营 0
+        assert!(!hash.any_in_radius(0.0, 0.0, 60.0, 0));
+        assert!(hash.any_in_radius(0.0, 0.0, 100.0, 0));
+        assert!(!hash.any_in_radius(200.0, 200.0, 10.0, u32::MAX));
+    }
+
+    #[test]
+    fn test_query_radius_with_data() {
+        let mut hash = SpatialHash::new(64.0);
+        hash.upsert_with_data(3, 100.0, 100.0, 10.0, 1, 0b01);
+        hash.upsert_with_data(1, 120.0, 100.0, 10.0, 2, 0b10);
+        hash.upsert(2, 110.0, 100.0, 10.0, 1);
+        hash.upsert(9, 900.0, 900.0, 10.0, 1);
+
+        assert_eq!(
+            hash.query_radius_with_data(110.0, 100.0, 20.0),
+            vec![1, 0b10, 2, 0, 3, 0b01]
+        );
+
+        // 位置更新保留标记
+        hash.upsert(3, 105.0, 100.0, 10.0, 1);
+        hash.batch_update_positions(&[1.0, 115.0, 100.0]);
+        assert_eq!(
+            hash.query_radius_with_data(110.0, 100.0, 20.0),
+            vec![1, 0b10, 2, 0, 3, 0b01]
+        );
+    }
+
+    #[test]
+    fn test_all_entities() {
+        let mut hash = SpatialHash::new(64.0);
+        assert!(hash.all_entities().is_empty());
+
+        hash.upsert(9, 1.0, 2.0, 3.0, 1);
+        hash.upsert(4, 500.0, -20.0, 8.0, 0);
+        hash.upsert(6, 10.0, 10.0, 5.0, 2);
+        hash.remove(6);
+
+        assert_eq!(
+            hash.all_entities(),
+            vec![4.0, 500.0, -20.0, 8.0, 0.0, 9.0, 1.0, 2.0, 3.0, 1.0]
+        );
+        assert_eq!(hash.all_entities().len(), hash.count() as usize * 5);
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let mut hash = SpatialHash::new(48.0);
+        hash.upsert(3, 100.0, 100.0, 16.0, 1);
+        hash.upsert_with_data(1, 110.0, 100.0, 12.5, 0, 0b101);
+        hash.upsert_with_velocity(1, 110.0, 100.0, -3.0, 4.5, 12.5, 0);
+        hash.upsert_with_layer(2, -40.0, 900.0, 8.0, 2, 1);
+
+        let bytes = hash.serialize();
+        let restored = SpatialHash::deserialize(&bytes).unwrap();
+
+        assert_eq!(restored.count(), 3);
+        for id in 1..=3 {
+            assert_eq!(restored.get_entity(id), hash.get_entity(id));
+        }
+        let one = restored.entities[&1];
+        assert_eq!((one.vx, one.vy, one.user_data), (-3.0, 4.5, 0b101));
+        assert_eq!(restored.entities[&2].layer, 1);
+        assert_eq!(restored.query_at(-40.0, 900.0), vec![2]);
+        assert_eq!(restored.detect_all_collisions().len(), 2);
+        assert_eq!(restored.serialize(), bytes);
+
+        // 版本 1 快照只含位置、半径与阵营
+        let mut v1 = bytes[..SNAPSHOT_HEADER_SIZE].to_vec();
+        v1[4..6].copy_from_slice(&1u16.to_le_bytes());
+        v1[12..16].copy_from_slice(&1u32.to_le_bytes());
+        let entity_2 = SNAPSHOT_HEADER_SIZE + 2 * SNAPSHOT_ENTITY_SIZE;
+        v1.extend_from_slice(&bytes[entity_2..entity_2 + SNAPSHOT_V1_ENTITY_SIZE]);
+        let old = SpatialHash::deserialize(&v1).unwrap();
+        assert_eq!(old.get_entity(3), hash.get_entity(3));
+        assert_eq!(old.entities[&3].layer, 0);
+
+        assert!(SpatialHash::deserialize(&bytes[..bytes.len() - 1]).is_none());
+        assert!(SpatialHash::deserialize(b"NOPE").is_none());
+
+        // 实体数量远超数据长度（wasm32 上乘法会溢出）
+        let mut huge = bytes[..SNAPSHOT_HEADER_SIZE].to_vec();
+        huge[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(SpatialHash::deserialize(&huge).is_none());
+    }
+
     #[test]
     fn test_aabb_collision() {
         assert!(check_aabb_collision(
@@ -376,4 +1779,75 @@ mod tests {
         assert!(check_circle_collision(0.0, 0.0, 10.0, 15.0, 0.0, 10.0));
         assert!(!check_circle_collision(0.0, 0.0, 10.0, 30.0, 0.0, 10.0));
     }
+
+    #[test]
+    fn test_point_in_capsule() {
+        // 线段 (0,0)-(100,0)，半径 10
+        assert!(point_in_capsule(50.0, 9.0, 0.0, 0.0, 100.0, 0.0, 10.0));
+        assert!(!point_in_capsule(50.0, 11.0, 0.0, 0.0, 100.0, 0.0, 10.0));
+        // 端点外侧按圆帽计算
+        assert!(point_in_capsule(-6.0, 6.0, 0.0, 0.0, 100.0, 0.0, 10.0));
+        assert!(!point_in_capsule(-8.0, 8.0, 0.0, 0.0, 100.0, 0.0, 10.0));
+        assert!(point_in_capsule(109.0, 0.0, 0.0, 0.0, 100.0, 0.0, 10.0));
+        // 退化为点（A == B）
+        assert!(point_in_capsule(3.0, 4.0, 0.0, 0.0, 0.0, 0.0, 5.0));
+        assert!(!point_in_capsule(3.0, 4.1, 0.0, 0.0, 0.0, 0.0, 5.0));
+    }
+
+    #[test]
+    fn test_capsule_circle_collision() {
+        assert!(capsule_circle_collision(
+            0.0, 0.0, 100.0, 0.0, 10.0, 50.0, 25.0, 15.0
+        ));
+        assert!(!capsule_circle_collision(
+            0.0, 0.0, 100.0, 0.0, 10.0, 50.0, 26.0, 15.0
+        ));
+        // 圆在端点之外
+        assert!(capsule_circle_collision(
+            0.0, 0.0, 100.0, 0.0, 10.0, 120.0, 0.0, 10.0
+        ));
+        assert!(!capsule_circle_collision(
+            0.0, 0.0, 100.0, 0.0, 10.0, 121.0, 0.0, 10.0
+        ));
+    }
+
+    #[test]
+    fn test_segment_intersects_rect() {
+        // 矩形 (10,10)-(20,20)
+        // 横穿
+        assert!(segment_intersects_rect(
+            0.0, 15.0, 30.0, 15.0, 10.0, 10.0, 10.0, 10.0
+        ));
+        // 对角穿过
+        assert!(segment_intersects_rect(
+            0.0, 0.0, 30.0, 30.0, 10.0, 10.0, 10.0, 10.0
+        ));
+        // 完全在内部
+        assert!(segment_intersects_rect(
+            12.0, 12.0, 18.0, 18.0, 10.0, 10.0, 10.0, 10.0
+        ));
+        // 在矩形前止步
+        assert!(!segment_intersects_rect(
+            0.0, 15.0, 9.0, 15.0, 10.0, 10.0, 10.0, 10.0
+        ));
+        // 平行且在外侧
+        assert!(!segment_intersects_rect(
+            0.0, 25.0, 30.0, 25.0, 10.0, 10.0, 10.0, 10.0
+        ));
+        // 斜线擦过角外
+        assert!(!segment_intersects_rect(
+            0.0, 12.0, 12.0, 0.0, 10.0, 10.0, 10.0, 10.0
+        ));
+        // 恰好触及边界
+        assert!(segment_intersects_rect(
+            0.0, 10.0, 30.0, 10.0, 10.0, 10.0, 10.0, 10.0
+        ));
+        // 退化为点
+        assert!(segment_intersects_rect(
+            15.0, 15.0, 15.0, 15.0, 10.0, 10.0, 10.0, 10.0
+        ));
+        assert!(!segment_intersects_rect(
+            5.0, 5.0, 5.0, 5.0, 10.0, 10.0, 10.0, 10.0
+        ));
+    }
 }
@@ -4,12 +4,14 @@
 //! - A* 寻路算法
 //! - ASF 精灵帧解码 (RLE 解压)
 //! - MPC 精灵帧解码 (RLE 解压)
+//! - MMF 地图解码
 //! - 空间碰撞检测
 
 use wasm_bindgen::prelude::*;
 
 pub mod asf_decoder;
 pub mod collision;
+pub mod mmf_codec;
 pub mod mpc_decoder;
 pub mod msf_codec;
 pub mod pathfinder;
@@ -28,6 +30,38 @@ pub fn version() -> String {
     env!("CARGO_PKG_VERSION").to_string()
 }
 
+/// 编译时启用的可选功能
+fn enabled_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "console_error_panic_hook") {
+        features.push("console_error_panic_hook");
+    }
+    if cfg!(feature = "zstd-encode") {
+        features.push("zstd-encode");
+    }
+    features
+}
+
+/// 获取构建信息：版本、启用的 feature、目标指针宽度
+///
+/// 格式：`0.1.0; features=console_error_panic_hook,zstd-encode; pointer_width=32`
+/// （无 feature 时为 `features=none`），用于排查不同构建间的行为差异
+#[wasm_bindgen]
+pub fn build_info() -> String {
+    let features = enabled_features();
+    let features = if features.is_empty() {
+        "none".to_string()
+    } else {
+        features.join(",")
+    };
+    format!(
+        "{}; features={}; pointer_width={}",
+        env!("CARGO_PKG_VERSION"),
+        features,
+        usize::BITS
+    )
+}
+
 /// Zstd 解压（暴露给 JS，用于 MMF 地图格式解压）
 #[wasm_bindgen]
 pub fn zstd_decompress(data: &[u8]) -> Result<Vec<u8>, JsError> {
@@ -51,4 +85,15 @@ mod tests {
     fn test_version() {
         assert!(!version().is_empty());
     }
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert!(info.starts_with(&version()));
+        assert!(info.contains(&format!("pointer_width={}", usize::BITS)));
+        assert_eq!(
+            info.contains("console_error_panic_hook"),
+            cfg!(feature = "console_error_panic_hook")
+        );
+    }
 }
@@ -0,0 +1,249 @@
+//! MMF (Miu Map Format) v1 — WASM decoder
+//!
+//! Layout (see docs/mmf-format.md):
+//! ```text
+//! [Magic "MMF1" (4)] [Version u16] [Flags u16]               = 8 bytes
+//! [columns u16] [rows u16] [msfCount u16] [trapCount u16]
+//! [reserved u32]                                              = 12 bytes
+//! [MSF Table: msfCount × (nameLen u8 + name + flags u8)]
+//! [Trap Table: trapCount × (trapIndex u8 + pathLen u16 + path)]  (HAS_TRAPS only)
+//! [Extension Chunks...]
+//! [Sentinel "END\0" (4) + 0u32 (4)]                          = 8 bytes
+//! [Zstd-compressed Tile Data Blob]                            = variable
+//! ```
+//!
+//! Tile blob (decompressed), `tiles = columns × rows`:
+//! layer1, layer2, layer3 (`tiles × 2` bytes each, `[msfIndex, frame]`),
+//! then barriers and traps (`tiles` bytes each).
+
+use crate::msf_codec::zstd_decompress;
+use wasm_bindgen::prelude::*;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+const MMF_MAGIC: &[u8; 4] = b"MMF1";
+const MMF_VERSION: u16 = 1;
+
+/// Flags bit 0: tile data blob is zstd-compressed
+const FLAG_ZSTD: u16 = 1;
+/// Flags bit 1: trap table is present
+const FLAG_HAS_TRAPS: u16 = 1 << 1;
+const CHUNK_END: &[u8; 4] = b"END\0";
+
+/// Preamble + map header
+const HEADER_SIZE: usize = 20;
+
+// ============================================================================
+// MMF Header / map data (returned to JS)
+// ============================================================================
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct MmfHeader {
+    pub version: u16,
+    pub flags: u16,
+    pub columns: u16,
+    pub rows: u16,
+    pub msf_count: u16,
+    pub trap_count: u16,
+}
+
+/// Fully decoded map: tables plus the five per-tile layers
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct MmfMap {
+    pub header: MmfHeader,
+    /// MSF file names; tile `msfIndex` N refers to `msf_names[N - 1]` (0 = empty)
+    pub msf_names: Vec<String>,
+    /// MSF entry flags (bit 0: looping), parallel to `msf_names`
+    pub msf_flags: Vec<u8>,
+    /// Trap table: `trap_indices[i]` runs `trap_scripts[i]`
+    pub trap_indices: Vec<u8>,
+    pub trap_scripts: Vec<String>,
+    /// `[msfIndex, frame]` per tile
+    pub layer1: Vec<u8>,
+    pub layer2: Vec<u8>,
+    pub layer3: Vec<u8>,
+    /// One byte per tile
+    pub barriers: Vec<u8>,
+    pub traps: Vec<u8>,
+}
+
+// ============================================================================
+// Parsing
+// ============================================================================
+
+/// Parse the MMF v1 preamble and map header
+#[wasm_bindgen]
+pub fn parse_mmf_header(data: &[u8]) -> Option<MmfHeader> {
+    if data.len() < HEADER_SIZE || &data[0..4] != MMF_MAGIC {
+        return None;
+    }
+    let read_u16 = |off: usize| u16::from_le_bytes([data[off], data[off + 1]]);
+
+    let version = read_u16(4);
+    if version != MMF_VERSION {
+        return None;
+    }
+
+    Some(MmfHeader {
+        version,
+        flags: read_u16(6),
+        columns: read_u16(8),
+        rows: read_u16(10),
+        msf_count: read_u16(12),
+        trap_count: read_u16(14),
+    })
+}
+
+/// Decode a whole MMF file: header, MSF table, trap table and tile layers
+///
+/// Returns None on bad magic/version, truncated tables, a missing END
+/// sentinel, or a tile blob smaller than `columns × rows × 8` bytes.
+#[wasm_bindgen]
+pub fn decode_mmf(data: &[u8]) -> Option<MmfMap> {
+    let header = parse_mmf_header(data)?;
+    let mut off = HEADER_SIZE;
+
+    // MSF table
+    let mut msf_names = Vec::with_capacity(header.msf_count as usize);
+    let mut msf_flags = Vec::with_capacity(header.msf_count as usize);
+    for _ in 0..header.msf_count {
+        let name_len = *data.get(off)? as usize;
+        off += 1;
+        let name = data.get(off..off + name_len)?;
+        msf_names.push(String::from_utf8_lossy(name).into_owned());
+        off += name_len;
+        msf_flags.push(*data.get(off)?);
+        off += 1;
+    }
+
+    // Trap table
+    let mut trap_indices = Vec::new();
+    let mut trap_scripts = Vec::new();
+    if header.flags & FLAG_HAS_TRAPS != 0 {
+        for _ in 0..header.trap_count {
+            let entry = data.get(off..off + 3)?;
+            let path_len = u16::from_le_bytes([entry[1], entry[2]]) as usize;
+            off += 3;
+            let path = data.get(off..off + path_len)?;
+            trap_indices.push(entry[0]);
+            trap_scripts.push(String::from_utf8_lossy(path).into_owned());
+            off += path_len;
+        }
+    }
+
+    // Skip extension chunks
+    loop {
+        let chunk = data.get(off..off + 8)?;
+        let chunk_len = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as usize;
+        off += 8;
+        if &chunk[0..4] == CHUNK_END {
+            break;
+        }
+        off = off.checked_add(chunk_len)?;
+    }
+
+    let blob = if header.flags & FLAG_ZSTD != 0 {
+        zstd_decompress(&data[off..])?
+    } else {
+        data[off..].to_vec()
+    };
+
+    let tiles = header.columns as usize * header.rows as usize;
+    if blob.len() < tiles * 8 {
+        return None;
+    }
+    let (layer1, rest) = blob.split_at(tiles * 2);
+    let (layer2, rest) = rest.split_at(tiles * 2);
+    let (layer3, rest) = rest.split_at(tiles * 2);
+    let (barriers, rest) = rest.split_at(tiles);
+    let traps = &rest[..tiles];
+
+    Some(MmfMap {
+        header,
+        msf_names,
+        msf_flags,
+        trap_indices,
+        trap_scripts,
+        layer1: layer1.to_vec(),
+        layer2: layer2.to_vec(),
+        layer3: layer3.to_vec(),
+        barriers: barriers.to_vec(),
+        traps: traps.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build an uncompressed 2×1 MMF with one MSF entry and one trap
+    fn build_mmf() -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MMF_MAGIC);
+        out.extend_from_slice(&MMF_VERSION.to_le_bytes());
+        out.extend_from_slice(&FLAG_HAS_TRAPS.to_le_bytes()); // not compressed
+        out.extend_from_slice(&2u16.to_le_bytes()); // columns
+        out.extend_from_slice(&1u16.to_le_bytes()); // rows
+        out.extend_from_slice(&1u16.to_le_bytes()); // msf_count
+        out.extend_from_slice(&1u16.to_le_bytes()); // trap_count
+        out.extend_from_slice(&0u32.to_le_bytes());
+
+        let name = "map003-1.msf".as_bytes();
+        out.push(name.len() as u8);
+        out.extend_from_slice(name);
+        out.push(1); // looping
+
+        let script = "script/map/trap1.txt".as_bytes();
+        out.push(7);
+        out.extend_from_slice(&(script.len() as u16).to_le_bytes());
+        out.extend_from_slice(script);
+
+        // Unknown chunk is skipped
+        out.extend_from_slice(b"XTRA");
+        out.extend_from_slice(&2u32.to_le_bytes());
+        out.extend_from_slice(&[0xAA, 0xBB]);
+        out.extend_from_slice(CHUNK_END);
+        out.extend_from_slice(&0u32.to_le_bytes());
+
+        out.extend_from_slice(&[1, 0, 1, 3]); // layer1
+        out.extend_from_slice(&[0, 0, 0, 0]); // layer2
+        out.extend_from_slice(&[0, 0, 1, 5]); // layer3
+        out.extend_from_slice(&[0, 0x80]); // barriers
+        out.extend_from_slice(&[7, 0]); // traps
+        out
+    }
+
+    #[test]
+    fn test_parse_mmf_header() {
+        let data = build_mmf();
+        let header = parse_mmf_header(&data).unwrap();
+        assert_eq!((header.columns, header.rows), (2, 1));
+        assert_eq!((header.msf_count, header.trap_count), (1, 1));
+
+        assert!(parse_mmf_header(&data[..19]).is_none());
+        let mut bad_version = data.clone();
+        bad_version[4] = 2;
+        assert!(parse_mmf_header(&bad_version).is_none());
+    }
+
+    #[test]
+    fn test_decode_mmf() {
+        let data = build_mmf();
+        let map = decode_mmf(&data).unwrap();
+        assert_eq!(map.msf_names, vec!["map003-1.msf".to_string()]);
+        assert_eq!(map.msf_flags, vec![1]);
+        assert_eq!(map.trap_indices, vec![7]);
+        assert_eq!(map.trap_scripts, vec!["script/map/trap1.txt".to_string()]);
+        assert_eq!(map.layer1, vec![1, 0, 1, 3]);
+        assert_eq!(map.layer3, vec![0, 0, 1, 5]);
+        assert_eq!(map.barriers, vec![0, 0x80]);
+        assert_eq!(map.traps, vec![7, 0]);
+
+        // Tile blob one byte short
+        assert!(decode_mmf(&data[..data.len() - 1]).is_none());
+    }
+}
@@ -3,6 +3,10 @@
 //! MPC 文件格式：
 //! - Header(64) + HeadData(64) + Palette(colors*4) + FrameOffsets(frames*4) + RLE压缩帧数据
 //!
+//! SHD（阴影）文件与 MPC 共用头部布局，但没有调色板（Shd.cs）：
+//! - 帧偏移表紧跟在 128 字节头之后，头中的 colors 字段忽略
+//! - RLE 颜色段只有计数字节，不跟调色板索引，每个像素都是半透明黑色阴影
+//!
 //! 优化策略：与 ASF 相同，JS 端预分配 buffer，WASM 直接写入
 
 use js_sys::Uint8Array;
@@ -23,6 +27,10 @@ pub struct MpcHeader {
     pub left: i32,
     /// 所有帧解码后的总字节数
     pub total_pixel_bytes: u32,
+    /// 偏移表或帧头无效、按 1×1 空帧处理的帧数
+    pub rejected_frames: u32,
+    /// SHD 阴影文件（无调色板，color_count 恒为 0）
+    pub is_shadow: bool,
 }
 
 /// 解析 MPC 头信息（包括计算总像素大小）
@@ -34,7 +42,8 @@ pub fn parse_mpc_header(data: &[u8]) -> Option<MpcHeader> {
 
     // Check header
     let header_str = std::str::from_utf8(&data[0..12]).ok()?;
-    if !header_str.starts_with("MPC File Ver") && !header_str.starts_with("SHD File Ver") {
+    let is_shadow = header_str.starts_with("SHD File Ver");
+    if !header_str.starts_with("MPC File Ver") && !is_shadow {
         return None;
     }
 
@@ -45,7 +54,12 @@ pub fn parse_mpc_header(data: &[u8]) -> Option<MpcHeader> {
     let global_height = get_u32_le(data, offset + 8);
     let frame_count = get_u32_le(data, offset + 12);
     let direction = get_u32_le(data, offset + 16);
-    let color_count = get_u32_le(data, offset + 20);
+    // SHD 没有调色板，帧偏移表从 128 开始
+    let color_count = if is_shadow {
+        0
+    } else {
+        get_u32_le(data, offset + 20)
+    };
     let interval = get_u32_le(data, offset + 24);
     let mut bottom = get_i32_le(data, offset + 28);
 
@@ -57,30 +71,16 @@ pub fn parse_mpc_header(data: &[u8]) -> Option<MpcHeader> {
     }
 
     // Calculate total pixel bytes
-    let palette_start = 128usize;
-    let offsets_start = palette_start + (color_count as usize) * 4;
-    let frame_data_start = offsets_start + (frame_count as usize) * 4;
-
+    let frames = frame_table(data, color_count as usize, frame_count as usize);
     let mut total_pixel_bytes = 0u32;
-    for i in 0..frame_count as usize {
-        let off = offsets_start + i * 4;
-        if off + 4 > data.len() {
-            break;
-        }
-        let data_offset = get_u32_le(data, off) as usize;
-        let ds = frame_data_start + data_offset;
-        if ds + 12 > data.len() {
-            total_pixel_bytes += 4; // 1x1 invalid frame
-            continue;
-        }
-
-        let width = get_u32_le(data, ds + 4);
-        let height = get_u32_le(data, ds + 8);
-
-        if width == 0 || height == 0 || width > 2048 || height > 2048 {
-            total_pixel_bytes += 4;
-        } else {
-            total_pixel_bytes += width * height * 4;
+    let mut rejected_frames = 0u32;
+    for frame in &frames {
+        match frame {
+            Some(f) => total_pixel_bytes += (f.width * f.height * 4) as u32,
+            None => {
+                total_pixel_bytes += 4; // 1x1 invalid frame
+                rejected_frames += 1;
+            }
         }
     }
 
@@ -95,9 +95,75 @@ pub fn parse_mpc_header(data: &[u8]) -> Option<MpcHeader> {
         bottom,
         left,
         total_pixel_bytes,
+        rejected_frames,
+        is_shadow,
     })
 }
 
+/// 偏移表中一帧的位置与尺寸（已通过 frame_table 的校验）
+struct FrameEntry {
+    /// 帧数据起始位置（dataLen 字段处）
+    ds: usize,
+    data_len: usize,
+    width: usize,
+    height: usize,
+}
+
+/// 读取并校验帧偏移表，每个可读的偏移项对应一个元素，None 表示无效帧（按 1×1 空帧处理）
+///
+/// 有效帧需满足：帧头完整、ds + dataLen 不超出文件（加法溢出同样判为无效）、
+/// 偏移不小于前一个有效帧的偏移、宽高在 1..=2048 之间。损坏的偏移可能指向
+/// 其它帧的中间，读出看似合理的宽高，单靠尺寸检查拦不住
+fn frame_table(data: &[u8], color_count: usize, frame_count: usize) -> Vec<Option<FrameEntry>> {
+    let offsets_start = PALETTE_START + color_count * 4;
+    let frame_data_start = offsets_start + frame_count * 4;
+
+    let mut frames = Vec::with_capacity(frame_count);
+    let mut last_offset = 0usize;
+    for i in 0..frame_count {
+        let off = offsets_start + i * 4;
+        if off + 4 > data.len() {
+            break;
+        }
+        let data_offset = get_u32_le(data, off) as usize;
+        // wasm32 上 usize 为 32 位，偏移和长度相加可能回绕
+        let ds = match frame_data_start.checked_add(data_offset) {
+            Some(ds)
+                if data_offset >= last_offset
+                    && ds.checked_add(12).is_some_and(|end| end <= data.len()) =>
+            {
+                ds
+            }
+            _ => {
+                frames.push(None);
+                continue;
+            }
+        };
+
+        let data_len = get_u32_le(data, ds) as usize;
+        let width = get_u32_le(data, ds + 4) as usize;
+        let height = get_u32_le(data, ds + 8) as usize;
+        if ds.checked_add(data_len).is_none_or(|end| end > data.len())
+            || width == 0
+            || height == 0
+            || width > 2048
+            || height > 2048
+        {
+            frames.push(None);
+            continue;
+        }
+
+        last_offset = data_offset;
+        frames.push(Some(FrameEntry {
+            ds,
+            data_len,
+            width,
+            height,
+        }));
+    }
+    frames
+}
+
 /// 解码 MPC 帧到预分配的 buffer
 ///
 /// 参数:
@@ -122,29 +188,9 @@ pub fn decode_mpc_frames(
     let color_count = header.color_count as usize;
     let frame_count = header.frame_count as usize;
 
-    // Read palette (BGRA -> RGBA)
-    let mut palette = [[0u8; 4]; 256];
-    let palette_start = 128usize;
-    for i in 0..color_count.min(256) {
-        let off = palette_start + i * 4;
-        if off + 4 > data.len() {
-            break;
-        }
-        palette[i] = [data[off + 2], data[off + 1], data[off], 255]; // BGR -> RGB
-    }
-
-    // Read frame data offsets
-    let offsets_start = palette_start + color_count * 4;
-    let mut data_offsets = Vec::with_capacity(frame_count);
-    for i in 0..frame_count {
-        let off = offsets_start + i * 4;
-        if off + 4 > data.len() {
-            break;
-        }
-        data_offsets.push(get_u32_le(data, off) as usize);
-    }
-
-    let frame_data_start = offsets_start + frame_count * 4;
+    let palette = read_palette(data, color_count);
+    let palette = (!header.is_shadow).then_some(&palette);
+    let frames = frame_table(data, color_count, frame_count);
 
     // Prepare output buffers
     let mut pixel_data = vec![0u8; header.total_pixel_bytes as usize];
@@ -154,31 +200,20 @@ pub fn decode_mpc_frames(
     let mut out_offset = 0usize;
 
     // Decode all frames
-    for i in 0..frame_count {
-        if i >= data_offsets.len() {
-            break;
-        }
-
-        let ds = frame_data_start + data_offsets[i];
-        if ds + 12 > data.len() {
-            frame_sizes[i * 2] = 1;
-            frame_sizes[i * 2 + 1] = 1;
-            frame_offsets[i] = out_offset as u32;
-            out_offset += 4;
-            continue;
-        }
-
-        let data_len = get_u32_le(data, ds) as usize;
-        let width = get_u32_le(data, ds + 4) as usize;
-        let height = get_u32_le(data, ds + 8) as usize;
-
-        if width == 0 || height == 0 || width > 2048 || height > 2048 {
+    for (i, frame) in frames.iter().enumerate() {
+        let Some(&FrameEntry {
+            ds,
+            data_len,
+            width,
+            height,
+        }) = frame.as_ref()
+        else {
             frame_sizes[i * 2] = 1;
             frame_sizes[i * 2 + 1] = 1;
             frame_offsets[i] = out_offset as u32;
             out_offset += 4;
             continue;
-        }
+        };
 
         frame_sizes[i * 2] = width as u32;
         frame_sizes[i * 2 + 1] = height as u32;
@@ -190,7 +225,7 @@ pub fn decode_mpc_frames(
 
         decode_rle_frame(
             data,
-            &palette,
+            palette,
             rle_start,
             rle_end,
             width,
@@ -214,11 +249,82 @@ pub fn decode_mpc_frames(
     frame_count as u32
 }
 
-/// RLE 解压缩单帧
+/// 解码单帧到预分配的 buffer（地图瓦片按需解码，无需解出全部帧）
+///
+/// 向 `output` 开头写入 `width * height * 4` 字节 RGBA，
+/// 返回 `(width << 16) | height`。
+/// 数据无效、索引越界、帧偏移或尺寸无效（见 frame_table）或 `output` 太小时返回 0。
+#[wasm_bindgen]
+pub fn decode_mpc_single_frame(data: &[u8], frame_index: u32, output: &Uint8Array) -> u32 {
+    let (width, height, pixels) = match decode_single_frame(data, frame_index as usize) {
+        Some(v) => v,
+        None => return 0,
+    };
+    if (output.length() as usize) < pixels.len() {
+        return 0;
+    }
+    output.subarray(0, pixels.len() as u32).copy_from(&pixels);
+    ((width as u32) << 16) | height as u32
+}
+
+/// 通过帧偏移表定位并解码单帧，返回 (width, height, RGBA)
+pub(crate) fn decode_single_frame(
+    data: &[u8],
+    frame_index: usize,
+) -> Option<(usize, usize, Vec<u8>)> {
+    let header = parse_mpc_header(data)?;
+    let color_count = header.color_count as usize;
+    let frame_count = header.frame_count as usize;
+    if frame_index >= frame_count {
+        return None;
+    }
+
+    let frames = frame_table(data, color_count, frame_count);
+    let &FrameEntry {
+        ds,
+        data_len,
+        width,
+        height,
+    } = frames.get(frame_index)?.as_ref()?;
+
+    let palette = read_palette(data, color_count);
+    let mut pixels = vec![0u8; width * height * 4];
+    decode_rle_frame(
+        data,
+        (!header.is_shadow).then_some(&palette),
+        ds + 20,
+        ds + data_len,
+        width,
+        height,
+        &mut pixels,
+    );
+    Some((width, height, pixels))
+}
+
+/// 调色板起始偏移（Header 64 + HeadData 64）
+const PALETTE_START: usize = 128;
+
+/// 读取调色板 (BGRA -> RGBA，alpha 固定 255)
+fn read_palette(data: &[u8], color_count: usize) -> [[u8; 4]; 256] {
+    let mut palette = [[0u8; 4]; 256];
+    for (i, entry) in palette.iter_mut().enumerate().take(color_count.min(256)) {
+        let off = PALETTE_START + i * 4;
+        if off + 4 > data.len() {
+            break;
+        }
+        *entry = [data[off + 2], data[off + 1], data[off], 255]; // BGR -> RGB
+    }
+    palette
+}
+
+/// SHD 阴影像素颜色（Shd.cs: Color.Black * 0.6f）
+const SHADOW_COLOR: [u8; 4] = [0, 0, 0, 153];
+
+/// RLE 解压缩单帧，palette 为 None 时按 SHD 解码（颜色段不带索引，全部为阴影色）
 #[inline]
 fn decode_rle_frame(
     data: &[u8],
-    palette: &[[u8; 4]; 256],
+    palette: Option<&[[u8; 4]; 256]>,
     mut data_offset: usize,
     data_end: usize,
     width: usize,
@@ -244,7 +350,7 @@ fn decode_rle_frame(
                 pixels[idx + 3] = 0;
                 pixel_idx += 1;
             }
-        } else {
+        } else if let Some(palette) = palette {
             // Colored pixels
             let color_count = byte as usize;
             for _ in 0..color_count {
@@ -263,6 +369,14 @@ fn decode_rle_frame(
                 }
                 pixel_idx += 1;
             }
+        } else {
+            // Shadow pixels：只有计数字节
+            let end = (pixel_idx + byte as usize).min(max_pixels);
+            while pixel_idx < end {
+                let idx = pixel_idx * 4;
+                pixels[idx..idx + 4].copy_from_slice(&SHADOW_COLOR);
+                pixel_idx += 1;
+            }
         }
     }
 
@@ -322,4 +436,131 @@ mod tests {
         let result = parse_mpc_header(data);
         assert!(result.is_none());
     }
+
+    /// 构造最小 MPC：2 色调色板，每帧 (width, height, RLE)
+    fn build_mpc(frames: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut data = vec![0u8; 128];
+        data[..15].copy_from_slice(b"MPC File Ver2.0");
+        let frame_count = frames.len() as u32;
+        data[64 + 12..64 + 16].copy_from_slice(&frame_count.to_le_bytes());
+        data[64 + 20..64 + 24].copy_from_slice(&2u32.to_le_bytes());
+        // 调色板 (BGRA)：0 = 红，1 = 蓝
+        data.extend_from_slice(&[0, 0, 255, 0, 255, 0, 0, 0]);
+
+        let mut frame_blob = Vec::new();
+        let mut offsets = Vec::new();
+        for (w, h, rle) in frames {
+            offsets.push(frame_blob.len() as u32);
+            frame_blob.extend_from_slice(&(20 + rle.len() as u32).to_le_bytes());
+            frame_blob.extend_from_slice(&w.to_le_bytes());
+            frame_blob.extend_from_slice(&h.to_le_bytes());
+            frame_blob.extend_from_slice(&[0u8; 8]);
+            frame_blob.extend_from_slice(rle);
+        }
+        for off in offsets {
+            data.extend_from_slice(&off.to_le_bytes());
+        }
+        data.extend_from_slice(&frame_blob);
+        // 补足 parse_mpc_header 要求的最小长度
+        if data.len() < 160 {
+            data.resize(160, 0);
+        }
+        data
+    }
+
+    #[test]
+    fn test_shadow_file() {
+        // SHD：与 build_mpc 相同的帧布局，但没有调色板；colors 字段即使非 0 也忽略
+        let mut data = build_mpc(&[
+            // 1 透明 + 2 阴影 + 1 透明
+            (4, 1, vec![0x81, 0x02, 0x81]),
+            (1, 2, vec![0x02]),
+        ]);
+        data.drain(128..136);
+        data[..15].copy_from_slice(b"SHD File Ver2.0");
+
+        let header = parse_mpc_header(&data).unwrap();
+        assert!(header.is_shadow);
+        assert_eq!(header.color_count, 0);
+        assert_eq!(header.rejected_frames, 0);
+        assert_eq!(header.total_pixel_bytes, 4 * 4 + 2 * 4);
+
+        let (w, h, pixels) = decode_single_frame(&data, 0).unwrap();
+        assert_eq!((w, h), (4, 1));
+        let mut expected = vec![0u8; 16];
+        expected[4..12].copy_from_slice(&[0, 0, 0, 153, 0, 0, 0, 153]);
+        assert_eq!(pixels, expected);
+
+        let (w, h, pixels) = decode_single_frame(&data, 1).unwrap();
+        assert_eq!((w, h), (1, 2));
+        assert_eq!(pixels, vec![0, 0, 0, 153, 0, 0, 0, 153]);
+
+        assert!(
+            !parse_mpc_header(&build_mpc(&[(1, 1, vec![0x81])]))
+                .unwrap()
+                .is_shadow
+        );
+    }
+
+    #[test]
+    fn test_decode_single_frame() {
+        let data = build_mpc(&[
+            (2, 1, vec![0x02, 0, 0]),
+            // 1 透明 + 1 蓝
+            (2, 1, vec![0x81, 0x01, 1]),
+        ]);
+        let (w, h, pixels) = decode_single_frame(&data, 1).unwrap();
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(pixels, vec![0, 0, 0, 0, 0, 0, 255, 255]);
+
+        let (_, _, first) = decode_single_frame(&data, 0).unwrap();
+        assert_eq!(first, vec![255, 0, 0, 255, 255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_decode_single_frame_invalid() {
+        let data = build_mpc(&[(0, 4, vec![]), (2, 1, vec![0x02, 0, 0])]);
+        // 宽度为 0 的帧
+        assert!(decode_single_frame(&data, 0).is_none());
+        // 索引越界
+        assert!(decode_single_frame(&data, 2).is_none());
+        assert!(decode_single_frame(&data, 1).is_some());
+    }
+
+    #[test]
+    fn test_corrupt_offset_table() {
+        let frames = [
+            (2, 1, vec![0x02, 0, 0]),
+            (2, 1, vec![0x81, 0x01, 1]),
+            (2, 1, vec![0x02, 1, 1]),
+        ];
+        // 偏移表位于 128 + 2 色 * 4，帧数据从 148 开始，每帧 23 字节
+        let offset_pos = |i: usize| 136 + i * 4;
+        let clean = build_mpc(&frames);
+        assert_eq!(parse_mpc_header(&clean).unwrap().rejected_frames, 0);
+
+        // 帧 2 的偏移回退到帧 0 中间：偏移递减，判为无效
+        let mut data = clean.clone();
+        data[offset_pos(2)..offset_pos(2) + 4].copy_from_slice(&5u32.to_le_bytes());
+        let header = parse_mpc_header(&data).unwrap();
+        assert_eq!(header.rejected_frames, 1);
+        assert_eq!(header.total_pixel_bytes, 8 + 8 + 4);
+        assert!(decode_single_frame(&data, 2).is_none());
+        assert!(decode_single_frame(&data, 1).is_some());
+
+        // 偏移加到 usize 上限附近：判为无效而不是回绕
+        let mut data = clean.clone();
+        data[offset_pos(1)..offset_pos(1) + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(parse_mpc_header(&data).unwrap().rejected_frames, 1);
+        assert!(decode_single_frame(&data, 1).is_none());
+
+        // 帧 1 的 dataLen 超出文件末尾：判为无效，后续帧不受影响
+        let mut data = clean;
+        let ds = 148 + 23;
+        data[ds..ds + 4].copy_from_slice(&1000u32.to_le_bytes());
+        assert_eq!(parse_mpc_header(&data).unwrap().rejected_frames, 1);
+        assert!(decode_single_frame(&data, 1).is_none());
+        let (_, _, last) = decode_single_frame(&data, 2).unwrap();
+        assert_eq!(last, vec![0, 0, 255, 255, 0, 0, 255, 255]);
+    }
 }
@@ -1,7 +1,8 @@
 //! MSF (Miu Sprite Format) v2 — WASM decoder
 //!
 //! v2 format: Indexed8 (1bpp) palette-based, zstd-compressed.
-//! No row filters — raw palette indices stored directly.
+//! Raw palette indices by default; flags bit 2 marks frames stored with a
+//! per-row left-delta filter (see `unfilter_row_delta`).
 //!
 //! Layout:
 //! ```text
@@ -10,22 +11,26 @@
 //! [PixelFormat u8] [PaletteSize u16] [Reserved u8]       = 4 bytes
 //! [Palette: RGBA × paletteSize]                          = paletteSize * 4
 //! [Frame Table: frameCount × 16]                         = frameCount * 16
-//! [Extension Chunks...]
+//! [Extension Chunks: e.g. "IVAL" interval ms, "TAGS"]   = 8 + len each
 //! [Sentinel "END\0" (4) + 0u32 (4)]                     = 8 bytes
 //! [Zstd-compressed Frame Data Blob]                      = variable
 //! ```
 //!
 //! Frame data (decompressed) = raw palette indices, width×height bytes per frame.
 //! Palette entries with alpha=0 represent transparent pixels.
+//!
+//! Encoding is available too (`encode_msf`); zstd-compressed output requires
+//! the `zstd-encode` feature.
 
 use js_sys::Uint8Array;
+use std::sync::atomic::{AtomicU8, Ordering};
 use wasm_bindgen::prelude::*;
 
 // ============================================================================
 // Zstd decompression (pure Rust via ruzstd, works in WASM)
 // ============================================================================
 
-fn zstd_decompress(data: &[u8]) -> Option<Vec<u8>> {
+pub(crate) fn zstd_decompress(data: &[u8]) -> Option<Vec<u8>> {
     use ruzstd::StreamingDecoder;
     use std::io::Read;
     let mut decoder = StreamingDecoder::new(data).ok()?;
@@ -34,12 +39,48 @@ fn zstd_decompress(data: &[u8]) -> Option<Vec<u8>> {
     Some(buf)
 }
 
+// ============================================================================
+// Brotli decompression (pure Rust, works in WASM)
+// ============================================================================
+
+fn brotli_decompress(data: &[u8]) -> Option<Vec<u8>> {
+    use std::io::Read;
+    let mut decoder = brotli_decompressor::Decompressor::new(data, 4096);
+    let mut buf = Vec::new();
+    decoder.read_to_end(&mut buf).ok()?;
+    Some(buf)
+}
+
+/// Zstd compression (pure Rust, only with the `zstd-encode` feature)
+#[cfg(feature = "zstd-encode")]
+fn zstd_compress(data: &[u8]) -> Vec<u8> {
+    use ruzstd_encoder::encoding::{compress_to_vec, CompressionLevel};
+    compress_to_vec(data, CompressionLevel::Fastest)
+}
+
 // ============================================================================
 // Constants
 // ============================================================================
 
 const MSF_MAGIC: &[u8; 4] = b"MSF2";
+const MSF_VERSION: u16 = 2;
+
+/// Flags bit 0: frame data blob is zstd-compressed
+const FLAG_ZSTD: u16 = 1;
+/// Flags bit 1: frame data blob is brotli-compressed (decode only)
+const FLAG_BROTLI: u16 = 1 << 1;
+/// Flags bit 2: each frame row is left-delta filtered (per byte, stride = bpp)
+const FLAG_ROW_DELTA: u16 = 1 << 2;
+/// Flags bit 3: a `CRC3` chunk holds the CRC-32 of the stored blob
+const FLAG_CRC: u16 = 1 << 3;
 const CHUNK_END: &[u8; 4] = b"END\0";
+/// Extension chunk: exact source frame interval in ms (u16), since fps is rounded
+const CHUNK_IVAL: &[u8; 4] = b"IVAL";
+/// Extension chunk: CRC-32 (IEEE) of the blob exactly as stored, u32
+const CHUNK_CRC3: &[u8; 4] = b"CRC3";
+/// Extension chunk: named animation sub-ranges, per entry
+/// `name_len u8, name (UTF-8), start_frame u16, end_frame u16, loop u8`
+const CHUNK_TAGS: &[u8; 4] = b"TAGS";
 
 /// Pixel format enum
 #[repr(u8)]
@@ -69,6 +110,59 @@ impl PixelFormat {
     }
 }
 
+// ============================================================================
+// Decode errors
+// ============================================================================
+
+/// Why the last decode call returned 0 (see `last_msf_error`)
+#[wasm_bindgen]
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MsfError {
+    /// Last call succeeded (a 0 return then means a valid sheet with no frames)
+    None = 0,
+    /// Missing "MSF2" magic
+    BadMagic = 1,
+    /// Header, palette or frame table extends past the end of the data
+    TruncatedTable = 2,
+    /// No END sentinel after the extension chunks
+    MissingEnd = 3,
+    /// Frame blob failed to decompress (or both compression flags are set)
+    Decompression = 4,
+    /// A caller-provided output buffer is smaller than the decoded data
+    OutputTooSmall = 5,
+    /// Header pixel format byte is not Rgba8, Indexed8 or Indexed8Alpha8
+    UnknownPixelFormat = 6,
+    /// `decode_msf_direction` was given a direction past the last frame
+    DirectionOutOfRange = 7,
+}
+
+/// Error of the most recent `decode_msf_*` call; a plain atomic since WASM is single-threaded
+static LAST_MSF_ERROR: AtomicU8 = AtomicU8::new(MsfError::None as u8);
+
+fn set_last_msf_error(error: MsfError) {
+    LAST_MSF_ERROR.store(error as u8, Ordering::Relaxed);
+}
+
+/// Error code of the most recent `decode_msf_frames*`, `decode_msf_direction`
+/// or `decode_msf_individual_frames` call
+///
+/// Those return 0 both for an empty sheet and on failure; check this after a
+/// 0 return to tell them apart (`MsfError.None` means the sheet was empty).
+#[wasm_bindgen]
+pub fn last_msf_error() -> MsfError {
+    match LAST_MSF_ERROR.load(Ordering::Relaxed) {
+        1 => MsfError::BadMagic,
+        2 => MsfError::TruncatedTable,
+        3 => MsfError::MissingEnd,
+        4 => MsfError::Decompression,
+        5 => MsfError::OutputTooSmall,
+        6 => MsfError::UnknownPixelFormat,
+        7 => MsfError::DirectionOutOfRange,
+        _ => MsfError::None,
+    }
+}
+
 // ============================================================================
 // MSF Header (returned to JS)
 // ============================================================================
@@ -88,6 +182,22 @@ pub struct MsfHeader {
     pub frames_per_direction: u16,
     /// Total RGBA bytes for all frames when decoded individually
     pub total_individual_pixel_bytes: u32,
+    /// Every frame is zero-sized: nothing to draw, decoding can be skipped
+    pub is_empty: bool,
+    /// Exact frame interval in ms from the `IVAL` chunk (0 if absent)
+    pub interval_ms: u16,
+}
+
+/// Named animation sub-range from the `TAGS` chunk (e.g. idle / walk / attack)
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MsfTag {
+    pub name: String,
+    /// First frame of the range, counted within one direction
+    pub start_frame: u16,
+    /// Last frame of the range (inclusive)
+    pub end_frame: u16,
+    pub looping: bool,
 }
 
 // ============================================================================
@@ -144,17 +254,27 @@ pub fn parse_msf_header(data: &[u8]) -> Option<MsfHeader> {
     let frame_table_start = palette_start + palette_size as usize * 4;
     let fc = frame_count as usize;
     let mut total_individual_pixel_bytes = 0u32;
+    // A truncated frame table is never reported as empty
+    let mut is_empty = false;
+    let mut interval_ms = 0u16;
     if frame_table_start + fc * FRAME_ENTRY_SIZE <= data.len() {
+        is_empty = true;
         for i in 0..fc {
             let ft_off = frame_table_start + i * FRAME_ENTRY_SIZE;
             let w = u16::from_le_bytes([data[ft_off + 4], data[ft_off + 5]]) as u32;
             let h = u16::from_le_bytes([data[ft_off + 6], data[ft_off + 7]]) as u32;
             if w > 0 && h > 0 {
                 total_individual_pixel_bytes += w * h * 4;
+                is_empty = false;
             } else {
                 total_individual_pixel_bytes += 4;
             }
         }
+
+        let ext_start = frame_table_start + fc * FRAME_ENTRY_SIZE;
+        if let Some(&[lo, hi, ..]) = find_extension_chunk(data, ext_start, CHUNK_IVAL) {
+            interval_ms = u16::from_le_bytes([lo, hi]);
+        }
     }
 
     Some(MsfHeader {
@@ -169,56 +289,274 @@ pub fn parse_msf_header(data: &[u8]) -> Option<MsfHeader> {
         palette_size,
         frames_per_direction,
         total_individual_pixel_bytes,
+        is_empty,
+        interval_ms,
     })
 }
 
-/// Internal: parse full MSF structure
-fn parse_msf_structure(
-    data: &[u8],
-) -> Option<(
-    u16,            // canvas_width
-    u16,            // canvas_height
-    usize,          // frame_count
-    u8,             // pixel_format_byte
-    u16,            // palette_size
-    [[u8; 4]; 256], // palette
-    Vec<MsfFrameEntry>,
-    usize, // blob_start
-    u16,   // flags
-)> {
+/// Payload of the first extension chunk with the given id, scanning from
+/// `ext_start` (end of the frame table) up to END
+///
+/// Returns None if the chunk is absent or the chunk list is truncated.
+fn find_extension_chunk<'a>(data: &'a [u8], ext_start: usize, id: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut ext_off = ext_start;
+    while ext_off.saturating_add(8) <= data.len() {
+        let chunk_id = &data[ext_off..ext_off + 4];
+        let chunk_len = u32::from_le_bytes([
+            data[ext_off + 4],
+            data[ext_off + 5],
+            data[ext_off + 6],
+            data[ext_off + 7],
+        ]) as usize;
+        ext_off += 8;
+        if chunk_id == CHUNK_END {
+            return None;
+        }
+        if chunk_id == id {
+            return data.get(ext_off..ext_off.checked_add(chunk_len)?);
+        }
+        ext_off = ext_off.saturating_add(chunk_len);
+    }
+    None
+}
+
+/// CRC-32 (IEEE 802.3, reflected, as zlib / PNG) lookup table
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |c, &b| {
+        CRC32_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8)
+    })
+}
+
+/// Check the blob against the `CRC3` chunk, e.g. after a download
+///
+/// Returns true when the CRC matches, or when the file carries no CRC
+/// (flags bit 3 clear). Returns false for a mismatch, a missing or malformed
+/// `CRC3` chunk while bit 3 is set, or an unparseable file.
+#[wasm_bindgen]
+pub fn verify_msf_crc(data: &[u8]) -> bool {
+    let Some(msf) = parse_msf_structure(data) else {
+        return false;
+    };
+    if msf.flags & FLAG_CRC == 0 {
+        return true;
+    }
+    match find_extension_chunk(data, extension_start(data, &msf), CHUNK_CRC3) {
+        Some(&[a, b, c, d]) => u32::from_le_bytes([a, b, c, d]) == crc32(&data[msf.blob_start..]),
+        _ => false,
+    }
+}
+
+/// Read the animation sub-ranges from the `TAGS` extension chunk
+///
+/// Returns an empty list for files without tags or that fail to parse;
+/// a truncated entry ends the list.
+#[wasm_bindgen]
+pub fn get_msf_tags(data: &[u8]) -> Vec<MsfTag> {
+    let Some(msf) = parse_msf_structure(data) else {
+        return Vec::new();
+    };
+    let Some(mut chunk) = find_extension_chunk(data, extension_start(data, &msf), CHUNK_TAGS)
+    else {
+        return Vec::new();
+    };
+    let mut tags = Vec::new();
+    while let Some((&name_len, rest)) = chunk.split_first() {
+        let name_len = name_len as usize;
+        let Some(entry) = rest.get(..name_len + 5) else {
+            break;
+        };
+        let fields = &entry[name_len..];
+        tags.push(MsfTag {
+            name: String::from_utf8_lossy(&entry[..name_len]).into_owned(),
+            start_frame: u16::from_le_bytes([fields[0], fields[1]]),
+            end_frame: u16::from_le_bytes([fields[2], fields[3]]),
+            looping: fields[4] != 0,
+        });
+        chunk = &rest[name_len + 5..];
+    }
+    tags
+}
+
+/// Offset of the first extension chunk (end of the frame table)
+fn extension_start(data: &[u8], msf: &MsfStructure) -> usize {
+    let palette_size = u16::from_le_bytes([data[25], data[26]]) as usize;
+    28 + palette_size * 4 + msf.entries.len() * FRAME_ENTRY_SIZE
+}
+
+/// Extract the raw RGBA palette (`palette_size * 4` bytes, e.g. for palette swaps)
+///
+/// Returns None for files without a palette (Rgba8) or truncated data.
+#[wasm_bindgen]
+pub fn get_msf_palette(data: &[u8]) -> Option<Vec<u8>> {
     if data.len() < 28 || &data[0..4] != MSF_MAGIC {
         return None;
     }
+    let palette_size = u16::from_le_bytes([data[25], data[26]]) as usize;
+    let palette_start = 28;
+    let palette_end = palette_start + palette_size * 4;
+    if palette_size == 0 || palette_end > data.len() {
+        return None;
+    }
+    Some(data[palette_start..palette_end].to_vec())
+}
+
+/// Check an MSF file's structure, returning a description of the first problem
+///
+/// Verifies magic, version, pixel format, that palette and frame table fit,
+/// that the END sentinel exists, and that every frame lies within the
+/// decompressed blob.
+pub fn validate_msf(data: &[u8]) -> Result<(), String> {
+    parse_validated_msf(data, &mut Vec::new()).map(|_| ())
+}
+
+/// `validate_msf`, handing back the parsed structure and frame blob so a
+/// caller that goes on to decode doesn't parse or decompress twice
+fn parse_validated_msf<'a>(
+    data: &'a [u8],
+    buf: &'a mut Vec<u8>,
+) -> Result<(MsfStructure, &'a [u8]), String> {
+    if data.len() < 28 {
+        return Err(format!(
+            "file too short: {} bytes, need at least 28",
+            data.len()
+        ));
+    }
+    let msf = try_parse_msf_structure(data).map_err(|e| match e {
+        MsfError::BadMagic => format!("bad magic {:?}, expected \"MSF2\"", &data[0..4]),
+        MsfError::TruncatedTable => format!(
+            "frame table ({} frames, palette {}) runs past the end of the {}-byte file",
+            u16::from_le_bytes([data[12], data[13]]),
+            u16::from_le_bytes([data[25], data[26]]),
+            data.len()
+        ),
+        MsfError::MissingEnd => "missing END sentinel after the extension chunks".to_string(),
+        other => format!("failed to parse MSF structure ({:?})", other),
+    })?;
+    let version = u16::from_le_bytes([data[4], data[5]]);
+    if version != MSF_VERSION {
+        return Err(format!(
+            "unsupported version {}, expected {}",
+            version, MSF_VERSION
+        ));
+    }
+    let blob = validate_structure(data, &msf, buf)?;
+    Ok((msf, blob))
+}
+
+/// Check what the structure parser leaves to the decoders: a known pixel
+/// format, a blob that decompresses, and every frame inside it. Returns the blob.
+fn validate_structure<'a>(
+    data: &'a [u8],
+    msf: &MsfStructure,
+    buf: &'a mut Vec<u8>,
+) -> Result<&'a [u8], String> {
+    if PixelFormat::from_u8(msf.pixel_format).is_none() {
+        return Err(format!("unknown pixel format {}", msf.pixel_format));
+    }
+    let blob = get_blob(data, msf, buf)
+        .ok_or_else(|| format!("failed to decompress frame data (flags {:#06x})", msf.flags))?;
+
+    for (i, entry) in msf.entries.iter().enumerate() {
+        let end = (entry.data_offset as usize).saturating_add(entry.data_length as usize);
+        if end > blob.len() {
+            return Err(format!(
+                "frame {} data {}..{} exceeds blob size {}",
+                i,
+                entry.data_offset,
+                end,
+                blob.len()
+            ));
+        }
+    }
+
+    Ok(blob)
+}
+
+/// Validate an MSF file for JS: returns an error description, or "" if valid
+#[wasm_bindgen(js_name = validate_msf)]
+pub fn validate_msf_js(data: &[u8]) -> String {
+    validate_msf(data).err().unwrap_or_default()
+}
+
+/// Parsed MSF layout: everything before the frame data blob
+struct MsfStructure {
+    canvas_width: u16,
+    canvas_height: u16,
+    frame_count: usize,
+    directions: u8,
+    anchor_x: i16,
+    pixel_format: u8,
+    palette: [[u8; 4]; 256],
+    entries: Vec<MsfFrameEntry>,
+    blob_start: usize,
+    flags: u16,
+}
+
+/// Internal: parse full MSF structure
+fn parse_msf_structure(data: &[u8]) -> Option<MsfStructure> {
+    try_parse_msf_structure(data).ok()
+}
+
+/// Parse full MSF structure, reporting which part is malformed
+fn try_parse_msf_structure(data: &[u8]) -> Result<MsfStructure, MsfError> {
+    if data.len() < 4 || &data[0..4] != MSF_MAGIC {
+        return Err(MsfError::BadMagic);
+    }
+    if data.len() < 28 {
+        return Err(MsfError::TruncatedTable);
+    }
 
     let flags = u16::from_le_bytes([data[6], data[7]]);
     let off = 8;
     let canvas_width = u16::from_le_bytes([data[off], data[off + 1]]);
     let canvas_height = u16::from_le_bytes([data[off + 2], data[off + 3]]);
     let frame_count = u16::from_le_bytes([data[off + 4], data[off + 5]]) as usize;
+    let directions = data[off + 6];
+    let anchor_x = i16::from_le_bytes([data[off + 8], data[off + 9]]);
 
     let pf_off = 24;
-    let pixel_format_byte = data[pf_off];
+    let pixel_format = data[pf_off];
     let palette_size = u16::from_le_bytes([data[pf_off + 1], data[pf_off + 2]]) as usize;
 
     let mut palette = [[0u8; 4]; 256];
     let palette_start = 28;
-    for i in 0..palette_size.min(256) {
+    for (i, entry) in palette.iter_mut().enumerate().take(palette_size.min(256)) {
         let po = palette_start + i * 4;
         if po + 4 > data.len() {
             break;
         }
-        palette[i] = [data[po], data[po + 1], data[po + 2], data[po + 3]];
+        *entry = [data[po], data[po + 1], data[po + 2], data[po + 3]];
     }
 
     let frame_table_start = palette_start + palette_size * 4;
     if frame_table_start + frame_count * FRAME_ENTRY_SIZE > data.len() {
-        return None;
+        return Err(MsfError::TruncatedTable);
     }
 
-    let mut frame_entries = Vec::with_capacity(frame_count);
+    let mut entries = Vec::with_capacity(frame_count);
     let mut ft_off = frame_table_start;
     for _ in 0..frame_count {
-        frame_entries.push(MsfFrameEntry {
+        entries.push(MsfFrameEntry {
             offset_x: i16::from_le_bytes([data[ft_off], data[ft_off + 1]]),
             offset_y: i16::from_le_bytes([data[ft_off + 2], data[ft_off + 3]]),
             width: u16::from_le_bytes([data[ft_off + 4], data[ft_off + 5]]),
@@ -242,8 +580,8 @@ fn parse_msf_structure(
     // Skip extension chunks until END sentinel
     let mut ext_off = ft_off;
     loop {
-        if ext_off + 8 > data.len() {
-            return None;
+        if ext_off.saturating_add(8) > data.len() {
+            return Err(MsfError::MissingEnd);
         }
         let chunk_id = &data[ext_off..ext_off + 4];
         let chunk_len = u32::from_le_bytes([
@@ -256,34 +594,100 @@ fn parse_msf_structure(
         if chunk_id == CHUNK_END {
             break;
         }
-        ext_off += chunk_len;
+        ext_off = ext_off.saturating_add(chunk_len);
     }
 
-    Some((
+    Ok(MsfStructure {
         canvas_width,
         canvas_height,
         frame_count,
-        pixel_format_byte,
-        palette_size as u16,
+        directions,
+        anchor_x,
+        pixel_format,
         palette,
-        frame_entries,
-        ext_off,
+        entries,
+        blob_start: ext_off,
         flags,
-    ))
+    })
 }
 
-/// Get decompressed blob from MSF data
-fn get_blob<'a>(
-    data: &'a [u8],
-    blob_start: usize,
-    flags: u16,
-    buf: &'a mut Vec<u8>,
-) -> Option<&'a [u8]> {
-    if (flags & 1) != 0 {
-        *buf = zstd_decompress(&data[blob_start..])?;
-        Some(buf.as_slice())
-    } else {
-        Some(&data[blob_start..])
+/// Per-frame layout as flattened `[offset_x, offset_y, width, height, ...]`
+///
+/// Offsets are relative to the canvas origin; use with the header anchor to
+/// position tight-cropped frames.
+#[wasm_bindgen]
+pub fn get_msf_frame_table(data: &[u8]) -> Option<Vec<i32>> {
+    let msf = parse_msf_structure(data)?;
+    let mut table = Vec::with_capacity(msf.entries.len() * 4);
+    for entry in &msf.entries {
+        table.push(entry.offset_x as i32);
+        table.push(entry.offset_y as i32);
+        table.push(entry.width as i32);
+        table.push(entry.height as i32);
+    }
+    Some(table)
+}
+
+/// Length of the frame data blob after decompression, for sizing scratch
+/// buffers in the `MsfReader` flow
+///
+/// Uncompressed files report the stored blob length without copying it;
+/// compressed ones are decompressed to measure. Returns 0 if the data is
+/// invalid or fails to decompress.
+#[wasm_bindgen]
+pub fn msf_blob_decompressed_size(data: &[u8]) -> u32 {
+    let Some(msf) = parse_msf_structure(data) else {
+        return 0;
+    };
+    if msf.flags & (FLAG_ZSTD | FLAG_BROTLI) == 0 {
+        return (data.len() - msf.blob_start) as u32;
+    }
+    let mut buf = Vec::new();
+    get_blob(data, &msf, &mut buf).map_or(0, |blob| blob.len() as u32)
+}
+
+/// Get decompressed blob from MSF data (zstd or brotli, chosen by flags),
+/// with row filtering undone when flags bit 2 is set
+fn get_blob<'a>(data: &'a [u8], msf: &MsfStructure, buf: &'a mut Vec<u8>) -> Option<&'a [u8]> {
+    let raw = &data[msf.blob_start..];
+    match (msf.flags & FLAG_ZSTD != 0, msf.flags & FLAG_BROTLI != 0) {
+        (true, true) => return None,
+        (true, false) => *buf = zstd_decompress(raw)?,
+        (false, true) => *buf = brotli_decompress(raw)?,
+        (false, false) if msf.flags & FLAG_ROW_DELTA != 0 => *buf = raw.to_vec(),
+        (false, false) => return Some(raw),
+    }
+    if msf.flags & FLAG_ROW_DELTA != 0 {
+        let bpp = PixelFormat::from_u8(msf.pixel_format)?.bytes_per_pixel();
+        unfilter_row_delta(buf, &msf.entries, bpp);
+    }
+    Some(buf.as_slice())
+}
+
+/// Undo the left-delta row filter in place: `row[i] += row[i - bpp]`
+///
+/// Frames sharing a payload (same `data_offset`) are reconstructed once.
+/// Payloads that don't fit the blob (or whose size overflows `usize`) are
+/// left alone; the per-frame bounds checks in the decoders reject them later.
+fn unfilter_row_delta(blob: &mut [u8], entries: &[MsfFrameEntry], bpp: usize) {
+    let mut done = std::collections::HashSet::new();
+    for entry in entries {
+        let start = entry.data_offset as usize;
+        let row_bytes = entry.width as usize * bpp;
+        let Some(end) = row_bytes
+            .checked_mul(entry.height as usize)
+            .and_then(|len| start.checked_add(len))
+        else {
+            continue;
+        };
+        if end == start || end > blob.len() || !done.insert(start) {
+            continue;
+        }
+        for row in blob[start..end].chunks_exact_mut(row_bytes) {
+            for i in bpp..row_bytes {
+                row[i] = row[i].wrapping_add(row[i - bpp]);
+            }
+        }
     }
 }
 
@@ -304,216 +708,824 @@ fn lookup_indexed8(palette: &[[u8; 4]; 256], idx: u8, dst: &mut [u8]) {
 // Decoding — exported to WASM
 // ============================================================================
 
-/// Decode all frames into canvas-sized RGBA (for ASF sprites)
-#[wasm_bindgen]
-pub fn decode_msf_frames(data: &[u8], output: &Uint8Array) -> u32 {
-    let (canvas_width, canvas_height, frame_count, pf_byte, _, palette, entries, blob_start, flags) =
-        match parse_msf_structure(data) {
-            Some(v) => v,
-            None => return 0,
-        };
-
-    let pixel_format = match PixelFormat::from_u8(pf_byte) {
-        Some(pf) => pf,
-        None => return 0,
-    };
-    let mut decomp_buf = Vec::new();
-    let blob = match get_blob(data, blob_start, flags, &mut decomp_buf) {
-        Some(b) => b,
-        None => return 0,
-    };
-
-    let cw = canvas_width as usize;
-    let ch = canvas_height as usize;
-    let frame_size = cw * ch * 4;
-    let mut all_pixels = vec![0u8; frame_size * frame_count];
-
-    for (i, entry) in entries.iter().enumerate() {
-        if entry.width == 0 || entry.height == 0 {
-            continue;
-        }
+/// Composite one frame entry into a canvas-sized RGBA buffer (`dst` = one frame)
+///
+/// Pixels outside the frame's rectangle are left untouched.
+fn composite_frame(
+    pixel_format: PixelFormat,
+    palette: &[[u8; 4]; 256],
+    entry: &MsfFrameEntry,
+    blob: &[u8],
+    cw: usize,
+    dst: &mut [u8],
+) {
+    if entry.width == 0 || entry.height == 0 {
+        return;
+    }
 
-        let fw = entry.width as usize;
-        let fh = entry.height as usize;
-        let ox = entry.offset_x as usize;
-        let oy = entry.offset_y as usize;
-        let blob_off = entry.data_offset as usize;
-        let blob_len = entry.data_length as usize;
+    let fw = entry.width as usize;
+    let fh = entry.height as usize;
+    let ox = entry.offset_x as usize;
+    let oy = entry.offset_y as usize;
+    let blob_off = entry.data_offset as usize;
+    let blob_len = entry.data_length as usize;
 
-        if blob_off + blob_len > blob.len() {
-            continue;
-        }
+    if blob_off + blob_len > blob.len() {
+        return;
+    }
 
-        let raw = &blob[blob_off..blob_off + blob_len];
-        let frame_start = i * frame_size;
+    let raw = &blob[blob_off..blob_off + blob_len];
 
-        match pixel_format {
-            PixelFormat::Indexed8 => {
-                for y in 0..fh {
-                    for x in 0..fw {
-                        let src = y * fw + x;
-                        if src >= raw.len() {
-                            continue;
-                        }
-                        let dst = frame_start + ((oy + y) * cw + ox + x) * 4;
-                        if dst + 4 <= all_pixels.len() {
-                            lookup_indexed8(&palette, raw[src], &mut all_pixels[dst..dst + 4]);
-                        }
+    match pixel_format {
+        PixelFormat::Indexed8 => {
+            for y in 0..fh {
+                for x in 0..fw {
+                    let src = y * fw + x;
+                    if src >= raw.len() {
+                        continue;
+                    }
+                    let d = ((oy + y) * cw + ox + x) * 4;
+                    if d + 4 <= dst.len() {
+                        lookup_indexed8(palette, raw[src], &mut dst[d..d + 4]);
                     }
                 }
             }
-            PixelFormat::Indexed8Alpha8 => {
-                for y in 0..fh {
-                    for x in 0..fw {
-                        let src = (y * fw + x) * 2;
-                        if src + 1 >= raw.len() {
-                            continue;
-                        }
-                        let alpha = raw[src + 1];
-                        if alpha == 0 {
-                            continue;
-                        }
-                        let dst = frame_start + ((oy + y) * cw + ox + x) * 4;
-                        if dst + 4 <= all_pixels.len() {
-                            let c = &palette[raw[src] as usize];
-                            all_pixels[dst] = c[0];
-                            all_pixels[dst + 1] = c[1];
-                            all_pixels[dst + 2] = c[2];
-                            all_pixels[dst + 3] = alpha;
-                        }
+        }
+        PixelFormat::Indexed8Alpha8 => {
+            for y in 0..fh {
+                for x in 0..fw {
+                    let src = (y * fw + x) * 2;
+                    if src + 1 >= raw.len() {
+                        continue;
+                    }
+                    let alpha = raw[src + 1];
+                    if alpha == 0 {
+                        continue;
+                    }
+                    let d = ((oy + y) * cw + ox + x) * 4;
+                    if d + 4 <= dst.len() {
+                        let c = &palette[raw[src] as usize];
+                        dst[d] = c[0];
+                        dst[d + 1] = c[1];
+                        dst[d + 2] = c[2];
+                        dst[d + 3] = alpha;
                     }
                 }
             }
-            PixelFormat::Rgba8 => {
-                for y in 0..fh {
-                    let src_start = y * fw * 4;
-                    let dst_start = frame_start + ((oy + y) * cw + ox) * 4;
-                    let row_bytes = fw * 4;
-                    if src_start + row_bytes <= raw.len()
-                        && dst_start + row_bytes <= all_pixels.len()
-                    {
-                        all_pixels[dst_start..dst_start + row_bytes]
-                            .copy_from_slice(&raw[src_start..src_start + row_bytes]);
-                    }
+        }
+        PixelFormat::Rgba8 => {
+            for y in 0..fh {
+                let src_start = y * fw * 4;
+                let dst_start = ((oy + y) * cw + ox) * 4;
+                let row_bytes = fw * 4;
+                if src_start + row_bytes <= raw.len() && dst_start + row_bytes <= dst.len() {
+                    dst[dst_start..dst_start + row_bytes]
+                        .copy_from_slice(&raw[src_start..src_start + row_bytes]);
                 }
             }
         }
     }
-
-    output.copy_from(&all_pixels);
-    frame_count as u32
 }
 
-/// Decode pixel data from blob into destination buffer
-fn decode_frame_pixels(
+/// Composite one frame entry into an `rw × rh` RGBA window whose top-left
+/// corner sits at canvas position `(rx, ry)`
+///
+/// Only the part of the frame that overlaps both the window and the canvas is
+/// converted; everything else in `dst` is left untouched. Frames with negative
+/// offsets start left of / above the canvas and lose the pixels outside it.
+#[allow(clippy::too_many_arguments)]
+fn composite_frame_region(
     pixel_format: PixelFormat,
     palette: &[[u8; 4]; 256],
+    entry: &MsfFrameEntry,
     raw: &[u8],
+    (cw, ch): (usize, usize),
+    (rx, ry, rw, rh): (usize, usize, usize, usize),
     dst: &mut [u8],
-    fw: usize,
-    fh: usize,
 ) {
-    let npixels = fw * fh;
-    match pixel_format {
-        PixelFormat::Indexed8 => {
-            for p in 0..npixels {
-                if p >= raw.len() {
-                    break;
+    let fw = entry.width as usize;
+    let (ox, oy) = (entry.offset_x as i64, entry.offset_y as i64);
+    let on_canvas = |v: i64| v.max(0) as usize;
+    let x0 = on_canvas(ox).max(rx);
+    let y0 = on_canvas(oy).max(ry);
+    let x1 = on_canvas(ox + fw as i64).min(rx.saturating_add(rw)).min(cw);
+    let y1 = on_canvas(oy + entry.height as i64)
+        .min(ry.saturating_add(rh))
+        .min(ch);
+
+    for cy in y0..y1 {
+        for cx in x0..x1 {
+            // cx ≥ ox and cy ≥ oy here, so the frame coordinates are never negative
+            let p = (cy as i64 - oy) as usize * fw + (cx as i64 - ox) as usize;
+            let d = ((cy - ry) * rw + (cx - rx)) * 4;
+            match pixel_format {
+                PixelFormat::Indexed8 => {
+                    if let Some(&idx) = raw.get(p) {
+                        lookup_indexed8(palette, idx, &mut dst[d..d + 4]);
+                    }
                 }
-                lookup_indexed8(palette, raw[p], &mut dst[p * 4..p * 4 + 4]);
-            }
-        }
-        PixelFormat::Indexed8Alpha8 => {
-            for p in 0..npixels {
-                let src = p * 2;
-                if src + 1 >= raw.len() {
-                    break;
+                PixelFormat::Indexed8Alpha8 => {
+                    if let Some(&[idx, alpha]) = raw.get(p * 2..p * 2 + 2) {
+                        if alpha != 0 {
+                            let c = &palette[idx as usize];
+                            dst[d..d + 4].copy_from_slice(&[c[0], c[1], c[2], alpha]);
+                        }
+                    }
                 }
-                let alpha = raw[src + 1];
-                if alpha == 0 {
-                    continue;
+                PixelFormat::Rgba8 => {
+                    if let Some(px) = raw.get(p * 4..p * 4 + 4) {
+                        dst[d..d + 4].copy_from_slice(px);
+                    }
                 }
-                let c = &palette[raw[src] as usize];
-                dst[p * 4] = c[0];
-                dst[p * 4 + 1] = c[1];
-                dst[p * 4 + 2] = c[2];
-                dst[p * 4 + 3] = alpha;
             }
         }
-        PixelFormat::Rgba8 => {
-            let bytes = npixels * 4;
-            if bytes <= raw.len() {
-                dst[..bytes].copy_from_slice(&raw[..bytes]);
+    }
+}
+
+/// Mirror a canvas-sized RGBA frame across the vertical line `x = anchor_x`
+///
+/// Column `x` moves to `2 * anchor_x - 1 - x`, so the anchor stays at the same
+/// canvas position; pixels mirrored outside the canvas are dropped.
+fn mirror_frame_h(src: &[u8], dst: &mut [u8], cw: usize, ch: usize, anchor_x: i16) {
+    dst.fill(0);
+    let axis = 2 * anchor_x as i64 - 1;
+    for y in 0..ch {
+        for x in 0..cw {
+            let mx = axis - x as i64;
+            if mx < 0 || mx >= cw as i64 {
+                continue;
             }
+            let s = (y * cw + x) * 4;
+            let d = (y * cw + mx as usize) * 4;
+            dst[d..d + 4].copy_from_slice(&src[s..s + 4]);
         }
     }
 }
 
-/// Find tight bounding box of non-transparent pixels in an RGBA buffer
-fn find_tight_bbox(buf: &[u8], fw: usize, fh: usize) -> (usize, usize, usize, usize) {
-    let mut min_r = fh;
-    let mut max_r = 0usize;
-    let mut min_c = fw;
-    let mut max_c = 0usize;
-    for row in 0..fh {
-        for col in 0..fw {
-            if buf[(row * fw + col) * 4 + 3] > 0 {
-                if row < min_r {
-                    min_r = row;
-                }
-                if row + 1 > max_r {
-                    max_r = row + 1;
-                }
-                if col < min_c {
-                    min_c = col;
+/// Apply `[index, r, g, b, ...]` overrides to a palette, keeping each entry's alpha
+fn apply_palette_overrides(palette: &mut [[u8; 4]; 256], overrides: &[u8]) {
+    for o in overrides.chunks_exact(4) {
+        let entry = &mut palette[o[0] as usize];
+        entry[0] = o[1];
+        entry[1] = o[2];
+        entry[2] = o[3];
+    }
+}
+
+/// Convert straight-alpha RGBA to premultiplied alpha in place
+fn premultiply_alpha(pixels: &mut [u8]) {
+    for px in pixels.chunks_exact_mut(4) {
+        let a = px[3] as u16;
+        if a == 255 {
+            continue;
+        }
+        px[0] = (px[0] as u16 * a / 255) as u8;
+        px[1] = (px[1] as u16 * a / 255) as u8;
+        px[2] = (px[2] as u16 * a / 255) as u8;
+    }
+}
+
+/// Scale every alpha by `opacity / 255` in place (transparent pixels stay 0)
+fn scale_alpha(pixels: &mut [u8], opacity: u8) {
+    if opacity == 255 {
+        return;
+    }
+    for px in pixels.chunks_exact_mut(4) {
+        px[3] = (px[3] as u16 * opacity as u16 / 255) as u8;
+    }
+}
+
+/// Options for canvas-sized decoding
+#[derive(Default)]
+struct CanvasDecodeOptions<'a> {
+    /// Mirror around the anchor (see `mirror_frame_h`)
+    flip_h: bool,
+    /// Write premultiplied-alpha RGBA
+    premultiply: bool,
+    /// Global opacity multiplied into every alpha (see `scale_alpha`)
+    opacity: Option<u8>,
+    /// `[index, r, g, b, ...]` palette overrides (see `apply_palette_overrides`)
+    palette_overrides: &'a [u8],
+    /// Only decode the frames of this direction (default: all frames)
+    direction: Option<u8>,
+}
+
+/// Decode all frames into one canvas-sized RGBA buffer
+fn decode_canvas_frames(
+    data: &[u8],
+    opts: &CanvasDecodeOptions,
+) -> Result<(Vec<u8>, usize), MsfError> {
+    let mut msf = try_parse_msf_structure(data)?;
+    apply_palette_overrides(&mut msf.palette, opts.palette_overrides);
+    let flip_h = opts.flip_h;
+    let pixel_format =
+        PixelFormat::from_u8(msf.pixel_format).ok_or(MsfError::UnknownPixelFormat)?;
+
+    let frames = match opts.direction {
+        Some(dir) => {
+            let fpd = if msf.directions > 0 {
+                (msf.frame_count / msf.directions as usize).max(1)
+            } else {
+                msf.frame_count.max(1)
+            };
+            let start = dir as usize * fpd;
+            if start >= msf.frame_count {
+                return Err(MsfError::DirectionOutOfRange);
+            }
+            start..(start + fpd).min(msf.frame_count)
+        }
+        None => 0..msf.frame_count,
+    };
+
+    let frame_count = frames.len();
+    let cw = msf.canvas_width as usize;
+    let ch = msf.canvas_height as usize;
+    let frame_size = cw * ch * 4;
+    let mut all_pixels = vec![0u8; frame_size * frame_count];
+
+    // Only zero-sized frames: the output stays fully transparent, so skip
+    // decompressing the blob and compositing altogether
+    if msf.entries[frames.clone()]
+        .iter()
+        .all(|e| e.width == 0 || e.height == 0)
+    {
+        return Ok((all_pixels, frame_count));
+    }
+
+    let mut decomp_buf = Vec::new();
+    let blob = get_blob(data, &msf, &mut decomp_buf).ok_or(MsfError::Decompression)?;
+    let mut frame_buf = if flip_h {
+        vec![0u8; frame_size]
+    } else {
+        Vec::new()
+    };
+
+    for (i, entry) in msf.entries[frames].iter().enumerate() {
+        let dst = &mut all_pixels[i * frame_size..(i + 1) * frame_size];
+        if flip_h {
+            frame_buf.fill(0);
+            composite_frame(pixel_format, &msf.palette, entry, blob, cw, &mut frame_buf);
+            mirror_frame_h(&frame_buf, dst, cw, ch, msf.anchor_x);
+        } else {
+            composite_frame(pixel_format, &msf.palette, entry, blob, cw, dst);
+        }
+    }
+
+    if let Some(opacity) = opts.opacity {
+        scale_alpha(&mut all_pixels, opacity);
+    }
+    if opts.premultiply {
+        premultiply_alpha(&mut all_pixels);
+    }
+
+    Ok((all_pixels, frame_count))
+}
+
+/// Copy a `decode_canvas_frames` result into `output` and record the error code
+///
+/// Returns the frame count, or 0 on failure or if `output` is too small.
+fn write_canvas_frames(result: Result<(Vec<u8>, usize), MsfError>, output: &Uint8Array) -> u32 {
+    let (all_pixels, frame_count) = match result {
+        Ok(v) => v,
+        Err(e) => {
+            set_last_msf_error(e);
+            return 0;
+        }
+    };
+    if (output.length() as usize) < all_pixels.len() {
+        set_last_msf_error(MsfError::OutputTooSmall);
+        return 0;
+    }
+    output
+        .subarray(0, all_pixels.len() as u32)
+        .copy_from(&all_pixels);
+    set_last_msf_error(MsfError::None);
+    frame_count as u32
+}
+
+/// Decode all frames into canvas-sized RGBA (for ASF sprites)
+///
+/// Returns the frame count; 0 is ambiguous, see `last_msf_error`.
+#[wasm_bindgen]
+pub fn decode_msf_frames(data: &[u8], output: &Uint8Array) -> u32 {
+    decode_msf_frames_flipped(data, output, false)
+}
+
+/// Decode all frames into canvas-sized RGBA, mirrored horizontally when `flip_h`
+///
+/// Frames are mirrored around the sprite anchor (`anchor_x`), so a left-facing
+/// sprite generated from right-facing art is drawn at the same position.
+#[wasm_bindgen]
+pub fn decode_msf_frames_flipped(data: &[u8], output: &Uint8Array, flip_h: bool) -> u32 {
+    let opts = CanvasDecodeOptions {
+        flip_h,
+        ..Default::default()
+    };
+    write_canvas_frames(decode_canvas_frames(data, &opts), output)
+}
+
+/// Decode all frames into canvas-sized RGBA with palette entries replaced (team colors)
+///
+/// `overrides` is a list of `[index, r, g, b]` tuples applied on top of the
+/// file's palette; alpha is kept from the file. Rgba8 files are unaffected.
+#[wasm_bindgen]
+pub fn decode_msf_frames_with_palette_override(
+    data: &[u8],
+    output: &Uint8Array,
+    overrides: &[u8],
+) -> u32 {
+    let opts = CanvasDecodeOptions {
+        palette_overrides: overrides,
+        ..Default::default()
+    };
+    write_canvas_frames(decode_canvas_frames(data, &opts), output)
+}
+
+/// Decode all frames into canvas-sized premultiplied-alpha RGBA (for WebGL)
+///
+/// RGB channels are written as `c * a / 255`; fully transparent pixels stay zeroed.
+#[wasm_bindgen]
+pub fn decode_msf_frames_premultiplied(data: &[u8], output: &Uint8Array) -> u32 {
+    let opts = CanvasDecodeOptions {
+        premultiply: true,
+        ..Default::default()
+    };
+    write_canvas_frames(decode_canvas_frames(data, &opts), output)
+}
+
+/// Decode all frames into canvas-sized RGBA at reduced opacity (fade-in effects)
+///
+/// Every written alpha is scaled by `opacity / 255`; for Indexed8 that is the
+/// palette alpha. Transparent pixels stay transparent.
+#[wasm_bindgen]
+pub fn decode_msf_frames_with_opacity(data: &[u8], output: &Uint8Array, opacity: u8) -> u32 {
+    let opts = CanvasDecodeOptions {
+        opacity: Some(opacity),
+        ..Default::default()
+    };
+    write_canvas_frames(decode_canvas_frames(data, &opts), output)
+}
+
+/// Decode only the frames of one direction into canvas-sized RGBA
+///
+/// `output` holds `frames_per_direction` canvas-sized frames (fewer for a
+/// short last direction). Returns the number of frames written, or 0 if the
+/// direction is out of range.
+#[wasm_bindgen]
+pub fn decode_msf_direction(data: &[u8], direction: u8, output: &Uint8Array) -> u32 {
+    let opts = CanvasDecodeOptions {
+        direction: Some(direction),
+        ..Default::default()
+    };
+    write_canvas_frames(decode_canvas_frames(data, &opts), output)
+}
+
+/// Decode all frames into a caller-owned, reusable buffer (e.g. per-frame palette swaps)
+///
+/// Same output layout as `decode_msf_frames`, but only a single frame-sized
+/// scratch buffer is allocated instead of one covering the whole sheet.
+/// `output` must hold at least `canvas_width * canvas_height * 4 * frame_count`
+/// bytes; returns 0 if it is too small or the data is invalid.
+#[wasm_bindgen]
+pub fn decode_msf_frames_into(data: &[u8], output: &Uint8Array) -> u32 {
+    let msf = match try_parse_msf_structure(data) {
+        Ok(v) => v,
+        Err(e) => {
+            set_last_msf_error(e);
+            return 0;
+        }
+    };
+
+    let pixel_format = match PixelFormat::from_u8(msf.pixel_format) {
+        Some(pf) => pf,
+        None => {
+            set_last_msf_error(MsfError::UnknownPixelFormat);
+            return 0;
+        }
+    };
+
+    let frame_count = msf.frame_count;
+    let cw = msf.canvas_width as usize;
+    let ch = msf.canvas_height as usize;
+    let frame_size = cw * ch * 4;
+    if (output.length() as usize) < frame_size * frame_count {
+        set_last_msf_error(MsfError::OutputTooSmall);
+        return 0;
+    }
+
+    let mut decomp_buf = Vec::new();
+    let blob = match get_blob(data, &msf, &mut decomp_buf) {
+        Some(b) => b,
+        None => {
+            set_last_msf_error(MsfError::Decompression);
+            return 0;
+        }
+    };
+
+    let mut frame_buf = vec![0u8; frame_size];
+    for (i, entry) in msf.entries.iter().enumerate() {
+        frame_buf.fill(0);
+        composite_frame(pixel_format, &msf.palette, entry, blob, cw, &mut frame_buf);
+        let start = (i * frame_size) as u32;
+        output
+            .subarray(start, start + frame_size as u32)
+            .copy_from(&frame_buf);
+    }
+
+    set_last_msf_error(MsfError::None);
+    frame_count as u32
+}
+
+/// Decode pixel data from blob into destination buffer
+fn decode_frame_pixels(
+    pixel_format: PixelFormat,
+    palette: &[[u8; 4]; 256],
+    raw: &[u8],
+    dst: &mut [u8],
+    fw: usize,
+    fh: usize,
+) {
+    let npixels = fw * fh;
+    match pixel_format {
+        PixelFormat::Indexed8 => {
+            for p in 0..npixels {
+                if p >= raw.len() {
+                    break;
                 }
-                if col + 1 > max_c {
-                    max_c = col + 1;
+                // Fully transparent entries stay [0, 0, 0, 0], as in verify_mpc
+                if palette[raw[p] as usize][3] == 0 {
+                    continue;
+                }
+                lookup_indexed8(palette, raw[p], &mut dst[p * 4..p * 4 + 4]);
+            }
+        }
+        PixelFormat::Indexed8Alpha8 => {
+            for p in 0..npixels {
+                let src = p * 2;
+                if src + 1 >= raw.len() {
+                    break;
+                }
+                let alpha = raw[src + 1];
+                if alpha == 0 {
+                    continue;
                 }
+                let c = &palette[raw[src] as usize];
+                dst[p * 4] = c[0];
+                dst[p * 4 + 1] = c[1];
+                dst[p * 4 + 2] = c[2];
+                dst[p * 4 + 3] = alpha;
+            }
+        }
+        PixelFormat::Rgba8 => {
+            let bytes = npixels * 4;
+            if bytes <= raw.len() {
+                dst[..bytes].copy_from_slice(&raw[..bytes]);
             }
         }
     }
-    if min_r == fh {
-        // fully transparent frame — emit 1×1
-        (0, 1, 0, 1)
-    } else {
-        (min_r, max_r, min_c, max_c)
+}
+
+/// Decode one frame at its own size into RGBA, returns (width, height, pixels)
+///
+/// Empty frames decode to a single transparent pixel, matching
+/// `decode_msf_individual_frames`.
+fn decode_single_frame(data: &[u8], frame_index: usize) -> Option<(usize, usize, Vec<u8>)> {
+    MsfReader::from_bytes(data)?.frame_rgba(frame_index)
+}
+
+/// Decode a single frame by index (for lazy per-frame loading)
+///
+/// Writes `width * height * 4` RGBA bytes to the start of `output` and returns
+/// `(width << 16) | height`. Returns 0 if the data is invalid, the index is out
+/// of range, or `output` is too small.
+#[wasm_bindgen]
+pub fn decode_msf_single_frame(data: &[u8], frame_index: u32, output: &Uint8Array) -> u32 {
+    let (fw, fh, pixels) = match decode_single_frame(data, frame_index as usize) {
+        Some(v) => v,
+        None => return 0,
+    };
+    if (output.length() as usize) < pixels.len() {
+        return 0;
     }
+    output.subarray(0, pixels.len() as u32).copy_from(&pixels);
+    ((fw as u32) << 16) | fh as u32
 }
 
-/// Decode frames as individual images (for MPC per-frame varying sizes)
+/// Composite a single frame and keep only a canvas sub-rectangle (minimap,
+/// cropped portraits)
 ///
-/// `canvas_offsets_output`: optional, if provided receives per-frame i16 pairs
-/// [offset_x, offset_y, ...] indicating each frame's position within the canvas.
-/// When provided, tight-bbox cropping is applied to reduce GPU memory.
-/// When absent (MPC tiles), frames are decoded at their original sizes.
+/// Writes the `rw * rh * 4` RGBA window whose top-left corner is canvas pixel
+/// `(rx, ry)` to the start of `output` and returns `(rw << 16) | rh`. Parts of
+/// the window outside the frame or the canvas are transparent. Returns 0 if
+/// the data is invalid, the index is out of range, the window is empty, or
+/// `output` is too small.
 #[wasm_bindgen]
-pub fn decode_msf_individual_frames(
+pub fn decode_msf_frame_region(
     data: &[u8],
-    pixel_output: &Uint8Array,
-    frame_sizes_output: &Uint8Array,
-    frame_offsets_output: &Uint8Array,
-    canvas_offsets_output: Option<Uint8Array>,
+    frame_index: u32,
+    rx: u32,
+    ry: u32,
+    rw: u32,
+    rh: u32,
+    output: &Uint8Array,
 ) -> u32 {
-    let (_cw, _ch, frame_count, pf_byte, _, palette, entries, blob_start, flags) =
-        match parse_msf_structure(data) {
+    if rw == 0 || rh == 0 || rw > u16::MAX as u32 || rh > u16::MAX as u32 {
+        return 0;
+    }
+    if (output.length() as usize) < rw as usize * rh as usize * 4 {
+        return 0;
+    }
+    let region = (rx as usize, ry as usize, rw as usize, rh as usize);
+    let pixels = match MsfReader::from_bytes(data)
+        .and_then(|reader| reader.region_rgba(frame_index as usize, region))
+    {
+        Some(v) => v,
+        None => return 0,
+    };
+    output.subarray(0, pixels.len() as u32).copy_from(&pixels);
+    (rw << 16) | rh
+}
+
+/// Decode frame 0 scaled down so its longer side is at most `max_dim` (asset
+/// browser previews)
+///
+/// Frames already within `max_dim` are returned at their own size. Writes
+/// `width * height * 4` RGBA bytes to the start of `output` and returns
+/// `(width << 16) | height`. Returns 0 if the data is invalid, has no frames,
+/// `max_dim` is 0, or `output` is too small.
+#[wasm_bindgen]
+pub fn decode_msf_thumbnail(data: &[u8], max_dim: u32, output: &Uint8Array) -> u32 {
+    let (tw, th, pixels) = match thumbnail_rgba(data, max_dim as usize) {
+        Some(v) => v,
+        None => return 0,
+    };
+    if (output.length() as usize) < pixels.len() {
+        return 0;
+    }
+    output.subarray(0, pixels.len() as u32).copy_from(&pixels);
+    ((tw as u32) << 16) | th as u32
+}
+
+/// Frame 0 box-downsampled to fit `max_dim`, returns (width, height, pixels)
+fn thumbnail_rgba(data: &[u8], max_dim: usize) -> Option<(usize, usize, Vec<u8>)> {
+    if max_dim == 0 {
+        return None;
+    }
+    let (w, h, pixels) = decode_single_frame(data, 0)?;
+    let longer = w.max(h);
+    if longer <= max_dim {
+        return Some((w, h, pixels));
+    }
+    let tw = (w * max_dim).div_ceil(longer).clamp(1, max_dim);
+    let th = (h * max_dim).div_ceil(longer).clamp(1, max_dim);
+    Some((tw, th, downsample_box(&pixels, w, h, tw, th)))
+}
+
+/// Box-filter RGBA `w × h` down to `tw × th`
+///
+/// Colour is averaged weighted by alpha so transparent pixels don't darken
+/// edges; alpha is the plain average over the box.
+fn downsample_box(pixels: &[u8], w: usize, h: usize, tw: usize, th: usize) -> Vec<u8> {
+    let mut out = vec![0u8; tw * th * 4];
+    for oy in 0..th {
+        let (y0, y1) = (oy * h / th, ((oy + 1) * h / th).max(oy * h / th + 1));
+        for ox in 0..tw {
+            let (x0, x1) = (ox * w / tw, ((ox + 1) * w / tw).max(ox * w / tw + 1));
+            let mut rgb = [0u32; 3];
+            let mut alpha = 0u32;
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let p = &pixels[(y * w + x) * 4..(y * w + x) * 4 + 4];
+                    let a = p[3] as u32;
+                    for c in 0..3 {
+                        rgb[c] += p[c] as u32 * a;
+                    }
+                    alpha += a;
+                }
+            }
+            if alpha == 0 {
+                continue;
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as u32;
+            let dst = &mut out[(oy * tw + ox) * 4..(oy * tw + ox) * 4 + 4];
+            for c in 0..3 {
+                dst[c] = ((rgb[c] + alpha / 2) / alpha) as u8;
+            }
+            dst[3] = ((alpha + count / 2) / count) as u8;
+        }
+    }
+    out
+}
+
+// ============================================================================
+// MsfReader: decompress once, decode frames on demand
+// ============================================================================
+
+/// Parsed MSF file with the frame blob already decompressed
+///
+/// Use this instead of `decode_msf_single_frame` when decoding several frames
+/// of the same file: the blob is decompressed once in the constructor, so each
+/// `decode_frame` call only converts one frame.
+#[wasm_bindgen]
+pub struct MsfReader {
+    header: MsfHeader,
+    pixel_format: PixelFormat,
+    palette: [[u8; 4]; 256],
+    entries: Vec<MsfFrameEntry>,
+    blob: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl MsfReader {
+    /// Parse and decompress an MSF file; throws with the validation error if invalid
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &[u8]) -> Result<MsfReader, JsError> {
+        let mut decomp_buf = Vec::new();
+        let (msf, blob) =
+            parse_validated_msf(data, &mut decomp_buf).map_err(|e| JsError::new(&e))?;
+        let blob = blob.to_vec();
+        parse_msf_header(data)
+            .and_then(|header| Self::from_parts(header, msf, blob))
+            .ok_or_else(|| JsError::new("failed to decode MSF"))
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.entries.len() as u32
+    }
+
+    pub fn header(&self) -> MsfHeader {
+        self.header.clone()
+    }
+
+    /// Decode one frame at its own size into `output`
+    ///
+    /// Same contract as `decode_msf_single_frame`: returns `(width << 16) | height`,
+    /// or 0 if the index is out of range or `output` is too small.
+    pub fn decode_frame(&self, frame_index: u32, output: &Uint8Array) -> u32 {
+        let (fw, fh, pixels) = match self.frame_rgba(frame_index as usize) {
             Some(v) => v,
             None => return 0,
         };
+        if (output.length() as usize) < pixels.len() {
+            return 0;
+        }
+        output.subarray(0, pixels.len() as u32).copy_from(&pixels);
+        ((fw as u32) << 16) | fh as u32
+    }
+}
+
+impl MsfReader {
+    fn from_bytes(data: &[u8]) -> Option<Self> {
+        let header = parse_msf_header(data)?;
+        let msf = parse_msf_structure(data)?;
+        let mut decomp_buf = Vec::new();
+        let blob = get_blob(data, &msf, &mut decomp_buf)?.to_vec();
+        Self::from_parts(header, msf, blob)
+    }
+
+    fn from_parts(header: MsfHeader, msf: MsfStructure, blob: Vec<u8>) -> Option<Self> {
+        let pixel_format = PixelFormat::from_u8(msf.pixel_format)?;
+        Some(Self {
+            header,
+            pixel_format,
+            palette: msf.palette,
+            entries: msf.entries,
+            blob,
+        })
+    }
+
+    fn frame_rgba(&self, frame_index: usize) -> Option<(usize, usize, Vec<u8>)> {
+        let entry = self.entries.get(frame_index)?;
+        let fw = entry.width as usize;
+        let fh = entry.height as usize;
+        if fw == 0 || fh == 0 {
+            return Some((1, 1, vec![0u8; 4]));
+        }
+
+        let blob_off = entry.data_offset as usize;
+        let blob_len = entry.data_length as usize;
+        if blob_off + blob_len > self.blob.len() {
+            return None;
+        }
+
+        let mut pixels = vec![0u8; fw * fh * 4];
+        let raw = &self.blob[blob_off..blob_off + blob_len];
+        decode_frame_pixels(self.pixel_format, &self.palette, raw, &mut pixels, fw, fh);
+        Some((fw, fh, pixels))
+    }
+
+    /// Composite one frame into the canvas window `(rx, ry, rw, rh)`
+    fn region_rgba(
+        &self,
+        frame_index: usize,
+        (rx, ry, rw, rh): (usize, usize, usize, usize),
+    ) -> Option<Vec<u8>> {
+        let entry = self.entries.get(frame_index)?;
+        let mut pixels = vec![0u8; rw * rh * 4];
+        if entry.width == 0 || entry.height == 0 {
+            return Some(pixels);
+        }
+
+        let blob_off = entry.data_offset as usize;
+        let blob_len = entry.data_length as usize;
+        if blob_off + blob_len > self.blob.len() {
+            return None;
+        }
+
+        let canvas = (
+            self.header.canvas_width as usize,
+            self.header.canvas_height as usize,
+        );
+        composite_frame_region(
+            self.pixel_format,
+            &self.palette,
+            entry,
+            &self.blob[blob_off..blob_off + blob_len],
+            canvas,
+            (rx, ry, rw, rh),
+            &mut pixels,
+        );
+        Some(pixels)
+    }
+}
+
+/// Find tight bounding box of non-transparent pixels in an RGBA buffer
+fn find_tight_bbox(buf: &[u8], fw: usize, fh: usize) -> (usize, usize, usize, usize) {
+    let mut min_r = fh;
+    let mut max_r = 0usize;
+    let mut min_c = fw;
+    let mut max_c = 0usize;
+    for row in 0..fh {
+        for col in 0..fw {
+            if buf[(row * fw + col) * 4 + 3] > 0 {
+                if row < min_r {
+                    min_r = row;
+                }
+                if row + 1 > max_r {
+                    max_r = row + 1;
+                }
+                if col < min_c {
+                    min_c = col;
+                }
+                if col + 1 > max_c {
+                    max_c = col + 1;
+                }
+            }
+        }
+    }
+    if min_r == fh {
+        // fully transparent frame — emit 1×1
+        (0, 1, 0, 1)
+    } else {
+        (min_r, max_r, min_c, max_c)
+    }
+}
 
-    let pixel_format = match PixelFormat::from_u8(pf_byte) {
-        Some(pf) => pf,
-        None => return 0,
-    };
+/// Frames decoded at their own sizes, before copying into the JS buffers
+struct IndividualFrames {
+    /// RGBA for every frame back to back; sized for the uncropped frames
+    pixels: Vec<u8>,
+    /// [width, height] per frame (1×1 for empty frames)
+    frame_sizes: Vec<u32>,
+    /// Byte offset of each frame in `pixels`
+    frame_offsets: Vec<u32>,
+    /// [offset_x, offset_y] per frame within the canvas
+    canvas_offsets: Vec<i16>,
+}
+
+/// Reverse the row order of a `row_bytes`-wide image in place
+fn flip_rows(pixels: &mut [u8], row_bytes: usize) {
+    let rows = pixels.len() / row_bytes.max(1);
+    for r in 0..rows / 2 {
+        let (top, bottom) = pixels.split_at_mut((rows - 1 - r) * row_bytes);
+        top[r * row_bytes..(r + 1) * row_bytes].swap_with_slice(&mut bottom[..row_bytes]);
+    }
+}
+
+/// Decode every frame at its own size, optionally tight-cropped
+///
+/// Shared by `decode_msf_individual_frames`; accepts every MSF2 pixel format,
+/// including the Indexed8 sheets produced from MPC map tiles. With `flip_y`
+/// each frame's rows are stored bottom-to-top (WebGL upload order); sizes and
+/// canvas offsets are unaffected and still describe the frame top-down.
+fn decode_individual(
+    data: &[u8],
+    tight_crop: bool,
+    flip_y: bool,
+) -> Result<IndividualFrames, MsfError> {
+    let msf = try_parse_msf_structure(data)?;
+    let pixel_format =
+        PixelFormat::from_u8(msf.pixel_format).ok_or(MsfError::UnknownPixelFormat)?;
     let mut decomp_buf = Vec::new();
-    let blob = match get_blob(data, blob_start, flags, &mut decomp_buf) {
-        Some(b) => b,
-        None => return 0,
-    };
+    let blob = get_blob(data, &msf, &mut decomp_buf).ok_or(MsfError::Decompression)?;
+
+    let palette = &msf.palette;
+    let entries = &msf.entries;
+    let frame_count = msf.frame_count;
 
     // Calculate total output size
     let mut total_pixel_bytes = 0usize;
-    for entry in &entries {
+    for entry in entries {
         if entry.width > 0 && entry.height > 0 {
             total_pixel_bytes += (entry.width as usize) * (entry.height as usize) * 4;
         } else {
@@ -521,8 +1533,6 @@ pub fn decode_msf_individual_frames(
         }
     }
 
-    let do_tight_crop = canvas_offsets_output.is_some();
-
     let mut all_pixels = vec![0u8; total_pixel_bytes];
     let mut frame_sizes = vec![0u32; frame_count * 2];
     let mut frame_offsets = vec![0u32; frame_count];
@@ -535,7 +1545,7 @@ pub fn decode_msf_individual_frames(
         .map(|e| (e.width as usize) * (e.height as usize))
         .max()
         .unwrap_or(0);
-    let mut frame_buf = if do_tight_crop {
+    let mut frame_buf = if tight_crop {
         vec![0u8; max_frame_pixels * 4]
     } else {
         Vec::new()
@@ -562,14 +1572,14 @@ pub fn decode_msf_individual_frames(
         let blob_len = entry.data_length as usize;
         let npixels = fw * fh;
 
-        if do_tight_crop {
+        if tight_crop {
             // Decode into temp buffer, then tight-crop into output
             let buf = &mut frame_buf[..npixels * 4];
             buf.fill(0);
 
             if blob_off + blob_len <= blob.len() {
                 let raw = &blob[blob_off..blob_off + blob_len];
-                decode_frame_pixels(pixel_format, &palette, raw, buf, fw, fh);
+                decode_frame_pixels(pixel_format, palette, raw, buf, fw, fh);
             }
 
             let (r0, r1, c0, c1) = find_tight_bbox(buf, fw, fh);
@@ -591,11 +1601,19 @@ pub fn decode_msf_individual_frames(
                         .copy_from_slice(&buf[src_start..src_start + len]);
                 }
             }
+            if flip_y {
+                flip_rows(
+                    &mut all_pixels[out_offset..out_offset + tw * th * 4],
+                    tw * 4,
+                );
+            }
 
             out_offset += tw * th * 4;
         } else {
             // No tight-crop: decode directly into output at original size
             let frame_bytes = npixels * 4;
+            canvas_offsets[i * 2] = entry.offset_x;
+            canvas_offsets[i * 2 + 1] = entry.offset_y;
             frame_sizes[i * 2] = fw as u32;
             frame_sizes[i * 2 + 1] = fh as u32;
             frame_offsets[i] = out_offset as u32;
@@ -604,33 +1622,404 @@ pub fn decode_msf_individual_frames(
                 let raw = &blob[blob_off..blob_off + blob_len];
                 let dst = &mut all_pixels[out_offset..out_offset + frame_bytes];
                 dst.fill(0);
-                decode_frame_pixels(pixel_format, &palette, raw, dst, fw, fh);
+                decode_frame_pixels(pixel_format, palette, raw, dst, fw, fh);
+                if flip_y {
+                    flip_rows(dst, fw * 4);
+                }
             }
 
             out_offset += frame_bytes;
         }
     }
 
-    // copy_from requires src.len() == dest.byte_length(), so copy the full buffer.
-    pixel_output.copy_from(&all_pixels);
+    Ok(IndividualFrames {
+        pixels: all_pixels,
+        frame_sizes,
+        frame_offsets,
+        canvas_offsets,
+    })
+}
+
+/// Decode frames as individual images (for MPC per-frame varying sizes)
+///
+/// `canvas_offsets_output`: optional, if provided receives per-frame i16 pairs
+/// [offset_x, offset_y, ...] indicating each frame's position within the canvas.
+/// When provided, tight-bbox cropping is applied to reduce GPU memory.
+/// When absent (MPC tiles), frames are decoded at their original sizes.
+///
+/// Returns the frame count; 0 is ambiguous, see `last_msf_error`. Each output
+/// must be at least as large as the data written to it (`OutputTooSmall`).
+#[wasm_bindgen]
+pub fn decode_msf_individual_frames(
+    data: &[u8],
+    pixel_output: &Uint8Array,
+    frame_sizes_output: &Uint8Array,
+    frame_offsets_output: &Uint8Array,
+    canvas_offsets_output: Option<Uint8Array>,
+) -> u32 {
+    let tight_crop = canvas_offsets_output.is_some();
+    write_individual_frames(
+        decode_individual(data, tight_crop, false),
+        pixel_output,
+        frame_sizes_output,
+        frame_offsets_output,
+        canvas_offsets_output,
+    )
+}
+
+/// Same as `decode_msf_individual_frames`, but each frame's rows are written
+/// bottom-to-top for direct WebGL texture upload
+///
+/// Frame sizes, byte offsets and canvas offsets are identical to the
+/// unflipped call; only the row order inside each frame changes.
+#[wasm_bindgen]
+pub fn decode_msf_individual_frames_flip_y(
+    data: &[u8],
+    pixel_output: &Uint8Array,
+    frame_sizes_output: &Uint8Array,
+    frame_offsets_output: &Uint8Array,
+    canvas_offsets_output: Option<Uint8Array>,
+) -> u32 {
+    let tight_crop = canvas_offsets_output.is_some();
+    write_individual_frames(
+        decode_individual(data, tight_crop, true),
+        pixel_output,
+        frame_sizes_output,
+        frame_offsets_output,
+        canvas_offsets_output,
+    )
+}
+
+/// Copy decoded individual frames into the JS buffers, recording the error
+fn write_individual_frames(
+    result: Result<IndividualFrames, MsfError>,
+    pixel_output: &Uint8Array,
+    frame_sizes_output: &Uint8Array,
+    frame_offsets_output: &Uint8Array,
+    canvas_offsets_output: Option<Uint8Array>,
+) -> u32 {
+    let frames = match result {
+        Ok(v) => v,
+        Err(e) => {
+            set_last_msf_error(e);
+            return 0;
+        }
+    };
+    let frame_count = frames.frame_offsets.len();
+
+    // Tight-cropped pixels never exceed the uncropped total checked here
+    let too_small = (pixel_output.length() as usize) < frames.pixels.len()
+        || (frame_sizes_output.length() as usize) < frame_count * 8
+        || (frame_offsets_output.length() as usize) < frame_count * 4
+        || canvas_offsets_output
+            .as_ref()
+            .is_some_and(|co| (co.length() as usize) < frame_count * 4);
+    if too_small {
+        set_last_msf_error(MsfError::OutputTooSmall);
+        return 0;
+    }
+
+    // copy_from requires src.len() == dest.byte_length(), so copy through a
+    // subarray of exactly the written size.
+    let copy_prefix =
+        |dst: &Uint8Array, src: &[u8]| dst.subarray(0, src.len() as u32).copy_from(src);
+    copy_prefix(pixel_output, &frames.pixels);
 
-    let sizes_bytes: Vec<u8> = frame_sizes.iter().flat_map(|v| v.to_le_bytes()).collect();
-    frame_sizes_output.copy_from(&sizes_bytes);
+    let sizes_bytes: Vec<u8> = frames
+        .frame_sizes
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    copy_prefix(frame_sizes_output, &sizes_bytes);
 
-    let offsets_bytes: Vec<u8> = frame_offsets.iter().flat_map(|v| v.to_le_bytes()).collect();
-    frame_offsets_output.copy_from(&offsets_bytes);
+    let offsets_bytes: Vec<u8> = frames
+        .frame_offsets
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    copy_prefix(frame_offsets_output, &offsets_bytes);
 
     if let Some(ref co) = canvas_offsets_output {
-        let co_bytes: Vec<u8> = canvas_offsets
+        let co_bytes: Vec<u8> = frames
+            .canvas_offsets
             .iter()
             .flat_map(|v| v.to_le_bytes())
             .collect();
-        co.copy_from(&co_bytes);
+        copy_prefix(co, &co_bytes);
     }
 
+    set_last_msf_error(MsfError::None);
     frame_count as u32
 }
 
+/// Packed sprite sheet: RGBA pixels plus `[x, y, w, h]` per frame
+struct MsfAtlas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    layout: Vec<u32>,
+}
+
+/// Pack all frames at their own (tight-cropped) sizes into a grid of `cols` columns
+///
+/// Simple shelf packing: each row of `cols` frames is a shelf as tall as its
+/// tallest frame, frames are placed left to right, and the atlas is as wide as
+/// the widest shelf. Empty frames take a 1×1 transparent cell. Returns None
+/// if either atlas dimension exceeds `u16::MAX` (it couldn't be reported).
+fn pack_msf_atlas(data: &[u8], cols: usize) -> Option<MsfAtlas> {
+    if cols == 0 {
+        return None;
+    }
+    let reader = MsfReader::from_bytes(data)?;
+    let frames = (0..reader.entries.len())
+        .map(|i| reader.frame_rgba(i))
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut layout = Vec::with_capacity(frames.len() * 4);
+    let (mut width, mut height) = (0usize, 0usize);
+    for shelf in frames.chunks(cols) {
+        let mut x = 0usize;
+        for &(fw, fh, _) in shelf {
+            layout.extend_from_slice(&[x as u32, height as u32, fw as u32, fh as u32]);
+            x += fw;
+        }
+        width = width.max(x);
+        height += shelf.iter().map(|&(_, fh, _)| fh).max().unwrap_or(0);
+    }
+    if width > u16::MAX as usize || height > u16::MAX as usize {
+        return None;
+    }
+
+    let mut pixels = vec![0u8; width * height * 4];
+    for (i, (fw, fh, frame)) in frames.iter().enumerate() {
+        let (x, y) = (layout[i * 4] as usize, layout[i * 4 + 1] as usize);
+        for row in 0..*fh {
+            let dst = ((y + row) * width + x) * 4;
+            pixels[dst..dst + fw * 4].copy_from_slice(&frame[row * fw * 4..(row + 1) * fw * 4]);
+        }
+    }
+
+    Some(MsfAtlas {
+        width,
+        height,
+        pixels,
+        layout,
+    })
+}
+
+/// Decode all frames into one RGBA texture atlas with `cols` frames per row (for WebGL)
+///
+/// Frames keep their own sizes and are shelf-packed row by row. `output`
+/// receives `atlas_width * atlas_height * 4` bytes; `layout_out` receives
+/// `[x, y, w, h]` per frame as little-endian u32 (16 bytes per frame).
+/// Returns `(atlas_width << 16) | atlas_height`, or 0 if the data is invalid,
+/// `cols` is 0, the atlas would be wider or taller than 65535, or either
+/// buffer is too small.
+#[wasm_bindgen]
+pub fn decode_msf_to_atlas(
+    data: &[u8],
+    cols: u32,
+    output: &Uint8Array,
+    layout_out: &Uint8Array,
+) -> u32 {
+    let atlas = match pack_msf_atlas(data, cols as usize) {
+        Some(a) => a,
+        None => return 0,
+    };
+    let layout_bytes: Vec<u8> = atlas.layout.iter().flat_map(|v| v.to_le_bytes()).collect();
+    if (output.length() as usize) < atlas.pixels.len()
+        || (layout_out.length() as usize) < layout_bytes.len()
+    {
+        return 0;
+    }
+    output
+        .subarray(0, atlas.pixels.len() as u32)
+        .copy_from(&atlas.pixels);
+    layout_out
+        .subarray(0, layout_bytes.len() as u32)
+        .copy_from(&layout_bytes);
+    ((atlas.width as u32) << 16) | atlas.height as u32
+}
+
+// ============================================================================
+// Encoding
+// ============================================================================
+
+/// One frame to encode; `data` is already in the target pixel format
+#[derive(Clone, Debug)]
+pub struct MsfEncodeFrame {
+    pub offset_x: i16,
+    pub offset_y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub data: Vec<u8>,
+}
+
+/// Everything needed to write an MSF v2 file
+#[derive(Clone, Debug)]
+pub struct MsfEncodeInput {
+    pub canvas_width: u16,
+    pub canvas_height: u16,
+    pub directions: u8,
+    pub fps: u8,
+    pub anchor_x: i16,
+    pub anchor_y: i16,
+    pub pixel_format: PixelFormat,
+    pub palette: Vec<[u8; 4]>,
+    pub frames: Vec<MsfEncodeFrame>,
+}
+
+/// Concatenate frame data into one blob and build the matching frame table
+fn concat_frames(input: &MsfEncodeInput) -> (Vec<MsfFrameEntry>, Vec<u8>) {
+    let mut entries = Vec::with_capacity(input.frames.len());
+    let mut concat_raw = Vec::new();
+    for frame in &input.frames {
+        entries.push(MsfFrameEntry {
+            offset_x: frame.offset_x,
+            offset_y: frame.offset_y,
+            width: frame.width,
+            height: frame.height,
+            data_offset: concat_raw.len() as u32,
+            data_length: frame.data.len() as u32,
+        });
+        concat_raw.extend_from_slice(&frame.data);
+    }
+    (entries, concat_raw)
+}
+
+/// Write header, palette, frame table and END sentinel, followed by `blob`
+fn write_msf(
+    input: &MsfEncodeInput,
+    entries: &[MsfFrameEntry],
+    flags: u16,
+    blob: &[u8],
+) -> Vec<u8> {
+    let total =
+        8 + 16 + 4 + input.palette.len() * 4 + entries.len() * FRAME_ENTRY_SIZE + 8 + blob.len();
+    let mut out = Vec::with_capacity(total);
+
+    // Preamble
+    out.extend_from_slice(MSF_MAGIC);
+    out.extend_from_slice(&MSF_VERSION.to_le_bytes());
+    out.extend_from_slice(&flags.to_le_bytes());
+
+    // Header (16 bytes)
+    out.extend_from_slice(&input.canvas_width.to_le_bytes());
+    out.extend_from_slice(&input.canvas_height.to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.push(input.directions);
+    out.push(input.fps);
+    out.extend_from_slice(&input.anchor_x.to_le_bytes());
+    out.extend_from_slice(&input.anchor_y.to_le_bytes());
+    out.extend_from_slice(&[0u8; 4]);
+
+    // Pixel format block
+    out.push(input.pixel_format as u8);
+    out.extend_from_slice(&(input.palette.len() as u16).to_le_bytes());
+    out.push(0);
+
+    for entry in &input.palette {
+        out.extend_from_slice(entry);
+    }
+
+    for entry in entries {
+        out.extend_from_slice(&entry.offset_x.to_le_bytes());
+        out.extend_from_slice(&entry.offset_y.to_le_bytes());
+        out.extend_from_slice(&entry.width.to_le_bytes());
+        out.extend_from_slice(&entry.height.to_le_bytes());
+        out.extend_from_slice(&entry.data_offset.to_le_bytes());
+        out.extend_from_slice(&entry.data_length.to_le_bytes());
+    }
+
+    out.extend_from_slice(CHUNK_END);
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(blob);
+    out
+}
+
+/// Encode an MSF v2 file with an uncompressed frame blob (flags = 0)
+pub fn encode_msf(input: &MsfEncodeInput) -> Vec<u8> {
+    let (entries, concat_raw) = concat_frames(input);
+    write_msf(input, &entries, 0, &concat_raw)
+}
+
+/// Encode an MSF v2 file with a zstd-compressed frame blob (flags bit 0)
+#[cfg(feature = "zstd-encode")]
+pub fn encode_msf_compressed(input: &MsfEncodeInput) -> Vec<u8> {
+    let (entries, concat_raw) = concat_frames(input);
+    write_msf(input, &entries, FLAG_ZSTD, &zstd_compress(&concat_raw))
+}
+
+/// Encode canvas-sized RGBA frames (e.g. runtime-generated sprites) as Rgba8 MSF
+///
+/// `frames_rgba` holds `canvas_width * canvas_height * 4` bytes per frame.
+/// Each frame is cropped to its non-transparent bounding box. The blob is
+/// zstd-compressed when built with the `zstd-encode` feature.
+#[wasm_bindgen]
+pub fn encode_msf_rgba(
+    frames_rgba: &[u8],
+    canvas_width: u16,
+    canvas_height: u16,
+    directions: u8,
+    fps: u8,
+    anchor_x: i16,
+    anchor_y: i16,
+) -> Vec<u8> {
+    let cw = canvas_width as usize;
+    let ch = canvas_height as usize;
+    let frame_size = cw * ch * 4;
+    let frame_count = frames_rgba.len().checked_div(frame_size).unwrap_or(0);
+
+    let mut frames = Vec::with_capacity(frame_count);
+    for i in 0..frame_count {
+        let buf = &frames_rgba[i * frame_size..(i + 1) * frame_size];
+        if !buf.chunks_exact(4).any(|px| px[3] > 0) {
+            frames.push(MsfEncodeFrame {
+                offset_x: 0,
+                offset_y: 0,
+                width: 0,
+                height: 0,
+                data: Vec::new(),
+            });
+            continue;
+        }
+
+        let (r0, r1, c0, c1) = find_tight_bbox(buf, cw, ch);
+        let mut data = Vec::with_capacity((r1 - r0) * (c1 - c0) * 4);
+        for row in r0..r1 {
+            data.extend_from_slice(&buf[(row * cw + c0) * 4..(row * cw + c1) * 4]);
+        }
+        frames.push(MsfEncodeFrame {
+            offset_x: c0 as i16,
+            offset_y: r0 as i16,
+            width: (c1 - c0) as u16,
+            height: (r1 - r0) as u16,
+            data,
+        });
+    }
+
+    let input = MsfEncodeInput {
+        canvas_width,
+        canvas_height,
+        directions,
+        fps,
+        anchor_x,
+        anchor_y,
+        pixel_format: PixelFormat::Rgba8,
+        palette: Vec::new(),
+        frames,
+    };
+
+    #[cfg(feature = "zstd-encode")]
+    {
+        encode_msf_compressed(&input)
+    }
+    #[cfg(not(feature = "zstd-encode"))]
+    {
+        encode_msf(&input)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -642,4 +2031,846 @@ mod tests {
         assert_eq!(PixelFormat::from_u8(2), Some(PixelFormat::Indexed8Alpha8));
         assert_eq!(PixelFormat::from_u8(99), None);
     }
+
+    /// Build an uncompressed MSF with the given frames (w, h, raw pixel data)
+    fn build_msf(
+        pixel_format: PixelFormat,
+        palette: &[[u8; 4]],
+        frames: &[(u16, u16, Vec<u8>)],
+    ) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MSF_MAGIC);
+        out.extend_from_slice(&2u16.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes()); // flags: uncompressed
+        let cw = frames.iter().map(|f| f.0).max().unwrap_or(0);
+        let ch = frames.iter().map(|f| f.1).max().unwrap_or(0);
+        out.extend_from_slice(&cw.to_le_bytes());
+        out.extend_from_slice(&ch.to_le_bytes());
+        out.extend_from_slice(&(frames.len() as u16).to_le_bytes());
+        out.extend_from_slice(&[1, 10, 0, 0, 0, 0, 0, 0, 0, 0]); // dirs, fps, anchor, reserved
+        out.push(pixel_format as u8);
+        out.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+        out.push(0);
+        for c in palette {
+            out.extend_from_slice(c);
+        }
+        let mut blob_off = 0u32;
+        for (w, h, px) in frames {
+            out.extend_from_slice(&0i16.to_le_bytes());
+            out.extend_from_slice(&0i16.to_le_bytes());
+            out.extend_from_slice(&w.to_le_bytes());
+            out.extend_from_slice(&h.to_le_bytes());
+            out.extend_from_slice(&blob_off.to_le_bytes());
+            out.extend_from_slice(&(px.len() as u32).to_le_bytes());
+            blob_off += px.len() as u32;
+        }
+        out.extend_from_slice(CHUNK_END);
+        out.extend_from_slice(&0u32.to_le_bytes());
+        for (_, _, px) in frames {
+            out.extend_from_slice(px);
+        }
+        out
+    }
+
+    #[test]
+    fn test_decode_single_frame() {
+        let palette = [[0, 0, 0, 0], [255, 0, 0, 255], [0, 0, 255, 255]];
+        let data = build_msf(
+            PixelFormat::Indexed8,
+            &palette,
+            &[(2, 1, vec![1, 1]), (1, 2, vec![2, 0]), (0, 0, vec![])],
+        );
+
+        let (w, h, px) = decode_single_frame(&data, 1).unwrap();
+        assert_eq!((w, h), (1, 2));
+        assert_eq!(px, vec![0, 0, 255, 255, 0, 0, 0, 0]);
+
+        let (w, h, px) = decode_single_frame(&data, 2).unwrap();
+        assert_eq!((w, h, px.len()), (1, 1, 4));
+
+        assert!(decode_single_frame(&data, 3).is_none());
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        let palette = [[0, 0, 0, 0], [200, 100, 0, 255], [0, 0, 100, 255]];
+        // 4×2 frame: left half red/transparent, right half blue
+        let data = build_msf(
+            PixelFormat::Indexed8,
+            &palette,
+            &[(4, 2, vec![1, 0, 2, 2, 0, 0, 2, 2]), (1, 1, vec![2])],
+        );
+
+        // Fits already: frame 0 unchanged
+        assert_eq!(
+            thumbnail_rgba(&data, 4).unwrap(),
+            decode_single_frame(&data, 0).unwrap()
+        );
+
+        // 4×2 → 2×1: transparent pixels don't darken the red box
+        let (w, h, px) = thumbnail_rgba(&data, 2).unwrap();
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(px, vec![200, 100, 0, 64, 0, 0, 100, 255]);
+
+        // Longer side clamps to max_dim, shorter side keeps at least 1 pixel
+        let (w, h, px) = thumbnail_rgba(&data, 1).unwrap();
+        assert_eq!((w, h), (1, 1));
+        assert_eq!(px[3], 159);
+
+        assert!(thumbnail_rgba(&data, 0).is_none());
+        assert!(thumbnail_rgba(&data[..20], 8).is_none());
+    }
+
+    #[test]
+    fn test_reader_matches_single_frame() {
+        let palette = [[0, 0, 0, 0], [10, 20, 30, 255]];
+        let plain = build_msf(
+            PixelFormat::Indexed8,
+            &palette,
+            &[(2, 1, vec![1, 0]), (1, 1, vec![1]), (0, 0, vec![])],
+        );
+        let blob_start = parse_msf_structure(&plain).unwrap().blob_start;
+
+        // Brotli-compressed copy: the reader decompresses it once up front
+        let mut compressed = Vec::new();
+        {
+            use std::io::Write;
+            let mut w = brotli::CompressorWriter::new(&mut compressed, 4096, 9, 22);
+            w.write_all(&plain[blob_start..]).unwrap();
+        }
+        let mut data = plain[..blob_start].to_vec();
+        data[6..8].copy_from_slice(&FLAG_BROTLI.to_le_bytes());
+        data.extend_from_slice(&compressed);
+
+        let reader = MsfReader::from_bytes(&data).unwrap();
+        assert_eq!(reader.frame_count(), 3);
+        assert_eq!(reader.header().canvas_width, 2);
+        for i in 0..3 {
+            assert_eq!(reader.frame_rgba(i), decode_single_frame(&plain, i));
+        }
+        assert!(reader.frame_rgba(3).is_none());
+    }
+
+    #[test]
+    fn test_pack_atlas_shelves() {
+        let palette = [[0, 0, 0, 0], [255, 0, 0, 255], [0, 0, 255, 255]];
+        let data = build_msf(
+            PixelFormat::Indexed8,
+            &palette,
+            &[
+                (2, 1, vec![1, 1]),
+                (1, 3, vec![2, 2, 2]),
+                (3, 2, vec![1, 0, 2, 2, 0, 1]),
+                (0, 0, vec![]),
+            ],
+        );
+
+        let atlas = pack_msf_atlas(&data, 2).unwrap();
+        // Shelf 0: 2×1 + 1×3 (height 3); shelf 1: 3×2 + empty 1×1 (height 2)
+        assert_eq!((atlas.width, atlas.height), (4, 5));
+        assert_eq!(
+            atlas.layout,
+            vec![0, 0, 2, 1, 2, 0, 1, 3, 0, 3, 3, 2, 3, 3, 1, 1]
+        );
+        assert_eq!(atlas.pixels.len(), 4 * 5 * 4);
+
+        // Every frame is copied unchanged into its cell
+        for i in 0..4 {
+            let (fw, fh, frame) = decode_single_frame(&data, i).unwrap();
+            let cell = &atlas.layout[i * 4..i * 4 + 4];
+            assert_eq!((cell[2] as usize, cell[3] as usize), (fw, fh));
+            for row in 0..fh {
+                let dst = ((cell[1] as usize + row) * atlas.width + cell[0] as usize) * 4;
+                assert_eq!(
+                    &atlas.pixels[dst..dst + fw * 4],
+                    &frame[row * fw * 4..(row + 1) * fw * 4]
+                );
+            }
+        }
+
+        assert!(pack_msf_atlas(&data, 0).is_none());
+        let single_row = pack_msf_atlas(&data, 16).unwrap();
+        assert_eq!((single_row.width, single_row.height), (7, 3));
+
+        // Two 40000-wide frames on one shelf don't fit in the u16 width
+        let wide = build_msf(
+            PixelFormat::Indexed8,
+            &palette,
+            &[(40000, 1, vec![1; 40000]), (40000, 1, vec![2; 40000])],
+        );
+        assert!(pack_msf_atlas(&wide, 2).is_none());
+        assert_eq!(pack_msf_atlas(&wide, 1).unwrap().width, 40000);
+    }
+
+    #[test]
+    fn test_get_msf_palette() {
+        let palette = [[0, 0, 0, 0], [255, 0, 0, 255]];
+        let data = build_msf(PixelFormat::Indexed8, &palette, &[(1, 1, vec![1])]);
+        assert_eq!(
+            get_msf_palette(&data),
+            Some(vec![0, 0, 0, 0, 255, 0, 0, 255])
+        );
+
+        let rgba = encode_msf_rgba(&[9, 9, 9, 255], 1, 1, 1, 10, 0, 0);
+        assert_eq!(get_msf_palette(&rgba), None);
+    }
+
+    #[test]
+    fn test_palette_override() {
+        let palette = [[0, 0, 0, 0], [255, 0, 0, 255], [0, 255, 0, 128]];
+        let data = build_msf(PixelFormat::Indexed8, &palette, &[(3, 1, vec![0, 1, 2])]);
+
+        let (px, _) = decode_canvas_frames(
+            &data,
+            &CanvasDecodeOptions {
+                palette_overrides: &[1, 0, 0, 255, 2, 9, 9, 9],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(px, vec![0, 0, 0, 0, 0, 0, 255, 255, 9, 9, 9, 128]);
+
+        // A trailing incomplete tuple is ignored
+        let (px, _) = decode_canvas_frames(
+            &data,
+            &CanvasDecodeOptions {
+                palette_overrides: &[1, 7, 7],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(&px[4..8], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_premultiplied_output() {
+        let palette = [[0, 0, 0, 0], [200, 100, 50, 255]];
+        // Indexed8Alpha8: [index, alpha] pairs, second pixel half transparent
+        let data = build_msf(
+            PixelFormat::Indexed8Alpha8,
+            &palette,
+            &[(2, 1, vec![0, 0, 1, 128])],
+        );
+
+        let (straight, _) = decode_canvas_frames(&data, &CanvasDecodeOptions::default()).unwrap();
+        assert_eq!(straight, vec![0, 0, 0, 0, 200, 100, 50, 128]);
+
+        let opts = CanvasDecodeOptions {
+            premultiply: true,
+            ..Default::default()
+        };
+        let (premul, _) = decode_canvas_frames(&data, &opts).unwrap();
+        assert_eq!(premul, vec![0, 0, 0, 0, 100, 50, 25, 128]);
+    }
+
+    #[test]
+    fn test_opacity_output() {
+        let opts = CanvasDecodeOptions {
+            opacity: Some(128),
+            ..Default::default()
+        };
+        let palette = [[0, 0, 0, 0], [200, 100, 50, 255], [10, 20, 30, 200]];
+
+        let indexed = build_msf(PixelFormat::Indexed8, &palette, &[(3, 1, vec![0, 1, 2])]);
+        let (px, _) = decode_canvas_frames(&indexed, &opts).unwrap();
+        assert_eq!(px, vec![0, 0, 0, 0, 200, 100, 50, 128, 10, 20, 30, 100]);
+
+        let alpha = build_msf(
+            PixelFormat::Indexed8Alpha8,
+            &palette,
+            &[(2, 1, vec![1, 0, 1, 64])],
+        );
+        let (px, _) = decode_canvas_frames(&alpha, &opts).unwrap();
+        assert_eq!(px, vec![0, 0, 0, 0, 200, 100, 50, 32]);
+
+        let rgba = build_msf(
+            PixelFormat::Rgba8,
+            &[],
+            &[(2, 1, vec![1, 2, 3, 0, 4, 5, 6, 255])],
+        );
+        let (px, _) = decode_canvas_frames(&rgba, &opts).unwrap();
+        assert_eq!(px, vec![1, 2, 3, 0, 4, 5, 6, 128]);
+    }
+
+    #[test]
+    fn test_empty_animation() {
+        let palette = [[0, 0, 0, 0], [10, 20, 30, 255]];
+        let mut data = build_msf(
+            PixelFormat::Indexed8,
+            &palette,
+            &[(0, 0, vec![]), (0, 0, vec![])],
+        );
+        // 2×2 canvas, and a blob that would fail to decompress if it were read
+        data[8..12].copy_from_slice(&[2, 0, 2, 0]);
+        data[6..8].copy_from_slice(&FLAG_ZSTD.to_le_bytes());
+        data.extend_from_slice(b"not zstd");
+
+        assert!(parse_msf_header(&data).unwrap().is_empty);
+        let (px, count) = decode_canvas_frames(&data, &CanvasDecodeOptions::default()).unwrap();
+        assert_eq!((px.len(), count), (2 * 2 * 4 * 2, 2));
+        assert!(px.iter().all(|&b| b == 0));
+
+        let drawn = build_msf(PixelFormat::Indexed8, &palette, &[(1, 1, vec![1])]);
+        assert!(!parse_msf_header(&drawn).unwrap().is_empty);
+    }
+
+    #[test]
+    fn test_decode_direction() {
+        // 2 directions × 2 frames, each 1×1 with a distinct palette index
+        let palette = [
+            [0, 0, 0, 0],
+            [1, 0, 0, 255],
+            [2, 0, 0, 255],
+            [3, 0, 0, 255],
+            [4, 0, 0, 255],
+        ];
+        let mut data = build_msf(
+            PixelFormat::Indexed8,
+            &palette,
+            &[
+                (1, 1, vec![1]),
+                (1, 1, vec![2]),
+                (1, 1, vec![3]),
+                (1, 1, vec![4]),
+            ],
+        );
+        data[14] = 2; // directions
+
+        let opts = CanvasDecodeOptions {
+            direction: Some(1),
+            ..Default::default()
+        };
+        let (px, count) = decode_canvas_frames(&data, &opts).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(px, vec![3, 0, 0, 255, 4, 0, 0, 255]);
+
+        let opts = CanvasDecodeOptions {
+            direction: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            decode_canvas_frames(&data, &opts),
+            Err(MsfError::DirectionOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_brotli_blob() {
+        let palette = [[0, 0, 0, 0], [10, 20, 30, 255]];
+        let plain = build_msf(PixelFormat::Indexed8, &palette, &[(2, 1, vec![1, 0])]);
+        let blob_start = parse_msf_structure(&plain).unwrap().blob_start;
+
+        let mut compressed = Vec::new();
+        {
+            use std::io::Write;
+            let mut w = brotli::CompressorWriter::new(&mut compressed, 4096, 9, 22);
+            w.write_all(&plain[blob_start..]).unwrap();
+        }
+        let mut data = plain[..blob_start].to_vec();
+        data[6..8].copy_from_slice(&FLAG_BROTLI.to_le_bytes());
+        data.extend_from_slice(&compressed);
+
+        let (px, _) = decode_canvas_frames(&data, &CanvasDecodeOptions::default()).unwrap();
+        assert_eq!(px, vec![10, 20, 30, 255, 0, 0, 0, 0]);
+        assert_eq!(msf_blob_decompressed_size(&plain), 2);
+        assert_eq!(msf_blob_decompressed_size(&data), 2);
+
+        // zstd and brotli bits are mutually exclusive
+        data[6..8].copy_from_slice(&(FLAG_ZSTD | FLAG_BROTLI).to_le_bytes());
+        assert_eq!(
+            decode_canvas_frames(&data, &CanvasDecodeOptions::default()),
+            Err(MsfError::Decompression)
+        );
+        assert_eq!(msf_blob_decompressed_size(&data), 0);
+    }
+
+    #[test]
+    fn test_row_delta_blob() {
+        let palette = [[0, 0, 0, 0], [10, 20, 30, 255], [40, 50, 60, 255]];
+        let frame = vec![1, 255, 2, 128, 2, 255, 0, 0, 1, 64, 1, 255, 2, 255, 1, 0];
+        let plain = build_msf(
+            PixelFormat::Indexed8Alpha8,
+            &palette,
+            &[(2, 2, frame.clone()), (2, 2, frame.clone())],
+        );
+        let msf = parse_msf_structure(&plain).unwrap();
+
+        // Both frames share one payload, filtered once: row[i] -= row[i - 2]
+        let mut data = plain[..msf.blob_start].to_vec();
+        data[6..8].copy_from_slice(&FLAG_ROW_DELTA.to_le_bytes());
+        let table = msf.blob_start - 8 - 2 * FRAME_ENTRY_SIZE;
+        data[table + FRAME_ENTRY_SIZE + 8..table + FRAME_ENTRY_SIZE + 12]
+            .copy_from_slice(&0u32.to_le_bytes());
+        let mut filtered = frame.clone();
+        for row in filtered.chunks_exact_mut(4) {
+            for i in (2..4).rev() {
+                row[i] = row[i].wrapping_sub(row[i - 2]);
+            }
+        }
+        assert_ne!(filtered, frame);
+        data.extend_from_slice(&filtered);
+
+        let opts = CanvasDecodeOptions::default();
+        assert_eq!(
+            decode_canvas_frames(&data, &opts).unwrap().0,
+            decode_canvas_frames(&plain, &opts).unwrap().0
+        );
+        assert_eq!(validate_msf(&data), Ok(()));
+
+        // Entries whose payload would run past the blob (or usize) are skipped
+        let huge = MsfFrameEntry {
+            offset_x: 0,
+            offset_y: 0,
+            width: u16::MAX,
+            height: u16::MAX,
+            data_offset: u32::MAX,
+            data_length: 0,
+        };
+        let mut blob = filtered.clone();
+        unfilter_row_delta(&mut blob, &[huge], 2);
+        assert_eq!(blob, filtered);
+    }
+
+    #[test]
+    fn test_decode_error_codes() {
+        let palette = [[0, 0, 0, 0], [10, 20, 30, 255]];
+        let data = build_msf(PixelFormat::Indexed8, &palette, &[(2, 1, vec![1, 0])]);
+        let decode = |d: &[u8]| decode_canvas_frames(d, &CanvasDecodeOptions::default());
+
+        // A valid sheet without frames is not an error
+        let empty = build_msf(PixelFormat::Indexed8, &palette, &[]);
+        assert_eq!(decode(&empty), Ok((vec![], 0)));
+
+        let mut bad_magic = data.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(decode(&bad_magic).unwrap_err(), MsfError::BadMagic);
+        assert_eq!(decode(&data[..20]).unwrap_err(), MsfError::TruncatedTable);
+
+        // Frame table cut off / END sentinel cut off
+        let frame_table_start = 28 + palette.len() * 4;
+        assert_eq!(
+            decode(&data[..frame_table_start + 8]).unwrap_err(),
+            MsfError::TruncatedTable
+        );
+        assert_eq!(
+            decode(&data[..frame_table_start + FRAME_ENTRY_SIZE + 4]).unwrap_err(),
+            MsfError::MissingEnd
+        );
+
+        let mut bad_format = data.clone();
+        bad_format[24] = 9;
+        assert_eq!(
+            decode(&bad_format).unwrap_err(),
+            MsfError::UnknownPixelFormat
+        );
+
+        let mut bad_zstd = data.clone();
+        bad_zstd[6..8].copy_from_slice(&FLAG_ZSTD.to_le_bytes());
+        assert_eq!(decode(&bad_zstd).unwrap_err(), MsfError::Decompression);
+    }
+
+    #[test]
+    fn test_validate_msf() {
+        let palette = [[0, 0, 0, 0], [1, 2, 3, 255]];
+        let data = build_msf(PixelFormat::Indexed8, &palette, &[(2, 2, vec![1, 1, 0, 1])]);
+        assert_eq!(validate_msf(&data), Ok(()));
+        assert_eq!(validate_msf_js(&data), "");
+
+        assert!(validate_msf(&data[..10]).unwrap_err().contains("too short"));
+
+        let mut bad = data.clone();
+        bad[0] = b'X';
+        assert!(validate_msf(&bad).unwrap_err().contains("magic"));
+
+        // Truncated blob: frame 0 needs 4 bytes
+        let err = validate_msf(&data[..data.len() - 1]).unwrap_err();
+        assert!(err.contains("frame 0"), "{}", err);
+
+        // Cut inside the END sentinel
+        let ft_end = 28 + palette.len() * 4 + FRAME_ENTRY_SIZE;
+        assert!(validate_msf(&data[..ft_end - 1])
+            .unwrap_err()
+            .contains("frame table"));
+        assert!(validate_msf(&data[..ft_end + 4])
+            .unwrap_err()
+            .contains("END"));
+
+        // Oversized chunk length must not wrap around and loop forever
+        let mut huge = data[..ft_end].to_vec();
+        huge.extend_from_slice(b"XTRA");
+        huge.extend_from_slice(&0xFFFF_FFF8u32.to_le_bytes());
+        huge.extend_from_slice(&data[ft_end..]);
+        assert!(validate_msf(&huge).unwrap_err().contains("END"));
+    }
+
+    #[test]
+    fn test_composite_frame_at_offset() {
+        let mut palette = [[0u8; 4]; 256];
+        palette[1] = [10, 20, 30, 255];
+        let entry = MsfFrameEntry {
+            offset_x: 1,
+            offset_y: 1,
+            width: 1,
+            height: 1,
+            data_offset: 1,
+            data_length: 1,
+        };
+        let mut dst = vec![0u8; 3 * 2 * 4];
+        composite_frame(
+            PixelFormat::Indexed8,
+            &palette,
+            &entry,
+            &[0, 1],
+            3,
+            &mut dst,
+        );
+        assert_eq!(&dst[16..20], &[10, 20, 30, 255]);
+        assert_eq!(dst.iter().filter(|&&b| b != 0).count(), 4);
+    }
+
+    #[test]
+    fn test_frame_region() {
+        let palette = [[0, 0, 0, 0], [10, 20, 30, 255], [40, 50, 60, 255]];
+        let mut data = build_msf(
+            PixelFormat::Indexed8,
+            &palette,
+            &[(3, 2, vec![1, 2, 1, 2, 1, 2])],
+        );
+        // 5×4 canvas with the frame at (1, 1)
+        data[8..12].copy_from_slice(&[5, 0, 4, 0]);
+        let entry_off = 28 + palette.len() * 4;
+        data[entry_off..entry_off + 4].copy_from_slice(&[1, 0, 1, 0]);
+        let reader = MsfReader::from_bytes(&data).unwrap();
+
+        let (canvas, _) = decode_canvas_frames(&data, &CanvasDecodeOptions::default()).unwrap();
+        let crop = |rx: usize, ry: usize, rw: usize, rh: usize| -> Vec<u8> {
+            let mut out = vec![0u8; rw * rh * 4];
+            for y in 0..rh {
+                for x in 0..rw {
+                    let (cx, cy) = (rx + x, ry + y);
+                    if cx < 5 && cy < 4 {
+                        let s = (cy * 5 + cx) * 4;
+                        let d = (y * rw + x) * 4;
+                        out[d..d + 4].copy_from_slice(&canvas[s..s + 4]);
+                    }
+                }
+            }
+            out
+        };
+        for region in [(0, 0, 5, 4), (2, 1, 2, 2), (0, 0, 1, 1), (3, 2, 4, 4)] {
+            assert_eq!(
+                reader.region_rgba(0, region).unwrap(),
+                crop(region.0, region.1, region.2, region.3),
+                "{:?}",
+                region
+            );
+        }
+        assert_eq!(
+            reader.region_rgba(0, (3, 2, 1, 1)).unwrap(),
+            vec![40, 50, 60, 255]
+        );
+        assert!(reader.region_rgba(1, (0, 0, 1, 1)).is_none());
+        // A window reaching past usize::MAX is clipped, not overflowed
+        assert_eq!(
+            reader.region_rgba(0, (usize::MAX - 1, 0, 2, 1)).unwrap(),
+            vec![0; 8]
+        );
+
+        // Frame at (-1, -1): only its bottom-right 2×1 part lands on the canvas
+        let mut shifted = build_msf(
+            PixelFormat::Indexed8,
+            &palette,
+            &[(3, 2, vec![1, 1, 1, 2, 2, 2])],
+        );
+        shifted[entry_off..entry_off + 4].copy_from_slice(&(-1i16).to_le_bytes().repeat(2));
+        let reader = MsfReader::from_bytes(&shifted).unwrap();
+        let mut expected = vec![40, 50, 60, 255, 40, 50, 60, 255];
+        expected.extend_from_slice(&[0; 4]);
+        assert_eq!(reader.region_rgba(0, (0, 0, 3, 1)).unwrap(), expected);
+        assert_eq!(reader.region_rgba(0, (0, 1, 3, 1)).unwrap(), vec![0; 12]);
+    }
+
+    #[test]
+    fn test_decode_flipped_around_anchor() {
+        // 4×1 canvas, anchor at x = 2 (canvas center): pixel at x = 0 → x = 3
+        let mut frame = vec![0u8; 4 * 4];
+        frame[0..4].copy_from_slice(&[1, 2, 3, 255]);
+        frame[4..8].copy_from_slice(&[4, 5, 6, 255]);
+        let data = encode_msf_rgba(&frame, 4, 1, 1, 10, 2, 0);
+
+        let (plain, _) = decode_canvas_frames(&data, &CanvasDecodeOptions::default()).unwrap();
+        assert_eq!(plain, frame);
+
+        let (flipped, count) = decode_canvas_frames(
+            &data,
+            &CanvasDecodeOptions {
+                flip_h: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(&flipped[8..12], &[4, 5, 6, 255]);
+        assert_eq!(&flipped[12..16], &[1, 2, 3, 255]);
+        assert_eq!(&flipped[0..8], &[0u8; 8]);
+    }
+
+    #[test]
+    fn test_interval_chunk() {
+        let palette = [[0, 0, 0, 0], [255, 0, 0, 255]];
+        let plain = build_msf(PixelFormat::Indexed8, &palette, &[(2, 1, vec![1, 0])]);
+        assert_eq!(parse_msf_header(&plain).unwrap().interval_ms, 0);
+
+        // Insert an unknown chunk and IVAL before the END sentinel
+        let end_pos = 28 + palette.len() * 4 + FRAME_ENTRY_SIZE;
+        assert_eq!(&plain[end_pos..end_pos + 4], CHUNK_END);
+        let mut ext = Vec::new();
+        ext.extend_from_slice(b"XTRA");
+        ext.extend_from_slice(&3u32.to_le_bytes());
+        ext.extend_from_slice(&[9, 9, 9]);
+        ext.extend_from_slice(CHUNK_IVAL);
+        ext.extend_from_slice(&2u32.to_le_bytes());
+        ext.extend_from_slice(&66u16.to_le_bytes());
+        let mut data = plain.clone();
+        data.splice(end_pos..end_pos, ext);
+
+        let header = parse_msf_header(&data).unwrap();
+        assert_eq!(header.interval_ms, 66);
+        assert_eq!(header.fps, 10);
+        assert_eq!(
+            decode_single_frame(&data, 0).unwrap(),
+            decode_single_frame(&plain, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_verify_crc() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let palette = [[0, 0, 0, 0], [255, 0, 0, 255]];
+        let plain = build_msf(PixelFormat::Indexed8, &palette, &[(2, 2, vec![1, 0, 0, 1])]);
+        // No CRC: nothing to check
+        assert!(verify_msf_crc(&plain));
+
+        let end_pos = 28 + palette.len() * 4 + FRAME_ENTRY_SIZE;
+        let blob_crc = crc32(&plain[end_pos + 8..]);
+        let mut data = plain.clone();
+        data[6..8].copy_from_slice(&FLAG_CRC.to_le_bytes());
+        let mut chunk = CHUNK_CRC3.to_vec();
+        chunk.extend_from_slice(&4u32.to_le_bytes());
+        chunk.extend_from_slice(&blob_crc.to_le_bytes());
+        data.splice(end_pos..end_pos, chunk);
+        assert!(verify_msf_crc(&data));
+        assert_eq!(
+            decode_single_frame(&data, 0),
+            decode_single_frame(&plain, 0)
+        );
+
+        // Tampered blob byte
+        let mut tampered = data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(!verify_msf_crc(&tampered));
+
+        // Flag set but chunk missing
+        let mut missing = plain.clone();
+        missing[6..8].copy_from_slice(&FLAG_CRC.to_le_bytes());
+        assert!(!verify_msf_crc(&missing));
+        assert!(!verify_msf_crc(&data[..20]));
+    }
+
+    #[test]
+    fn test_tags_chunk() {
+        let palette = [[0, 0, 0, 0], [255, 0, 0, 255]];
+        let plain = build_msf(PixelFormat::Indexed8, &palette, &[(2, 1, vec![1, 0])]);
+        assert!(get_msf_tags(&plain).is_empty());
+
+        let mut payload = Vec::new();
+        for (name, start, end, looping) in [("idle", 0u16, 3u16, 1u8), ("攻击", 4, 9, 0)] {
+            payload.push(name.len() as u8);
+            payload.extend_from_slice(name.as_bytes());
+            payload.extend_from_slice(&start.to_le_bytes());
+            payload.extend_from_slice(&end.to_le_bytes());
+            payload.push(looping);
+        }
+        // Truncated trailing entry is ignored
+        payload.extend_from_slice(&[4, b'w', b'a']);
+        let end_pos = 28 + palette.len() * 4 + FRAME_ENTRY_SIZE;
+        let mut chunk = CHUNK_TAGS.to_vec();
+        chunk.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(&payload);
+        let mut data = plain.clone();
+        data.splice(end_pos..end_pos, chunk);
+
+        let tag = |name: &str, start_frame, end_frame, looping| MsfTag {
+            name: name.to_string(),
+            start_frame,
+            end_frame,
+            looping,
+        };
+        assert_eq!(
+            get_msf_tags(&data),
+            vec![tag("idle", 0, 3, true), tag("攻击", 4, 9, false)]
+        );
+        assert_eq!(
+            decode_single_frame(&data, 0),
+            decode_single_frame(&plain, 0)
+        );
+        assert!(get_msf_tags(&data[..20]).is_empty());
+    }
+
+    #[test]
+    fn test_individual_frames_flip_y() {
+        let palette = [
+            [0, 0, 0, 0],
+            [255, 0, 0, 255],
+            [0, 255, 0, 255],
+            [0, 0, 255, 255],
+        ];
+        // 2×3 frame: rows [1 1] [2 0] [3 3]; 1×1 frame; empty frame
+        let data = build_msf(
+            PixelFormat::Indexed8,
+            &palette,
+            &[
+                (2, 3, vec![1, 1, 2, 0, 3, 3]),
+                (1, 1, vec![2]),
+                (0, 0, vec![]),
+            ],
+        );
+        let px = |i: usize| palette[i];
+        let rows =
+            |r: [[usize; 2]; 3]| -> Vec<u8> { r.iter().flatten().flat_map(|&i| px(i)).collect() };
+
+        let plain = decode_individual(&data, false, false).unwrap();
+        let flipped = decode_individual(&data, false, true).unwrap();
+        assert_eq!(plain.pixels[..24], rows([[1, 1], [2, 0], [3, 3]]));
+        assert_eq!(flipped.pixels[..24], rows([[3, 3], [2, 0], [1, 1]]));
+        assert_eq!(flipped.pixels[24..], plain.pixels[24..]);
+        assert_eq!(flipped.frame_sizes, plain.frame_sizes);
+        assert_eq!(flipped.frame_offsets, plain.frame_offsets);
+
+        // Tight crop drops nothing here (corners are opaque), offsets stay top-down
+        let cropped = decode_individual(&data, true, true).unwrap();
+        assert_eq!(cropped.pixels[..24], flipped.pixels[..24]);
+        assert_eq!(cropped.canvas_offsets, plain.canvas_offsets);
+    }
+
+    /// Convert an MPC to an Indexed8 MSF2 sheet (palette entry 0 transparent,
+    /// frames at their own sizes) and decode it back frame by frame
+    #[test]
+    fn test_individual_frames_from_mpc() {
+        // MPC: 2-colour palette (BGRA), frames of different sizes
+        let rles: [(u32, u32, Vec<u8>); 3] = [
+            (2, 1, vec![0x02, 0, 1]),
+            (3, 2, vec![0x81, 0x02, 1, 1, 0x82, 0x01, 0]),
+            (1, 1, vec![0x81]),
+        ];
+        let mut mpc = vec![0u8; 128];
+        mpc[..15].copy_from_slice(b"MPC File Ver2.0");
+        mpc[64 + 12..64 + 16].copy_from_slice(&(rles.len() as u32).to_le_bytes());
+        mpc[64 + 20..64 + 24].copy_from_slice(&2u32.to_le_bytes());
+        mpc.extend_from_slice(&[0, 0, 255, 0, 255, 0, 0, 0]);
+        let mut blob = Vec::new();
+        for (w, h, rle) in &rles {
+            mpc.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+            blob.extend_from_slice(&(20 + rle.len() as u32).to_le_bytes());
+            blob.extend_from_slice(&w.to_le_bytes());
+            blob.extend_from_slice(&h.to_le_bytes());
+            blob.extend_from_slice(&[0u8; 8]);
+            blob.extend_from_slice(rle);
+        }
+        mpc.extend_from_slice(&blob);
+        mpc.resize(mpc.len().max(160), 0);
+
+        // Re-index the decoded RGBA: 0 = transparent, then each distinct colour
+        let decoded: Vec<_> = (0..rles.len())
+            .map(|i| crate::mpc_decoder::decode_single_frame(&mpc, i).unwrap())
+            .collect();
+        let mut palette = vec![[0u8; 4]];
+        let frames = decoded
+            .iter()
+            .map(|(w, h, rgba)| {
+                let data = rgba
+                    .chunks_exact(4)
+                    .map(|px| {
+                        let px = [px[0], px[1], px[2], px[3]];
+                        if px[3] == 0 {
+                            return 0;
+                        }
+                        let idx = palette.iter().position(|c| *c == px).unwrap_or_else(|| {
+                            palette.push(px);
+                            palette.len() - 1
+                        });
+                        idx as u8
+                    })
+                    .collect();
+                MsfEncodeFrame {
+                    offset_x: 0,
+                    offset_y: 0,
+                    width: *w as u16,
+                    height: *h as u16,
+                    data,
+                }
+            })
+            .collect();
+        let msf = encode_msf(&MsfEncodeInput {
+            canvas_width: 3,
+            canvas_height: 2,
+            directions: 1,
+            fps: 10,
+            anchor_x: 0,
+            anchor_y: 0,
+            pixel_format: PixelFormat::Indexed8,
+            palette,
+            frames,
+        });
+        assert_eq!(&msf[..4], b"MSF2");
+
+        let out = decode_individual(&msf, false, false).unwrap();
+        assert_eq!(out.frame_sizes, vec![2, 1, 3, 2, 1, 1]);
+        for (i, (_, _, rgba)) in decoded.iter().enumerate() {
+            let start = out.frame_offsets[i] as usize;
+            assert_eq!(
+                &out.pixels[start..start + rgba.len()],
+                &rgba[..],
+                "frame {}",
+                i
+            );
+        }
+
+        // Tight crop: frame 1 keeps only its 2×2 opaque block at (1, 0)
+        let cropped = decode_individual(&msf, true, false).unwrap();
+        assert_eq!(&cropped.frame_sizes[2..4], &[2, 2]);
+        assert_eq!(&cropped.canvas_offsets[2..4], &[1, 0]);
+        assert_eq!(&cropped.frame_sizes[4..6], &[1, 1]);
+    }
+
+    #[test]
+    fn test_encode_msf_rgba_round_trip() {
+        // 4×3 canvas, 2 frames: one 2×1 red bar at (1, 1), one fully transparent
+        let (cw, ch) = (4usize, 3usize);
+        let mut frames = vec![0u8; cw * ch * 4 * 2];
+        for x in 1..3 {
+            let p = (cw + x) * 4;
+            frames[p..p + 4].copy_from_slice(&[255, 0, 0, 255]);
+        }
+
+        let data = encode_msf_rgba(&frames, cw as u16, ch as u16, 1, 12, 2, 3);
+        let header = parse_msf_header(&data).unwrap();
+        assert_eq!((header.canvas_width, header.canvas_height), (4, 3));
+        assert_eq!((header.frame_count, header.fps), (2, 12));
+        assert_eq!((header.anchor_x, header.anchor_y), (2, 3));
+        assert_eq!(header.pixel_format, PixelFormat::Rgba8 as u8);
+
+        let msf = parse_msf_structure(&data).unwrap();
+        assert_eq!(msf.flags & FLAG_ZSTD, cfg!(feature = "zstd-encode") as u16);
+        assert_eq!(
+            get_msf_frame_table(&data),
+            Some(vec![1, 1, 2, 1, 0, 0, 0, 0])
+        );
+
+        let (w, h, px) = decode_single_frame(&data, 0).unwrap();
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(px, vec![255, 0, 0, 255, 255, 0, 0, 255]);
+        assert_eq!(decode_single_frame(&data, 1).unwrap().2, vec![0u8; 4]);
+    }
 }
@@ -10,8 +10,9 @@
 //! - PathStraightLine: 直线，忽略障碍物（用于飞行者）
 
 use hashbrown::{HashMap, HashSet};
+use std::cell::RefCell;
 use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, VecDeque};
 use wasm_bindgen::prelude::*;
 
 // === Debug logging (only in WASM + debug builds; no-op in release & native tests) ===
@@ -66,6 +67,37 @@ pub enum PathType {
     PathStraightLine = 4,
 }
 
+/// 代价度量：A* 的 g_cost 累加与启发函数（以及贪心搜索的优先级）使用的距离
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CostMetric {
+    /// 等距像素距离（默认）：E/W 一步 64，N/S 一步 32，斜向一步约 35.8
+    Pixel = 0,
+    /// 菱形格坐标下的八方向距离：沿 SE/SW 轴一步 1.0，N/S/E/W 一步 √2
+    Octile = 1,
+    /// 瓦片坐标 (x, y) 的曼哈顿距离 |dx| + |dy|，用于非等距的俯视地图
+    Manhattan = 2,
+    /// 瓦片坐标 (x, y) 的切比雪夫距离 max(|dx|, |dy|)，用于非等距的俯视地图
+    Chebyshev = 3,
+}
+
+impl CostMetric {
+    fn distance(self, a: &Vec2, b: &Vec2) -> f64 {
+        match self {
+            CostMetric::Pixel => a.pixel_distance(b),
+            CostMetric::Octile => a.octile_distance(b),
+            CostMetric::Manhattan => a.manhattan_distance(b),
+            CostMetric::Chebyshev => a.chebyshev_distance(b),
+        }
+    }
+}
+
+/// 间距惩罚：距障碍 d 格（d ≤ clearance）时，该步代价乘以 1 + (clearance + 1 - d) × 此值
+const CLEARANCE_WEIGHT: f64 = 1.0;
+
+/// 间距上限：u8::MAX 是距离场中“超出间距”的标记，间距不能取到它
+const MAX_CLEARANCE: u8 = u8::MAX - 1;
+
 /// 2D 向量/位置
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 struct Vec2 {
@@ -82,7 +114,7 @@ impl Vec2 {
     /// 与 TS coordinate.ts tileToPixel 完全一致：
     ///   baseX = (row % 2) * 32 + TILE_WIDTH * col  (TILE_WIDTH = 64)
     ///   baseY = 16 * row
-    fn to_pixel(&self) -> (f64, f64) {
+    fn to_pixel(self) -> (f64, f64) {
         let col = self.x;
         let row = self.y;
         let px = ((row & 1) * 32 + 64 * col) as f64;
@@ -98,13 +130,64 @@ impl Vec2 {
         let dy = py2 - py1;
         (dx * dx + dy * dy).sqrt()
     }
+
+    /// 转换为以 SE/SW 为轴的菱形格坐标（SE 方向 u+1，SW 方向 v+1）
+    fn to_iso(self) -> (i32, i32) {
+        let odd = self.y & 1;
+        (self.x + (self.y + odd) / 2, (self.y - odd) / 2 - self.x)
+    }
+
+    /// 菱形格坐标下的八方向距离：轴向一步 1.0，对角一步 √2
+    fn octile_distance(&self, other: &Vec2) -> f64 {
+        let (u1, v1) = self.to_iso();
+        let (u2, v2) = other.to_iso();
+        let du = (u2 - u1).abs() as f64;
+        let dv = (v2 - v1).abs() as f64;
+        du.max(dv) + (std::f64::consts::SQRT_2 - 1.0) * du.min(dv)
+    }
+
+    /// 瓦片坐标下的曼哈顿距离
+    /// 每步代价即两端的该距离，满足三角不等式，作启发函数时可采纳且一致
+    fn manhattan_distance(&self, other: &Vec2) -> f64 {
+        ((other.x - self.x).abs() + (other.y - self.y).abs()) as f64
+    }
+
+    /// 瓦片坐标下的切比雪夫距离，可采纳性同 manhattan_distance
+    fn chebyshev_distance(&self, other: &Vec2) -> f64 {
+        (other.x - self.x).abs().max((other.y - self.y).abs()) as f64
+    }
 }
 
+/// A* 平局打破：到达同一格的 g_cost 相差不超过此值时视为等长，取离起点-终点连线更近的前驱
+///
+/// 开阔地图上大量路径等长，只认先到的前驱时，路径会先沿一个方向走到底再斜插，
+/// 或斜向/正向交替形成锯齿。只在等长前驱之间取舍，不改动 f_cost 与扩展顺序，
+/// 启发函数保持可采纳，路径长度与 TS findPathPerfect 一致
+const TIE_BREAK_EPSILON: f64 = 1e-6;
+
+/// 节点到 start→end 连线的像素垂直距离（start == end 时为 0）
+fn line_deviation(start: Vec2, end: Vec2, node: Vec2) -> f64 {
+    let (sx, sy) = start.to_pixel();
+    let (ex, ey) = end.to_pixel();
+    let (nx, ny) = node.to_pixel();
+    let (lx, ly) = (sx - ex, sy - ey);
+    let len = (lx * lx + ly * ly).sqrt();
+    if len == 0.0 {
+        return 0.0;
+    }
+    // |(start - end) × (node - end)| / |start - end|
+    (lx * (ny - ey) - ly * (nx - ex)).abs() / len
+}
+
+/// 启用 8 个"长对角"方向所需的 can_move_direction_count
+const LONG_MOVE_DIRECTION_COUNT: i32 = 16;
+
 /// A* 节点
 #[derive(Clone, Copy)]
 struct PathNode {
     tile: Vec2,
     f_cost: f64, // g + h
+    #[allow(dead_code)]
     g_cost: f64, // 从起点到当前节点的代价
 }
 
@@ -132,6 +215,58 @@ impl Ord for PathNode {
     }
 }
 
+/// 地图 barrier 字节（MAP / MMF 障碍层）：人物不可通行的取值
+/// 0x40 Trans、0x60 CanOverTrans（武功可穿透）与 0x80 Obstacle、0xA0 CanOverObstacle；
+/// 其余取值（0x00 None、0x20 CanOver 等）可通行。与原版一致按精确值比较，低位噪声视为可通行
+const BARRIER_TRANS: u8 = 0x40;
+const BARRIER_CAN_OVER_TRANS: u8 = 0x60;
+/// 硬障碍（武功也不可穿透）
+const BARRIER_OBSTACLE: u8 = 0x80;
+const BARRIER_CAN_OVER_OBSTACLE: u8 = 0xA0;
+
+/// export_bitmaps 格式魔数与版本
+const BITMAPS_MAGIC: &[u8; 4] = b"PFB1";
+const BITMAPS_VERSION: u16 = 1;
+/// 头部: magic(4) + version u16 + reserved u16 + map_width u32 + map_height u32
+const BITMAPS_HEADER_SIZE: usize = 16;
+
+/// 搜索用的复用缓冲区：每次搜索前 clear()，保留已分配的容量，
+/// 避免 NPC 密集的帧里反复分配 WASM 堆内存
+#[derive(Default)]
+struct SearchScratch {
+    frontier: BinaryHeap<PathNode>,
+    came_from: HashMap<Vec2, Vec2>,
+    cost_so_far: HashMap<Vec2, f64>,
+}
+
+impl SearchScratch {
+    fn clear(&mut self) {
+        self.frontier.clear();
+        self.came_from.clear();
+        self.cost_so_far.clear();
+    }
+}
+
+/// find_path_capped 的结果
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct CappedPath {
+    /// 起点方向的前若干点 [x1, y1, x2, y2, ...]，空数组表示无路径
+    pub path: Vec<i32>,
+    /// 完整路径超过 max_path_len，path 只是前一段
+    pub truncated: bool,
+}
+
+/// find_path_debug 的结果
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct DebugPath {
+    /// 与 find_path 相同的路径 [x1, y1, x2, y2, ...]
+    pub path: Vec<i32>,
+    /// A* 写入过 cost_so_far 的所有格子 [x, y, g_cost, ...]，顺序不定
+    pub explored: Vec<f32>,
+}
+
 /// 寻路器状态（可复用以减少内存分配）
 #[wasm_bindgen]
 pub struct PathFinder {
@@ -148,28 +283,112 @@ pub struct PathFinder {
     /// 动态障碍物位图（hasObstacle）：NPC / Obj / Magic 占用的格子
     /// 由 TS 侧每帧更新
     dynamic_bitmap: Vec<u8>,
+    /// 开放边界：越界邻居不生成，但不视为硬障碍（不触发对角线阻挡）
+    open_borders: bool,
+    /// 代价度量（默认 Pixel）
+    cost_metric: CostMetric,
+    /// 允许贴角穿过：true（默认）时只有硬障碍阻挡相邻的对角移动，
+    /// false 时任意障碍都阻挡
+    allow_corner_cutting: bool,
+    /// search_simple / search_perfect 的复用缓冲区（&self 方法内借用）
+    scratch: RefCell<SearchScratch>,
+    /// 与障碍保持的最小间距（格），0 = 关闭
+    clearance: u8,
+    /// 间距内的格子直接视为不可走，而不只是提高代价
+    clearance_strict: bool,
+    /// 各格到最近静态障碍的距离（按 get_neighbors 步数，超过 clearance 记为 u8::MAX），
+    /// 首次 A* 搜索时按需计算，障碍变化时清空
+    clearance_field: RefCell<Option<Vec<u8>>>,
 }
 
 #[wasm_bindgen]
 impl PathFinder {
     /// 创建新的寻路器
+    ///
+    /// 宽高小于 1 时按 1 处理（得到 1×1 地图），避免位图尺寸计算出错
     #[wasm_bindgen(constructor)]
     pub fn new(map_width: i32, map_height: i32) -> Self {
-        let size = ((map_width * map_height + 7) / 8) as usize;
+        let map_width = map_width.max(1);
+        let map_height = map_height.max(1);
+        let size = (map_width as usize * map_height as usize).div_ceil(8);
         Self {
             map_width,
             map_height,
             obstacle_bitmap: vec![0; size],
             hard_obstacle_bitmap: vec![0; size],
             dynamic_bitmap: vec![0; size],
+            open_borders: false,
+            cost_metric: CostMetric::Pixel,
+            allow_corner_cutting: true,
+            scratch: RefCell::new(SearchScratch::default()),
+            clearance: 0,
+            clearance_strict: false,
+            clearance_field: RefCell::new(None),
         }
     }
 
+    /// 设置开放边界（默认关闭，越界格子视为硬障碍）
+    ///
+    /// 开启后单位可沿地图最外圈移动：越界邻居只是不参与扩展，
+    /// 不会再阻挡贴边格子的 S/N/E/W 方向
+    #[wasm_bindgen]
+    pub fn set_open_borders(&mut self, open: bool) {
+        self.open_borders = open;
+        // 距离场把地图边缘当作障碍与否取决于此开关
+        self.invalidate_clearance();
+    }
+
+    /// 设置代价度量（默认 Pixel）
+    ///
+    /// 同时作用于 A* 的 g_cost 累加、启发函数和贪心搜索的优先级；
+    /// reachable_tiles 的 max_cost 与直线路径仍按像素计算
+    #[wasm_bindgen]
+    pub fn set_cost_metric(&mut self, metric: CostMetric) {
+        self.cost_metric = metric;
+    }
+
+    /// 设置是否允许贴角穿过（默认允许）
+    ///
+    /// 关闭后，对角移动（S/W/N/E）两侧的 SW/NW/NE/SE 邻格中任一为障碍（软硬皆可）
+    /// 即阻挡，单位不能从墙角缝隙中穿过；开启时只有硬障碍会阻挡
+    #[wasm_bindgen]
+    pub fn set_allow_corner_cutting(&mut self, allow: bool) {
+        self.allow_corner_cutting = allow;
+    }
+
+    /// 设置与障碍保持的最小间距（格，默认 0 = 关闭），用于不宜贴墙走的单位
+    ///
+    /// Perfect 系列（A*）寻路中，距静态障碍（obstacle_bitmap，
+    /// 非开放边界时也包括地图边缘）不超过 tiles 格的格子代价更高，越近越高，
+    /// 走廊中的路径因此居中。起点和终点不受影响；超过 254 按 254 处理
+    #[wasm_bindgen]
+    pub fn set_clearance(&mut self, tiles: u8) {
+        self.clearance = tiles.min(MAX_CLEARANCE);
+        self.invalidate_clearance();
+    }
+
+    /// 设置间距是否为硬性要求（默认否）：开启后间距内的格子不可走，
+    /// 窄于 2 × clearance + 1 的通道将无路可走
+    #[wasm_bindgen]
+    pub fn set_clearance_strict(&mut self, strict: bool) {
+        self.clearance_strict = strict;
+    }
+
+    /// 丢弃缓存的障碍距离场，下次 A* 搜索时重新计算
+    ///
+    /// set_obstacle / apply_obstacle_delta / import_bitmaps 会自动调用；
+    /// 通过 obstacle_bitmap_ptr 直接写入位图后需手动调用
+    #[wasm_bindgen]
+    pub fn invalidate_clearance(&self) {
+        self.clearance_field.borrow_mut().take();
+    }
+
     /// 设置单个格子的障碍状态（仅测试用，运行时通过共享内存指针写入）
     pub fn set_obstacle(&mut self, x: i32, y: i32, is_obstacle: bool, is_hard: bool) {
         if x < 0 || y < 0 || x >= self.map_width || y >= self.map_height {
             return;
         }
+        self.invalidate_clearance();
         let index = (y * self.map_width + x) as usize;
         let byte_index = index / 8;
         let bit_index = index % 8;
@@ -189,6 +408,93 @@ impl PathFinder {
         }
     }
 
+    /// 批量修改静态障碍（开门、移动平台等），原地写入位图，不重新分配
+    ///
+    /// changes 为 [x1, y1, flags1, x2, y2, flags2, ...]，flags bit 0 = 障碍，
+    /// bit 1 = 硬障碍；越界格子和末尾不完整的三元组被忽略
+    #[wasm_bindgen]
+    pub fn apply_obstacle_delta(&mut self, changes: &[i32]) {
+        for c in changes.chunks_exact(3) {
+            self.set_obstacle(c[0], c[1], c[2] & 1 != 0, c[2] & 2 != 0);
+        }
+    }
+
+    /// 由每格的 barrier 字节（行优先，与 MMF 障碍层一致）一次性重建两张静态障碍位图
+    ///
+    /// Trans / CanOverTrans / Obstacle / CanOverObstacle 为障碍，其中 Obstacle /
+    /// CanOverObstacle 同时为硬障碍（取值见 BARRIER_* 常量）。barriers 短于格子数时
+    /// 缺少的格子视为可通行，多余的字节被忽略。原地写入，位图指针保持有效
+    #[wasm_bindgen]
+    pub fn set_obstacles_from_barriers(&mut self, barriers: &[u8]) {
+        self.obstacle_bitmap.fill(0);
+        self.hard_obstacle_bitmap.fill(0);
+        let tiles = self.map_width as usize * self.map_height as usize;
+        for (index, &barrier) in barriers.iter().take(tiles).enumerate() {
+            let mask = 1 << (index % 8);
+            match barrier {
+                BARRIER_OBSTACLE | BARRIER_CAN_OVER_OBSTACLE => {
+                    self.obstacle_bitmap[index / 8] |= mask;
+                    self.hard_obstacle_bitmap[index / 8] |= mask;
+                }
+                BARRIER_TRANS | BARRIER_CAN_OVER_TRANS => {
+                    self.obstacle_bitmap[index / 8] |= mask;
+                }
+                _ => {}
+            }
+        }
+        self.invalidate_clearance();
+    }
+
+    /// 导出静态障碍位图，供加载器跨会话缓存
+    ///
+    /// 格式：16 字节头（魔数 PFB1、版本、保留、地图宽高）
+    /// + obstacle_bitmap + hard_obstacle_bitmap；动态障碍位图每帧重建，不导出
+    #[wasm_bindgen]
+    pub fn export_bitmaps(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(BITMAPS_HEADER_SIZE + self.obstacle_bitmap.len() * 2);
+        out.extend_from_slice(BITMAPS_MAGIC);
+        out.extend_from_slice(&BITMAPS_VERSION.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(&(self.map_width as u32).to_le_bytes());
+        out.extend_from_slice(&(self.map_height as u32).to_le_bytes());
+        out.extend_from_slice(&self.obstacle_bitmap);
+        out.extend_from_slice(&self.hard_obstacle_bitmap);
+        out
+    }
+
+    /// 从 export_bitmaps 的输出恢复静态障碍位图
+    ///
+    /// 原地复制，obstacle_bitmap_ptr 等指针保持有效。魔数 / 版本不匹配、
+    /// 地图宽高与当前寻路器不同或长度不符时返回 false，位图保持不变
+    #[wasm_bindgen]
+    pub fn import_bitmaps(&mut self, data: &[u8]) -> bool {
+        let size = self.obstacle_bitmap.len();
+        if data.len() != BITMAPS_HEADER_SIZE + size * 2 || &data[0..4] != BITMAPS_MAGIC {
+            return false;
+        }
+        let read_u32 = |off: usize| {
+            u32::from_le_bytes([data[off], data[off + 1], data[off + 2], data[off + 3]])
+        };
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != BITMAPS_VERSION
+            || read_u32(8) != self.map_width as u32
+            || read_u32(12) != self.map_height as u32
+        {
+            return false;
+        }
+
+        let bitmaps = &data[BITMAPS_HEADER_SIZE..];
+        self.obstacle_bitmap.copy_from_slice(&bitmaps[..size]);
+        self.hard_obstacle_bitmap.copy_from_slice(&bitmaps[size..]);
+        self.invalidate_clearance();
+        true
+    }
+
+    /// 检查格子是否在地图范围内
+    fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.map_width && y < self.map_height
+    }
+
     /// 检查格子是否为障碍
     fn is_obstacle(&self, x: i32, y: i32) -> bool {
         if x < 0 || y < 0 || x >= self.map_width || y >= self.map_height {
@@ -238,6 +544,54 @@ impl PathFinder {
         }
     }
 
+    /// 从所有静态障碍（及非开放边界时的地图外圈）出发 BFS，
+    /// 得到每格到最近障碍的步数；只展开到 clearance 为止
+    fn build_clearance_field(&self) -> Vec<u8> {
+        let width = self.map_width as usize;
+        let mut field = vec![u8::MAX; width * self.map_height as usize];
+        let mut queue = VecDeque::new();
+        let index = |p: Vec2| p.y as usize * width + p.x as usize;
+
+        for y in 0..self.map_height {
+            for x in 0..self.map_width {
+                if self.is_obstacle(x, y) {
+                    field[index(Vec2::new(x, y))] = 0;
+                    queue.push_back(Vec2::new(x, y));
+                }
+            }
+        }
+        // 地图外视为障碍：贴边格子距离 1（排在所有距离 0 的格子之后，BFS 仍按层推进）
+        if !self.open_borders {
+            for y in 0..self.map_height {
+                for x in 0..self.map_width {
+                    let pos = Vec2::new(x, y);
+                    let touches_edge = self
+                        .get_neighbors(pos)
+                        .iter()
+                        .any(|n| !self.in_bounds(n.x, n.y));
+                    if touches_edge && field[index(pos)] > 1 {
+                        field[index(pos)] = 1;
+                        queue.push_back(pos);
+                    }
+                }
+            }
+        }
+
+        while let Some(pos) = queue.pop_front() {
+            let next = field[index(pos)] + 1;
+            if next > self.clearance {
+                continue;
+            }
+            for n in self.get_neighbors(pos) {
+                if self.in_bounds(n.x, n.y) && field[index(n)] > next {
+                    field[index(n)] = next;
+                    queue.push_back(n);
+                }
+            }
+        }
+        field
+    }
+
     /// 返回 dynamic_bitmap 在 WASM 内存中的指针（用于 JS 零拷贝写入）
     #[wasm_bindgen]
     pub fn dynamic_bitmap_ptr(&self) -> *const u8 {
@@ -262,9 +616,30 @@ impl PathFinder {
         self.obstacle_bitmap.len()
     }
 
+    /// 地图宽度（列数，构造时小于 1 的值已按 1 处理）
+    #[wasm_bindgen]
+    pub fn width(&self) -> i32 {
+        self.map_width
+    }
+
+    /// 地图高度（行数）
+    #[wasm_bindgen]
+    pub fn height(&self) -> i32 {
+        self.map_height
+    }
+
+    /// obstacle_bitmap 中置位的格子数，可用来粗略确认位图确实已写入
+    #[wasm_bindgen]
+    pub fn obstacle_count(&self) -> u32 {
+        self.obstacle_bitmap.iter().map(|b| b.count_ones()).sum()
+    }
+
     /// A* 寻路主入口
     /// 同时考虑静态障碍物（obstacle_bitmap）和动态障碍物（dynamic_bitmap）
     /// 返回路径数组 [x1, y1, x2, y2, ...]，空数组表示无路径
+    ///
+    /// can_move_direction_count 为 1/2/4/8；为 16 时 A* 模式（Simple/Perfect）额外
+    /// 允许长对角移动（见 get_long_neighbors），路径中相邻两点可能不是 8 邻居
     #[wasm_bindgen]
     pub fn find_path(
         &self,
@@ -275,46 +650,511 @@ impl PathFinder {
         path_type: PathType,
         can_move_direction_count: i32,
     ) -> Vec<i32> {
-        #[cfg(all(target_arch = "wasm32", debug_assertions))]
-        let t0 = perf_now();
+        self.find_path_with_avoid(
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            path_type,
+            can_move_direction_count,
+            &HashSet::new(),
+            0.0,
+            false,
+        )
+    }
 
+    /// 与 find_path 相同，但路径按终点→起点顺序返回：
+    /// [end_x, end_y, ..., start_x, start_y]
+    ///
+    /// 适合从数组末尾弹出下一步的移动队列，省去 JS 侧每次反转
+    #[wasm_bindgen]
+    pub fn find_path_reversed(
+        &self,
+        start_x: i32,
+        start_y: i32,
+        end_x: i32,
+        end_y: i32,
+        path_type: PathType,
+        can_move_direction_count: i32,
+    ) -> Vec<i32> {
+        self.find_path_with_avoid(
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            path_type,
+            can_move_direction_count,
+            &HashSet::new(),
+            0.0,
+            true,
+        )
+    }
+
+    /// 与 find_path 相同，但路径总代价（按 cost_metric，默认像素）超过 max_cost 时
+    /// 返回空数组，让 NPC 放弃绕得太远的路线而原地待命
+    ///
+    /// 与 max_try 不同：max_try 限制扩展的节点数，这里限制路径长度。Perfect 系列
+    /// 在搜索中即剪掉 g_cost + 启发值超过 max_cost 的格子，提前结束；
+    /// 其余类型先照常寻路，再按路径逐段累计代价判断
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_path_bounded(
+        &self,
+        start_x: i32,
+        start_y: i32,
+        end_x: i32,
+        end_y: i32,
+        path_type: PathType,
+        can_move_direction_count: i32,
+        max_cost: f64,
+    ) -> Vec<i32> {
         let start = Vec2::new(start_x, start_y);
         let end = Vec2::new(end_x, end_y);
+        match path_type {
+            PathType::PerfectMaxNpcTry | PathType::PerfectMaxPlayerTry => {
+                if start == end || self.is_obstacle(end_x, end_y) {
+                    return vec![];
+                }
+                let mut scratch = self.scratch.borrow_mut();
+                self.search_perfect(
+                    &mut scratch,
+                    &[start],
+                    end,
+                    Self::max_try_count(path_type),
+                    can_move_direction_count,
+                    &HashSet::new(),
+                    0.0,
+                    max_cost,
+                    false,
+                );
+                self.reconstruct_path(&scratch.came_from, &[start], end, false)
+            }
+            _ => {
+                let path = self.find_path(
+                    start_x,
+                    start_y,
+                    end_x,
+                    end_y,
+                    path_type,
+                    can_move_direction_count,
+                );
+                let cost: f64 = path
+                    .chunks_exact(2)
+                    .map(|p| Vec2::new(p[0], p[1]))
+                    .collect::<Vec<_>>()
+                    .windows(2)
+                    .map(|w| self.cost_metric.distance(&w[0], &w[1]))
+                    .sum();
+                if cost > max_cost {
+                    vec![]
+                } else {
+                    path
+                }
+            }
+        }
+    }
 
-        // 起点终点相同
-        if start == end {
+    /// 与 find_path 相同，同时返回 A* 探索过的格子及其 g_cost，供寻路调试器绘制热力图
+    ///
+    /// 只有 Perfect 系列会填充 explored；其余类型及起点即终点、终点为障碍等
+    /// 未进入搜索的情况下 explored 为空
+    #[wasm_bindgen]
+    pub fn find_path_debug(
+        &self,
+        start_x: i32,
+        start_y: i32,
+        end_x: i32,
+        end_y: i32,
+        path_type: PathType,
+        can_move_direction_count: i32,
+    ) -> DebugPath {
+        // 先清空，避免未进入 search_perfect 时读到上一次搜索的残留
+        self.scratch.borrow_mut().clear();
+        let path = self.find_path(
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            path_type,
+            can_move_direction_count,
+        );
+        let explored = self
+            .scratch
+            .borrow()
+            .cost_so_far
+            .iter()
+            .flat_map(|(tile, &g_cost)| [tile.x as f32, tile.y as f32, g_cost as f32])
+            .collect();
+        DebugPath { path, explored }
+    }
+
+    /// 与 find_path 相同，但最多返回起点方向的前 max_path_len 个点（含起点），
+    /// 用于 UI 预览残影路径；超出时 truncated 为 true
+    ///
+    /// 与 max_try 不同：max_try 限制搜索量，这里只截短输出，搜索照常进行
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_path_capped(
+        &self,
+        start_x: i32,
+        start_y: i32,
+        end_x: i32,
+        end_y: i32,
+        path_type: PathType,
+        can_move_direction_count: i32,
+        max_path_len: u32,
+    ) -> CappedPath {
+        let mut path = self.find_path_with_avoid(
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            path_type,
+            can_move_direction_count,
+            &HashSet::new(),
+            0.0,
+            false,
+        );
+        let max_len = (max_path_len as usize).saturating_mul(2);
+        let truncated = path.len() > max_len;
+        path.truncate(max_len);
+        CappedPath { path, truncated }
+    }
+
+    /// 带回避格子的寻路（威胁区域：火焰、敌方远程覆盖等）
+    /// avoid_tiles 为 [x1, y1, x2, y2, ...]，仅对本次查询生效
+    ///
+    /// 进入回避格子时 g_cost 额外加 penalty，格子本身仍可通行：
+    /// 有绕行路径时优先绕行，没有时照常穿过
+    /// 仅 A* 模式（PerfectMaxNpcTry / PerfectMaxPlayerTry）计入 penalty，
+    /// 其余模式与 find_path 相同
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_path_avoiding(
+        &self,
+        start_x: i32,
+        start_y: i32,
+        end_x: i32,
+        end_y: i32,
+        path_type: PathType,
+        can_move_direction_count: i32,
+        avoid_tiles: &[i32],
+        penalty: f64,
+    ) -> Vec<i32> {
+        let avoid: HashSet<Vec2> = avoid_tiles
+            .chunks_exact(2)
+            .map(|c| Vec2::new(c[0], c[1]))
+            .collect();
+        let penalty = if penalty.is_finite() {
+            penalty.max(0.0)
+        } else {
+            0.0
+        };
+        self.find_path_with_avoid(
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            path_type,
+            can_move_direction_count,
+            &avoid,
+            penalty,
+            false,
+        )
+    }
+
+    /// 多起点寻路：从 starts（[x1, y1, x2, y2, ...]）中最先到达终点的那个出发
+    /// （例如警报响起时选最近的刷怪点派援兵），返回的路径以该起点开头
+    ///
+    /// Simple / Perfect 模式把所有起点以 g_cost 0 同时放入 frontier，只搜索一次，
+    /// 比逐个起点调用 find_path 便宜；PathOneStep / PathStraightLine 逐个起点生成
+    /// 路径，取到达终点且最短的一条。某个起点就是终点时返回只含该点的路径
+    #[wasm_bindgen]
+    pub fn find_path_from_nearest(
+        &self,
+        starts: &[i32],
+        end_x: i32,
+        end_y: i32,
+        path_type: PathType,
+        can_move_direction_count: i32,
+    ) -> Vec<i32> {
+        let starts: Vec<Vec2> = starts
+            .chunks_exact(2)
+            .map(|c| Vec2::new(c[0], c[1]))
+            .collect();
+        let end = Vec2::new(end_x, end_y);
+
+        if starts.is_empty() || self.is_obstacle(end_x, end_y) {
             return vec![];
         }
+        if starts.contains(&end) {
+            return vec![end_x, end_y];
+        }
 
-        // 终点是障碍物
-        if self.is_obstacle(end_x, end_y) {
+        let max_try = Self::max_try_count(path_type);
+        match path_type {
+            PathType::SimpleMaxNpcTry => {
+                let mut scratch = self.scratch.borrow_mut();
+                self.search_simple(
+                    &mut scratch,
+                    &starts,
+                    end,
+                    max_try,
+                    can_move_direction_count,
+                    false,
+                );
+                self.reconstruct_path(&scratch.came_from, &starts, end, false)
+            }
+            PathType::PerfectMaxNpcTry | PathType::PerfectMaxPlayerTry => {
+                let mut scratch = self.scratch.borrow_mut();
+                self.search_perfect(
+                    &mut scratch,
+                    &starts,
+                    end,
+                    max_try,
+                    can_move_direction_count,
+                    &HashSet::new(),
+                    0.0,
+                    f64::INFINITY,
+                    false,
+                );
+                self.reconstruct_path(&scratch.came_from, &starts, end, false)
+            }
+            PathType::PathOneStep | PathType::PathStraightLine => starts
+                .iter()
+                .map(|&start| {
+                    self.find_path(
+                        start.x,
+                        start.y,
+                        end_x,
+                        end_y,
+                        path_type,
+                        can_move_direction_count,
+                    )
+                })
+                .filter(|path| path.ends_with(&[end_x, end_y]))
+                .min_by_key(|path| path.len())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// 判断终点是否可达（例如置灰移动按钮），不构建路径
+    ///
+    /// Simple / Perfect 模式结果与 `!find_path(...).is_empty()` 相同，终点一加入
+    /// 搜索树即返回，省去 reconstruct_path；PathOneStep / PathStraightLine 本身
+    /// 很便宜，直接生成路径并要求最后一点是终点
+    #[wasm_bindgen]
+    pub fn can_reach(
+        &self,
+        start_x: i32,
+        start_y: i32,
+        end_x: i32,
+        end_y: i32,
+        path_type: PathType,
+        can_move_direction_count: i32,
+    ) -> bool {
+        let start = Vec2::new(start_x, start_y);
+        let end = Vec2::new(end_x, end_y);
+        if start == end || self.is_obstacle(end_x, end_y) {
+            return false;
+        }
+
+        let max_try = Self::max_try_count(path_type);
+        match path_type {
+            PathType::PathOneStep => {
+                let path = self.find_path_step(start, end, max_try, can_move_direction_count);
+                path.ends_with(&[end_x, end_y])
+            }
+            PathType::SimpleMaxNpcTry => {
+                let mut scratch = self.scratch.borrow_mut();
+                self.search_simple(
+                    &mut scratch,
+                    &[start],
+                    end,
+                    max_try,
+                    can_move_direction_count,
+                    true,
+                );
+                scratch.came_from.contains_key(&end)
+            }
+            PathType::PerfectMaxNpcTry | PathType::PerfectMaxPlayerTry => {
+                let mut scratch = self.scratch.borrow_mut();
+                self.search_perfect(
+                    &mut scratch,
+                    &[start],
+                    end,
+                    max_try,
+                    can_move_direction_count,
+                    &HashSet::new(),
+                    0.0,
+                    f64::INFINITY,
+                    true,
+                );
+                scratch.came_from.contains_key(&end)
+            }
+            PathType::PathStraightLine => self
+                .find_straight_line(start, end)
+                .ends_with(&[end_x, end_y]),
+        }
+    }
+
+    /// 移动范围：从起点按 Dijkstra 扩展，返回累计像素代价 ≤ max_cost 的所有格子
+    /// 返回 [x1, y1, cost1, x2, y2, cost2, ...]，cost 四舍五入为整数像素，
+    /// 按 cost 升序排列（相同时按 y、x），包含 cost 为 0 的起点
+    ///
+    /// 通行规则与 search_perfect 一致：静态障碍与对角线阻挡、方向限制相同；
+    /// 被动态障碍占据的格子本身可达（会出现在结果中），但不能穿过
+    #[wasm_bindgen]
+    pub fn reachable_tiles(
+        &self,
+        start_x: i32,
+        start_y: i32,
+        max_cost: f64,
+        can_move_direction_count: i32,
+    ) -> Vec<i32> {
+        let start = Vec2::new(start_x, start_y);
+        if !self.in_bounds(start.x, start.y) || max_cost.is_nan() || max_cost < 0.0 {
             return vec![];
         }
 
-        let max_try = match path_type {
+        let mut frontier = BinaryHeap::new();
+        let mut cost_so_far: HashMap<Vec2, f64> = HashMap::new();
+        frontier.push(PathNode {
+            tile: start,
+            f_cost: 0.0,
+            g_cost: 0.0,
+        });
+        cost_so_far.insert(start, 0.0);
+
+        while let Some(current_node) = frontier.pop() {
+            let current = current_node.tile;
+            // 已有更短路径（堆中的过期条目）
+            if current_node.g_cost > cost_so_far[&current] {
+                continue;
+            }
+            if current != start && self.has_dynamic_obstacle(current.x, current.y) {
+                continue;
+            }
+
+            // 以起点作为"目标"：find_valid_neighbors 对目标格的放行不会产生额外格子
+            for neighbor in self.find_valid_neighbors(current, start, can_move_direction_count) {
+                let new_cost = current_node.g_cost + current.pixel_distance(&neighbor);
+                if new_cost > max_cost {
+                    continue;
+                }
+                if cost_so_far
+                    .get(&neighbor)
+                    .is_none_or(|&existing| new_cost < existing)
+                {
+                    cost_so_far.insert(neighbor, new_cost);
+                    frontier.push(PathNode {
+                        tile: neighbor,
+                        f_cost: new_cost,
+                        g_cost: new_cost,
+                    });
+                }
+            }
+        }
+
+        let mut tiles: Vec<(i32, i32, i32)> = cost_so_far
+            .into_iter()
+            .map(|(tile, cost)| (cost.round() as i32, tile.y, tile.x))
+            .collect();
+        tiles.sort_unstable();
+        tiles
+            .into_iter()
+            .flat_map(|(cost, y, x)| [x, y, cost])
+            .collect()
+    }
+
+    /// 各寻路类型的 maxTry（PathStraightLine 不搜索，返回 -1）
+    fn max_try_count(path_type: PathType) -> i32 {
+        match path_type {
             PathType::PathOneStep => 10,
             PathType::SimpleMaxNpcTry => 100,
             PathType::PerfectMaxNpcTry => 100,
             // C++ 使用 128*128=16384；500 对远距离目标不够，A* 频繁失败触发贪心回退 → 穿墙
             PathType::PerfectMaxPlayerTry => 4000,
-            PathType::PathStraightLine => {
-                let result = self.find_straight_line(start, end);
-                pathfind_log!(path_type, start_x, start_y, end_x, end_y, result, t0);
-                return result;
-            }
-        };
+            PathType::PathStraightLine => -1,
+        }
+    }
+
+    /// find_path / find_path_avoiding / find_path_reversed 的共同实现
+    /// goal_first 时路径按终点→起点顺序返回
+    #[allow(clippy::too_many_arguments)]
+    fn find_path_with_avoid(
+        &self,
+        start_x: i32,
+        start_y: i32,
+        end_x: i32,
+        end_y: i32,
+        path_type: PathType,
+        can_move_direction_count: i32,
+        avoid: &HashSet<Vec2>,
+        penalty: f64,
+        goal_first: bool,
+    ) -> Vec<i32> {
+        #[cfg(all(target_arch = "wasm32", debug_assertions))]
+        let t0 = perf_now();
+
+        let start = Vec2::new(start_x, start_y);
+        let end = Vec2::new(end_x, end_y);
+
+        // 起点终点相同
+        if start == end {
+            return vec![];
+        }
 
+        // 终点是障碍物
+        if self.is_obstacle(end_x, end_y) {
+            return vec![];
+        }
+
+        let max_try = Self::max_try_count(path_type);
         let result = match path_type {
             PathType::PathOneStep => {
-                self.find_path_step(start, end, max_try, can_move_direction_count)
+                let path = self.find_path_step(start, end, max_try, can_move_direction_count);
+                if goal_first {
+                    Self::reverse_points(&path)
+                } else {
+                    path
+                }
             }
             PathType::SimpleMaxNpcTry => {
-                self.find_path_simple(start, end, max_try, can_move_direction_count)
+                let mut scratch = self.scratch.borrow_mut();
+                self.search_simple(
+                    &mut scratch,
+                    &[start],
+                    end,
+                    max_try,
+                    can_move_direction_count,
+                    false,
+                );
+                self.reconstruct_path(&scratch.came_from, &[start], end, goal_first)
             }
             PathType::PerfectMaxNpcTry | PathType::PerfectMaxPlayerTry => {
-                self.find_path_perfect(start, end, max_try, can_move_direction_count)
+                let mut scratch = self.scratch.borrow_mut();
+                self.search_perfect(
+                    &mut scratch,
+                    &[start],
+                    end,
+                    max_try,
+                    can_move_direction_count,
+                    avoid,
+                    penalty,
+                    f64::INFINITY,
+                    false,
+                );
+                self.reconstruct_path(&scratch.came_from, &[start], end, goal_first)
+            }
+            PathType::PathStraightLine => {
+                let path = self.find_straight_line(start, end);
+                if goal_first {
+                    Self::reverse_points(&path)
+                } else {
+                    path
+                }
             }
-            PathType::PathStraightLine => self.find_straight_line(start, end),
         };
 
         pathfind_log!(path_type, start_x, start_y, end_x, end_y, result, t0);
@@ -361,6 +1201,23 @@ impl PathFinder {
         }
     }
 
+    /// 获取 8 个长对角格子（16 方向移动用），索引 i 位于方向 i 与 i+1 之间
+    ///
+    /// 以 SE/SW 为轴的菱形格坐标下即 (±1,±2)、(±2,±1) 的"马步"偏移，
+    /// 等于先走方向 i 再走方向 i+1（如 S + SW）。传入 get_neighbors(pos) 的结果
+    fn get_long_neighbors(&self, neighbors: &[Vec2; 8]) -> [Vec2; 8] {
+        std::array::from_fn(|i| self.get_neighbors(neighbors[i])[(i + 1) % 8])
+    }
+
+    /// 长对角移动是否可行：目标格可通行，且途经的两侧格子（方向 i、i+1 的邻居）都不是障碍
+    fn can_long_move(&self, neighbors: &[Vec2; 8], direction: usize, target: Vec2) -> bool {
+        let side_a = neighbors[direction];
+        let side_b = neighbors[(direction + 1) % 8];
+        !self.is_obstacle(target.x, target.y)
+            && !self.is_obstacle(side_a.x, side_a.y)
+            && !self.is_obstacle(side_b.x, side_b.y)
+    }
+
     /// 检查是否可以向指定方向移动
     fn can_move_in_direction(&self, direction: usize, can_move_count: i32) -> bool {
         match can_move_count {
@@ -371,35 +1228,29 @@ impl PathFinder {
         }
     }
 
-    /// 获取被障碍物阻挡的方向索引集合
-    fn get_blocked_directions(&self, neighbors: &[Vec2; 8]) -> HashSet<usize> {
-        let mut blocked = HashSet::new();
+    /// 获取被障碍物阻挡的方向，第 i 位为 1 表示方向 i 被阻挡
+    fn get_blocked_directions(&self, neighbors: &[Vec2; 8]) -> u8 {
+        let mut blocked = 0u8;
 
         for (i, neighbor) in neighbors.iter().enumerate() {
+            // 开放边界：越界邻居只是不可达，不参与对角线阻挡
+            if self.open_borders && !self.in_bounds(neighbor.x, neighbor.y) {
+                blocked |= 1 << i;
+                continue;
+            }
+
             if self.is_obstacle(neighbor.x, neighbor.y) {
-                blocked.insert(i);
-
-                // 对角线阻挡（只对硬障碍物生效）
-                if self.is_hard_obstacle(neighbor.x, neighbor.y) {
-                    match i {
-                        1 => {
-                            blocked.insert(0);
-                            blocked.insert(2);
-                        } // SW -> S, W
-                        3 => {
-                            blocked.insert(2);
-                            blocked.insert(4);
-                        } // NW -> W, N
-                        5 => {
-                            blocked.insert(4);
-                            blocked.insert(6);
-                        } // NE -> N, E
-                        7 => {
-                            blocked.insert(0);
-                            blocked.insert(6);
-                        } // SE -> S, E
-                        _ => {}
-                    }
+                blocked |= 1 << i;
+
+                // 对角线阻挡（默认只对硬障碍物生效，禁止贴角时对所有障碍生效）
+                if !self.allow_corner_cutting || self.is_hard_obstacle(neighbor.x, neighbor.y) {
+                    blocked |= match i {
+                        1 => (1 << 0) | (1 << 2), // SW -> S, W
+                        3 => (1 << 2) | (1 << 4), // NW -> W, N
+                        5 => (1 << 4) | (1 << 6), // NE -> N, E
+                        7 => (1 << 0) | (1 << 6), // SE -> S, E
+                        _ => 0,
+                    };
                 }
             }
         }
@@ -407,22 +1258,33 @@ impl PathFinder {
         blocked
     }
 
-    /// 获取可通行的相邻格子
-    fn find_valid_neighbors(&self, pos: Vec2, destination: Vec2, can_move_count: i32) -> Vec<Vec2> {
+    /// 获取可通行的相邻格子（先 8 个直接邻居，16 方向时再接长对角），不分配内存
+    fn find_valid_neighbors(
+        &self,
+        pos: Vec2,
+        destination: Vec2,
+        can_move_count: i32,
+    ) -> impl Iterator<Item = Vec2> + '_ {
         let neighbors = self.get_neighbors(pos);
         let blocked = self.get_blocked_directions(&neighbors);
 
-        neighbors
-            .iter()
-            .enumerate()
-            .filter(|(i, neighbor)| {
+        let direct = (0..8)
+            .filter(move |&i| {
                 // 目标格子始终允许
-                let is_destination = **neighbor == destination;
-                is_destination
-                    || (!blocked.contains(i) && self.can_move_in_direction(*i, can_move_count))
+                neighbors[i] == destination
+                    || (blocked & (1 << i) == 0 && self.can_move_in_direction(i, can_move_count))
             })
-            .map(|(_, n)| *n)
-            .collect()
+            .map(move |i| neighbors[i]);
+
+        let long_neighbors = (can_move_count >= LONG_MOVE_DIRECTION_COUNT)
+            .then(|| self.get_long_neighbors(&neighbors));
+        let long = long_neighbors.into_iter().flat_map(move |targets| {
+            (0..8)
+                .filter(move |&i| self.can_long_move(&neighbors, i, targets[i]))
+                .map(move |i| targets[i])
+        });
+
+        direct.chain(long)
     }
 
     /// 简单贪心步进寻路
@@ -472,7 +1334,7 @@ impl PathFinder {
             for dir in direction_order.iter() {
                 let neighbor = neighbors[*dir];
                 // 与 TS 一致：检查 blocked(map) + hasObstacle(dynamic) + visited
-                if blocked.contains(dir)
+                if blocked & (1 << dir) != 0
                     || self.has_dynamic_obstacle(neighbor.x, neighbor.y)
                     || visited.contains(&neighbor)
                 {
@@ -512,22 +1374,33 @@ impl PathFinder {
     /// 与 TS findPathSimple 完全一致：
     /// - tryCount++ > maxTry（先递增再比较）
     /// - 扩展前检查 hasObstacle(current) && current != start
-    fn find_path_simple(
+    ///
+    /// starts 中的所有格子同时作为起点放入 frontier（find_path_from_nearest）
+    /// 结果写入 scratch.came_from；stop_when_reached 时终点一进入 came_from 即返回（can_reach）
+    fn search_simple(
         &self,
-        start: Vec2,
+        scratch: &mut SearchScratch,
+        starts: &[Vec2],
         end: Vec2,
         max_try: i32,
         can_move_count: i32,
-    ) -> Vec<i32> {
-        let mut frontier = BinaryHeap::new();
-        let mut came_from: HashMap<Vec2, Vec2> = HashMap::new();
+        stop_when_reached: bool,
+    ) {
+        scratch.clear();
+        let SearchScratch {
+            frontier,
+            came_from,
+            ..
+        } = scratch;
         let mut try_count = 0;
 
-        frontier.push(PathNode {
-            tile: start,
-            f_cost: 0.0,
-            g_cost: 0.0,
-        });
+        for &start in starts {
+            frontier.push(PathNode {
+                tile: start,
+                f_cost: 0.0,
+                g_cost: 0.0,
+            });
+        }
 
         while let Some(current_node) = frontier.pop() {
             // 与 TS 一致: if (tryCount++ > maxTry) break;
@@ -543,48 +1416,74 @@ impl PathFinder {
             }
 
             // 与 TS 一致: if (hasObstacle(current) && current != startTile) continue;
-            if current != start && self.has_dynamic_obstacle(current.x, current.y) {
+            if !starts.contains(&current) && self.has_dynamic_obstacle(current.x, current.y) {
                 continue;
             }
 
             for neighbor in self.find_valid_neighbors(current, end, can_move_count) {
                 if !came_from.contains_key(&neighbor) {
-                    let priority = neighbor.pixel_distance(&end);
+                    let priority = self.cost_metric.distance(&neighbor, &end);
                     frontier.push(PathNode {
                         tile: neighbor,
                         f_cost: priority,
                         g_cost: 0.0,
                     });
                     came_from.insert(neighbor, current);
+                    if stop_when_reached && neighbor == end {
+                        return;
+                    }
                 }
             }
         }
-
-        self.reconstruct_path(&came_from, start, end)
     }
 
     /// A* 寻路算法
-    /// 与 TS findPathPerfect 完全一致：
+    /// 与 TS findPathPerfect 一致：
     /// - tryCount++ > maxTryCount（先递增再比较）
     /// - 扩展前检查 hasObstacle(current) && current != start
-    fn find_path_perfect(
+    /// - 区别：等长前驱中取离起点-终点连线更近的一个（见 TIE_BREAK_EPSILON）
+    /// - g_cost 与启发函数使用 cost_metric（默认像素距离）
+    /// - 进入 avoid 中的格子时 g_cost 额外加 penalty（find_path_avoiding）
+    /// - 多个起点时 g_cost 均从 0 开始，不做平局打破（没有唯一的起点-终点连线）
+    /// - 设置了 clearance 时，贴近障碍的格子加价或（strict）跳过，终点除外
+    /// - g_cost + 启发值超过 max_cost 的邻居不入队（find_path_bounded，其余调用传 INFINITY）
+    ///
+    /// 结果写入 scratch.came_from；starts、stop_when_reached 含义同 search_simple
+    #[allow(clippy::too_many_arguments)]
+    fn search_perfect(
         &self,
-        start: Vec2,
+        scratch: &mut SearchScratch,
+        starts: &[Vec2],
         end: Vec2,
         max_try: i32,
         can_move_count: i32,
-    ) -> Vec<i32> {
-        let mut frontier = BinaryHeap::new();
-        let mut came_from: HashMap<Vec2, Vec2> = HashMap::new();
-        let mut cost_so_far: HashMap<Vec2, f64> = HashMap::new();
+        avoid: &HashSet<Vec2>,
+        penalty: f64,
+        max_cost: f64,
+        stop_when_reached: bool,
+    ) {
+        scratch.clear();
+        let SearchScratch {
+            frontier,
+            came_from,
+            cost_so_far,
+        } = scratch;
         let mut try_count = 0;
 
-        frontier.push(PathNode {
-            tile: start,
-            f_cost: 0.0,
-            g_cost: 0.0,
-        });
-        cost_so_far.insert(start, 0.0);
+        let mut clearance_field = self.clearance_field.borrow_mut();
+        let clearance_field = match self.clearance {
+            0 => None,
+            _ => Some(&*clearance_field.get_or_insert_with(|| self.build_clearance_field())),
+        };
+
+        for &start in starts {
+            frontier.push(PathNode {
+                tile: start,
+                f_cost: 0.0,
+                g_cost: 0.0,
+            });
+            cost_so_far.insert(start, 0.0);
+        }
 
         while let Some(current_node) = frontier.pop() {
             // 与 TS 一致: if (maxTryCount !== -1 && tryCount++ > maxTryCount) break;
@@ -602,38 +1501,75 @@ impl PathFinder {
             }
 
             // 与 TS 一致: if (hasObstacle(current) && current != startTile) continue;
-            if current != start && self.has_dynamic_obstacle(current.x, current.y) {
+            if !starts.contains(&current) && self.has_dynamic_obstacle(current.x, current.y) {
                 continue;
             }
 
             for neighbor in self.find_valid_neighbors(current, end, can_move_count) {
-                let new_cost =
-                    cost_so_far.get(&current).unwrap_or(&0.0) + current.pixel_distance(&neighbor);
+                let mut step_cost = self.cost_metric.distance(&current, &neighbor);
+                if let Some(field) = clearance_field.filter(|_| neighbor != end) {
+                    let dist = field[(neighbor.y * self.map_width + neighbor.x) as usize];
+                    if dist <= self.clearance {
+                        if self.clearance_strict {
+                            continue;
+                        }
+                        step_cost *= 1.0 + (self.clearance + 1 - dist) as f64 * CLEARANCE_WEIGHT;
+                    }
+                }
+                let mut new_cost = cost_so_far.get(&current).unwrap_or(&0.0) + step_cost;
+                if avoid.contains(&neighbor) {
+                    new_cost += penalty;
+                }
 
-                if !cost_so_far.contains_key(&neighbor)
-                    || new_cost < *cost_so_far.get(&neighbor).unwrap()
-                {
+                let estimate = new_cost + self.cost_metric.distance(&neighbor, &end);
+                if estimate > max_cost {
+                    continue;
+                }
+
+                let old_cost = cost_so_far.get(&neighbor).copied();
+                if let ([start], Some(old)) = (starts, old_cost) {
+                    if (new_cost - old).abs() <= TIE_BREAK_EPSILON {
+                        // 等长：只换前驱，g_cost 不变，无需重新入队
+                        if let Some(&prev) = came_from.get(&neighbor) {
+                            if line_deviation(*start, end, current)
+                                < line_deviation(*start, end, prev)
+                            {
+                                came_from.insert(neighbor, current);
+                            }
+                        }
+                        continue;
+                    }
+                }
+                if old_cost.is_none_or(|old| new_cost < old) {
                     cost_so_far.insert(neighbor, new_cost);
-                    let priority = new_cost + neighbor.pixel_distance(&end);
                     frontier.push(PathNode {
                         tile: neighbor,
-                        f_cost: priority,
+                        f_cost: estimate,
                         g_cost: new_cost,
                     });
                     came_from.insert(neighbor, current);
+                    if stop_when_reached && neighbor == end {
+                        return;
+                    }
                 }
             }
         }
-
-        self.reconstruct_path(&came_from, start, end)
     }
 
     /// 直线路径（忽略障碍物）
     /// 与 TS getLinePath 一致：贪心最近邻搜索，每步选最接近终点的邻居
     fn find_straight_line(&self, start: Vec2, end: Vec2) -> Vec<i32> {
+        // 起点即终点：直接返回，不进入循环
+        if start == end {
+            return vec![start.x, start.y];
+        }
+
         let mut path = vec![];
         let mut frontier = BinaryHeap::new();
-        let mut max_try: i32 = 100;
+        // 贪心直线每步至少逼近一格，最多约 2 * max(dx, dy) 步即可到达；
+        // 超出说明无法收敛，提前结束（同时保留原有的 100 步上限）
+        let span = (end.x - start.x).abs().max((end.y - start.y).abs());
+        let mut max_try: i32 = span.saturating_mul(2).saturating_add(2).min(100);
 
         frontier.push(PathNode {
             tile: start,
@@ -673,32 +1609,34 @@ impl PathFinder {
         path
     }
 
-    /// 重建路径
+    /// 重建路径，回溯到 starts 中任一格子为止
     fn reconstruct_path(
         &self,
         came_from: &HashMap<Vec2, Vec2>,
-        start: Vec2,
+        starts: &[Vec2],
         end: Vec2,
+        goal_first: bool,
     ) -> Vec<i32> {
         if !came_from.contains_key(&end) {
             return vec![];
         }
 
         // 从终点回溯到起点，收集所有点
-        let mut points = Vec::new();
+        let mut points = vec![end];
         let mut current = end;
 
-        while current != start {
-            points.push(current);
+        while !starts.contains(&current) {
             match came_from.get(&current) {
                 Some(prev) => current = *prev,
                 None => break,
             }
+            points.push(current);
         }
-        points.push(start);
 
-        // 反转得到从起点到终点的顺序
-        points.reverse();
+        // 反转得到从起点到终点的顺序（goal_first 时保持终点→起点）
+        if !goal_first {
+            points.reverse();
+        }
 
         // 转换为 [x1, y1, x2, y2, ...] 格式
         let mut path = Vec::with_capacity(points.len() * 2);
@@ -710,6 +1648,11 @@ impl PathFinder {
         path
     }
 
+    /// 反转 [x1, y1, x2, y2, ...] 中点的顺序
+    fn reverse_points(path: &[i32]) -> Vec<i32> {
+        path.chunks_exact(2).rev().flatten().copied().collect()
+    }
+
     /// 从 delta 计算方向索引
     /// 与 TS direction.ts getDirectionIndex(direction, 8) 完全一致：
     /// - 方向 0 为 South (0,1)，顺时针
@@ -765,6 +1708,760 @@ mod tests {
         println!("test_empty_map_diagonal: path length = {} points", len / 2);
     }
 
+    /// 测试 1b: 空地图路径单调接近终点，且贴近起点-终点连线
+    #[test]
+    fn test_empty_map_path_hugs_line() {
+        let pathfinder = PathFinder::new(100, 100);
+        for end in [Vec2::new(10, 10), Vec2::new(20, 7)] {
+            let path = pathfinder.find_path(0, 0, end.x, end.y, PathType::PerfectMaxPlayerTry, 8);
+            assert!(validate_path(&path, (0, 0), (end.x, end.y), &pathfinder).is_ok());
+
+            let tiles: Vec<Vec2> = path.chunks(2).map(|c| Vec2::new(c[0], c[1])).collect();
+            for pair in tiles.windows(2) {
+                assert!(
+                    pair[1].pixel_distance(&end) < pair[0].pixel_distance(&end),
+                    "path to {:?} moves away from goal at {:?} -> {:?}",
+                    end,
+                    pair[0],
+                    pair[1]
+                );
+            }
+            // 不做平局打破时 (20,7) 会沿第 1 行走到 (13,1)，偏离连线 57 像素
+            let max_deviation = tiles
+                .iter()
+                .map(|&t| line_deviation(Vec2::new(0, 0), end, t))
+                .fold(0.0, f64::max);
+            assert!(
+                max_deviation <= 32.0,
+                "deviation {} to {:?}",
+                max_deviation,
+                end
+            );
+        }
+
+        // 平局打破不影响最优性：绕墙的路径长度与 Dijkstra 求得的最短代价一致
+        let mut walled = PathFinder::new(40, 40);
+        for y in 7..19 {
+            walled.set_obstacle(13, y, true, true);
+        }
+        let path = walled.find_path(4, 27, 17, 6, PathType::PerfectMaxPlayerTry, 8);
+        assert_eq!(&path[path.len() - 2..], &[17, 6]);
+        let length: f64 = path
+            .chunks(2)
+            .map(|c| Vec2::new(c[0], c[1]))
+            .collect::<Vec<_>>()
+            .windows(2)
+            .map(|w| w[0].pixel_distance(&w[1]))
+            .sum();
+        let reachable = walled.reachable_tiles(4, 27, 4000.0, 8);
+        let shortest = reachable
+            .chunks(3)
+            .find(|t| t[0] == 17 && t[1] == 6)
+            .unwrap()[2];
+        assert_eq!(length.round() as i32, shortest);
+    }
+
+    /// 测试 1c: 16 方向移动使用长对角步，路径更短；默认 8 方向不变
+    #[test]
+    fn test_sixteen_directions() {
+        let pathfinder = PathFinder::new(100, 100);
+        let path8 = pathfinder.find_path(10, 10, 13, 30, PathType::PerfectMaxPlayerTry, 8);
+        let path16 = pathfinder.find_path(10, 10, 13, 30, PathType::PerfectMaxPlayerTry, 16);
+        assert!(validate_path(&path8, (10, 10), (13, 30), &pathfinder).is_ok());
+        assert_eq!((path16[0], path16[1]), (10, 10));
+        assert_eq!(&path16[path16.len() - 2..], &[13, 30]);
+        assert!(path16.len() < path8.len());
+
+        // 每一步都是 8 邻居或长对角邻居
+        let tiles: Vec<Vec2> = path16.chunks(2).map(|c| Vec2::new(c[0], c[1])).collect();
+        assert!(tiles.windows(2).any(|w| !is_valid_neighbor(w[0], w[1])));
+        for w in tiles.windows(2) {
+            let neighbors = pathfinder.get_neighbors(w[0]);
+            let long = pathfinder.get_long_neighbors(&neighbors);
+            assert!(neighbors.contains(&w[1]) || long.contains(&w[1]));
+        }
+
+        // 长对角 = 方向 i + 方向 i+1，与顺序无关
+        for pos in [Vec2::new(5, 6), Vec2::new(5, 7)] {
+            let neighbors = pathfinder.get_neighbors(pos);
+            let long = pathfinder.get_long_neighbors(&neighbors);
+            for (i, target) in long.iter().enumerate() {
+                let j = (i + 1) % 8;
+                assert_eq!(*target, pathfinder.get_neighbors(neighbors[j])[i]);
+            }
+        }
+    }
+
+    /// 测试 1d: 长对角途经的侧面格子为障碍时不可走
+    #[test]
+    fn test_long_move_blocked_by_side_tile() {
+        let mut pathfinder = PathFinder::new(100, 100);
+        let start = Vec2::new(10, 10);
+        let neighbors = pathfinder.get_neighbors(start);
+        let target = pathfinder.get_long_neighbors(&neighbors)[0]; // S + SW
+
+        let valid: Vec<Vec2> = pathfinder
+            .find_valid_neighbors(start, Vec2::new(0, 0), 16)
+            .collect();
+        assert!(valid.contains(&target));
+        assert!(!pathfinder
+            .find_valid_neighbors(start, Vec2::new(0, 0), 8)
+            .any(|n| n == target));
+
+        // SW 侧为软障碍也会阻挡
+        pathfinder.set_obstacle(neighbors[1].x, neighbors[1].y, true, false);
+        let valid: Vec<Vec2> = pathfinder
+            .find_valid_neighbors(start, Vec2::new(0, 0), 16)
+            .collect();
+        assert!(!valid.contains(&target));
+        assert!(valid.contains(&neighbors[0]));
+    }
+
+    /// 测试 1e: 移动范围
+    #[test]
+    fn test_reachable_tiles() {
+        let mut pathfinder = PathFinder::new(20, 20);
+        let start = Vec2::new(5, 6);
+        let as_map = |flat: Vec<i32>| -> HashMap<Vec2, i32> {
+            flat.chunks(3)
+                .map(|c| (Vec2::new(c[0], c[1]), c[2]))
+                .collect()
+        };
+
+        // 只有起点
+        assert_eq!(pathfinder.reachable_tiles(5, 6, 0.0, 8), vec![5, 6, 0]);
+
+        // 邻居代价：S/N 32，斜向约 35.8，E/W 64
+        let neighbors = pathfinder.get_neighbors(start);
+        let near = as_map(pathfinder.reachable_tiles(5, 6, 40.0, 8));
+        assert_eq!(near.len(), 7); // 起点 + S/N + 4 个斜向
+        let one_step = as_map(pathfinder.reachable_tiles(5, 6, 64.0, 8));
+        for n in neighbors {
+            assert_eq!(one_step[&n], start.pixel_distance(&n).round() as i32);
+        }
+        // 4 方向时斜向邻居不可直接到达
+        let four_dir = as_map(pathfinder.reachable_tiles(5, 6, 40.0, 4));
+        assert_eq!(four_dir.len(), 3); // 起点 + S + N
+
+        // 代价与 search_perfect 的路径长度一致
+        let reach = as_map(pathfinder.reachable_tiles(5, 6, 300.0, 8));
+        let target = Vec2::new(8, 12);
+        let path = pathfinder.find_path(5, 6, 8, 12, PathType::PerfectMaxPlayerTry, 8);
+        let tiles: Vec<Vec2> = path.chunks(2).map(|c| Vec2::new(c[0], c[1])).collect();
+        let path_cost: f64 = tiles.windows(2).map(|w| w[0].pixel_distance(&w[1])).sum();
+        assert_eq!(reach[&target], path_cost.round() as i32);
+
+        // 结果按 cost 升序
+        let flat = pathfinder.reachable_tiles(5, 6, 300.0, 8);
+        let costs: Vec<i32> = flat.chunks(3).map(|c| c[2]).collect();
+        assert!(costs.windows(2).all(|w| w[0] <= w[1]));
+
+        // 静态障碍不可达；动态障碍格可达但不能穿过
+        let south = pathfinder.get_neighbors(start)[0];
+        pathfinder.set_obstacle(south.x, south.y, true, false);
+        let reach = as_map(pathfinder.reachable_tiles(5, 6, 64.0, 8));
+        assert!(!reach.contains_key(&south));
+
+        let mut pathfinder = PathFinder::new(20, 20);
+        let east = pathfinder.get_neighbors(start)[6];
+        let idx = (east.y * 20 + east.x) as usize;
+        pathfinder.dynamic_bitmap[idx / 8] |= 1 << (idx % 8);
+        let reach = as_map(pathfinder.reachable_tiles(5, 6, 128.0, 8));
+        let beyond = pathfinder.get_neighbors(east)[6];
+        assert_eq!(reach[&east], 64);
+        // 直穿 east 需 128，绕行（NE + E + SE）约 136
+        assert!(!reach.contains_key(&beyond));
+        let reach = as_map(pathfinder.reachable_tiles(5, 6, 150.0, 8));
+        assert_eq!(reach[&beyond], 136);
+
+        assert!(pathfinder.reachable_tiles(-1, 0, 100.0, 8).is_empty());
+    }
+
+    /// 测试 1f: 回避格子
+    #[test]
+    fn test_find_path_avoiding() {
+        let pathfinder = PathFinder::new(30, 30);
+        let to_tiles =
+            |path: &[i32]| -> Vec<Vec2> { path.chunks(2).map(|c| Vec2::new(c[0], c[1])).collect() };
+
+        // 把直线路径的中段设为回避区，有空间时应绕开
+        let direct = pathfinder.find_path(5, 10, 20, 10, PathType::PerfectMaxPlayerTry, 8);
+        let direct_tiles = to_tiles(&direct);
+        let avoid: Vec<i32> = direct_tiles[1..direct_tiles.len() - 1]
+            .iter()
+            .flat_map(|t| [t.x, t.y])
+            .collect();
+        let detour = pathfinder.find_path_avoiding(
+            5,
+            10,
+            20,
+            10,
+            PathType::PerfectMaxPlayerTry,
+            8,
+            &avoid,
+            1000.0,
+        );
+        assert!(validate_path(&detour, (5, 10), (20, 10), &pathfinder).is_ok());
+        assert!(!detour.is_empty());
+        let avoid_set: HashSet<Vec2> = direct_tiles[1..direct_tiles.len() - 1]
+            .iter()
+            .copied()
+            .collect();
+        assert!(to_tiles(&detour).iter().all(|t| !avoid_set.contains(t)));
+
+        // penalty 为 0 时与 find_path 相同
+        let same = pathfinder.find_path_avoiding(
+            5,
+            10,
+            20,
+            10,
+            PathType::PerfectMaxPlayerTry,
+            8,
+            &avoid,
+            0.0,
+        );
+        assert_eq!(same, direct);
+
+        // 墙上唯一缺口是回避格子：没有其他路时仍穿过
+        let mut pathfinder = PathFinder::new(30, 30);
+        for y in 0..30 {
+            if y != 10 {
+                pathfinder.set_obstacle(15, y, true, false);
+            }
+        }
+        let gap = [15, 10];
+        let path = pathfinder.find_path_avoiding(
+            5,
+            10,
+            25,
+            10,
+            PathType::PerfectMaxPlayerTry,
+            8,
+            &gap,
+            1000.0,
+        );
+        assert!(!path.is_empty());
+        assert!(validate_path(&path, (5, 10), (25, 10), &pathfinder).is_ok());
+        assert!(to_tiles(&path).contains(&Vec2::new(15, 10)));
+    }
+
+    /// 测试 1g: 可达性判断
+    #[test]
+    fn test_can_reach() {
+        let mut pathfinder = PathFinder::new(30, 30);
+        // 完整的墙（软障碍，不阻挡对角线），终点 (25, 10) 在墙另一侧
+        for y in 0..30 {
+            pathfinder.set_obstacle(15, y, true, false);
+        }
+        let searches = [
+            PathType::SimpleMaxNpcTry,
+            PathType::PerfectMaxNpcTry,
+            PathType::PerfectMaxPlayerTry,
+        ];
+        let targets = [(8, 12), (14, 3), (25, 10), (15, 10), (5, 10), (29, 29)];
+        for path_type in searches {
+            for (ex, ey) in targets {
+                let path = pathfinder.find_path(5, 10, ex, ey, path_type, 8);
+                assert_eq!(
+                    pathfinder.can_reach(5, 10, ex, ey, path_type, 8),
+                    !path.is_empty(),
+                    "{:?} -> ({}, {})",
+                    path_type,
+                    ex,
+                    ey
+                );
+            }
+        }
+        assert!(pathfinder.can_reach(5, 10, 8, 12, PathType::PerfectMaxPlayerTry, 8));
+        assert!(!pathfinder.can_reach(5, 10, 25, 10, PathType::PerfectMaxPlayerTry, 8));
+
+        // 直线模式忽略障碍；单步模式只走约 10 步
+        assert!(pathfinder.can_reach(5, 10, 25, 10, PathType::PathStraightLine, 8));
+        assert!(pathfinder.can_reach(5, 10, 7, 10, PathType::PathOneStep, 8));
+        assert!(!pathfinder.can_reach(5, 10, 12, 29, PathType::PathOneStep, 8));
+    }
+
+    /// 测试 1h: 批量障碍更新
+    #[test]
+    fn test_apply_obstacle_delta() {
+        let mut pathfinder = PathFinder::new(10, 10);
+        let ptr = pathfinder.obstacle_bitmap_ptr();
+        pathfinder.apply_obstacle_delta(&[2, 3, 1, 4, 5, 3, -1, 0, 3, 10, 0, 3, 7]);
+        assert_eq!(pathfinder.obstacle_bitmap_ptr(), ptr);
+        assert!(pathfinder.is_obstacle(2, 3) && !pathfinder.is_hard_obstacle(2, 3));
+        assert!(pathfinder.is_obstacle(4, 5) && pathfinder.is_hard_obstacle(4, 5));
+        let set: usize = pathfinder
+            .obstacle_bitmap
+            .iter()
+            .map(|b| b.count_ones() as usize)
+            .sum();
+        assert_eq!(set, 2);
+
+        // flags = 0 清除
+        pathfinder.apply_obstacle_delta(&[4, 5, 0]);
+        assert!(!pathfinder.is_obstacle(4, 5) && !pathfinder.is_hard_obstacle(4, 5));
+        assert!(pathfinder.is_obstacle(2, 3));
+    }
+
+    /// 测试 1i: 终点在前的路径
+    #[test]
+    fn test_find_path_reversed() {
+        let mut pathfinder = PathFinder::new(30, 30);
+        for y in 0..8 {
+            pathfinder.set_obstacle(10, y, true, true);
+        }
+        for path_type in [
+            PathType::PathOneStep,
+            PathType::SimpleMaxNpcTry,
+            PathType::PerfectMaxNpcTry,
+            PathType::PerfectMaxPlayerTry,
+            PathType::PathStraightLine,
+        ] {
+            let forward = pathfinder.find_path(5, 4, 15, 4, path_type, 8);
+            let reversed = pathfinder.find_path_reversed(5, 4, 15, 4, path_type, 8);
+            assert!(!forward.is_empty(), "{:?}", path_type);
+            assert_eq!(reversed, PathFinder::reverse_points(&forward));
+            assert_eq!(&reversed[reversed.len() - 2..], &[5, 4]);
+        }
+        let reversed = pathfinder.find_path_reversed(5, 4, 15, 4, PathType::PerfectMaxPlayerTry, 8);
+        assert_eq!(&reversed[..2], &[15, 4]);
+        assert!(pathfinder
+            .find_path_reversed(5, 4, 5, 4, PathType::PerfectMaxPlayerTry, 8)
+            .is_empty());
+    }
+
+    /// 测试 1j: Octile 代价度量
+    #[test]
+    fn test_cost_metric_octile() {
+        let origin = Vec2::new(10, 10);
+        let neighbors = PathFinder::new(30, 30).get_neighbors(origin);
+        for (i, n) in neighbors.iter().enumerate() {
+            // 偶数索引（S/W/N/E）为菱形格对角，奇数索引（SW/NW/NE/SE）为轴向
+            let expected = if i % 2 == 0 {
+                std::f64::consts::SQRT_2
+            } else {
+                1.0
+            };
+            assert!((origin.octile_distance(n) - expected).abs() < 1e-9, "{}", i);
+        }
+        assert!((Vec2::new(3, -3).octile_distance(&Vec2::new(3, -1)) - 2f64.sqrt()).abs() < 1e-9);
+
+        let mut pathfinder = PathFinder::new(30, 30);
+        for y in 0..20 {
+            pathfinder.set_obstacle(10, y, true, true);
+        }
+        let pixel = pathfinder.find_path(5, 4, 15, 4, PathType::PerfectMaxPlayerTry, 8);
+        pathfinder.set_cost_metric(CostMetric::Octile);
+        let octile = pathfinder.find_path(5, 4, 15, 4, PathType::PerfectMaxPlayerTry, 8);
+        assert_eq!(&octile[..2], &[5, 4]);
+        assert_eq!(&octile[octile.len() - 2..], &[15, 4]);
+        assert!(octile
+            .chunks(2)
+            .all(|p| !pathfinder.is_obstacle(p[0], p[1])));
+
+        let cost = |path: &[i32]| -> f64 {
+            let tiles: Vec<Vec2> = path.chunks(2).map(|p| Vec2::new(p[0], p[1])).collect();
+            tiles.windows(2).map(|w| w[0].octile_distance(&w[1])).sum()
+        };
+        assert!(cost(&octile) <= cost(&pixel) + 1e-9);
+
+        pathfinder.set_cost_metric(CostMetric::Pixel);
+        assert_eq!(
+            pathfinder.find_path(5, 4, 15, 4, PathType::PerfectMaxPlayerTry, 8),
+            pixel
+        );
+    }
+
+    /// 测试 1k: 障碍位图导出 / 导入
+    #[test]
+    fn test_export_import_bitmaps() {
+        let mut source = PathFinder::new(30, 20);
+        source.set_obstacle(3, 4, true, false);
+        source.set_obstacle(29, 19, true, true);
+        let exported = source.export_bitmaps();
+        assert_eq!(&exported[..4], b"PFB1");
+
+        let mut target = PathFinder::new(30, 20);
+        assert_eq!((target.width(), target.height()), (30, 20));
+        assert_eq!(target.obstacle_count(), 0);
+        target.set_obstacle(0, 0, true, true);
+        let ptr = target.obstacle_bitmap_ptr();
+        assert!(target.import_bitmaps(&exported));
+        assert_eq!(target.obstacle_count(), 2);
+        assert_eq!(target.obstacle_bitmap_ptr(), ptr);
+        assert_eq!(target.obstacle_bitmap, source.obstacle_bitmap);
+        assert_eq!(target.hard_obstacle_bitmap, source.hard_obstacle_bitmap);
+        assert!(!target.is_obstacle(0, 0));
+        assert!(target.is_obstacle(3, 4));
+        assert_eq!(
+            target.find_path(0, 0, 10, 10, PathType::PerfectMaxPlayerTry, 8),
+            source.find_path(0, 0, 10, 10, PathType::PerfectMaxPlayerTry, 8)
+        );
+
+        // 尺寸不同（位图字节数相同也拒绝）、截断、魔数错误
+        let mut other = PathFinder::new(20, 30);
+        assert!(!other.import_bitmaps(&exported));
+        assert!(!target.import_bitmaps(&exported[..exported.len() - 1]));
+        let mut bad_magic = exported.clone();
+        bad_magic[0] = b'X';
+        assert!(!target.import_bitmaps(&bad_magic));
+        assert!(target.is_obstacle(3, 4));
+    }
+
+    /// 测试 1l: 禁止贴角穿过
+    #[test]
+    fn test_disallow_corner_cutting() {
+        // L 形软障碍墙：竖直段 x = 10（锯齿相连，无缝隙），水平段 y = 12
+        // （同一行相邻格只在角上相接，默认可以从角缝穿过）
+        let mut pathfinder = PathFinder::new(30, 30);
+        for y in 0..=12 {
+            pathfinder.set_obstacle(10, y, true, false);
+        }
+        for x in 10..=20 {
+            pathfinder.set_obstacle(x, 12, true, false);
+        }
+
+        // 路径中是否有对角移动的任一侧邻格为障碍
+        let cuts_corner = |pathfinder: &PathFinder, path: &[i32]| {
+            path.chunks(2).collect::<Vec<_>>().windows(2).any(|w| {
+                let from = Vec2::new(w[0][0], w[0][1]);
+                let to = Vec2::new(w[1][0], w[1][1]);
+                let neighbors = pathfinder.get_neighbors(from);
+                let Some(d) = neighbors.iter().position(|&n| n == to) else {
+                    return false;
+                };
+                d % 2 == 0
+                    && [neighbors[(d + 7) % 8], neighbors[(d + 1) % 8]]
+                        .iter()
+                        .any(|n| pathfinder.is_obstacle(n.x, n.y))
+            })
+        };
+
+        let cutting = pathfinder.find_path(15, 5, 15, 20, PathType::PerfectMaxPlayerTry, 8);
+        assert!(!cutting.is_empty());
+        assert!(cuts_corner(&pathfinder, &cutting));
+
+        pathfinder.set_allow_corner_cutting(false);
+        let path = pathfinder.find_path(15, 5, 15, 20, PathType::PerfectMaxPlayerTry, 8);
+        assert_eq!(&path[path.len() - 2..], &[15, 20]);
+        assert!(!cuts_corner(&pathfinder, &path));
+        assert!(path.len() > cutting.len());
+        assert!(path.chunks(2).all(|p| !pathfinder.is_obstacle(p[0], p[1])));
+    }
+
+    /// 测试 1m: 截短路径输出
+    #[test]
+    fn test_find_path_capped() {
+        let mut pathfinder = PathFinder::new(30, 30);
+        pathfinder.set_obstacle(5, 5, true, false);
+        let full = pathfinder.find_path(0, 0, 10, 10, PathType::PerfectMaxPlayerTry, 8);
+        let points = full.len() / 2;
+        assert!(points > 3);
+
+        let capped = pathfinder.find_path_capped(0, 0, 10, 10, PathType::PerfectMaxPlayerTry, 8, 3);
+        assert!(capped.truncated);
+        assert_eq!(capped.path, &full[..6]);
+        assert_eq!(&capped.path[..2], &[0, 0]);
+
+        // 上限恰好等于或大于路径长度时不截短
+        for cap in [points as u32, points as u32 + 5] {
+            let capped =
+                pathfinder.find_path_capped(0, 0, 10, 10, PathType::PerfectMaxPlayerTry, 8, cap);
+            assert!(!capped.truncated);
+            assert_eq!(capped.path, full);
+        }
+
+        // 无路径时为空且未截短
+        pathfinder.set_obstacle(20, 20, true, false);
+        let none = pathfinder.find_path_capped(0, 0, 20, 20, PathType::PerfectMaxPlayerTry, 8, 3);
+        assert!(none.path.is_empty());
+        assert!(!none.truncated);
+    }
+
+    /// 测试 1n: 多起点寻路选最近的起点
+    #[test]
+    fn test_find_path_from_nearest() {
+        let mut pathfinder = PathFinder::new(40, 40);
+        let starts = [0, 0, 20, 20, 38, 2];
+        let ty = PathType::PerfectMaxPlayerTry;
+
+        let path = pathfinder.find_path_from_nearest(&starts, 18, 18, ty, 8);
+        assert_eq!(&path[..2], &[20, 20]);
+        assert_eq!(&path[path.len() - 2..], &[18, 18]);
+        let direct = pathfinder.find_path(20, 20, 18, 18, ty, 8);
+        assert_eq!(path.len(), direct.len());
+
+        // 最近的起点被围住：改由次近的起点出发
+        let neighbors = pathfinder.get_neighbors(Vec2::new(20, 20));
+        for n in neighbors {
+            pathfinder.set_obstacle(n.x, n.y, true, true);
+        }
+        let path = pathfinder.find_path_from_nearest(&starts, 18, 18, ty, 8);
+        assert!(path.len() >= 4);
+        assert_ne!(&path[..2], &[20, 20]);
+        assert_eq!(&path[path.len() - 2..], &[18, 18]);
+        let simple =
+            pathfinder.find_path_from_nearest(&starts, 18, 18, PathType::SimpleMaxNpcTry, 8);
+        assert_ne!(&simple[..2], &[20, 20]);
+
+        // 直线模式逐个起点比较
+        let line = pathfinder.find_path_from_nearest(&starts, 36, 4, PathType::PathStraightLine, 8);
+        assert_eq!(&line[..2], &[38, 2]);
+
+        // 起点即终点、没有起点
+        assert_eq!(
+            pathfinder.find_path_from_nearest(&starts, 0, 0, ty, 8),
+            vec![0, 0]
+        );
+        assert!(pathfinder
+            .find_path_from_nearest(&[], 18, 18, ty, 8)
+            .is_empty());
+    }
+
+    /// 测试 1o: 与障碍保持间距
+    #[test]
+    fn test_clearance() {
+        let mut pathfinder = PathFinder::new(40, 80);
+        let ty = PathType::PerfectMaxPlayerTry;
+        // 中央一块障碍，起点终点分居两侧，路径需绕行
+        for y in 30..50 {
+            for x in 15..25 {
+                pathfinder.set_obstacle(x, y, true, true);
+            }
+        }
+        let min_dist = |pf: &PathFinder, path: &[i32]| {
+            let field = pf.build_clearance_field();
+            path[2..path.len() - 2]
+                .chunks(2)
+                .map(|p| field[(p[1] * pf.map_width + p[0]) as usize])
+                .min()
+                .unwrap()
+        };
+
+        pathfinder.set_clearance(3);
+        let field = pathfinder.build_clearance_field();
+        let at = |x: i32, y: i32| field[(y * 40 + x) as usize];
+        assert_eq!(at(20, 40), 0);
+        assert_eq!(at(25, 40), 1);
+        assert_eq!(at(26, 40), 2);
+        assert_eq!(at(0, 40), 1); // 地图边缘
+        assert_eq!(at(8, 40), u8::MAX);
+
+        // 开放边界时地图边缘不算障碍，切换后缓存的距离场需丢弃
+        pathfinder.find_path(20, 20, 20, 60, ty, 8);
+        assert!(pathfinder.clearance_field.borrow().is_some());
+        pathfinder.set_open_borders(true);
+        assert!(pathfinder.clearance_field.borrow().is_none());
+        assert_eq!(pathfinder.build_clearance_field()[40 * 40], u8::MAX);
+        pathfinder.set_open_borders(false);
+
+        // 最大间距不溢出，且不会把所有格子都当作贴近障碍
+        pathfinder.set_clearance(u8::MAX);
+        assert_eq!(pathfinder.clearance, MAX_CLEARANCE);
+        assert!(!pathfinder.find_path(20, 20, 20, 60, ty, 8).is_empty());
+
+        pathfinder.set_clearance(0);
+        let hugging = pathfinder.find_path(20, 20, 20, 60, ty, 8);
+        assert!(!hugging.is_empty());
+        pathfinder.set_clearance(3);
+        assert_eq!(min_dist(&pathfinder, &hugging), 1);
+
+        let soft = pathfinder.find_path(20, 20, 20, 60, ty, 8);
+        assert_eq!(&soft[soft.len() - 2..], &[20, 60]);
+        assert!(min_dist(&pathfinder, &soft) >= 3);
+
+        pathfinder.set_clearance_strict(true);
+        let strict = pathfinder.find_path(20, 20, 20, 60, ty, 8);
+        assert!(min_dist(&pathfinder, &strict) > 3);
+
+        // 障碍变化后距离场重新计算：封住一侧，另一侧仍可绕行
+        for y in 0..80 {
+            pathfinder.set_obstacle(5, y, true, true);
+        }
+        let blocked = pathfinder.find_path(20, 20, 20, 60, ty, 8);
+        assert!(!blocked.is_empty());
+        assert!(blocked.chunks(2).all(|p| p[0] > 5));
+        assert!(min_dist(&pathfinder, &blocked) > 3);
+    }
+
+    /// 测试 1p: 调试输出探索过的格子
+    #[test]
+    fn test_find_path_debug() {
+        let mut pathfinder = PathFinder::new(30, 30);
+        pathfinder.set_obstacle(5, 5, true, false);
+        let ty = PathType::PerfectMaxPlayerTry;
+
+        let debug = pathfinder.find_path_debug(0, 0, 10, 10, ty, 8);
+        assert_eq!(debug.path, pathfinder.find_path(0, 0, 10, 10, ty, 8));
+        assert_eq!(debug.explored.len() % 3, 0);
+        let explored: Vec<(i32, i32, f32)> = debug
+            .explored
+            .chunks(3)
+            .map(|e| (e[0] as i32, e[1] as i32, e[2]))
+            .collect();
+        assert!(explored.contains(&(0, 0, 0.0)));
+        // 路径上每个点都被探索过，g_cost 沿路径递增
+        let path_costs: Vec<f32> = debug
+            .path
+            .chunks(2)
+            .map(|p| {
+                explored
+                    .iter()
+                    .find(|e| (e.0, e.1) == (p[0], p[1]))
+                    .unwrap()
+                    .2
+            })
+            .collect();
+        assert!(path_costs.windows(2).all(|w| w[0] < w[1]));
+        assert!(!explored.iter().any(|e| (e.0, e.1) == (5, 5)));
+
+        // 非 A* 类型与未搜索的情况不返回探索记录（不残留上一次的结果）
+        let simple = pathfinder.find_path_debug(0, 0, 10, 10, PathType::SimpleMaxNpcTry, 8);
+        assert!(!simple.path.is_empty());
+        assert!(simple.explored.is_empty());
+        pathfinder.find_path_debug(0, 0, 10, 10, ty, 8);
+        let blocked = pathfinder.find_path_debug(0, 0, 5, 5, ty, 8);
+        assert!(blocked.path.is_empty() && blocked.explored.is_empty());
+    }
+
+    /// 测试 1q: 限制路径总代价
+    #[test]
+    fn test_find_path_bounded() {
+        let mut pathfinder = PathFinder::new(40, 80);
+        let ty = PathType::PerfectMaxPlayerTry;
+        let cost = |path: &[i32]| -> f64 {
+            path.chunks(2)
+                .zip(path.chunks(2).skip(1))
+                .map(|(a, b)| Vec2::new(a[0], a[1]).pixel_distance(&Vec2::new(b[0], b[1])))
+                .sum()
+        };
+
+        let direct = pathfinder.find_path(10, 10, 10, 30, ty, 8);
+        let direct_cost = cost(&direct);
+        assert_eq!(
+            pathfinder.find_path_bounded(10, 10, 10, 30, ty, 8, direct_cost + 1.0),
+            direct
+        );
+        assert!(pathfinder
+            .find_path_bounded(10, 10, 10, 30, ty, 8, direct_cost * 0.9)
+            .is_empty());
+
+        // 横墙只在最右端留缺口：路径存在但绕得很远
+        for x in 0..38 {
+            for y in 19..21 {
+                pathfinder.set_obstacle(x, y, true, true);
+            }
+        }
+        let detour = pathfinder.find_path(10, 10, 10, 30, ty, 8);
+        assert!(!detour.is_empty());
+        let detour_cost = cost(&detour);
+        assert!(detour_cost > direct_cost * 3.0);
+        assert!(pathfinder
+            .find_path_bounded(10, 10, 10, 30, ty, 8, direct_cost * 2.0)
+            .is_empty());
+        let bounded = pathfinder.find_path_bounded(10, 10, 10, 30, ty, 8, detour_cost + 1.0);
+        assert_eq!(cost(&bounded), detour_cost);
+
+        // 非 A* 类型按结果路径的代价判断
+        let line = pathfinder.find_path(10, 10, 10, 30, PathType::PathStraightLine, 8);
+        let line_cost = cost(&line);
+        assert_eq!(
+            pathfinder.find_path_bounded(10, 10, 10, 30, PathType::PathStraightLine, 8, line_cost),
+            line
+        );
+        assert!(pathfinder
+            .find_path_bounded(
+                10,
+                10,
+                10,
+                30,
+                PathType::PathStraightLine,
+                8,
+                line_cost - 1.0
+            )
+            .is_empty());
+    }
+
+    /// 测试 1r: 瓦片坐标的 Manhattan / Chebyshev 度量
+    #[test]
+    fn test_cost_metric_tile_space() {
+        let origin = Vec2::new(10, 10);
+        let end = Vec2::new(17, 3);
+        let neighbors = PathFinder::new(30, 30).get_neighbors(origin);
+        for metric in [CostMetric::Manhattan, CostMetric::Chebyshev] {
+            // 一致性：h(n) ≤ c(n, m) + h(m)
+            for n in &neighbors {
+                assert!(
+                    metric.distance(&origin, &end)
+                        <= metric.distance(&origin, n) + metric.distance(n, &end),
+                    "{:?}",
+                    metric
+                );
+            }
+        }
+        assert_eq!(origin.manhattan_distance(&end), 14.0);
+        assert_eq!(origin.chebyshev_distance(&end), 7.0);
+
+        let mut pathfinder = PathFinder::new(30, 30);
+        for y in 0..20 {
+            pathfinder.set_obstacle(10, y, true, true);
+        }
+        let pixel = pathfinder.find_path(5, 4, 15, 4, PathType::PerfectMaxPlayerTry, 8);
+        for metric in [CostMetric::Manhattan, CostMetric::Chebyshev] {
+            pathfinder.set_cost_metric(metric);
+            let path = pathfinder.find_path(5, 4, 15, 4, PathType::PerfectMaxPlayerTry, 8);
+            assert_eq!(&path[..2], &[5, 4]);
+            assert_eq!(&path[path.len() - 2..], &[15, 4]);
+            assert!(path.chunks(2).all(|p| !pathfinder.is_obstacle(p[0], p[1])));
+
+            let cost = |path: &[i32]| -> f64 {
+                let tiles: Vec<Vec2> = path.chunks(2).map(|p| Vec2::new(p[0], p[1])).collect();
+                tiles
+                    .windows(2)
+                    .map(|w| metric.distance(&w[0], &w[1]))
+                    .sum()
+            };
+            assert!(cost(&path) <= cost(&pixel) + 1e-9, "{:?}", metric);
+            // 必须绕过墙底（y ≥ 20）：代价不低于起点到墙底再到终点
+            let gap = Vec2::new(10, 20);
+            let lower =
+                metric.distance(&Vec2::new(5, 4), &gap) + metric.distance(&gap, &Vec2::new(15, 4));
+            assert!(cost(&path) >= lower - 1e-9, "{:?}", metric);
+        }
+    }
+
+    /// 测试 1s: 由 barrier 字节重建障碍位图
+    #[test]
+    fn test_set_obstacles_from_barriers() {
+        let mut pathfinder = PathFinder::new(4, 3);
+        pathfinder.set_obstacle(3, 2, true, true);
+        let ptr = pathfinder.obstacle_bitmap_ptr();
+        // 第 0、1 行各 4 格，第 2 行只给 1 格，其余缺省为可通行
+        let barriers = [0x00, 0x20, 0x40, 0x60, 0x80, 0xA0, 0x41, 0xFF, 0x80];
+        pathfinder.set_obstacles_from_barriers(&barriers);
+
+        assert_eq!(pathfinder.obstacle_bitmap_ptr(), ptr);
+        assert_eq!(pathfinder.obstacle_count(), 5);
+        let obstacles: Vec<(i32, i32)> = (0..12)
+            .map(|i| (i % 4, i / 4))
+            .filter(|&(x, y)| pathfinder.is_obstacle(x, y))
+            .collect();
+        assert_eq!(obstacles, vec![(2, 0), (3, 0), (0, 1), (1, 1), (0, 2)]);
+        let hard: Vec<(i32, i32)> = (0..12)
+            .map(|i| (i % 4, i / 4))
+            .filter(|&(x, y)| pathfinder.is_hard_obstacle(x, y))
+            .collect();
+        assert_eq!(hard, vec![(0, 1), (1, 1), (0, 2)]);
+
+        // 与逐格 set_obstacle 的结果一致
+        let mut manual = PathFinder::new(4, 3);
+        for &(x, y) in &obstacles {
+            manual.set_obstacle(x, y, true, hard.contains(&(x, y)));
+        }
+        assert_eq!(manual.obstacle_bitmap, pathfinder.obstacle_bitmap);
+        assert_eq!(manual.hard_obstacle_bitmap, pathfinder.hard_obstacle_bitmap);
+    }
+
     /// 测试 2: 起点终点相同
     #[test]
     fn test_same_start_end() {
@@ -901,7 +2598,7 @@ mod tests {
         }
 
         let len = path.len();
-        if len % 2 != 0 {
+        if !len.is_multiple_of(2) {
             return Err("Path length should be even".to_string());
         }
 
@@ -950,7 +2647,7 @@ mod tests {
     fn is_valid_neighbor(from: Vec2, to: Vec2) -> bool {
         let pf = PathFinder::new(1000, 1000); // 临时实例用于获取邻居
         let neighbors = pf.get_neighbors(from);
-        neighbors.iter().any(|n| *n == to)
+        neighbors.contains(&to)
     }
 
     /// 路径有效性测试 1: 空地图路径
@@ -1110,6 +2807,111 @@ mod tests {
         assert_eq!(path[3], 6);
     }
 
+    /// 边界测试 3: 1×1 地图
+    #[test]
+    fn test_single_tile_map() {
+        let mut pathfinder = PathFinder::new(1, 1);
+        assert!(!pathfinder.is_obstacle(0, 0));
+        assert!(pathfinder.is_obstacle(1, 0), "Out of bounds is obstacle");
+        assert!(pathfinder
+            .find_path(0, 0, 0, 0, PathType::PerfectMaxPlayerTry, 8)
+            .is_empty());
+        assert!(pathfinder
+            .find_path(0, 0, 1, 1, PathType::PerfectMaxPlayerTry, 8)
+            .is_empty());
+
+        pathfinder.set_obstacle(0, 0, true, true);
+        assert!(pathfinder.is_obstacle(0, 0));
+    }
+
+    /// 边界测试 4: 非正尺寸钳制为 1×1
+    #[test]
+    fn test_non_positive_dimensions_clamped() {
+        for (w, h) in [(0, 0), (-1, 5), (5, -1), (i32::MIN, i32::MIN)] {
+            let pathfinder = PathFinder::new(w, h);
+            assert!(pathfinder.map_width >= 1 && pathfinder.map_height >= 1);
+            assert_eq!(pathfinder.map_width, w.max(1));
+            assert_eq!(pathfinder.map_height, h.max(1));
+            assert!(!pathfinder.is_obstacle(0, 0));
+        }
+    }
+
+    /// 边界测试 5: 直线寻路起点终点相同（终点上有动态障碍物）
+    #[test]
+    fn test_straight_line_same_tile() {
+        let mut pathfinder = PathFinder::new(10, 10);
+        let index = 5 * 10 + 5;
+        pathfinder.dynamic_bitmap[index / 8] |= 1 << (index % 8);
+        let path = pathfinder.find_path(5, 5, 5, 5, PathType::PathStraightLine, 8);
+        assert!(path.is_empty());
+        // 直接调用也不会死循环
+        let path = pathfinder.find_straight_line(Vec2::new(5, 5), Vec2::new(5, 5));
+        assert_eq!(path, vec![5, 5]);
+    }
+
+    /// 边界测试 6: 直线寻路的步数上限不截断可达路径
+    #[test]
+    fn test_straight_line_reaches_end() {
+        let pathfinder = PathFinder::new(100, 100);
+        for (ex, ey) in [(20, 20), (41, 39), (10, 60), (45, 5)] {
+            let path = pathfinder.find_straight_line(Vec2::new(40, 40), Vec2::new(ex, ey));
+            let len = path.len();
+            assert_eq!((path[len - 2], path[len - 1]), (ex, ey));
+        }
+    }
+
+    /// 边界测试 7: 开放边界下沿左边缘寻路到 (0,0)
+    #[test]
+    fn test_open_borders_left_edge() {
+        // 只有 x=0 的偶数行可走，必须沿边缘一直向北
+        let mut pathfinder = PathFinder::new(10, 20);
+        for y in 0..20 {
+            for x in 0..10 {
+                if x > 0 || y % 2 == 1 {
+                    pathfinder.set_obstacle(x, y, true, false);
+                }
+            }
+        }
+
+        // 默认封闭边界：越界的西北格是硬障碍，向北被对角线阻挡
+        assert!(pathfinder
+            .find_path(0, 10, 0, 0, PathType::PerfectMaxPlayerTry, 8)
+            .is_empty());
+
+        pathfinder.set_open_borders(true);
+        let path = pathfinder.find_path(0, 10, 0, 0, PathType::PerfectMaxPlayerTry, 8);
+        assert_eq!(path, vec![0, 10, 0, 8, 0, 6, 0, 4, 0, 2, 0, 0]);
+        assert!(validate_path(&path, (0, 10), (0, 0), &pathfinder).is_ok());
+    }
+
+    /// 边界测试 8: 开放边界下沿右边缘寻路到最远角
+    #[test]
+    fn test_open_borders_far_corner() {
+        // 只有 x=9 的奇数行可走（奇数行的东北/东南邻居越界）
+        let mut pathfinder = PathFinder::new(10, 20);
+        for y in 0..20 {
+            for x in 0..10 {
+                if x < 9 || y % 2 == 0 {
+                    pathfinder.set_obstacle(x, y, true, false);
+                }
+            }
+        }
+
+        assert!(pathfinder
+            .find_path(9, 9, 9, 19, PathType::PerfectMaxPlayerTry, 8)
+            .is_empty());
+
+        pathfinder.set_open_borders(true);
+        let path = pathfinder.find_path(9, 9, 9, 19, PathType::PerfectMaxPlayerTry, 8);
+        assert_eq!(path, vec![9, 9, 9, 11, 9, 13, 9, 15, 9, 17, 9, 19]);
+        assert!(validate_path(&path, (9, 9), (9, 19), &pathfinder).is_ok());
+
+        // 越界终点仍然不可达
+        assert!(pathfinder
+            .find_path(9, 9, 10, 19, PathType::PerfectMaxPlayerTry, 8)
+            .is_empty());
+    }
+
     /// 性能基准测试
     #[test]
     fn benchmark_pathfinding() {
@@ -1142,4 +2944,57 @@ mod tests {
             elapsed.as_secs_f64() * 1000.0 / total_runs as f64
         );
     }
+
+    /// 性能基准测试：大量短距离 NPC 寻路（每帧数十个 NPC 的场景，分配开销占比最高）
+    /// 同时校验复用缓冲区不会把上一次搜索的状态带进下一次
+    #[test]
+    fn benchmark_pathfinding_npc() {
+        use std::time::Instant;
+
+        let build = || {
+            let mut pathfinder = PathFinder::new(100, 100);
+            for i in 0..200 {
+                let x = (i * 7) % 100;
+                let y = (i * 13) % 100;
+                pathfinder.set_obstacle(x, y, true, true);
+            }
+            pathfinder
+        };
+        let pathfinder = build();
+        let query = |i: i32| {
+            let sx = 20 + i % 60;
+            let sy = 20 + (i * 3) % 60;
+            (sx, sy, sx + 4, sy + 6)
+        };
+
+        let iterations = 20_000;
+        let mut found = 0;
+        let start = Instant::now();
+        for i in 0..iterations {
+            let (sx, sy, ex, ey) = query(i);
+            let path = pathfinder.find_path(sx, sy, ex, ey, PathType::PerfectMaxNpcTry, 8);
+            if path.ends_with(&[ex, ey]) {
+                found += 1;
+            }
+        }
+        let elapsed = start.elapsed();
+        assert!(found > iterations * 9 / 10, "found {}", found);
+
+        for i in (0..iterations).step_by(997) {
+            let (sx, sy, ex, ey) = query(i);
+            assert_eq!(
+                pathfinder.find_path(sx, sy, ex, ey, PathType::PerfectMaxNpcTry, 8),
+                build().find_path(sx, sy, ex, ey, PathType::PerfectMaxNpcTry, 8),
+                "query {}",
+                i
+            );
+        }
+
+        println!(
+            "benchmark_pathfinding_npc: {} runs in {:?} ({:.4}ms avg)",
+            iterations,
+            elapsed,
+            elapsed.as_secs_f64() * 1000.0 / iterations as f64
+        );
+    }
 }
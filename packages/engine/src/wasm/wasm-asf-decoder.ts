@@ -137,8 +137,13 @@ function decodeMsf(
     });
   }
 
-  // MSF fps → interval (ms)
-  const interval = header.fps > 0 ? Math.round(1000 / header.fps) : 67;
+  // MSF interval (ms): exact IVAL value if present, else derived from fps
+  const interval =
+    header.interval_ms > 0
+      ? header.interval_ms
+      : header.fps > 0
+        ? Math.round(1000 / header.fps)
+        : 67;
 
   return {
     width: header.canvas_width,
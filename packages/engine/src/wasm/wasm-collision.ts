@@ -16,6 +16,7 @@ interface WasmSpatialHash {
   remove(id: number): void;
   batch_update_positions(positions: Float32Array): void;
   query_radius(x: number, y: number, radius: number): Uint32Array;
+  query_radius_sorted(x: number, y: number, radius: number): Uint32Array;
   query_at(x: number, y: number): Uint32Array;
   query_at_by_group(x: number, y: number, group: number): Uint32Array;
   query_at_excluding_group(x: number, y: number, excludeGroup: number): Uint32Array;
@@ -151,6 +152,14 @@ export class WasmSpatialHashWrapper {
     return Array.from(this.hash.query_radius(x, y, radius));
   }
 
+  /**
+   * 查询圆形范围内的实体，按距离由近到远排序（用于目标优先级）
+   */
+  queryRadiusSorted(x: number, y: number, radius: number): number[] {
+    if (!this.hash) return [];
+    return Array.from(this.hash.query_radius_sorted(x, y, radius));
+  }
+
   /**
    * 查询指定位置的实体
    */
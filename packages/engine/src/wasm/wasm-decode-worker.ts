@@ -82,7 +82,12 @@ function decodeAsfInWorker(data: Uint8Array): AsfPayload | null {
     );
     if (decoded === 0) return null;
 
-    const interval = header.fps > 0 ? Math.round(1000 / header.fps) : 67;
+    const interval =
+      header.interval_ms > 0
+        ? header.interval_ms
+        : header.fps > 0
+          ? Math.round(1000 / header.fps)
+          : 67;
     return {
       kind: "asf",
       width: header.canvas_width,
@@ -36,6 +36,10 @@ interface WasmMsfHeader {
   palette_size: number;
   frames_per_direction: number;
   total_individual_pixel_bytes: number;
+  /** 所有帧宽或高为 0：无需解码 */
+  is_empty: boolean;
+  /** IVAL 扩展块中的原始帧间隔 (ms)，无此块时为 0 */
+  interval_ms: number;
 }
 
 interface WasmMpcHeader {
@@ -49,6 +53,10 @@ interface WasmMpcHeader {
   bottom: number;
   left: number;
   total_pixel_bytes: number;
+  /** 偏移表或帧头无效、按 1×1 空帧处理的帧数 */
+  rejected_frames: number;
+  /** SHD 阴影文件（无调色板，color_count 为 0） */
+  is_shadow: boolean;
 }
 
 export interface WasmModule {
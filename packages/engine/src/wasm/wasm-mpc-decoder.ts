@@ -8,6 +8,7 @@
  * 使用前需要 await initWasm()
  */
 
+import { logger } from "../core/logger";
 import type { Mpc, MpcFrame, MpcHead } from "../map/types";
 import type { WasmModule } from "./wasm-manager";
 import { getWasmModule, MSF_MAGIC } from "./wasm-manager";
@@ -44,6 +45,11 @@ function decodeOriginalMpc(data: Uint8Array, wasmModule: WasmModule): Mpc | null
   if (!header) {
     return null;
   }
+  if (header.rejected_frames > 0) {
+    logger.warn(
+      `[MpcDecoder] ${header.rejected_frames}/${header.frame_count} frames rejected (corrupt offsets)`
+    );
+  }
 
   const pixelOutput = new Uint8Array(header.total_pixel_bytes);
   const frameSizesOutput = new Uint8Array(header.frame_count * 2 * 4);
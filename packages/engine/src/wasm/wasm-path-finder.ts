@@ -19,7 +19,6 @@
  */
 
 import { logger } from "../core/logger";
-import { CAN_OVER_OBSTACLE, CAN_OVER_TRANS, OBSTACLE, TRANS } from "../core/map-constants";
 import type { Vector2 } from "../core/types";
 import { PathType } from "../utils/path-finder";
 import type { WasmModule } from "./wasm-manager";
@@ -78,6 +77,7 @@ interface WasmPathFinderInstance {
   obstacle_bitmap_ptr(): number;
   hard_obstacle_bitmap_ptr(): number;
   bitmap_byte_size(): number;
+  set_obstacles_from_barriers(barriers: Uint8Array): void;
   free(): void;
 }
 
@@ -219,42 +219,18 @@ function ensureViews(): boolean {
 
 /**
  * 从 MapData.barriers 同步静态障碍物到 WASM 内存
- * 零拷贝：直接写入 WASM 线性内存中的 obstacle_bitmap / hard_obstacle_bitmap
+ * barrier → 障碍 / 硬障碍的映射由 WASM set_obstacles_from_barriers 统一处理，
+ * 原地写入 obstacle_bitmap / hard_obstacle_bitmap
  *
  * @param barriers  MapData.barriers (Uint8Array, 每个字节是一个 tile 的 barrier flags)
  * @param cols      mapColumnCounts
  * @param rows      mapRowCounts
  */
 export function syncStaticObstacles(barriers: Uint8Array, cols: number, rows: number): void {
-  if (!ensureViews() || !obstacleBitmapView || !hardObstacleBitmapView) return;
+  if (!wasmPf) return;
 
   const totalTiles = cols * rows;
-
-  // 清零
-  obstacleBitmapView.fill(0);
-  hardObstacleBitmapView.fill(0);
-
-  // 逐 tile 设置 bit
-  for (let i = 0; i < totalTiles; i++) {
-    const barrier = barriers[i] ?? 0xff; // 原始 byte，与 C++ 一致：精确值比较自然忽略低位噪声
-    const byteIdx = i >> 3;
-    const bitMask = 1 << (i & 7);
-
-    // isObstacleForCharacter (C++ canWalk): blocks toTrans/toJumpTrans/toObstacle/toJumpOpaque
-    if (
-      barrier === TRANS ||
-      barrier === CAN_OVER_TRANS ||
-      barrier === OBSTACLE ||
-      barrier === CAN_OVER_OBSTACLE
-    ) {
-      obstacleBitmapView[byteIdx] |= bitMask;
-    }
-
-    // isObstacle/hard (C++ toObstacle/toJumpOpaque only)
-    if (barrier === OBSTACLE || barrier === CAN_OVER_OBSTACLE) {
-      hardObstacleBitmapView[byteIdx] |= bitMask;
-    }
-  }
+  wasmPf.set_obstacles_from_barriers(barriers.subarray(0, totalTiles));
 
   logger.debug(
     `[WasmPathFinder] Static obstacles synced: ${totalTiles} tiles, ${bitmapByteSize} bytes`
@@ -351,7 +327,7 @@ export { PathType };
  * @param startTile 起点 tile 坐标
  * @param endTile   终点 tile 坐标
  * @param pathType  寻路类型 (PathType enum 值)
- * @param canMoveDirectionCount 可移动方向数（默认 8）
+ * @param canMoveDirectionCount 可移动方向数（默认 8；16 时 A* 模式允许长对角移动，相邻路径点可能不相邻）
  * @returns Vector2[] 路径（tile 坐标），空数组表示无路径
  */
 export function findPathWasm(